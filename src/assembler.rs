@@ -0,0 +1,548 @@
+use std::fmt::Display;
+
+use crate::{
+    disassembler::{ARITHMETIC, CONDITIONS, GPR32, GPR64},
+    utils::parse_address,
+};
+
+/// The size in bytes of an operand, 4 or 8
+type Size = u8;
+
+/// The instructions without operands and their encoding
+const FIXED: [(&str, &[u8]); 8] = [
+    ("nop", &[0x90]),
+    ("ret", &[0xc3]),
+    ("int3", &[0xcc]),
+    ("leave", &[0xc9]),
+    ("hlt", &[0xf4]),
+    ("syscall", &[0x0f, 0x05]),
+    ("cltq", &[0x48, 0x98]),
+    ("cqto", &[0x48, 0x99]),
+];
+
+/// The instructions with a single register or memory operand, with their opcode and extension
+const UNARY: [(&str, u8, u8); 4] = [
+    ("inc", 0xff, 0),
+    ("dec", 0xff, 1),
+    ("not", 0xf7, 2),
+    ("neg", 0xf7, 3),
+];
+
+/// The reason instructions cannot be assembled
+pub enum Error {
+    UnknownInstruction(String),
+    InvalidOperand(String),
+    /// The instruction exists but not with these operands
+    UnsupportedOperands(String),
+    /// No register operand gives the size, and the mnemonic has no `l` or `q` suffix
+    AmbiguousSize(String),
+    /// The immediate value doesn't fit in the operand
+    ImmediateTooLarge(i64),
+    /// The target of a jump or call is too far for a 32 bit displacement
+    TargetTooFar(usize),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::UnknownInstruction(mnemonic) => write!(f, "Unknown instruction '{mnemonic}'"),
+            Error::InvalidOperand(operand) => write!(f, "Invalid operand '{operand}'"),
+            Error::UnsupportedOperands(instruction) => {
+                write!(f, "Unsupported operands in '{instruction}'")
+            }
+            Error::AmbiguousSize(instruction) => write!(
+                f,
+                "The operand size of '{instruction}' is ambiguous, add the suffix l or q"
+            ),
+            Error::ImmediateTooLarge(value) => write!(f, "Immediate value {value:#x} is too large"),
+            Error::TargetTooFar(target) => write!(f, "Target {target:#x} is too far"),
+        }
+    }
+}
+
+/// An operand in AT&T syntax
+enum Operand {
+    /// The number of a general purpose register and its size
+    Register(u8, Size),
+    Immediate(i64),
+    /// `disp(%base)`, without base for a RIP relative address
+    Memory {
+        base: Option<u8>,
+        disp: i32,
+    },
+    /// The target of a jump or a call
+    Address(usize),
+    /// `*%reg`, the register holding the target of a jump or a call
+    Indirect(u8),
+}
+
+impl Operand {
+    fn is_register_or_memory(&self) -> bool {
+        matches!(self, Operand::Register(..) | Operand::Memory { .. })
+    }
+}
+
+/// Parses a number in decimal or hexadecimal, optionally negative
+fn parse_number(text: &str) -> Option<i64> {
+    match text.strip_prefix('-') {
+        Some(text) => parse_address(text).map(|value| (value as i64).wrapping_neg()),
+        None => parse_address(text).map(|value| value as i64),
+    }
+}
+
+/// Parses a register name without its `%`
+fn parse_register(name: &str) -> Option<(u8, Size)> {
+    let index = |registers: &[&str]| registers.iter().position(|&register| register == name);
+    index(&GPR64)
+        .map(|index| (index as u8, 8))
+        .or_else(|| index(&GPR32).map(|index| (index as u8, 4)))
+}
+
+/// Parses an operand, the targets of jumps and calls being resolved to an address by `resolve`
+fn parse_operand(text: &str, resolve: &dyn Fn(&str) -> Option<usize>) -> Result<Operand, Error> {
+    let invalid = || Error::InvalidOperand(text.to_string());
+    if let Some(name) = text.strip_prefix("*%") {
+        return match parse_register(name) {
+            Some((register, 8)) => Ok(Operand::Indirect(register)),
+            _ => Err(invalid()),
+        };
+    }
+    if let Some(name) = text.strip_prefix('%') {
+        let (register, size) = parse_register(name).ok_or_else(invalid)?;
+        return Ok(Operand::Register(register, size));
+    }
+    if let Some(value) = text.strip_prefix('$') {
+        return parse_number(value)
+            .map(Operand::Immediate)
+            .ok_or_else(invalid);
+    }
+    if let Some((disp, base)) = text.strip_suffix(')').and_then(|text| text.split_once('(')) {
+        let disp = match disp {
+            "" => 0,
+            disp => parse_number(disp)
+                .and_then(|disp| i32::try_from(disp).ok())
+                .ok_or_else(invalid)?,
+        };
+        let base = match base.strip_prefix('%') {
+            Some("rip") => None,
+            Some(name) => match parse_register(name) {
+                Some((register, 8)) => Some(register),
+                _ => return Err(invalid()),
+            },
+            None => return Err(invalid()),
+        };
+        return Ok(Operand::Memory { base, disp });
+    }
+    resolve(text).map(Operand::Address).ok_or_else(invalid)
+}
+
+/// Splits the operands at the commas that are not between parentheses
+fn split_operands(text: &str) -> Vec<&str> {
+    let mut operands = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(text[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    operands.push(text[start..].trim());
+    operands
+}
+
+/// Appends the REX prefix if needed, the opcode and the ModRM byte of `rm`, `reg` being a register or an opcode extension
+fn encode_modrm(bytes: &mut Vec<u8>, size: Size, opcode: &[u8], reg: u8, rm: &Operand) {
+    let rm_register = match *rm {
+        Operand::Register(register, _)
+        | Operand::Memory {
+            base: Some(register),
+            ..
+        } => register,
+        _ => 0,
+    };
+    let rex = ((size == 8) as u8) << 3 | (reg >> 3) << 2 | (rm_register >> 3);
+    if rex != 0 {
+        bytes.push(0x40 | rex);
+    }
+    bytes.extend_from_slice(opcode);
+    let reg = (reg & 7) << 3;
+    match *rm {
+        Operand::Memory { base: None, disp } => {
+            bytes.push(0x05 | reg);
+            bytes.extend_from_slice(&disp.to_le_bytes());
+        }
+        Operand::Memory {
+            base: Some(base),
+            disp,
+        } => {
+            // rbp and r13 without displacement encode a RIP relative address, a null displacement is used instead
+            let mode = match disp {
+                0 if base & 7 != 5 => 0,
+                -128..=127 => 1,
+                _ => 2,
+            };
+            bytes.push(mode << 6 | reg | (base & 7));
+            // rsp and r12 as a base need a SIB byte
+            if base & 7 == 4 {
+                bytes.push(0x24);
+            }
+            match mode {
+                1 => bytes.push(disp as u8),
+                2 => bytes.extend_from_slice(&disp.to_le_bytes()),
+                _ => {}
+            }
+        }
+        _ => bytes.push(0xc0 | reg | (rm_register & 7)),
+    }
+}
+
+/// Appends the immediate `value` encoded in 4 bytes, which must fit in an operand of `size`
+fn encode_imm32(bytes: &mut Vec<u8>, value: i64, size: Size) -> Result<(), Error> {
+    let fits = match size {
+        4 => (i32::MIN as i64..=u32::MAX as i64).contains(&value),
+        _ => i32::try_from(value).is_ok(),
+    };
+    if !fits {
+        return Err(Error::ImmediateTooLarge(value));
+    }
+    bytes.extend_from_slice(&(value as u32).to_le_bytes());
+    Ok(())
+}
+
+/// Appends a jump or call to `target` from `addr`, using `short` with a byte displacement if given and the target is close
+fn encode_relative(
+    bytes: &mut Vec<u8>,
+    addr: usize,
+    target: usize,
+    short: Option<&[u8]>,
+    near: &[u8],
+) -> Result<(), Error> {
+    let displacement = |len: usize| (target as i64).wrapping_sub(addr.wrapping_add(len) as i64);
+    if let Some(short) = short
+        && let Ok(disp) = i8::try_from(displacement(short.len() + 1))
+    {
+        bytes.extend_from_slice(short);
+        bytes.push(disp as u8);
+        return Ok(());
+    }
+    let disp =
+        i32::try_from(displacement(near.len() + 4)).map_err(|_| Error::TargetTooFar(target))?;
+    bytes.extend_from_slice(near);
+    bytes.extend_from_slice(&disp.to_le_bytes());
+    Ok(())
+}
+
+/// Encodes the instruction `text` located at `addr`
+fn encode(
+    text: &str,
+    addr: usize,
+    resolve: &dyn Fn(&str) -> Option<usize>,
+) -> Result<Vec<u8>, Error> {
+    let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let operands = match operands.trim() {
+        "" => Vec::new(),
+        operands => split_operands(operands)
+            .into_iter()
+            .map(|operand| parse_operand(operand, resolve))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let unsupported = || Error::UnsupportedOperands(text.to_string());
+    let known = |mnemonic: &str| {
+        FIXED.iter().any(|(name, _)| *name == mnemonic)
+            || UNARY.iter().any(|(name, _, _)| *name == mnemonic)
+            || ARITHMETIC.contains(&mnemonic)
+            || ["push", "pop", "mov", "movabs", "lea", "test", "jmp", "call"].contains(&mnemonic)
+            || mnemonic
+                .strip_prefix('j')
+                .is_some_and(|condition| CONDITIONS.contains(&condition))
+    };
+    // The suffix gives the size when no register operand does
+    let suffixed = |suffix, size| {
+        mnemonic
+            .strip_suffix(suffix)
+            .filter(|mnemonic| known(mnemonic))
+            .map(|mnemonic| (mnemonic, Some(size)))
+    };
+    let (mnemonic, suffix) = match known(mnemonic) {
+        true => (mnemonic, None),
+        false => suffixed('l', 4)
+            .or_else(|| suffixed('q', 8))
+            .ok_or_else(|| Error::UnknownInstruction(mnemonic.to_string()))?,
+    };
+    let register_size = operands.iter().find_map(|operand| match operand {
+        Operand::Register(_, size) => Some(*size),
+        _ => None,
+    });
+    let mismatched = |size: Size| suffix.or(register_size).is_some_and(|other| other != size);
+    if operands
+        .iter()
+        .any(|operand| matches!(operand, Operand::Register(_, size) if mismatched(*size)))
+    {
+        return Err(unsupported());
+    }
+    let size = || {
+        suffix
+            .or(register_size)
+            .ok_or_else(|| Error::AmbiguousSize(text.to_string()))
+    };
+
+    let mut bytes = Vec::new();
+    if let Some((_, encoding)) = FIXED.iter().find(|(name, _)| *name == mnemonic) {
+        if !operands.is_empty() {
+            return Err(unsupported());
+        }
+        bytes.extend_from_slice(encoding);
+        return Ok(bytes);
+    }
+    if let Some(&(_, opcode, extension)) = UNARY.iter().find(|(name, _, _)| *name == mnemonic) {
+        match operands.as_slice() {
+            [rm] if rm.is_register_or_memory() => {
+                encode_modrm(&mut bytes, size()?, &[opcode], extension, rm)
+            }
+            _ => return Err(unsupported()),
+        }
+        return Ok(bytes);
+    }
+    if let Some(extension) = ARITHMETIC.iter().position(|&name| name == mnemonic) {
+        let extension = extension as u8;
+        match operands.as_slice() {
+            [Operand::Immediate(value), rm] if rm.is_register_or_memory() => {
+                let size = size()?;
+                match i8::try_from(*value) {
+                    Ok(value) => {
+                        encode_modrm(&mut bytes, size, &[0x83], extension, rm);
+                        bytes.push(value as u8);
+                    }
+                    Err(_) => {
+                        encode_modrm(&mut bytes, size, &[0x81], extension, rm);
+                        encode_imm32(&mut bytes, *value, size)?;
+                    }
+                }
+            }
+            [Operand::Register(source, size), rm] if rm.is_register_or_memory() => {
+                encode_modrm(&mut bytes, *size, &[extension * 8 + 1], *source, rm);
+            }
+            [
+                memory @ Operand::Memory { .. },
+                Operand::Register(destination, size),
+            ] => {
+                encode_modrm(
+                    &mut bytes,
+                    *size,
+                    &[extension * 8 + 3],
+                    *destination,
+                    memory,
+                );
+            }
+            _ => return Err(unsupported()),
+        }
+        return Ok(bytes);
+    }
+    if let Some(condition) = mnemonic
+        .strip_prefix('j')
+        .and_then(|condition| CONDITIONS.iter().position(|&name| name == condition))
+    {
+        let [Operand::Address(target)] = operands.as_slice() else {
+            return Err(unsupported());
+        };
+        let condition = condition as u8;
+        encode_relative(
+            &mut bytes,
+            addr,
+            *target,
+            Some(&[0x70 + condition]),
+            &[0x0f, 0x80 + condition],
+        )?;
+        return Ok(bytes);
+    }
+    match (mnemonic, operands.as_slice()) {
+        ("push" | "pop", [Operand::Register(register, 8)]) => {
+            if *register >= 8 {
+                bytes.push(0x41);
+            }
+            let opcode = if mnemonic == "push" { 0x50 } else { 0x58 };
+            bytes.push(opcode + (register & 7));
+        }
+        ("push", [Operand::Immediate(value)]) => match i8::try_from(*value) {
+            Ok(value) => bytes.extend_from_slice(&[0x6a, value as u8]),
+            Err(_) => {
+                bytes.push(0x68);
+                encode_imm32(&mut bytes, *value, 8)?;
+            }
+        },
+        ("mov", [Operand::Immediate(value), Operand::Register(register, 4)]) => {
+            if *register >= 8 {
+                bytes.push(0x41);
+            }
+            bytes.push(0xb8 + (register & 7));
+            encode_imm32(&mut bytes, *value, 4)?;
+        }
+        ("mov", [Operand::Immediate(value), rm]) if rm.is_register_or_memory() => {
+            let size = size()?;
+            match (rm, i32::try_from(*value)) {
+                // A 64 bit register can be loaded with any value, with `movabs`
+                (Operand::Register(register, _), Err(_)) => {
+                    bytes.push(0x48 | (register >> 3));
+                    bytes.push(0xb8 + (register & 7));
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+                _ => {
+                    encode_modrm(&mut bytes, size, &[0xc7], 0, rm);
+                    encode_imm32(&mut bytes, *value, size)?;
+                }
+            }
+        }
+        ("movabs", [Operand::Immediate(value), Operand::Register(register, 8)]) => {
+            bytes.push(0x48 | (register >> 3));
+            bytes.push(0xb8 + (register & 7));
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        ("mov", [Operand::Register(source, size), rm]) if rm.is_register_or_memory() => {
+            encode_modrm(&mut bytes, *size, &[0x89], *source, rm);
+        }
+        (
+            "mov",
+            [
+                memory @ Operand::Memory { .. },
+                Operand::Register(destination, size),
+            ],
+        ) => {
+            encode_modrm(&mut bytes, *size, &[0x8b], *destination, memory);
+        }
+        (
+            "lea",
+            [
+                memory @ Operand::Memory { .. },
+                Operand::Register(destination, size),
+            ],
+        ) => {
+            encode_modrm(&mut bytes, *size, &[0x8d], *destination, memory);
+        }
+        ("test", [Operand::Register(source, size), rm]) if rm.is_register_or_memory() => {
+            encode_modrm(&mut bytes, *size, &[0x85], *source, rm);
+        }
+        ("test", [Operand::Immediate(value), rm]) if rm.is_register_or_memory() => {
+            let size = size()?;
+            encode_modrm(&mut bytes, size, &[0xf7], 0, rm);
+            encode_imm32(&mut bytes, *value, size)?;
+        }
+        ("jmp", [Operand::Address(target)]) => {
+            encode_relative(&mut bytes, addr, *target, Some(&[0xeb]), &[0xe9])?;
+        }
+        ("call", [Operand::Address(target)]) => {
+            encode_relative(&mut bytes, addr, *target, None, &[0xe8])?;
+        }
+        ("jmp" | "call", [Operand::Indirect(register)]) => {
+            let extension = if mnemonic == "jmp" { 4 } else { 2 };
+            encode_modrm(
+                &mut bytes,
+                4,
+                &[0xff],
+                extension,
+                &Operand::Register(*register, 8),
+            );
+        }
+        _ => return Err(unsupported()),
+    }
+    Ok(bytes)
+}
+
+/// Encodes the instructions `text` in AT&T syntax, separated by `;`, to be written at `addr`
+///
+/// Only a subset of the general purpose instructions is supported, with 32 and 64 bit registers,
+/// immediate values and `disp(%base)` memory operands.
+/// The targets of jumps and calls are addresses or locations resolved by `resolve`.
+pub fn assemble(
+    text: &str,
+    addr: usize,
+    resolve: impl Fn(&str) -> Option<usize>,
+) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    for instruction in text
+        .split(';')
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+    {
+        bytes.extend(encode(
+            instruction,
+            addr.wrapping_add(bytes.len()),
+            &resolve,
+        )?);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassembler::{Flavor, decode, set_flavor};
+
+    /// The address the instructions are assembled at, the target of the jumps is relative to it
+    const ADDR: usize = 0x401000;
+
+    /// Assembles `text` then disassembles each instruction in the syntax `flavor`, the spaces collapsed
+    fn round_trip(text: &str, flavor: Flavor) -> String {
+        let Ok(code) = assemble(text, ADDR, parse_address) else {
+            panic!("cannot assemble {text:?}");
+        };
+        set_flavor(flavor);
+        let mut instructions = Vec::new();
+        let mut offset = 0;
+        while offset < code.len() {
+            let instruction = decode(&code[offset..], ADDR + offset).unwrap();
+            offset += instruction.len;
+            let text = instruction.to_string();
+            instructions.push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+        instructions.join("; ")
+    }
+
+    /// Instructions in AT&T syntax, as the disassembler prints them, and in Intel syntax
+    const INSTRUCTIONS: [(&str, &str); 18] = [
+        ("mov %rsp,%rbp", "mov rbp,rsp"),
+        ("mov $0x2a,%eax", "mov eax,0x2a"),
+        ("add $0x10,%rsp", "add rsp,0x10"),
+        ("sub %r8,%r9", "sub r9,r8"),
+        ("xor %eax,%eax", "xor eax,eax"),
+        ("cmp $0x5,%edi", "cmp edi,0x5"),
+        ("test %eax,%eax", "test eax,eax"),
+        ("mov 0x8(%rbp),%rax", "mov rax,QWORD PTR [rbp+0x8]"),
+        ("lea -0x10(%rbp),%rdi", "lea rdi,[rbp-0x10]"),
+        ("incq 0x10(%rsp)", "inc QWORD PTR [rsp+0x10]"),
+        ("neg %rcx", "neg rcx"),
+        ("push %rbx", "push rbx"),
+        ("pop %r12", "pop r12"),
+        ("jmp 0x401000", "jmp 0x401000"),
+        ("jne 0x401000", "jne 0x401000"),
+        ("call 0x401100", "call 0x401100"),
+        ("syscall", "syscall"),
+        ("ret", "ret"),
+    ];
+
+    // A single test, as the flavor is shared by the whole program
+    #[test]
+    fn disassembling_assembled_instructions_gives_them_back() {
+        for (att, intel) in INSTRUCTIONS {
+            assert_eq!(round_trip(att, Flavor::Att), att);
+            assert_eq!(round_trip(att, Flavor::Intel), intel);
+        }
+        let text = "push %rbp; mov %rsp,%rbp; mov $0x0,%eax; leave; ret";
+        assert_eq!(round_trip(text, Flavor::Att), text);
+        set_flavor(Flavor::Intel);
+    }
+
+    #[test]
+    fn instructions_are_assembled_at_the_end_of_the_address_space() {
+        // The jump at 0 wraps around to itself
+        assert!(matches!(
+            assemble("nop; jmp 0x0", usize::MAX, parse_address),
+            Ok(code) if code == [0x90, 0xeb, 0xfe]
+        ));
+    }
+}
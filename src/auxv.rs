@@ -0,0 +1,110 @@
+use std::fs;
+
+use nix::unistd::Pid;
+
+/// The address of the program headers of the executable
+const AT_PHDR: u64 = 3;
+/// The entry point of the executable
+const AT_ENTRY: u64 = 9;
+
+/// The names of the types of the entries, from `<elf.h>`, with how their value is printed
+const ENTRY_TYPES: [(u64, &str, ValueKind); 30] = [
+    (0, "AT_NULL", ValueKind::Decimal),
+    (1, "AT_IGNORE", ValueKind::Hex),
+    (2, "AT_EXECFD", ValueKind::Decimal),
+    (AT_PHDR, "AT_PHDR", ValueKind::Hex),
+    (4, "AT_PHENT", ValueKind::Decimal),
+    (5, "AT_PHNUM", ValueKind::Decimal),
+    (6, "AT_PAGESZ", ValueKind::Decimal),
+    (7, "AT_BASE", ValueKind::Hex),
+    (8, "AT_FLAGS", ValueKind::Hex),
+    (AT_ENTRY, "AT_ENTRY", ValueKind::Hex),
+    (10, "AT_NOTELF", ValueKind::Decimal),
+    (11, "AT_UID", ValueKind::Decimal),
+    (12, "AT_EUID", ValueKind::Decimal),
+    (13, "AT_GID", ValueKind::Decimal),
+    (14, "AT_EGID", ValueKind::Decimal),
+    (15, "AT_PLATFORM", ValueKind::String),
+    (16, "AT_HWCAP", ValueKind::Hex),
+    (17, "AT_CLKTCK", ValueKind::Decimal),
+    (23, "AT_SECURE", ValueKind::Decimal),
+    (24, "AT_BASE_PLATFORM", ValueKind::String),
+    (25, "AT_RANDOM", ValueKind::Hex),
+    (26, "AT_HWCAP2", ValueKind::Hex),
+    (27, "AT_RSEQ_FEATURE_SIZE", ValueKind::Decimal),
+    (28, "AT_RSEQ_ALIGN", ValueKind::Decimal),
+    (29, "AT_HWCAP3", ValueKind::Hex),
+    (30, "AT_HWCAP4", ValueKind::Hex),
+    (31, "AT_EXECFN", ValueKind::String),
+    (32, "AT_SYSINFO", ValueKind::Hex),
+    (33, "AT_SYSINFO_EHDR", ValueKind::Hex),
+    (51, "AT_MINSIGSTKSZ", ValueKind::Decimal),
+];
+
+/// How the value of an entry of the auxiliary vector is printed
+#[derive(Clone, Copy, PartialEq)]
+pub enum ValueKind {
+    Decimal,
+    Hex,
+    /// The address of a null-terminated string in the memory of the program
+    String,
+}
+
+/// An entry of the auxiliary vector, the information the kernel gives to the program when it starts
+pub struct AuxvEntry {
+    pub key: u64,
+    pub value: u64,
+}
+
+impl AuxvEntry {
+    /// The name of the type of the entry, like `AT_ENTRY`, and how its value is printed
+    pub fn describe(&self) -> Option<(&'static str, ValueKind)> {
+        ENTRY_TYPES
+            .iter()
+            .find(|(key, _, _)| *key == self.key)
+            .map(|&(_, name, kind)| (name, kind))
+    }
+}
+
+/// Checks if the program `pid` is a 32-bit program, from the class of its ELF header
+fn is_32_bit(pid: Pid) -> bool {
+    fs::read(format!("/proc/{pid}/exe"))
+        .ok()
+        .and_then(|elf| elf.get(4).copied())
+        .is_some_and(|class| class == 1)
+}
+
+/// Reads the auxiliary vector of the program `pid` from `/proc/<pid>/auxv`, without its final `AT_NULL`
+///
+/// The entries are pairs of words, 4-byte words for a 32-bit program.
+pub fn read_auxv(pid: Pid) -> Option<Vec<AuxvEntry>> {
+    let data = fs::read(format!("/proc/{pid}/auxv")).ok()?;
+    let word = |bytes: &[u8]| {
+        let mut word = [0; 8];
+        word[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(word)
+    };
+    let size = if is_32_bit(pid) { 4 } else { 8 };
+    Some(
+        data.chunks_exact(2 * size)
+            .map(|entry| AuxvEntry {
+                key: word(&entry[..size]),
+                value: word(&entry[size..]),
+            })
+            .take_while(|entry| entry.key != 0)
+            .collect(),
+    )
+}
+
+/// Returns the runtime addresses of the entry point and of the program headers of the executable of `pid`
+///
+/// The kernel gives them in the auxiliary vector, the load bias of a position independent executable can be computed from them.
+pub fn entry_and_program_headers(pid: Pid) -> Option<(usize, usize)> {
+    let auxv = read_auxv(pid)?;
+    let value = |key| {
+        auxv.iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value as usize)
+    };
+    Some((value(AT_ENTRY)?, value(AT_PHDR)?))
+}
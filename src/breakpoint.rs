@@ -1,31 +1,172 @@
-use nix::{
-    sys::{
-        ptrace,
-        wait::{WaitStatus, waitpid},
-    },
-    unistd::Pid,
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use nix::{sys::ptrace, unistd::Pid};
+
+use crate::{
+    dwarf::DebugInfo,
+    symbols::{SharedLibrary, SymbolTable},
+    utils::{parse_address, read_data_fixed, write_data},
 };
 
-use crate::utils::{read_data_fixed, write_data};
+/// Whether the breakpoints that cannot be resolved yet are kept until a library defines them, changed by `set breakpoint pending`
+static PENDING: AtomicBool = AtomicBool::new(true);
+
+/// Keeps the breakpoints that cannot be resolved yet if `pending` is set, or rejects them
+pub fn set_pending(pending: bool) {
+    PENDING.store(pending, Ordering::Relaxed);
+}
+
+/// Checks if the breakpoints that cannot be resolved yet are kept
+pub fn keeps_pending() -> bool {
+    PENDING.load(Ordering::Relaxed)
+}
+
+/// The location of a breakpoint as given by the user
+pub enum BreakpointArg {
+    Address(usize),
+    Symbol(String, usize),
+    /// A symbol of a shared library, with the name of the library
+    Library(String, String, usize),
+    /// A line of a source file of the program, resolved through the line table
+    LineNumber(String, u64),
+}
+
+impl BreakpointArg {
+    /// Parses a breakpoint location
+    ///
+    /// Accepted forms are an address (`0x401136` or `4198710`) and a symbol
+    /// with an optional offset (`main`, `main+0x10` or `main+16`),
+    /// which can be prefixed by the name of a shared library (`libc:puts`),
+    /// and a line of a source file (`main.c:12`).
+    pub fn parse(arg: &str) -> Option<BreakpointArg> {
+        if let Some(addr) = parse_address(arg) {
+            return Some(BreakpointArg::Address(addr));
+        }
+        // A symbol name can't start with a digit, so a number after the colon is a line
+        if let Some((file, line)) = arg.rsplit_once(':')
+            && !file.is_empty()
+            && line.bytes().all(|byte| byte.is_ascii_digit())
+        {
+            return match line.parse() {
+                Ok(line) if line > 0 => Some(BreakpointArg::LineNumber(file.to_owned(), line)),
+                _ => None,
+            };
+        }
+        let (library, symbol) = match arg.split_once(':') {
+            Some((library, symbol)) if !library.is_empty() => (Some(library), symbol),
+            Some(_) => return None,
+            None => (None, arg),
+        };
+        let (name, offset) = match symbol.split_once('+') {
+            Some((name, offset)) => (name, parse_address(offset)?),
+            None => (symbol, 0),
+        };
+        if !is_symbol_name(name) {
+            return None;
+        }
+        match library {
+            Some(library) => Some(BreakpointArg::Library(
+                library.to_owned(),
+                name.to_owned(),
+                offset,
+            )),
+            None => Some(BreakpointArg::Symbol(name.to_owned(), offset)),
+        }
+    }
+
+    /// Resolves the breakpoint location to an address in the tracee
+    ///
+    /// The symbols of a shared library are only known once it has been loaded, see `SharedLibrary`.
+    /// A symbol missing from the program is looked up in the loaded libraries.
+    pub fn to_address(
+        &self,
+        symbols: Option<&SymbolTable>,
+        debug_info: Option<&DebugInfo>,
+        libraries: &[SharedLibrary],
+    ) -> Option<usize> {
+        match self {
+            BreakpointArg::Address(addr) => Some(*addr),
+            BreakpointArg::Symbol(name, offset) => symbols
+                .and_then(|symbols| symbols.lookup(name))
+                .or_else(|| {
+                    libraries
+                        .iter()
+                        .find_map(|library| library.symbols.lookup(name))
+                })?
+                .checked_add(*offset),
+            BreakpointArg::Library(library, name, offset) => libraries
+                .iter()
+                .find(|shared| shared.matches(library))?
+                .symbols
+                .lookup(name)?
+                .checked_add(*offset),
+            BreakpointArg::LineNumber(file, line) => debug_info?.line_address(file, *line),
+        }
+    }
+
+    /// Checks if the location can only be resolved once the shared libraries are loaded:
+    /// a symbol of a library, or a symbol missing from the program
+    pub fn in_library(&self, symbols: Option<&SymbolTable>) -> bool {
+        match self {
+            BreakpointArg::Address(_) | BreakpointArg::LineNumber(_, _) => false,
+            BreakpointArg::Symbol(name, _) => {
+                symbols.and_then(|symbols| symbols.lookup(name)).is_none()
+            }
+            BreakpointArg::Library(_, _, _) => true,
+        }
+    }
+
+    /// Returns the name of the shared library of the breakpoint location, if it is in one
+    pub fn library(&self) -> Option<&str> {
+        match self {
+            BreakpointArg::Library(library, _, _) => Some(library),
+            _ => None,
+        }
+    }
+}
+
+/// A breakpoint added by the user
+pub struct UserBreakpoint {
+    pub location: BreakpointArg,
+    /// The debugger commands run when the breakpoint is hit
+    pub commands: Vec<String>,
+    /// The number of times the breakpoint was hit since the program was run
+    pub hits: usize,
+    /// The expression printed by a probe, which doesn't stop the program when hit
+    pub probe: Option<String>,
+}
+
+/// Checks that `name` can be the name of an ELF symbol
+fn is_symbol_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || "_.$@".contains(c))
+}
 
 /// A representation of a software breakpoint on i386/x86_64
 pub struct Breakpoint {
     pub thread: Pid,
     pub addr: usize,
     saved_data: [u8; 1],
+    /// The trap is written in the thread's memory
+    inserted: bool,
 }
 
 impl Breakpoint {
     /// Creates a Software breakpoint in the thread pid
     ///
-    /// This writes the breakpoint to the thread's memory
+    /// This writes the breakpoint to the thread's memory,
+    /// `None` is returned if the memory at `addr` cannot be written.
     pub fn create(addr: usize, thread: Pid) -> Option<Self> {
         let mut breakpoint = Self {
             thread,
             addr,
             saved_data: [0],
+            inserted: false,
         };
-        breakpoint.write();
+        breakpoint.write()?;
 
         Some(breakpoint)
     }
@@ -34,36 +175,108 @@ impl Breakpoint {
     ///
     /// The original data at the breakpoin's location is saved, then the breakpoint is writter.
     /// The breakpoint is a trap instruction (int3 = 0xcc)
-    pub fn write(self: &mut Self) -> Option<()> {
+    /// Nothing is done if the breakpoint is already written.
+    pub fn write(&mut self) -> Option<()> {
+        if self.inserted {
+            return Some(());
+        }
         self.saved_data = read_data_fixed(self.thread, self.addr)?;
-        write_data(self.thread, self.addr, &[0xcc]).ok()
+        write_data(self.thread, self.addr, &[0xcc]).ok()?;
+        self.inserted = true;
+        Some(())
     }
 
     /// Restores the original data in the thread
     ///
-    /// This write the original program data in place of the breakpoint
-    pub fn restore_data(self: &Self) -> Option<()> {
-        write_data(self.thread, self.addr, &self.saved_data).ok()
+    /// This write the original program data in place of the breakpoint.
+    /// Nothing is done if the breakpoint is not written.
+    pub fn restore_data(&mut self) -> Option<()> {
+        if !self.inserted {
+            return Some(());
+        }
+        write_data(self.thread, self.addr, &self.saved_data).ok()?;
+        self.inserted = false;
+        Some(())
+    }
+
+    /// Checks if the trap is written in the thread's memory
+    pub fn is_inserted(&self) -> bool {
+        self.inserted
+    }
+
+    /// Returns the same breakpoint in `pid`, whose memory was forked from the thread's memory
+    ///
+    /// The trap is in the forked memory if it was written in the thread's memory at the fork.
+    pub fn forked(&self, pid: Pid) -> Self {
+        Self {
+            thread: pid,
+            addr: self.addr,
+            saved_data: self.saved_data,
+            inserted: self.inserted,
+        }
     }
 
-    /// Restores the thread's instruction pointer to the breakpoint location
+    /// Restores the instruction pointer of `thread` to the breakpoint location
     ///
     /// This write the rip register so that the next instruction executed
-    /// is the one located at the breakpoint
-    pub fn restore_rip(self: &Self) -> Option<()> {
-        let mut regs = ptrace::getregs(self.thread).ok()?;
+    /// is the one located at the breakpoint.
+    /// The memory is shared by all threads, but a breakpoint can be hit by any of them.
+    pub fn restore_rip(&self, thread: Pid) -> Option<()> {
+        let mut regs = ptrace::getregs(thread).ok()?;
         regs.rip = self.addr as _;
-        ptrace::setregs(self.thread, regs).ok()
+        ptrace::setregs(thread, regs).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_addresses() {
+        assert!(matches!(
+            BreakpointArg::parse("0x401136"),
+            Some(BreakpointArg::Address(0x401136))
+        ));
+        assert!(matches!(
+            BreakpointArg::parse("4198710"),
+            Some(BreakpointArg::Address(0x401136))
+        ));
     }
 
-    /// Continue running the program after the breakpoint has been hit and restored.
-    ///
-    /// To continue running the program, it is stepped by one instruction then the trap is rewritten
-    ///
-    pub fn run(self: &mut Self) -> Option<WaitStatus> {
-        ptrace::step(self.thread, None).ok()?;
-        let waitstatus = waitpid(self.thread, None).ok()?;
-        self.write()?;
-        Some(waitstatus)
+    #[test]
+    fn parse_symbols_with_an_offset() {
+        let offset = |arg| match BreakpointArg::parse(arg) {
+            Some(BreakpointArg::Symbol(name, offset)) if name == "main" => Some(offset),
+            _ => None,
+        };
+        assert_eq!(offset("main"), Some(0));
+        assert_eq!(offset("main+0x10"), Some(16));
+        assert_eq!(offset("main+16"), Some(16));
+        assert!(matches!(
+            BreakpointArg::parse("libc.so.6:puts+4"),
+            Some(BreakpointArg::Library(library, name, 4)) if library == "libc.so.6" && name == "puts"
+        ));
+    }
+
+    #[test]
+    fn parse_source_lines() {
+        assert!(matches!(
+            BreakpointArg::parse("th.c:7"),
+            Some(BreakpointArg::LineNumber(file, 7)) if file == "th.c"
+        ));
+        assert!(matches!(
+            BreakpointArg::parse("src/main.c:120"),
+            Some(BreakpointArg::LineNumber(file, 120)) if file == "src/main.c"
+        ));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_locations() {
+        for arg in [
+            "", "main+", "main+zz", "1main", ":puts", "libc:", "th.c:0", "th.c:-1", "ma in",
+        ] {
+            assert!(BreakpointArg::parse(arg).is_none(), "{arg:?}");
+        }
     }
 }
@@ -0,0 +1,113 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::{debugger::ForkMode, disassembler::Flavor};
+
+/// The default options of the debugger, read from the configuration file `~/.dbfsrc`
+///
+/// The options given on the command line override those of the file.
+#[derive(Default)]
+pub struct Config {
+    /// Whether the output is colored, `None` to color it only when it is a terminal
+    pub color: Option<bool>,
+    /// The syntax of the disassembled instructions, Intel syntax if it is not set
+    pub disassembly_flavor: Option<Flavor>,
+    /// Whether the programs are run with ASLR, disabled if it is not set
+    pub aslr: Option<bool>,
+    /// The process followed when the program forks, the parent if it is not set
+    pub follow_fork_mode: Option<ForkMode>,
+    /// Commands run before reading the input, in order
+    pub commands: Vec<String>,
+}
+
+impl Config {
+    /// Reads the configuration file in the home directory, the configuration is empty if there is none
+    pub fn load() -> Self {
+        let Some(path) = env::var_os("HOME").map(|home| PathBuf::from(home).join(".dbfsrc")) else {
+            return Self::default();
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text, &path.display().to_string()),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Parses the configuration file `path` whose content is `text`
+    ///
+    /// Each line is an option followed by its value, empty lines and lines starting with `#` are ignored.
+    /// The invalid lines are reported and ignored.
+    fn parse(text: &str, path: &str) -> Self {
+        let mut config = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let value = value.trim();
+            match (key, value) {
+                ("color", "auto") => config.color = None,
+                ("color", "always") => config.color = Some(true),
+                ("color", "never") => config.color = Some(false),
+                ("disassembly-flavor", flavor) if Flavor::parse(flavor).is_some() => {
+                    config.disassembly_flavor = Flavor::parse(flavor);
+                }
+                ("aslr", "on") => config.aslr = Some(true),
+                ("aslr", "off") => config.aslr = Some(false),
+                ("follow-fork-mode", mode) if ForkMode::parse(mode).is_some() => {
+                    config.follow_fork_mode = ForkMode::parse(mode);
+                }
+                ("command", command) if !command.is_empty() => {
+                    config.commands.push(command.to_owned());
+                }
+                ("color" | "disassembly-flavor" | "aslr" | "follow-fork-mode" | "command", _) => {
+                    eprintln!("{path}:{}: invalid value for '{key}'", number + 1);
+                }
+                _ => eprintln!("{path}:{}: unknown option '{key}'", number + 1),
+            }
+        }
+        config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_the_options() {
+        let config = Config::parse(
+            "# defaults\n\ncolor never\n  disassembly-flavor   att\naslr on\nfollow-fork-mode child\ncommand break main\ncommand run\n",
+            ".dbfsrc",
+        );
+        assert_eq!(config.color, Some(false));
+        assert!(matches!(config.disassembly_flavor, Some(Flavor::Att)));
+        assert_eq!(config.aslr, Some(true));
+        assert!(matches!(config.follow_fork_mode, Some(ForkMode::Child)));
+        assert_eq!(config.commands, ["break main", "run"]);
+    }
+
+    #[test]
+    fn parse_keeps_the_last_value_of_an_option() {
+        let config = Config::parse("color never\ncolor always\ncolor auto\n", ".dbfsrc");
+        assert_eq!(config.color, None);
+        let config = Config::parse("color auto\ncolor always\n", ".dbfsrc");
+        assert_eq!(config.color, Some(true));
+    }
+
+    #[test]
+    fn parse_ignores_unknown_options_and_invalid_values() {
+        let config = Config::parse(
+            "colour never\ncolor sometimes\ncolor\ndisassembly-flavor masm\naslr yes\nfollow-fork-mode both\ncommand\nverbose on\n",
+            ".dbfsrc",
+        );
+        assert_eq!(config.color, None);
+        assert!(config.disassembly_flavor.is_none());
+        assert_eq!(config.aslr, None);
+        assert!(config.follow_fork_mode.is_none());
+        assert!(config.commands.is_empty());
+        // The valid lines around an invalid one are still read
+        let config = Config::parse("color never\ncolor maybe\ncommand run\n", ".dbfsrc");
+        assert_eq!(config.color, Some(false));
+        assert_eq!(config.commands, ["run"]);
+    }
+}
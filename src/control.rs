@@ -0,0 +1,82 @@
+use std::{
+    ffi::CString,
+    fs, io,
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::{fs::FileTypeExt, net::UnixListener},
+    },
+    sync::OnceLock,
+};
+
+use nix::{
+    fcntl::{FcntlArg, fcntl},
+    libc::{self, c_int},
+    sys::signal::{SigHandler, Signal, signal},
+    unistd::dup2,
+};
+
+/// The standard input and output of the debugger before they were replaced by the connection of a client
+static TERMINAL: OnceLock<(RawFd, RawFd)> = OnceLock::new();
+/// The path of the socket, removed when the debugger is terminated
+static SOCKET_PATH: OnceLock<CString> = OnceLock::new();
+
+/// A Unix socket the debugger is controlled through, see `--listen`
+///
+/// The connection of the client replaces the standard input and output of the debugger,
+/// so that the client sends commands and receives their output.
+/// Clients are served one at a time.
+pub struct ControlSocket {
+    listener: UnixListener,
+}
+
+impl ControlSocket {
+    /// Listens on the socket `path`, replacing the socket left there by a previous debugger
+    pub fn bind(path: &str) -> io::Result<Self> {
+        if fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let _ = SOCKET_PATH.set(CString::new(path)?);
+        unsafe { libc::atexit(remove_socket) };
+        for terminating in [Signal::SIGINT, Signal::SIGTERM, Signal::SIGHUP] {
+            unsafe { signal(terminating, SigHandler::Handler(terminate)) }?;
+        }
+        let terminal = [0, 1].map(|fd| fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(3)));
+        if let [Ok(input), Ok(output)] = terminal {
+            let _ = TERMINAL.set((input, output));
+        }
+        Ok(Self { listener })
+    }
+
+    /// Waits for a client and makes its connection the standard input and output of the debugger
+    ///
+    /// The connection of the previous client is closed first, so that it sees the end of the output.
+    pub fn accept(&self) {
+        restore_terminal();
+        let (stream, _) = self.listener.accept().unwrap();
+        dup2(stream.as_raw_fd(), 0).unwrap();
+        dup2(stream.as_raw_fd(), 1).unwrap();
+    }
+}
+
+/// Gives back the standard input and output of the debugger to a launched program,
+/// so that the program doesn't read from or write to the client
+pub fn restore_terminal() {
+    if let Some(&(input, output)) = TERMINAL.get() {
+        let _ = dup2(input, 0);
+        let _ = dup2(output, 1);
+    }
+}
+
+/// Removes the socket, when the debugger exits
+extern "C" fn remove_socket() {
+    if let Some(path) = SOCKET_PATH.get() {
+        unsafe { libc::unlink(path.as_ptr()) };
+    }
+}
+
+/// Removes the socket and exits, when the debugger is terminated by a signal
+extern "C" fn terminate(_: c_int) {
+    remove_socket();
+    unsafe { libc::_exit(0) };
+}
@@ -0,0 +1,185 @@
+use std::{fs, mem, slice};
+
+use nix::{
+    libc::user_regs_struct,
+    sys::ptrace,
+    unistd::{Pid, getpgid, getpid, getsid},
+};
+
+use crate::{
+    maps::read_maps,
+    utils::{get_fpregs, read_data},
+};
+
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_FPREGSET: u32 = 2;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_FILE: u32 = 0x46494c45;
+const PAGE_SIZE: u64 = 0x1000;
+const HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+/// A segment of the core file, its data follows the notes
+struct Load {
+    vaddr: u64,
+    memsz: u64,
+    flags: u32,
+    data: Vec<u8>,
+}
+
+/// Views a C structure as its bytes
+fn bytes_of<T>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts((value as *const T).cast(), mem::size_of::<T>()) }
+}
+
+/// Appends a note named `CORE`, the name and the descriptor are padded to 4 bytes
+fn push_note(notes: &mut Vec<u8>, type_: u32, desc: &[u8]) {
+    notes.extend_from_slice(&5u32.to_le_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&type_.to_le_bytes());
+    notes.extend_from_slice(b"CORE\0\0\0\0");
+    notes.extend_from_slice(desc);
+    notes.resize(notes.len().next_multiple_of(4), 0);
+}
+
+/// The `elf_prstatus` of the thread `tid` of the program `pid`, stopped by the signal `signal`
+fn prstatus(pid: Pid, tid: Pid, signal: i32, regs: &user_regs_struct) -> Vec<u8> {
+    let mut desc = vec![0; 336];
+    desc[0..4].copy_from_slice(&signal.to_le_bytes());
+    desc[12..14].copy_from_slice(&(signal as i16).to_le_bytes());
+    desc[32..36].copy_from_slice(&tid.as_raw().to_le_bytes());
+    desc[36..40].copy_from_slice(&getpid().as_raw().to_le_bytes());
+    let pgrp = getpgid(Some(pid)).map_or(0, Pid::as_raw);
+    desc[40..44].copy_from_slice(&pgrp.to_le_bytes());
+    let sid = getsid(Some(pid)).map_or(0, Pid::as_raw);
+    desc[44..48].copy_from_slice(&sid.to_le_bytes());
+    desc[112..328].copy_from_slice(bytes_of(regs));
+    desc[328..332].copy_from_slice(&1i32.to_le_bytes());
+    desc
+}
+
+/// The `elf_prpsinfo` of the program `pid`, with its name and command line
+fn prpsinfo(pid: Pid) -> Vec<u8> {
+    let mut desc = vec![0; 136];
+    desc[1] = b'T';
+    desc[24..28].copy_from_slice(&pid.as_raw().to_le_bytes());
+    desc[28..32].copy_from_slice(&getpid().as_raw().to_le_bytes());
+    let name = fs::read(format!("/proc/{pid}/comm")).unwrap_or_default();
+    let name = name.strip_suffix(b"\n").unwrap_or(&name);
+    let len = name.len().min(15);
+    desc[40..40 + len].copy_from_slice(&name[..len]);
+    let mut args = fs::read(format!("/proc/{pid}/cmdline")).unwrap_or_default();
+    args.iter_mut()
+        .filter(|byte| **byte == 0)
+        .for_each(|byte| *byte = b' ');
+    let args = args.trim_ascii_end();
+    let len = args.len().min(79);
+    desc[56..56 + len].copy_from_slice(&args[..len]);
+    desc
+}
+
+/// Builds an ELF core file of the program `pid`, stopped with its threads `threads`
+///
+/// The first thread is the one a debugger loading the core file selects.
+/// The file has a `PT_LOAD` segment for each readable mapping, with the readable part of its content,
+/// and a `PT_NOTE` segment with the registers of the threads and the mapped files.
+/// Returns `None` if the mappings of the program can't be read.
+pub fn build(pid: Pid, threads: &[Pid]) -> Option<Vec<u8>> {
+    let maps = read_maps(pid)?;
+
+    let mut notes = Vec::new();
+    push_note(&mut notes, NT_PRPSINFO, &prpsinfo(pid));
+    for &tid in threads {
+        let regs = ptrace::getregs(tid).ok()?;
+        let signal = ptrace::getsiginfo(tid).map_or(0, |siginfo| siginfo.si_signo);
+        push_note(&mut notes, NT_PRSTATUS, &prstatus(pid, tid, signal, &regs));
+        if let Some(fpregs) = get_fpregs(tid) {
+            push_note(&mut notes, NT_FPREGSET, bytes_of(&fpregs));
+        }
+    }
+    if let Ok(auxv) = fs::read(format!("/proc/{pid}/auxv")) {
+        push_note(&mut notes, NT_AUXV, &auxv);
+    }
+    let files: Vec<_> = maps
+        .iter()
+        .filter(|map| map.path.as_ref().is_some_and(|path| path.starts_with('/')))
+        .collect();
+    let mut desc = Vec::new();
+    desc.extend_from_slice(&(files.len() as u64).to_le_bytes());
+    desc.extend_from_slice(&PAGE_SIZE.to_le_bytes());
+    for map in &files {
+        for value in [map.start, map.end, map.offset / PAGE_SIZE as usize] {
+            desc.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+    for map in &files {
+        desc.extend_from_slice(map.path.as_ref().unwrap().as_bytes());
+        desc.push(0);
+    }
+    push_note(&mut notes, NT_FILE, &desc);
+
+    let loads: Vec<Load> = maps
+        .iter()
+        .filter(|map| map.perms.starts_with('r'))
+        .filter(|map| map.path.as_deref() != Some("[vvar]"))
+        .map(|map| Load {
+            vaddr: map.start as _,
+            memsz: (map.end - map.start) as _,
+            flags: [('x', 1), ('w', 2), ('r', 4)]
+                .iter()
+                .filter(|(perm, _)| map.perms.contains(*perm))
+                .map(|(_, flag)| flag)
+                .sum(),
+            data: read_data(pid, map.start, map.end - map.start),
+        })
+        .collect();
+
+    let phnum = 1 + loads.len();
+    let notes_offset = HEADER_SIZE + phnum * PROGRAM_HEADER_SIZE;
+    let mut core = Vec::new();
+    core.extend_from_slice(b"\x7fELF\x02\x01\x01");
+    core.resize(16, 0);
+    core.extend_from_slice(&4u16.to_le_bytes()); // ET_CORE
+    core.extend_from_slice(&62u16.to_le_bytes()); // EM_X86_64
+    core.extend_from_slice(&1u32.to_le_bytes());
+    core.extend_from_slice(&0u64.to_le_bytes());
+    core.extend_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+    core.extend_from_slice(&0u64.to_le_bytes());
+    core.extend_from_slice(&0u32.to_le_bytes());
+    for value in [HEADER_SIZE, PROGRAM_HEADER_SIZE, phnum, 64, 0, 0] {
+        core.extend_from_slice(&(value as u16).to_le_bytes());
+    }
+
+    let mut push_header = |type_: u32, flags: u32, offset: usize, load: Option<&Load>, len| {
+        core.extend_from_slice(&type_.to_le_bytes());
+        core.extend_from_slice(&flags.to_le_bytes());
+        core.extend_from_slice(&(offset as u64).to_le_bytes());
+        let vaddr = load.map_or(0, |load| load.vaddr);
+        core.extend_from_slice(&vaddr.to_le_bytes());
+        core.extend_from_slice(&vaddr.to_le_bytes());
+        core.extend_from_slice(&(len as u64).to_le_bytes());
+        core.extend_from_slice(&load.map_or(len as u64, |load| load.memsz).to_le_bytes());
+        let align = if load.is_some() { PAGE_SIZE } else { 4 };
+        core.extend_from_slice(&align.to_le_bytes());
+    };
+    push_header(PT_NOTE, 0, notes_offset, None, notes.len());
+    // The segments are aligned on pages in the file, like their addresses
+    let mut offsets = Vec::new();
+    let mut offset = notes_offset + notes.len();
+    for load in &loads {
+        offset = offset.next_multiple_of(PAGE_SIZE as usize);
+        push_header(PT_LOAD, load.flags, offset, Some(load), load.data.len());
+        offsets.push(offset);
+        offset += load.data.len();
+    }
+
+    core.extend_from_slice(&notes);
+    for (load, offset) in loads.iter().zip(offsets) {
+        core.resize(offset, 0);
+        core.extend_from_slice(&load.data);
+    }
+    Some(core)
+}
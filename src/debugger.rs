@@ -0,0 +1,4435 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    env,
+    ffi::CString,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::exit,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use gimli::X86_64;
+use nix::{
+    errno::Errno,
+    libc::{self, TRAP_BRKPT, TRAP_TRACE, user_regs_struct},
+    sys::{
+        personality::{self, Persona},
+        ptrace,
+        signal::{self, Signal, raise},
+        wait::{WaitPidFlag, WaitStatus, waitpid},
+    },
+    unistd::{AccessFlags, ForkResult, Pid, access, execvpe, fork},
+};
+
+use crate::{
+    assembler,
+    auxv::{ValueKind, read_auxv},
+    breakpoint::{self, Breakpoint, BreakpointArg, UserBreakpoint},
+    control, coredump,
+    disassembler::{self, Instruction},
+    dwarf::{
+        DWARF_REGISTERS, DebugInfo, FrameBase, Function, Location, SourceLocation, Type, TypeKind,
+        Variable,
+    },
+    examine::{self, Format, Letter},
+    expression::{self, Access},
+    frame::{Frame, StackFrame},
+    maps::read_maps,
+    record::{self, History},
+    regex::Regex,
+    rendezvous::Rendezvous,
+    reporter::{AuxvLine, BreakpointHits, Event, MemberValue, Reporter, StackWord, VariableValue},
+    signals::{CtrlC, SignalHandling},
+    source,
+    symbols::{SharedLibrary, SymbolTable},
+    syscalls::{self, SyscallCatch, SyscallStop},
+    tui::CodeView,
+    unwind::{CallFrameInfo, Registers, Unwound},
+    utils::{
+        REGISTERS, format_string, get_fpregs, read_cstring, read_data, read_data_fixed, register,
+        register_mut, write_data,
+    },
+    watchpoint::{self, Scope, WatchKind, Watchpoint},
+    xstate,
+};
+
+/// Whether the programs are run with their addresses randomized, changed by `set aslr`
+static ASLR: AtomicBool = AtomicBool::new(false);
+
+/// Whether the debugger follows the child rather than the parent when the program forks, changed by `set follow-fork-mode`
+static FOLLOW_CHILD: AtomicBool = AtomicBool::new(false);
+
+/// The stops of the threads of the other inferiors, reported while waiting for the threads of one of them
+///
+/// All the inferiors are children of the debugger, so `waitpid` reports their threads to whichever is waiting.
+static OTHER_STOPS: Mutex<Vec<WaitStatus>> = Mutex::new(Vec::new());
+
+/// Runs the next programs with ASLR if `enabled`, or at the same addresses each time
+pub fn set_aslr(enabled: bool) {
+    ASLR.store(enabled, Ordering::Relaxed);
+}
+
+/// The process the debugger keeps debugging when the program forks, the other one is detached
+#[derive(Clone, Copy)]
+pub enum ForkMode {
+    Parent,
+    Child,
+}
+
+impl ForkMode {
+    /// Parses the name of a mode, `parent` or `child`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "parent" => Some(ForkMode::Parent),
+            "child" => Some(ForkMode::Child),
+            _ => None,
+        }
+    }
+}
+
+/// Follows the process `mode` at the next forks of the program
+pub fn set_follow_fork_mode(mode: ForkMode) {
+    FOLLOW_CHILD.store(matches!(mode, ForkMode::Child), Ordering::Relaxed);
+}
+
+/// Launches the tracee `program` with the arguments `args` and the environment `environment` and returns its Pid.
+/// ASLR is disabled for the tracee unless `set aslr on` enabled it, and the traces asks to be traced.
+/// For the tracer, this function guarantees that execve has already been called in the tracee.
+fn launch_program(program: &str, args: &[String], environment: &[CString]) -> Result<Pid, Errno> {
+    let program = CString::new(program).unwrap();
+    let argv: Vec<CString> = std::iter::once(program.clone())
+        .chain(
+            args.iter()
+                .filter_map(|arg| CString::new(arg.as_str()).ok()),
+        )
+        .collect();
+    match unsafe { fork() } {
+        Ok(ForkResult::Parent { child, .. }) => {
+            waitpid(child, None).unwrap();
+            ptrace::setoptions(
+                child,
+                ptrace::Options::PTRACE_O_TRACEEXEC
+                    | ptrace::Options::PTRACE_O_TRACECLONE
+                    | ptrace::Options::PTRACE_O_TRACEFORK
+                    | ptrace::Options::PTRACE_O_TRACESYSGOOD,
+            )
+            .unwrap();
+            ptrace::cont(child, None).unwrap();
+            match waitpid(child, None).unwrap() {
+                // The child exits with the error of execvpe
+                WaitStatus::Exited(_, errno) => Err(Errno::from_raw(errno)),
+                _ => Ok(child),
+            }
+        }
+        Ok(ForkResult::Child) => {
+            ptrace::traceme().unwrap();
+            if !ASLR.load(Ordering::Relaxed) {
+                personality::set(Persona::ADDR_NO_RANDOMIZE).unwrap();
+            }
+            raise(Signal::SIGSTOP).unwrap();
+            control::restore_terminal();
+            let Err(errno) = execvpe(&program, &argv, environment);
+            exit(errno as i32);
+        }
+        Err(errno) => Err(errno),
+    }
+}
+
+/// The number of source lines printed by `list` without argument
+const LISTED_LINES: u64 = 10;
+
+/// The maximal length of a string printed by `x/s`
+const STRING_MAX: usize = 200;
+
+/// Waits for the thread `tid`, whose stop may have been reported while waiting for another inferior
+fn wait_thread(tid: Pid) -> WaitStatus {
+    let mut others = OTHER_STOPS.lock().unwrap();
+    match others
+        .iter()
+        .position(|waitstatus| waitstatus.pid() == Some(tid))
+    {
+        Some(index) => others.remove(index),
+        None => waitpid(tid, Some(WaitPidFlag::__WALL)).unwrap(),
+    }
+}
+
+/// The parent of the process `pid`, read from `/proc`
+fn parent(pid: Pid) -> Option<Pid> {
+    let status = fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+    let ppid = status.lines().find_map(|line| line.strip_prefix("PPid:"))?;
+    ppid.trim().parse().ok().map(Pid::from_raw)
+}
+
+/// Checks if the thread stopped by a SIGTRAP because of a single step
+fn is_single_step(tid: Pid) -> bool {
+    ptrace::getsiginfo(tid).is_ok_and(|siginfo| siginfo.si_code == TRAP_TRACE)
+}
+
+/// Makes the stop of the thread `tid` look like a single step
+fn make_single_step(tid: Pid) {
+    let mut siginfo = ptrace::getsiginfo(tid).unwrap();
+    siginfo.si_signo = Signal::SIGTRAP as _;
+    siginfo.si_code = TRAP_TRACE;
+    ptrace::setsiginfo(tid, &siginfo).unwrap();
+}
+
+/// Checks if the thread `tid` stopped after executing a single step
+fn is_step_completed(waitstatus: &WaitStatus, tid: Pid) -> bool {
+    matches!(waitstatus, WaitStatus::Stopped(_, Signal::SIGTRAP)) && is_single_step(tid)
+}
+
+/// Detects if the last executed instruction was a call and returns the return address of the call
+///
+/// A call pushes the address of the next instruction, which is at most 15 bytes
+/// (the maximal length of an x86 instruction) after the call.
+fn call_return_address(
+    tid: Pid,
+    previous: &user_regs_struct,
+    regs: &user_regs_struct,
+) -> Option<usize> {
+    if regs.rsp != previous.rsp.wrapping_sub(8) {
+        return None;
+    }
+    let return_addr = usize::from_ne_bytes(read_data_fixed(tid, regs.rsp as _)?);
+    let call_addr = previous.rip as usize;
+    (call_addr < return_addr && return_addr <= call_addr + 15).then_some(return_addr)
+}
+
+/// Returns the address of the variable `variable` of `function`, in the frame whose canonical frame address is `cfa`
+///
+/// Returns `None` if the variable is not in memory or if its location is not supported.
+fn variable_address(function: &Function, variable: &Variable, cfa: usize) -> Option<usize> {
+    match variable.location? {
+        Location::FrameOffset(offset) => {
+            let base = match function.frame_base? {
+                FrameBase::Cfa => cfa,
+                // The frame pointer is pushed under the return address
+                FrameBase::Rbp => cfa - 16,
+            };
+            base.checked_add_signed(offset as _)
+        }
+        Location::Address(addr) => Some(addr),
+        Location::Register(_) | Location::Unsupported => None,
+    }
+}
+
+/// Where a variable of the current frame is stored
+enum Place {
+    Memory(usize),
+    /// In the general purpose register with this name
+    Register(&'static str),
+}
+
+/// Why a variable of the current frame can't be accessed
+enum Unavailable {
+    /// The variable has no location
+    OptimizedOut,
+    UnsupportedLocation,
+    UnsupportedRegister(u16),
+    /// The variable is in the frame of the function, which is not set up yet
+    BeforePrologue,
+    /// The frame of the function can't be found
+    NoAddress,
+}
+
+impl Unavailable {
+    /// Explains why the variable `name` can't be accessed to `action` it, like `set`
+    fn describe(&self, name: &str, action: &str) -> String {
+        match self {
+            Self::OptimizedOut => format!("'{name}' has no location, it may be optimized out"),
+            Self::UnsupportedLocation => {
+                format!("Cannot {action} '{name}': unsupported location expression")
+            }
+            Self::UnsupportedRegister(number) => {
+                format!("Cannot {action} '{name}': unsupported register {number}")
+            }
+            Self::BeforePrologue => {
+                format!("Cannot {action} '{name}' before the prologue of the function is executed")
+            }
+            Self::NoAddress => format!("Cannot find the address of '{name}'"),
+        }
+    }
+
+    /// A short explanation, printed instead of the value of a variable
+    fn short(&self) -> String {
+        match self {
+            Self::OptimizedOut => String::from("<optimized out>"),
+            Self::UnsupportedLocation => String::from("<unsupported location>"),
+            Self::UnsupportedRegister(number) => format!("<unsupported register {number}>"),
+            Self::BeforePrologue => String::from("<before the prologue>"),
+            Self::NoAddress => String::from("<unknown address>"),
+        }
+    }
+}
+
+/// Splits a cast of an address to a pointer, like `(struct point *)0x601040`, into the type and the address expression
+fn parse_cast(expression: &str) -> Option<(&str, &str)> {
+    let (cast, addr) = expression.strip_prefix('(')?.split_once(')')?;
+    let type_name = cast.trim_end().strip_suffix('*')?.trim();
+    type_name
+        .starts_with(|first: char| first.is_ascii_alphabetic() || first == '_')
+        .then_some((type_name, addr))
+}
+
+/// Checks if `expression` is the name of a variable rather than an expression, like `counter` or `_len`
+fn is_identifier(expression: &str) -> bool {
+    expression
+        .chars()
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && expression
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Returns the length of the instruction starting with `code` if it is a call
+///
+/// The calls are the near relative call (`e8`) and the indirect calls (`ff /2` and `ff /3`),
+/// whose length depends on their ModRM addressing.
+fn call_length(code: &[u8]) -> Option<usize> {
+    // Legacy prefixes, like `66` or `bnd` (`f2`), then an optional REX prefix
+    let mut len = code
+        .iter()
+        .take_while(|byte| {
+            [0x26, 0x2e, 0x36, 0x3e, 0x64, 0x65, 0x66, 0x67, 0xf2, 0xf3].contains(*byte)
+        })
+        .count();
+    if code.get(len).is_some_and(|byte| byte & 0xf0 == 0x40) {
+        len += 1;
+    }
+    match code.get(len)? {
+        0xe8 => Some(len + 5),
+        0xff => {
+            let modrm = *code.get(len + 1)?;
+            let (mode, reg, rm) = (modrm >> 6, (modrm >> 3) & 7, modrm & 7);
+            if reg != 2 && reg != 3 {
+                return None;
+            }
+            len += 2;
+            if mode != 3 && rm == 4 {
+                // The SIB byte, with no base register it is followed by a 32 bits displacement
+                let sib = *code.get(len)?;
+                len += 1;
+                if mode == 0 && sib & 7 == 5 {
+                    len += 4;
+                }
+            }
+            match mode {
+                0 if rm == 5 => len += 4,
+                1 => len += 1,
+                2 => len += 4,
+                _ => {}
+            }
+            Some(len)
+        }
+        _ => None,
+    }
+}
+
+/// Code of the program overwritten by `assemble`
+struct Patch {
+    addr: usize,
+    /// The bytes replaced by the patch, written back by `unpatch`
+    original: Vec<u8>,
+}
+
+/// A thread of the traced program
+struct Thread {
+    /// The number identifying the thread for the user, in creation order
+    id: usize,
+    pid: Pid,
+    /// The thread reported the stop following its creation, it can't be resumed before
+    attached: bool,
+    /// The thread has been resumed and hasn't been stopped since
+    running: bool,
+    /// A signal received while the thread was being stopped, delivered when it is resumed
+    pending_signal: Option<Signal>,
+    /// The registers at the last stop of the program
+    stop_regs: Option<user_regs_struct>,
+    /// The registers at the stop before the last one
+    previous_regs: Option<user_regs_struct>,
+    /// The last stop of the thread at a syscall
+    syscall_stop: Option<SyscallStop>,
+}
+
+/// The state of the debugger and of the debugged program
+///
+/// When the program stops, all its threads are stopped and the breakpoints are removed from its memory
+/// until it is resumed.
+pub struct Debugger {
+    program: String,
+    /// The file executed for `program`, `None` if it cannot be found
+    program_path: Option<PathBuf>,
+    /// The changes made to the environment of the debugger to get the environment of the program,
+    /// a variable is removed if its value is `None`
+    environment_changes: Vec<(String, Option<String>)>,
+    /// The arguments the program is run with, set by `set args`
+    args: Vec<String>,
+    symbols: Option<SymbolTable>,
+    /// The shared libraries loaded by the running program
+    libraries: Vec<SharedLibrary>,
+    debug_info: Option<DebugInfo>,
+    call_frame_info: Option<CallFrameInfo>,
+    user_breakpoints: Vec<UserBreakpoint>,
+    breakpoints: Vec<Breakpoint>,
+    /// The commands of the breakpoints hit at the last stop, not run yet
+    hit_commands: Vec<String>,
+    /// A breakpoint planted by the debugger to stop at a location, like a return address
+    temporary: Option<Breakpoint>,
+    /// The breakpoint in the function the dynamic linker calls when it loads a library,
+    /// planted while breakpoints wait for their library and left in the memory while the program is stopped
+    linker: Option<Breakpoint>,
+    /// The exit code of the last run of the program, 128 plus the signal if it was killed by one
+    exit_code: Option<i32>,
+    /// The watchpoints, the watchpoint `i` uses the debug register `i` unless they are checked by single stepping
+    watchpoints: Vec<Watchpoint>,
+    /// The debug registers can't be set, the written watchpoints are checked after each single step instead
+    software_watchpoints: bool,
+    /// The patches of the running program, in the order they were written
+    patches: Vec<Patch>,
+    /// The thread group leader of the running program
+    child: Option<Pid>,
+    /// The threads of the running program, in creation order
+    threads: Vec<Thread>,
+    /// The thread commands apply to, only meaningful while the program runs
+    current_thread: Pid,
+    next_thread_id: usize,
+    /// The syscalls the program stops at when it enters or leaves them
+    catch: Option<SyscallCatch>,
+    /// The program stops only when it leaves the caught syscalls
+    catch_exits_only: bool,
+    reporter: Reporter,
+    /// The location of the next source line listed by `list` without argument
+    next_listed: Option<SourceLocation>,
+    /// The registers saved by `regs save`, by name
+    snapshots: HashMap<String, user_regs_struct>,
+    /// The instructions executed by a thread since `record`
+    history: Option<History>,
+    /// The program has been resumed by `continue &` and hasn't stopped since
+    background: bool,
+    /// The index of the first breakpoint added while the program runs in the background, planted at the next stop
+    unplanted: Option<usize>,
+    /// The children of forks of the program that reported their first stop before the fork
+    forked: Vec<Pid>,
+    /// The handling of the signals changed by `handle`
+    signal_handlings: HashMap<Signal, SignalHandling>,
+    /// The expressions printed at each stop, with their number
+    displays: Vec<(usize, String)>,
+    next_display: usize,
+}
+
+impl Debugger {
+    /// Creates a debugger for `program`, reading its symbols and debugging information from `program_path`
+    pub fn new(program: String, program_path: Option<PathBuf>, reporter: Reporter) -> Self {
+        Self {
+            program,
+            environment_changes: Vec::new(),
+            args: Vec::new(),
+            symbols: program_path.as_deref().and_then(SymbolTable::load),
+            libraries: Vec::new(),
+            debug_info: program_path.as_deref().and_then(DebugInfo::load),
+            call_frame_info: program_path.as_deref().and_then(CallFrameInfo::load),
+            program_path,
+            user_breakpoints: Vec::new(),
+            breakpoints: Vec::new(),
+            hit_commands: Vec::new(),
+            temporary: None,
+            linker: None,
+            exit_code: None,
+            watchpoints: Vec::new(),
+            software_watchpoints: false,
+            patches: Vec::new(),
+            child: None,
+            threads: Vec::new(),
+            current_thread: Pid::from_raw(0),
+            next_thread_id: 1,
+            catch: None,
+            catch_exits_only: false,
+            reporter,
+            next_listed: None,
+            snapshots: HashMap::new(),
+            history: None,
+            background: false,
+            unplanted: None,
+            forked: Vec::new(),
+            signal_handlings: HashMap::new(),
+            displays: Vec::new(),
+            next_display: 0,
+        }
+    }
+
+    /// Returns the thread group leader of the running program
+    pub fn child(&self) -> Option<Pid> {
+        self.child
+    }
+
+    /// Returns the exit code of the last run of the program, `None` if it has not exited since it was run
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Adds a breakpoint, it is planted when the program is run
+    ///
+    /// A breakpoint in a shared library that isn't loaded is resolved when the program is run.
+    /// `arg` can list several locations separated by commas, a breakpoint is added at each location
+    /// that can be resolved and the invalid ones are reported one by one.
+    pub fn add_breakpoint(&mut self, arg: &str) {
+        let indices: Vec<usize> = arg
+            .split(',')
+            .filter(|location| !location.is_empty())
+            .filter_map(|location| self.push_breakpoint(location, None))
+            .collect();
+        match indices.as_slice() {
+            [] => {}
+            [index] => self
+                .reporter
+                .report(Event::BreakpointAdded { index: *index }),
+            [first, .., last] => self.reporter.report(Event::BreakpointsAdded {
+                first: *first,
+                last: *last,
+            }),
+        }
+    }
+
+    /// Adds a probe at `arg`, a breakpoint printing `expression` and its hit count and resuming the program when hit
+    pub fn add_probe(&mut self, arg: &str, expression: &str) {
+        if let Some(index) = self.push_breakpoint(arg, Some(expression.to_owned())) {
+            self.reporter.report(Event::BreakpointAdded { index });
+        }
+    }
+
+    /// Deletes the breakpoints resolving to `location`, given as for `breakpoint`, or all of them without it
+    ///
+    /// The traps of the deleted breakpoints are removed from the memory, and the following breakpoints are renumbered.
+    pub fn clear_breakpoints(&mut self, location: Option<&str>) {
+        let count = match location {
+            None => {
+                for breakpoint in self.breakpoints.iter_mut() {
+                    let _ = breakpoint.restore_data();
+                }
+                self.breakpoints = Vec::new();
+                std::mem::take(&mut self.user_breakpoints).len()
+            }
+            Some(location) => {
+                let addr = BreakpointArg::parse(location).and_then(|arg| {
+                    if let Some(pid) = self.child
+                        && arg
+                            .to_address(
+                                self.symbols.as_ref(),
+                                self.debug_info.as_ref(),
+                                &self.libraries,
+                            )
+                            .is_none()
+                    {
+                        self.read_libraries(pid);
+                    }
+                    arg.to_address(
+                        self.symbols.as_ref(),
+                        self.debug_info.as_ref(),
+                        &self.libraries,
+                    )
+                });
+                let Some(addr) = addr else {
+                    self.reporter
+                        .error(format!("Cannot resolve location '{location}'"));
+                    return;
+                };
+                let indices = self.breakpoint_indices(addr);
+                if indices.is_empty() {
+                    self.reporter.error(format!("No breakpoint at {location}"));
+                    return;
+                }
+                for &index in indices.iter().rev() {
+                    self.user_breakpoints.remove(index - 1);
+                }
+                if let Some(position) = self.breakpoints.iter().position(|bp| bp.addr == addr) {
+                    let _ = self.breakpoints[position].restore_data();
+                    self.breakpoints.remove(position);
+                }
+                indices.len()
+            }
+        };
+        // The commands of the last stop may belong to a deleted breakpoint
+        self.hit_commands = Vec::new();
+        self.reporter.report(Event::BreakpointsCleared { count });
+    }
+
+    /// Adds a breakpoint at `arg`, a probe of `probe` if given, and returns its index
+    ///
+    /// The errors and the pending breakpoints are reported, the added breakpoint is reported by the caller.
+    fn push_breakpoint(&mut self, arg: &str, probe: Option<String>) -> Option<usize> {
+        let breakpoint = BreakpointArg::parse(arg);
+        // The libraries opened since the last stop may define the location
+        if let (Some(breakpoint), Some(pid)) = (&breakpoint, self.child)
+            && breakpoint
+                .to_address(
+                    self.symbols.as_ref(),
+                    self.debug_info.as_ref(),
+                    &self.libraries,
+                )
+                .is_none()
+        {
+            self.read_libraries(pid);
+        }
+        // Before the program is run, the breakpoints of the libraries are resolved when it is
+        let unresolved = |breakpoint: &BreakpointArg| {
+            breakpoint
+                .to_address(
+                    self.symbols.as_ref(),
+                    self.debug_info.as_ref(),
+                    &self.libraries,
+                )
+                .is_none()
+                && (self.child.is_some() || breakpoint.library().is_none())
+        };
+        // A library that isn't loaded yet, or any library for a symbol missing from the program, may define it later
+        let later = |breakpoint: &BreakpointArg| match breakpoint.library() {
+            Some(library) => !self.libraries.iter().any(|shared| shared.matches(library)),
+            None => breakpoint.in_library(self.symbols.as_ref()),
+        };
+        match breakpoint {
+            Some(breakpoint)
+                if unresolved(&breakpoint)
+                    && later(&breakpoint)
+                    && !breakpoint::keeps_pending() =>
+            {
+                self.reporter.error(format!(
+                    "Cannot resolve breakpoint '{arg}', use set breakpoint pending on to set it when a library defines it"
+                ));
+                None
+            }
+            Some(breakpoint) if unresolved(&breakpoint) && !later(&breakpoint) => {
+                self.reporter
+                    .error(format!("Cannot resolve breakpoint '{arg}'"));
+                None
+            }
+            Some(breakpoint) => {
+                let pending = unresolved(&breakpoint);
+                self.user_breakpoints.push(UserBreakpoint {
+                    location: breakpoint,
+                    commands: Vec::new(),
+                    hits: 0,
+                    probe,
+                });
+                let index = self.user_breakpoints.len();
+                if !pending {
+                    // The memory of a program running in the background can't be written
+                    if self.background {
+                        self.unplanted.get_or_insert(index - 1);
+                    } else {
+                        self.plant_breakpoints(index - 1);
+                    }
+                    return Some(index);
+                }
+                self.reporter.report(Event::BreakpointPending { index });
+                if let Some(pid) = self.child
+                    && self.linker.is_none()
+                {
+                    self.watch_libraries(pid);
+                }
+                Some(index)
+            }
+            None => {
+                self.reporter.error(format!("Invalid breakpoint '{arg}'"));
+                None
+            }
+        }
+    }
+
+    /// Plants the traps of the breakpoints from the index `from` that resolve, while the program is stopped
+    ///
+    /// As for the other breakpoints, the traps are written in the memory when the program is resumed.
+    fn plant_breakpoints(&mut self, from: usize) {
+        let Some(pid) = self.child else {
+            return;
+        };
+        for index in from..self.user_breakpoints.len() {
+            let Some(addr) = self.user_breakpoints[index].location.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            ) else {
+                continue;
+            };
+            if self.breakpoints.iter().any(|bp| bp.addr == addr) {
+                continue;
+            }
+            match Breakpoint::create(addr, pid) {
+                Some(mut breakpoint) => {
+                    breakpoint.restore_data().unwrap();
+                    self.breakpoints.push(breakpoint);
+                }
+                None => self.reporter.error(format!(
+                    "Cannot insert breakpoint {} at {addr:#x}",
+                    index + 1
+                )),
+            }
+        }
+    }
+
+    /// Adds a hardware watchpoint of `len` bytes at `location`, given as for `breakpoint`
+    ///
+    /// Without `len`, the size of the symbol at `location` is used if it fits in a debug register, otherwise 8 bytes.
+    /// The watchpoint is set in the threads of the running program, or when the program is run.
+    pub fn add_watchpoint(&mut self, kind: WatchKind, location: &str, len: Option<usize>) {
+        let Some((arg, addr)) = BreakpointArg::parse(location).and_then(|arg| {
+            let addr = arg.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            )?;
+            Some((arg, addr))
+        }) else {
+            self.reporter
+                .error(format!("Cannot resolve location '{location}'"));
+            return;
+        };
+        let symbol = self
+            .symbols
+            .as_ref()
+            .and_then(|symbols| symbols.symbol_at(addr));
+        let len = len.unwrap_or_else(|| {
+            let size = symbol
+                .filter(|(symbol, start)| *start == addr && symbol.size > 0)
+                .map_or(8, |(symbol, _)| symbol.size);
+            // The first bytes of the symbol that a debug register can watch
+            [8, 4, 2, 1]
+                .into_iter()
+                .find(|&len| len <= size && addr.is_multiple_of(len))
+                .unwrap()
+        });
+        let symbol =
+            symbol.map(|(symbol, start)| (symbol.display_name().into_owned(), addr - start));
+        // The symbols of a position independent executable are relocated when it is run
+        let relocated = self.child.is_some()
+            || matches!(arg, BreakpointArg::Address(_))
+            || self
+                .symbols
+                .as_ref()
+                .is_some_and(|symbols| !symbols.is_position_independent());
+        if ![1, 2, 4, 8].contains(&len) {
+            self.reporter
+                .error("Watchpoints are 1, 2, 4 or 8 bytes long");
+            return;
+        }
+        if !addr.is_multiple_of(len) {
+            self.reporter.error(format!(
+                "A watchpoint of {len} bytes must be aligned on {len} bytes"
+            ));
+            return;
+        }
+        if self.software_watchpoints && kind != WatchKind::Write {
+            self.reporter.error(
+                "The debug registers can't be set, only the writes of the memory can be watched",
+            );
+            return;
+        }
+        if !self.software_watchpoints && self.watchpoints.len() == watchpoint::SLOTS {
+            self.reporter.error(format!(
+                "All the {} debug registers are used, no more watchpoints can be set",
+                watchpoint::SLOTS
+            ));
+            return;
+        }
+        let mut watchpoint = Watchpoint {
+            kind,
+            location: arg,
+            addr,
+            len,
+            hits: 0,
+            value: 0,
+            scope: None,
+            software: self.software_watchpoints,
+        };
+        if let Some(pid) = self.child {
+            watchpoint.value = watchpoint.read_value(pid).unwrap_or(0);
+            watchpoint.scope = self.stack_frame_of(addr);
+        }
+        self.watchpoints.push(watchpoint);
+        let attached = self.threads.iter().filter(|thread| thread.attached);
+        if !self.software_watchpoints
+            && attached
+                .map(|thread| watchpoint::install(thread.pid, &self.watchpoints))
+                .any(|result| result.is_err())
+            && !self.fall_back_to_software()
+        {
+            self.watchpoints.pop();
+            for thread in self.threads.iter().filter(|thread| thread.attached) {
+                let _ = watchpoint::install(thread.pid, &self.watchpoints);
+            }
+            self.reporter
+                .error(format!("Cannot set a watchpoint at {addr:#x}"));
+            return;
+        }
+        self.reporter.report(Event::WatchpointAdded {
+            index: self.watchpoints.len(),
+            kind,
+            addr: relocated.then_some(addr),
+            symbol: symbol
+                .as_ref()
+                .map(|(name, offset)| (name.as_ref(), *offset)),
+            len,
+            frame: self
+                .watchpoints
+                .last()
+                .unwrap()
+                .scope
+                .map(|scope| scope.cfa),
+            software: self.software_watchpoints,
+        });
+    }
+
+    /// Returns the current thread and the canonical frame address of the frame whose stack holds `addr`,
+    /// `None` if `addr` is not in the stack of the current thread
+    fn stack_frame_of(&self, addr: usize) -> Option<Scope> {
+        let tid = self.current_thread;
+        let regs = ptrace::getregs(tid).unwrap();
+        let maps = read_maps(tid)?;
+        let stack = maps.iter().find(|map| map.contains(regs.rsp as _))?;
+        // A function that calls no other function can keep its variables in the 128 bytes under the stack pointer
+        if addr < (regs.rsp as usize).saturating_sub(128) || !stack.contains(addr) {
+            return None;
+        }
+        self.scopes().into_iter().find(|scope| addr < scope.cfa)
+    }
+
+    /// Returns the frames of the current thread, from the innermost one, as far as they can be unwound
+    fn scopes(&self) -> Vec<Scope> {
+        let tid = self.current_thread;
+        let regs = ptrace::getregs(tid).unwrap();
+        let mut registers = Registers::new(&regs);
+        let mut innermost = Some(&regs);
+        let mut sp = regs.rsp as usize;
+        let mut scopes = Vec::new();
+        // Each frame is above the previous one
+        while let Some(pc) = registers.pc()
+            && let Some(unwound) = self
+                .unwind_frame(&registers, innermost)
+                .filter(|unwound| unwound.cfa > sp)
+        {
+            // The return address of a caller can be the first instruction of the next function
+            let lookup = if innermost.is_some() { pc } else { pc - 1 };
+            scopes.push(Scope {
+                thread: tid,
+                cfa: unwound.cfa,
+                function: self.function_range(lookup as _).map(|(start, _)| start),
+            });
+            sp = unwound.cfa;
+            registers = unwound.caller;
+            innermost = None;
+        }
+        scopes
+    }
+
+    /// Checks if the frame holding the memory watched by `watchpoint` has returned
+    ///
+    /// The frame has returned when the stack pointer of its thread reached its canonical frame address,
+    /// or when another function has been called in its place. The frames of other threads
+    /// are only checked with their stack pointer.
+    fn left_scope(&self, watchpoint: &Watchpoint) -> bool {
+        let Some(scope) = watchpoint.scope else {
+            return false;
+        };
+        let Some(regs) = self.child.and_then(|_| ptrace::getregs(scope.thread).ok()) else {
+            return true;
+        };
+        if regs.rsp as usize >= scope.cfa {
+            return true;
+        }
+        scope.thread == self.current_thread
+            && !self
+                .scopes()
+                .iter()
+                .any(|frame| frame.cfa == scope.cfa && frame.function == scope.function)
+    }
+
+    /// Deletes the watchpoints whose frame has returned, the next watchpoints taking their slots
+    fn delete_left_watchpoints(&mut self) {
+        let left: Vec<usize> = (0..self.watchpoints.len())
+            .filter(|&index| self.left_scope(&self.watchpoints[index]))
+            .collect();
+        if left.is_empty() {
+            return;
+        }
+        for &index in left.iter().rev() {
+            self.watchpoints.remove(index);
+        }
+        for index in left {
+            self.reporter
+                .report(Event::WatchpointScopeLeft { index: index + 1 });
+        }
+        if self.software_watchpoints {
+            return;
+        }
+        for thread in self.threads.iter().filter(|thread| thread.attached) {
+            let _ = watchpoint::install(thread.pid, &self.watchpoints);
+        }
+    }
+
+    /// Checks the watchpoints by single stepping from now on, as the debug registers can't be set
+    ///
+    /// Returns `false` if a read or access watchpoint is set, only the processor can check them.
+    /// The user is warned the first time, as the program then runs much slower.
+    fn fall_back_to_software(&mut self) -> bool {
+        if self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.kind != WatchKind::Write)
+        {
+            return false;
+        }
+        if !self.software_watchpoints {
+            self.software_watchpoints = true;
+            for watchpoint in self.watchpoints.iter_mut() {
+                watchpoint.software = true;
+            }
+            self.reporter.info(
+                "The debug registers can't be set, the watchpoints are checked by single stepping, the program runs much slower",
+            );
+        }
+        for thread in self.threads.iter().filter(|thread| thread.attached) {
+            let _ = watchpoint::install(thread.pid, &[]);
+        }
+        true
+    }
+
+    /// Returns the slots of the watchpoints whose value changed since it was last read, checked by single stepping
+    fn changed_watchpoints(&self, tid: Pid) -> Vec<usize> {
+        (0..self.watchpoints.len())
+            .filter(|&slot| {
+                let watchpoint = &self.watchpoints[slot];
+                watchpoint
+                    .read_value(tid)
+                    .is_some_and(|value| value != watchpoint.value)
+            })
+            .collect()
+    }
+
+    /// Lists the hardware watchpoints with the number of times they were hit
+    pub fn info_watchpoints(&self) {
+        self.reporter.report(Event::Watchpoints(&self.watchpoints));
+    }
+
+    /// Returns the commands run when the breakpoint `index` is hit, `None` if there is no such breakpoint
+    pub fn breakpoint_commands(&mut self, index: usize) -> Option<&mut Vec<String>> {
+        let breakpoint = self.user_breakpoints.get_mut(index.checked_sub(1)?)?;
+        Some(&mut breakpoint.commands)
+    }
+
+    /// Takes the commands of the breakpoints hit at the last stop
+    pub fn take_hit_commands(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.hit_commands)
+    }
+
+    /// Changes what is done when the program receives `signal` with the actions of the `handle` command
+    pub fn handle_signal(&mut self, signal: Signal, actions: &[&str]) {
+        if signal == Signal::SIGTRAP {
+            self.reporter
+                .error("SIGTRAP is used by the debugger and cannot be handled");
+            return;
+        }
+        let mut handling = self.signal_handling(signal);
+        for action in actions {
+            if handling.apply(action).is_none() {
+                self.reporter.error(format!("Invalid action '{action}'"));
+                return;
+            }
+        }
+        self.signal_handlings.insert(signal, handling);
+        self.reporter
+            .report(Event::SignalHandling { signal, handling });
+    }
+
+    /// Returns what is done when the program receives `signal`
+    fn signal_handling(&self, signal: Signal) -> SignalHandling {
+        self.signal_handlings
+            .get(&signal)
+            .copied()
+            .unwrap_or_else(|| SignalHandling::default(signal))
+    }
+
+    /// Makes the program stop when it enters or leaves a syscall, only when it leaves it if `exits_only` is set
+    ///
+    /// With no argument, the program stops at every syscall, otherwise only at the syscall `arg`,
+    /// given by name or number.
+    pub fn catch_syscall(&mut self, arg: Option<&str>, exits_only: bool) {
+        match SyscallCatch::parse(arg) {
+            Some(catch) => {
+                let stops = if exits_only { "the returns of " } else { "" };
+                match catch {
+                    SyscallCatch::All => {
+                        self.reporter.info(format!("Catching {stops}all syscalls"))
+                    }
+                    SyscallCatch::Only(number) => self.reporter.info(format!(
+                        "Catching {stops}syscall {}",
+                        syscalls::format_name(number)
+                    )),
+                }
+                self.catch = Some(catch);
+                self.catch_exits_only = exits_only;
+            }
+            None => self
+                .reporter
+                .error(format!("Unknown syscall '{}'", arg.unwrap_or_default())),
+        }
+    }
+
+    /// Sets the variable `name` to `value` in the environment of the program
+    pub fn set_env(&mut self, name: &str, value: &str) {
+        self.environment_changes
+            .push((name.to_owned(), Some(value.to_owned())));
+    }
+
+    /// Sets the arguments the program is run with
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Removes the variable `name` from the environment of the program
+    pub fn unset_env(&mut self, name: &str) {
+        self.environment_changes.push((name.to_owned(), None));
+    }
+
+    /// Builds the environment of the program from the environment of the debugger
+    fn environment(&self) -> Vec<CString> {
+        let mut environment: Vec<(String, String)> = env::vars().collect();
+        for (name, value) in &self.environment_changes {
+            environment.retain(|(var, _)| var != name);
+            if let Some(value) = value {
+                environment.push((name.clone(), value.clone()));
+            }
+        }
+        environment
+            .into_iter()
+            .filter_map(|(name, value)| CString::new(format!("{name}={value}")).ok())
+            .collect()
+    }
+
+    /// Launches the program and runs it until it stops
+    ///
+    /// The program is run with `args` if they are given, otherwise with the arguments set by `set args`.
+    pub fn run(&mut self, args: Option<&[String]>) {
+        if !self.launch(args) {
+            return;
+        }
+        let tid = self.current_thread;
+        // The program may have been run to its entry point, where a breakpoint can be
+        if self
+            .breakpoint_indices(ptrace::getregs(tid).unwrap().rip as _)
+            .is_empty()
+        {
+            self.cont();
+        } else {
+            self.report(&WaitStatus::Stopped(tid, Signal::SIGTRAP));
+        }
+    }
+
+    /// Launches the program and runs it until `main`, or its entry point if it has no `main` symbol
+    ///
+    /// The program stops there even if it has no breakpoint there.
+    pub fn start(&mut self) {
+        if self.symbols.is_none() {
+            self.reporter
+                .error(format!("Cannot find the entry point of '{}'", self.program));
+            return;
+        }
+        if !self.launch(None) {
+            return;
+        }
+        // The symbols are relocated by the launch, `main` is found without the dynamic linker
+        let Some(main) = self.symbols.as_ref().unwrap().lookup("main") else {
+            self.reporter
+                .info("Cannot find main, stopping at the entry point");
+            self.stop_at_entry();
+            return;
+        };
+        if self.run_to(main) {
+            self.reporter.report(Event::MainReached { addr: main });
+        }
+    }
+
+    /// Launches the program and runs it until its entry point, before the C runtime is initialized
+    pub fn starti(&mut self) {
+        if self.symbols.is_none() {
+            self.reporter
+                .error(format!("Cannot find the entry point of '{}'", self.program));
+            return;
+        }
+        if self.launch(None) {
+            self.stop_at_entry();
+        }
+    }
+
+    /// Runs the launched program until its entry point and reports it
+    fn stop_at_entry(&mut self) {
+        if !self.run_to_entry() {
+            return;
+        }
+        let entry = self.symbols.as_ref().unwrap().entry();
+        self.reporter.report(Event::EntryPoint { addr: entry });
+    }
+
+    /// Runs the launched program until its entry point
+    ///
+    /// Returns `false` if the program stopped elsewhere, the stop is then reported.
+    fn run_to_entry(&mut self) -> bool {
+        let Some(entry) = self.symbols.as_ref().map(SymbolTable::entry) else {
+            self.reporter
+                .error(format!("Cannot find the entry point of '{}'", self.program));
+            return true;
+        };
+        self.run_to(entry)
+    }
+
+    /// Runs the stopped program until the current thread reaches `addr`
+    ///
+    /// Returns `false` if the program stopped elsewhere, the stop is then reported.
+    fn run_to(&mut self, addr: usize) -> bool {
+        let tid = self.current_thread;
+        if ptrace::getregs(tid).unwrap().rip as usize == addr {
+            return true;
+        }
+        self.temporary = Breakpoint::create(addr, tid);
+        let waitstatus = match self.resume() {
+            Some(waitstatus) => waitstatus,
+            None => self.wait(),
+        };
+        self.stop(&waitstatus);
+        self.temporary = None;
+        if !matches!(waitstatus, WaitStatus::Stopped(_, Signal::SIGTRAP))
+            || ptrace::getregs(tid).unwrap().rip as usize != addr
+        {
+            self.report(&waitstatus);
+            return false;
+        }
+        true
+    }
+
+    /// Launches the program with `args`, or the arguments set by `set args`, returns `false` if it cannot be launched
+    ///
+    /// The program is stopped after its `execve`, with its breakpoints planted.
+    fn launch(&mut self, args: Option<&[String]>) -> bool {
+        if self.child.is_some() {
+            self.reporter.error("Program already running");
+            return false;
+        }
+        let Some(program_path) = self.program_path.as_deref() else {
+            self.reporter
+                .error(format!("Program '{}' not found", self.program));
+            return false;
+        };
+        if let Err(errno) = access(program_path, AccessFlags::X_OK) {
+            self.reporter.error(format!(
+                "Cannot execute '{}' : {}",
+                program_path.display(),
+                errno.desc()
+            ));
+            return false;
+        }
+        let args = args.unwrap_or(&self.args);
+        self.exit_code = None;
+        let pid = match launch_program(&self.program, args, &self.environment()) {
+            Ok(pid) => pid,
+            Err(errno) => {
+                self.reporter.error(format!(
+                    "Error launching '{}' : {}",
+                    self.program,
+                    errno.desc()
+                ));
+                return false;
+            }
+        };
+        if let Some(symbols) = self.symbols.as_mut() {
+            match symbols.relocate(pid) {
+                Some(bias) => {
+                    if let Some(debug_info) = self.debug_info.as_mut() {
+                        debug_info.relocate(bias);
+                    }
+                    if let Some(call_frame_info) = self.call_frame_info.as_mut() {
+                        call_frame_info.relocate(bias);
+                    }
+                }
+                None => self.reporter.error(format!(
+                    "Cannot find the load address of '{}'",
+                    self.program
+                )),
+            }
+        }
+        self.breakpoints = Vec::new();
+        self.libraries = Vec::new();
+        for breakpoint in &mut self.user_breakpoints {
+            breakpoint.hits = 0;
+        }
+        self.child = Some(pid);
+        self.current_thread = pid;
+        self.next_thread_id = 1;
+        // Like breakpoints, the watchpoints are resolved again as the load address may change between runs
+        for (index, watchpoint) in self.watchpoints.iter_mut().enumerate() {
+            match watchpoint.location.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            ) {
+                Some(addr) => watchpoint.addr = addr,
+                None => self
+                    .reporter
+                    .error(format!("Cannot resolve watchpoint {}", index + 1)),
+            }
+            watchpoint.value = watchpoint.read_value(pid).unwrap_or(0);
+        }
+        self.add_thread(pid, true);
+
+        // The shared libraries are loaded by the dynamic linker before the entry point is reached
+        if self
+            .user_breakpoints
+            .iter()
+            .any(|breakpoint| breakpoint.location.in_library(self.symbols.as_ref()))
+        {
+            if !self.run_to_entry() {
+                return false;
+            }
+            self.read_libraries(pid);
+        }
+
+        // The breakpoints are resolved again as the load address may change between runs,
+        // breakpoints resolving to the same address share a single trap
+        let mut pending = false;
+        for (index, breakpoint) in self.user_breakpoints.iter().enumerate() {
+            let Some(addr) = breakpoint.location.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            ) else {
+                let later = match breakpoint.location.library() {
+                    Some(library) => !self.libraries.iter().any(|shared| shared.matches(library)),
+                    None => breakpoint.location.in_library(self.symbols.as_ref()),
+                };
+                if later {
+                    self.reporter
+                        .report(Event::BreakpointPending { index: index + 1 });
+                    pending = true;
+                } else {
+                    self.reporter
+                        .error(format!("Cannot resolve breakpoint {}", index + 1));
+                }
+                continue;
+            };
+            if self.breakpoints.iter().any(|bp| bp.addr == addr) {
+                continue;
+            }
+            match Breakpoint::create(addr, pid) {
+                Some(breakpoint) => self.breakpoints.push(breakpoint),
+                None => self.reporter.error(format!(
+                    "Cannot insert breakpoint {} at {addr:#x}",
+                    index + 1
+                )),
+            }
+        }
+        if pending {
+            self.watch_libraries(pid);
+        }
+        true
+    }
+
+    /// Returns the `r_debug` structure of the dynamic linker, `None` for a statically linked program
+    fn rendezvous(&self, pid: Pid) -> Option<Rendezvous> {
+        Rendezvous::find(pid, self.symbols.as_ref()?.dynamic()?)
+    }
+
+    /// Lists the paths of the shared libraries loaded by the program with their load bias
+    ///
+    /// They are read from the list of the dynamic linker, or from the mappings of the program
+    /// before the linker has filled `r_debug`.
+    fn mapped_libraries(&self, pid: Pid) -> Vec<(String, usize)> {
+        self.rendezvous(pid)
+            .and_then(|rendezvous| rendezvous.libraries(pid))
+            .unwrap_or_else(|| SharedLibrary::mapped(pid))
+    }
+
+    /// Plants the breakpoint of the dynamic linker, to set the pending breakpoints when their library is loaded
+    ///
+    /// The linker calls the function at `r_brk` of `r_debug`, `_dl_debug_state`, each time it loads or unloads libraries.
+    fn watch_libraries(&mut self, pid: Pid) {
+        let addr = self
+            .rendezvous(pid)
+            .and_then(|rendezvous| rendezvous.breakpoint(pid))
+            .filter(|&addr| addr != 0)
+            .or_else(|| {
+                self.libraries
+                    .iter()
+                    .find_map(|library| library.symbols.lookup("_dl_debug_state"))
+            });
+        self.linker = addr.and_then(|addr| Breakpoint::create(addr, pid));
+        if self.linker.is_none() {
+            self.reporter
+                .error("Cannot find the dynamic linker, the pending breakpoints will not be set");
+        }
+    }
+
+    /// Reads the libraries loaded since the last time and plants the pending breakpoints of these libraries
+    ///
+    /// The breakpoints are written in the memory only if the program is running.
+    fn load_libraries(&mut self, tid: Pid, running: bool) {
+        self.read_libraries(tid);
+        let pid = self.child.unwrap();
+        for (index, breakpoint) in self.user_breakpoints.iter().enumerate() {
+            if !breakpoint.location.in_library(self.symbols.as_ref()) {
+                continue;
+            }
+            let Some(addr) = breakpoint.location.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            ) else {
+                continue;
+            };
+            if self.breakpoints.iter().any(|bp| bp.addr == addr) {
+                continue;
+            }
+            let Some(mut planted) = Breakpoint::create(addr, pid) else {
+                continue;
+            };
+            if !running {
+                planted.restore_data().unwrap();
+            }
+            self.breakpoints.push(planted);
+            self.reporter.report(Event::BreakpointResolved {
+                index: index + 1,
+                addr,
+            });
+        }
+    }
+
+    /// Reads the symbols of the libraries mapped since the last time
+    fn read_libraries(&mut self, tid: Pid) {
+        for (path, bias) in self.mapped_libraries(tid) {
+            if self.libraries.iter().any(|library| library.path == path) {
+                continue;
+            }
+            if let Some(library) = SharedLibrary::load(path, bias) {
+                self.libraries.push(library);
+            }
+        }
+    }
+
+    /// Checks if the thread `tid` stopped at the breakpoint of the dynamic linker
+    fn is_linker_hit(&self, tid: Pid) -> bool {
+        self.linker.as_ref().is_some_and(|linker| {
+            !is_single_step(tid) && ptrace::getregs(tid).unwrap().rip as usize == linker.addr + 1
+        })
+    }
+
+    /// Resumes all the threads until the program stops
+    ///
+    /// While recording, only the recorded thread runs, by single steps. When the watchpoints are checked
+    /// by single stepping, only the current thread runs, until a watched value changes or Ctrl-C.
+    pub fn cont(&mut self) {
+        let watching = self.software_watchpoints && !self.watchpoints.is_empty();
+        let stepped = match &self.history {
+            Some(history) => Some(history.thread),
+            None => watching.then_some(self.current_thread),
+        };
+        if let Some(tid) = stepped {
+            let ctrl_c = watching.then(CtrlC::catch);
+            loop {
+                let waitstatus = self.step_thread(tid);
+                if !is_step_completed(&waitstatus, tid)
+                    || self
+                        .breakpoints
+                        .iter()
+                        .any(|bp| bp.addr == ptrace::getregs(tid).unwrap().rip as usize)
+                    || (watching && !self.changed_watchpoints(tid).is_empty())
+                    || ctrl_c.as_ref().is_some_and(CtrlC::interrupted)
+                {
+                    self.wait_and_check(&waitstatus);
+                    return;
+                }
+            }
+        }
+        let waitstatus = match self.resume() {
+            Some(waitstatus) => waitstatus,
+            None => self.wait(),
+        };
+        self.wait_and_check(&waitstatus);
+    }
+
+    /// Resumes all the threads and returns without waiting for the program to stop
+    ///
+    /// The stop of the program is reported by `poll`, `interrupt` stops it.
+    pub fn cont_background(&mut self) {
+        if self.software_watchpoints && !self.watchpoints.is_empty() {
+            self.reporter
+                .error("The watchpoints are checked by single stepping, the program can't run in the background");
+            return;
+        }
+        if let Some(waitstatus) = self.resume() {
+            self.wait_and_check(&waitstatus);
+            return;
+        }
+        self.background = true;
+        self.reporter
+            .info("Continuing in the background, use interrupt to stop the program");
+    }
+
+    /// Checks if the program runs in the background
+    pub fn is_running(&self) -> bool {
+        self.background
+    }
+
+    /// Reports the stop of the program running in the background, if it stopped
+    pub fn poll(&mut self) {
+        if !self.background {
+            return;
+        }
+        let waitstatus = self.wait_with(WaitPidFlag::__WALL | WaitPidFlag::WNOHANG);
+        if waitstatus != WaitStatus::StillAlive {
+            self.wait_and_check(&waitstatus);
+        }
+    }
+
+    /// Stops the program running in the background with a `SIGSTOP`
+    pub fn interrupt(&mut self) {
+        if !self.background {
+            self.reporter
+                .error("The program is not running in the background");
+            return;
+        }
+        self.background = false;
+        signal::kill(self.child.unwrap(), Signal::SIGSTOP).unwrap();
+        let waitstatus = self.wait();
+        match waitstatus {
+            WaitStatus::Stopped(tid, Signal::SIGSTOP) => {
+                self.stop(&waitstatus);
+                self.switch_thread(tid);
+                self.print_location(ptrace::getregs(tid).unwrap().rip as _);
+            }
+            // The program stopped by itself before receiving the signal
+            _ => self.wait_and_check(&waitstatus),
+        }
+    }
+
+    /// Executes a single instruction of the current thread
+    ///
+    /// The other threads stay stopped, so that they don't run past breakpoints meanwhile.
+    pub fn stepi(&mut self) {
+        let waitstatus = self.step_thread(self.current_thread);
+        self.wait_and_check(&waitstatus);
+    }
+
+    /// Starts recording the instructions executed by the current thread, keeping the last `depth` ones
+    pub fn record(&mut self, depth: usize) {
+        let thread = self
+            .threads
+            .iter()
+            .find(|thread| thread.pid == self.current_thread)
+            .unwrap();
+        self.reporter.info(format!(
+            "Recording thread {}, the last {depth} instructions are kept",
+            thread.id
+        ));
+        self.history = Some(History::new(self.current_thread, depth));
+    }
+
+    /// Stops recording and forgets the recorded instructions
+    pub fn record_stop(&mut self) {
+        match self.history.take() {
+            Some(_) => self.reporter.info("Recording stopped"),
+            None => self.reporter.error("The program is not being recorded"),
+        }
+    }
+
+    /// Stops recording after the state of the recorded thread couldn't be saved or restored
+    fn stop_recording(&mut self, err: record::Error) {
+        self.reporter.error(format!("{err}, recording stopped"));
+        self.history = None;
+    }
+
+    /// Goes back before the last recorded instruction of the recorded thread
+    pub fn reverse_stepi(&mut self) {
+        let Some(history) = self.history.as_mut() else {
+            self.reporter.error("The program is not being recorded");
+            return;
+        };
+        let tid = history.thread;
+        match history.undo() {
+            Ok(true) => {}
+            Ok(false) => {
+                self.reporter.info("No more recorded history");
+                return;
+            }
+            Err(err) => {
+                self.stop_recording(err);
+                return;
+            }
+        }
+        self.switch_thread(tid);
+        self.print_location(ptrace::getregs(tid).unwrap().rip as _);
+    }
+
+    /// Goes back through the recorded instructions until a breakpoint or the beginning of the history
+    pub fn reverse_continue(&mut self) {
+        let Some(history) = self.history.as_mut() else {
+            self.reporter.error("The program is not being recorded");
+            return;
+        };
+        let tid = history.thread;
+        self.switch_thread(tid);
+        loop {
+            let history = self.history.as_mut().unwrap();
+            match history.undo() {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.reporter.info("No more recorded history");
+                    break;
+                }
+                Err(err) => {
+                    self.stop_recording(err);
+                    break;
+                }
+            }
+            let rip = ptrace::getregs(tid).unwrap().rip as usize;
+            let indices = self.breakpoint_indices(rip);
+            if !indices.is_empty() {
+                self.reporter
+                    .report(Event::BreakpointHit { indices, addr: rip });
+                return;
+            }
+        }
+        self.print_location(ptrace::getregs(tid).unwrap().rip as _);
+    }
+
+    /// Executes a single instruction of the current thread, stepping over calls
+    ///
+    /// When the instruction is a call, the program runs until the called function returns.
+    pub fn nexti(&mut self) {
+        let tid = self.current_thread;
+        let regs = ptrace::getregs(tid).unwrap();
+        let Some(len) =
+            read_data_fixed::<15>(tid, regs.rip as _).and_then(|code| call_length(&code))
+        else {
+            self.stepi();
+            return;
+        };
+        // The call pushes the return address, the function returned once it is popped
+        let return_addr = (regs.rip as usize).wrapping_add(len);
+        if let Some(waitstatus) =
+            self.run_until_return(return_addr, (regs.rsp as usize).wrapping_sub(8))
+        {
+            self.report(&waitstatus);
+            return;
+        }
+        self.reporter.report(Event::Location {
+            addr: return_addr,
+            function: None,
+            location: None,
+        });
+    }
+
+    /// Single steps the current thread until the value of the register `name` changes
+    ///
+    /// The other threads stay stopped. The program also stops if a breakpoint is reached.
+    pub fn watch_register(&mut self, name: &str) {
+        let tid = self.current_thread;
+        let Some(mut value) = register(&ptrace::getregs(tid).unwrap(), name) else {
+            self.reporter.error(format!("Unknown register '{name}'"));
+            return;
+        };
+        self.reporter.info(format!(
+            "Watching ${name} by single stepping, the program runs much slower"
+        ));
+        loop {
+            let waitstatus = self.step_thread(tid);
+            if !is_step_completed(&waitstatus, tid) {
+                self.wait_and_check(&waitstatus);
+                return;
+            }
+            let regs = ptrace::getregs(tid).unwrap();
+            if self
+                .breakpoints
+                .iter()
+                .any(|bp| bp.addr == regs.rip as usize)
+            {
+                self.wait_and_check(&waitstatus);
+                return;
+            }
+            let new_value = register(&regs, name).expect("the register exists");
+            if new_value != value {
+                self.reporter.report(Event::RegisterChanged {
+                    name,
+                    old: value,
+                    new: new_value,
+                });
+                self.print_location(regs.rip as _);
+                return;
+            }
+            value = new_value;
+        }
+    }
+
+    /// Single steps the current thread from `from` until it reaches `to`, both given as for `breakpoint`,
+    /// then prints the number of executed instructions and the most executed addresses
+    ///
+    /// If the current thread is not at `from`, the program first runs until it reaches it.
+    /// The other threads stay stopped while stepping. Counting also stops at a breakpoint,
+    /// when the program stops or exits, and on Ctrl-C.
+    pub fn count_instructions(&mut self, from: &str, to: &str) {
+        let resolve = |arg| {
+            BreakpointArg::parse(arg).and_then(|arg| {
+                arg.to_address(
+                    self.symbols.as_ref(),
+                    self.debug_info.as_ref(),
+                    &self.libraries,
+                )
+            })
+        };
+        let (Some(start), Some(end)) = (resolve(from), resolve(to)) else {
+            let arg = if resolve(from).is_none() { from } else { to };
+            self.reporter
+                .error(format!("Cannot resolve location '{arg}'"));
+            return;
+        };
+        if ptrace::getregs(self.current_thread).unwrap().rip as usize != start {
+            self.temporary = Breakpoint::create(start, self.current_thread);
+            let waitstatus = match self.resume() {
+                Some(waitstatus) => waitstatus,
+                None => self.wait(),
+            };
+            self.stop(&waitstatus);
+            self.temporary = None;
+            match waitstatus {
+                WaitStatus::Stopped(tid, Signal::SIGTRAP)
+                    if ptrace::getregs(tid).unwrap().rip as usize == start =>
+                {
+                    self.switch_thread(tid);
+                }
+                _ => {
+                    self.report(&waitstatus);
+                    self.reporter
+                        .info(format!("The program stopped before reaching {start:#x}"));
+                    return;
+                }
+            }
+        }
+
+        let tid = self.current_thread;
+        self.reporter.info(format!(
+            "Counting instructions from {start:#x} to {end:#x} by single stepping"
+        ));
+        let ctrl_c = CtrlC::catch();
+        let mut hits: HashMap<usize, usize> = HashMap::new();
+        let mut addr = start;
+        let mut count = 0;
+        let stop = loop {
+            *hits.entry(addr).or_default() += 1;
+            count += 1;
+            let waitstatus = self.step_thread(tid);
+            // Stepping over a syscall doesn't report a trace trap, any SIGTRAP is a step as no breakpoint is planted
+            if !matches!(waitstatus, WaitStatus::Stopped(_, Signal::SIGTRAP)) {
+                break Some(waitstatus);
+            }
+            addr = ptrace::getregs(tid).unwrap().rip as _;
+            if addr == end {
+                break None;
+            }
+            if ctrl_c.interrupted() || !self.breakpoint_indices(addr).is_empty() {
+                break Some(waitstatus);
+            }
+        };
+        drop(ctrl_c);
+
+        let mut hottest: Vec<(usize, usize)> = hits.into_iter().collect();
+        hottest.sort_by(|(addr, hits), (other_addr, other_hits)| {
+            other_hits.cmp(hits).then(addr.cmp(other_addr))
+        });
+        let hottest: Vec<_> = hottest
+            .into_iter()
+            .take(10)
+            .map(|(addr, hits)| {
+                let symbol = self
+                    .symbols
+                    .as_ref()
+                    .and_then(|symbols| symbols.symbol_at(addr))
+                    .map(|(symbol, start)| (symbol.display_name(), addr - start));
+                (addr, symbol, hits)
+            })
+            .collect();
+        let hottest = hottest
+            .iter()
+            .map(|(addr, symbol, hits)| {
+                let symbol = symbol
+                    .as_ref()
+                    .map(|(name, offset)| (name.as_ref(), *offset));
+                (*addr, symbol, *hits)
+            })
+            .collect();
+        self.reporter.report(Event::InstructionCount {
+            count,
+            reached: stop.is_none(),
+            hottest,
+        });
+        match stop {
+            Some(WaitStatus::Stopped(_, Signal::SIGTRAP)) => {
+                let indices = self.breakpoint_indices(addr);
+                match indices.is_empty() {
+                    true => self.print_location(addr),
+                    false => {
+                        self.count_hits(&indices);
+                        self.reporter.report(Event::BreakpointHit { indices, addr });
+                    }
+                }
+            }
+            Some(waitstatus) => self.wait_and_check(&waitstatus),
+            None => self.print_location(end),
+        }
+    }
+
+    /// Steps the current thread until the source line changes or the current function is left
+    ///
+    /// Called functions are stepped over, unless `step_into` is set and the called function has line info.
+    pub fn step_line(&mut self, step_into: bool) {
+        if self.debug_info.is_none() {
+            self.reporter
+                .error(format!("No debugging information for '{}'", self.program));
+            return;
+        }
+        let tid = self.current_thread;
+        let mut regs = ptrace::getregs(tid).unwrap();
+        let Some(start) = self.line_at(regs.rip as _) else {
+            self.reporter
+                .error(format!("No line info for {:#x}, use stepi", regs.rip));
+            return;
+        };
+        let start_function = self.function_entry(regs.rip as _);
+
+        loop {
+            let previous = regs;
+            let waitstatus = self.step_thread(tid);
+            if !is_step_completed(&waitstatus, tid) {
+                self.wait_and_check(&waitstatus);
+                return;
+            }
+            regs = ptrace::getregs(tid).unwrap();
+            let rip = regs.rip as usize;
+            if self.breakpoints.iter().any(|bp| bp.addr == rip) {
+                self.wait_and_check(&waitstatus);
+                return;
+            }
+            if let Some(return_addr) = call_return_address(tid, &previous, &regs) {
+                if step_into && self.line_at(rip).is_some() {
+                    break;
+                }
+                if let Some(waitstatus) = self.run_until_return(return_addr, regs.rsp as _) {
+                    self.report(&waitstatus);
+                    return;
+                }
+                regs = ptrace::getregs(tid).unwrap();
+                continue;
+            }
+            if self.function_entry(rip) != start_function {
+                break;
+            }
+            if self.line_at(rip).is_some_and(|location| location != start) {
+                break;
+            }
+        }
+        self.print_location(regs.rip as _);
+    }
+
+    /// Prints the call stack of the current thread, at most `limit` frames
+    ///
+    /// The frames are unwound with the call frame information of the program, or by following the chain
+    /// of saved frame pointers for the code without it, like the shared libraries.
+    /// The unwinding stops after `main`, at a null frame pointer or when a frame isn't above the previous one,
+    /// as the stack grows down.
+    pub fn backtrace(&self, limit: Option<usize>) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let mut registers = Registers::new(&regs);
+        let mut innermost = Some(&regs);
+        let mut frames: Vec<StackFrame> = Vec::new();
+        let mut previous_cfa = None;
+        let mut corrupted = false;
+        let mut truncated = false;
+        while let Some(pc) = registers.pc().filter(|&pc| pc != 0) {
+            let Some(unwound) = self.unwind_frame(&registers, innermost) else {
+                if innermost.is_some() {
+                    self.reporter
+                        .error("Cannot find the frame of the current function");
+                    return;
+                }
+                // The end of the chain of frame pointers is a null frame pointer
+                corrupted = registers.get(X86_64::RBP).is_some_and(|rbp| rbp != 0);
+                break;
+            };
+            if previous_cfa.is_some_and(|previous| unwound.cfa <= previous) {
+                corrupted = true;
+                break;
+            }
+            frames.push(self.stack_frame(pc as _, unwound.cfa, innermost));
+            if frames.last().unwrap().function.as_deref() == Some("main") {
+                break;
+            }
+            if limit.is_some_and(|limit| frames.len() >= limit) {
+                truncated = true;
+                break;
+            }
+            previous_cfa = Some(unwound.cfa);
+            registers = unwound.caller;
+            innermost = None;
+        }
+        self.reporter.report(Event::Backtrace {
+            frames,
+            corrupted,
+            truncated,
+        });
+    }
+
+    /// Prints the frame of the current function of the current thread
+    ///
+    /// The frame is unwound like for `backtrace`.
+    pub fn info_frame(&self) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let Some(unwound) = self.unwind_frame(&Registers::new(&regs), Some(&regs)) else {
+            self.reporter
+                .error("Cannot find the frame of the current function");
+            return;
+        };
+        self.reporter.report(Event::FrameInfo {
+            frame: self.stack_frame(regs.rip as _, unwound.cfa, Some(&regs)),
+            cfa: unwound.cfa,
+            return_addr: unwound.caller.pc().map(|pc| pc as _),
+            rbp: regs.rbp as _,
+            rsp: regs.rsp as _,
+            saved_rbp: unwound.saved_rbp,
+            locals: self.locals_range(&regs, &unwound),
+        });
+    }
+
+    /// Finds the range of the stack holding the local variables of the frame `unwound`, with the registers `regs`
+    ///
+    /// With debugging information the range covers the locals stored in the frame, found from their locations,
+    /// so a function keeping them under the stack pointer, in the red zone, is handled.
+    /// Without it, the locals are guessed to be between the stack pointer and the saved registers.
+    fn locals_range(&self, regs: &user_regs_struct, unwound: &Unwound) -> Option<(usize, usize)> {
+        let function = self
+            .debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.function_at(regs.rip as _));
+        let Some(function) = function else {
+            let start = regs.rsp as usize;
+            let end = unwound.saved_rbp.unwrap_or(unwound.cfa - 8);
+            return Some((start, end)).filter(|_| end > start);
+        };
+        function
+            .variables
+            .iter()
+            .filter(|variable| matches!(variable.location, Some(Location::FrameOffset(_))))
+            .filter_map(|variable| {
+                let start = variable_address(function, variable, unwound.cfa)?;
+                let size = variable.type_.as_ref().map_or(0, |type_| type_.byte_size);
+                Some((start, start + size.max(1)))
+            })
+            .reduce(|(start, end), (other_start, other_end)| {
+                (start.min(other_start), end.max(other_end))
+            })
+    }
+
+    /// Unwinds the frame of the current thread with the registers `registers`
+    ///
+    /// The registers of the thread are given for the innermost frame, whose instruction pointer
+    /// is the next instruction to execute instead of a return address.
+    /// Without call frame information, the frame pointer convention is used.
+    fn unwind_frame(
+        &self,
+        registers: &Registers,
+        innermost: Option<&user_regs_struct>,
+    ) -> Option<Unwound> {
+        let tid = self.current_thread;
+        let pc = registers.pc()? as usize;
+        // The return address of a caller can be the first instruction of the next function
+        let lookup = if innermost.is_some() { pc } else { pc - 1 };
+        let unwound = self
+            .call_frame_info
+            .iter()
+            .chain(
+                self.libraries
+                    .iter()
+                    .filter_map(|library| library.call_frame_info.as_ref()),
+            )
+            .find_map(|call_frame_info| call_frame_info.unwind(tid, lookup, registers));
+        if unwound.is_some() {
+            return unwound;
+        }
+
+        let rbp = registers.get(X86_64::RBP)? as usize;
+        let (sp, saved_rbp) = match innermost {
+            Some(regs) => {
+                let entry = self.function_range(pc).map(|(start, _)| start);
+                let frame = Frame::current(tid, regs, entry)?;
+                // The frame pointer of the caller is pushed under the return address by the prologue,
+                // then the frame pointer points to it
+                let saved_rbp = [rbp, regs.rsp as usize]
+                    .into_iter()
+                    .find(|&saved_rbp| frame.sp == saved_rbp + 8);
+                (frame.sp, saved_rbp)
+            }
+            None if rbp == 0 => return None,
+            None => (rbp + 8, Some(rbp)),
+        };
+        let mut caller = registers.callee_saved();
+        let return_addr = read_data_fixed(tid, sp).map(u64::from_ne_bytes)?;
+        caller.set(X86_64::RA, Some(return_addr));
+        caller.set(X86_64::RSP, Some(sp as u64 + 8));
+        if let Some(saved_rbp) = saved_rbp {
+            let rbp = read_data_fixed(tid, saved_rbp).map(u64::from_ne_bytes)?;
+            caller.set(X86_64::RBP, Some(rbp));
+        }
+        Some(Unwound {
+            cfa: sp + 8,
+            saved_rbp,
+            caller,
+        })
+    }
+
+    /// Describes the frame executing `pc` whose canonical frame address is `cfa`
+    ///
+    /// The registers are only given for the innermost frame, where the parameters are still
+    /// in the registers of the calling convention before the prologue is executed.
+    fn stack_frame(&self, pc: usize, cfa: usize, regs: Option<&user_regs_struct>) -> StackFrame {
+        let tid = self.current_thread;
+        // The return address of a caller can be the first instruction of the next line
+        let lookup = if regs.is_some() { pc } else { pc - 1 };
+        let debug_info = self.debug_info.as_ref();
+        let function = debug_info.and_then(|debug_info| debug_info.function_at(lookup));
+        let in_prologue = function.is_some_and(|function| {
+            regs.is_some()
+                && debug_info
+                    .and_then(|debug_info| debug_info.prologue_end(function))
+                    .is_some_and(|end| pc < end)
+        });
+        let arguments = function.map(|function| {
+            let argument_registers =
+                regs.map(|regs| [regs.rdi, regs.rsi, regs.rdx, regs.rcx, regs.r8, regs.r9]);
+            function
+                .parameters
+                .iter()
+                .enumerate()
+                .map(|(index, parameter)| {
+                    let value = match (in_prologue, argument_registers) {
+                        (true, Some(registers)) => registers.get(index).copied(),
+                        _ => variable_address(function, parameter, cfa)
+                            .and_then(|addr| read_data_fixed(tid, addr))
+                            .map(u64::from_ne_bytes),
+                    };
+                    let value = value
+                        .and_then(|value| parameter.type_.as_ref()?.format(value))
+                        .unwrap_or_else(|| String::from("?"));
+                    (parameter.name.clone(), value)
+                })
+                .collect()
+        });
+        let name = function.map(|function| function.name.clone()).or_else(|| {
+            let (symbol, _) = self.symbols.as_ref()?.symbol_at(lookup)?;
+            Some(symbol.display_name().into_owned())
+        });
+        StackFrame {
+            pc,
+            function: name,
+            arguments,
+            location: self.line_at(lookup),
+        }
+    }
+
+    /// Runs the program until the current function returns, then prints the returned value
+    ///
+    /// The value is formatted according to the return type of the function if it is known.
+    pub fn finish(&mut self) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let function = self
+            .debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.function_at(regs.rip as _));
+        let return_type = function.map(|function| function.return_type.clone());
+        // As for `backtrace`, the call frame information unwinds the functions without frame pointer
+        let Some((return_addr, cfa)) = self
+            .unwind_frame(&Registers::new(&regs), Some(&regs))
+            .and_then(|unwound| Some((unwound.caller.pc()? as usize, unwound.cfa)))
+        else {
+            self.reporter
+                .error("Cannot find the frame of the current function");
+            return;
+        };
+        // The return address is stored under the canonical frame address
+        if let Some(waitstatus) = self.run_until_return(return_addr, cfa - 8) {
+            self.report(&waitstatus);
+            return;
+        }
+
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let addr = regs.rip as usize;
+        if self.line_at(addr).is_some() {
+            self.print_location(addr);
+        } else {
+            // The caller has no debugging information, like the C runtime calling `main`
+            let symbol = self.symbol_at(addr);
+            let symbol = symbol
+                .as_ref()
+                .map(|(name, offset)| (name.as_ref(), *offset));
+            self.reporter.report(Event::Returned { addr, symbol });
+            self.show_displays();
+        }
+        match return_type {
+            Some(None) => {}
+            Some(Some(return_type)) => {
+                let value = match return_type.kind {
+                    TypeKind::Float(_) => get_fpregs(self.current_thread).map(|fpregs| {
+                        fpregs.xmm_space[0] as u64 | (fpregs.xmm_space[1] as u64) << 32
+                    }),
+                    _ => Some(regs.rax),
+                };
+                self.reporter.report(Event::ReturnValue {
+                    type_name: Some(&return_type.name),
+                    value: value.and_then(|value| return_type.format(value)),
+                    rax: regs.rax,
+                });
+            }
+            None => self.reporter.report(Event::ReturnValue {
+                type_name: None,
+                value: None,
+                rax: regs.rax,
+            }),
+        }
+    }
+
+    /// Detaches from the program, which keeps running without the breakpoints and watchpoints
+    ///
+    /// A program running in the background is stopped first, to remove the breakpoints from its memory.
+    pub fn detach(&mut self) {
+        let pid = self.child.unwrap();
+        if self.background {
+            self.background = false;
+            signal::kill(pid, Signal::SIGSTOP).unwrap();
+            let waitstatus = self.wait();
+            if self.handle_thread_event(&waitstatus) {
+                self.report(&waitstatus);
+                return;
+            }
+            self.stop(&waitstatus);
+        }
+        self.release();
+        for thread in self.threads.iter().filter(|thread| thread.attached) {
+            let _ = ptrace::detach(thread.pid, thread.pending_signal);
+        }
+        // The `SIGSTOP` sent to stop the program may still be pending
+        let _ = signal::kill(pid, Signal::SIGCONT);
+        self.clear_process();
+        self.reporter.report(Event::Detached { pid });
+    }
+
+    /// Removes everything the debugger changed in the stopped program to debug it
+    ///
+    /// The breakpoints are already out of the memory while the program is stopped, except the one of the linker,
+    /// the watchpoints are disabled in the debug registers. The patches are kept.
+    fn release(&mut self) {
+        self.remove_breakpoints();
+        if let Some(linker) = self.linker.as_mut() {
+            linker.restore_data().unwrap();
+        }
+        for thread in self.threads.iter().filter(|thread| thread.attached) {
+            let _ = watchpoint::install(thread.pid, &[]);
+        }
+    }
+
+    /// Kills the program
+    pub fn kill(&mut self) {
+        if self.background {
+            // A running thread cannot be killed through ptrace
+            signal::kill(self.child.unwrap(), Signal::SIGKILL).unwrap();
+            self.background = false;
+        } else {
+            ptrace::kill(self.child.unwrap()).unwrap();
+        }
+        let waitstatus = loop {
+            let waitstatus = self.wait_own(WaitPidFlag::__WALL).unwrap();
+            if !self.handle_thread_event(&waitstatus) {
+                break waitstatus;
+            }
+        };
+        self.report(&waitstatus);
+    }
+
+    /// Prints the general purpose registers of the current thread in a grid, or as the raw structure with `raw`
+    pub fn info_registers(&self, raw: bool) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        self.reporter.report(Event::Registers { regs: &regs, raw });
+    }
+
+    /// Prints the general purpose registers `names` of the current thread, given with or without `$`
+    pub fn info_named_registers(&self, names: &[&str]) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        for name in names {
+            let name = name.strip_prefix('$').unwrap_or(name);
+            match register(&regs, name) {
+                Some(value) => self.reporter.report(Event::Register { name, value }),
+                None => self.reporter.error(format!("Unknown register '{name}'")),
+            }
+        }
+    }
+
+    /// Prints the general purpose registers of the current thread that changed since the previous stop
+    pub fn info_changed_registers(&self) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let thread = self
+            .threads
+            .iter()
+            .find(|thread| thread.pid == self.current_thread)
+            .unwrap();
+        let Some(previous) = thread.previous_regs.as_ref() else {
+            self.reporter
+                .error("No registers of a previous stop of the thread");
+            return;
+        };
+        let changed = REGISTERS
+            .iter()
+            .map(|&name| {
+                let old = register(previous, name).unwrap();
+                (name, old, register(&regs, name).unwrap())
+            })
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        self.reporter.report(Event::ChangedRegisters(changed));
+    }
+
+    /// Sets the general purpose register `name` of the current thread to `value`
+    pub fn set_register(&self, name: &str, value: u64) {
+        let mut regs = ptrace::getregs(self.current_thread).unwrap();
+        let Some(register) = register_mut(&mut regs, name) else {
+            self.reporter.error(format!("Unknown register '{name}'"));
+            return;
+        };
+        *register = value;
+        match ptrace::setregs(self.current_thread, regs) {
+            Ok(()) => self.reporter.info(format!("${name} = {value:#x}")),
+            Err(errno) => self
+                .reporter
+                .error(format!("Cannot set ${name} : {}", errno.desc())),
+        }
+    }
+
+    /// Sets the variable `name` visible from the current thread to the value of `expression`
+    ///
+    /// The variable is looked up in the current function, then in the global variables.
+    /// Only the locations made of a single `DW_OP_fbreg`, `DW_OP_addr` or `DW_OP_reg` operation are supported.
+    pub fn set_variable(&self, name: &str, expression: &str) {
+        let tid = self.current_thread;
+        let mut regs = ptrace::getregs(tid).unwrap();
+        let Some((variable, place)) = self.find_variable(name, "set") else {
+            return;
+        };
+        let type_ = variable.type_.as_ref();
+        let Some(size) = type_
+            .filter(|type_| !matches!(type_.kind, TypeKind::Float(_)))
+            .and_then(Type::size)
+        else {
+            let type_name = type_.map_or("void", |type_| &type_.name);
+            self.reporter
+                .error(format!("Cannot set '{name}' of type '{type_name}'"));
+            return;
+        };
+        let value = match self.evaluate(expression) {
+            Ok(value) => value,
+            Err(err) => {
+                self.reporter.error(err.to_string());
+                return;
+            }
+        };
+        let written = match place {
+            Place::Register(register) => {
+                let register = register_mut(&mut regs, register).unwrap();
+                // Only the bytes of the variable are replaced in the register
+                let mask = u64::MAX >> (64 - 8 * size);
+                *register = (*register & !mask) | (value & mask);
+                ptrace::setregs(tid, regs).is_ok()
+            }
+            Place::Memory(addr) => write_data(tid, addr, &value.to_le_bytes()[..size]).is_ok(),
+        };
+        match written {
+            true => {
+                let formatted = type_
+                    .and_then(|type_| type_.format(value))
+                    .unwrap_or_else(|| format!("{value:#x}"));
+                self.reporter.info(format!("{name} = {formatted}"));
+            }
+            false => self.reporter.error(format!("Cannot write '{name}'")),
+        }
+    }
+
+    /// Prints the variable `name` of the current function or the global variable `name`
+    ///
+    /// The value is read from the location of the variable, and formatted according to its type,
+    /// a structure being printed as its members.
+    pub fn print_variable(&self, name: &str) {
+        let Some((variable, place)) = self.find_variable(name, "print") else {
+            return;
+        };
+        match self.read_variable(variable, place) {
+            Ok(value) => self.reporter.report(Event::Variable {
+                name,
+                type_name: variable.type_.as_ref().map_or("void", |type_| &type_.name),
+                value,
+            }),
+            Err(addr) => self
+                .reporter
+                .error(format!("Cannot access memory at {addr:#x}")),
+        }
+    }
+
+    /// Prints the parameters of the current function if `arguments` is set,
+    /// otherwise its local variables visible from the current instruction
+    pub fn info_variables(&self, arguments: bool) {
+        let Some(debug_info) = self.debug_info.as_ref() else {
+            self.reporter
+                .error(format!("No debugging information for '{}'", self.program));
+            return;
+        };
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let Some(function) = debug_info.function_at(regs.rip as _) else {
+            self.reporter.error(format!(
+                "No debugging information for the function at {:#x}",
+                regs.rip
+            ));
+            return;
+        };
+        let variables = match arguments {
+            true => function.parameters.iter().collect::<Vec<_>>(),
+            false => function
+                .variables
+                .iter()
+                .filter(|variable| variable.in_scope(regs.rip as _))
+                .collect(),
+        };
+        let variables = variables
+            .into_iter()
+            .map(|variable| VariableValue {
+                name: &variable.name,
+                type_name: variable.type_.as_ref().map_or("void", |type_| &type_.name),
+                value: self
+                    .variable_place(Some(function), variable, &regs)
+                    .map_err(|unavailable| unavailable.short())
+                    .and_then(|place| {
+                        self.read_variable(variable, place)
+                            .map_err(|addr| format!("<cannot access memory at {addr:#x}>"))
+                    }),
+            })
+            .collect();
+        self.reporter.report(Event::Variables {
+            arguments,
+            variables,
+        });
+    }
+
+    /// Reads the value of `variable` stored at `place` and formats it according to its type
+    ///
+    /// Returns the address of the variable if it can't be read.
+    fn read_variable(&self, variable: &Variable, place: Place) -> Result<String, usize> {
+        let type_ = variable.type_.as_ref();
+        let len = type_
+            .map(|type_| type_.byte_size)
+            .filter(|&len| len > 0)
+            .unwrap_or(8);
+        let bytes = self.place_bytes(place, len)?;
+        Ok(match type_ {
+            Some(type_) => type_.format_bytes(&bytes),
+            None => Type::unknown().format_bytes(&bytes),
+        })
+    }
+
+    /// Reads the `len` bytes stored at `place` in the current thread, all the bytes of the register for a register
+    ///
+    /// Returns the address of the bytes if they can't be read.
+    fn place_bytes(&self, place: Place, len: usize) -> Result<Vec<u8>, usize> {
+        let tid = self.current_thread;
+        match place {
+            Place::Register(register_name) => {
+                let regs = ptrace::getregs(tid).unwrap();
+                Ok(register(&regs, register_name)
+                    .unwrap()
+                    .to_le_bytes()
+                    .to_vec())
+            }
+            Place::Memory(addr) => {
+                let bytes = read_data(tid, addr, len);
+                if bytes.len() < len {
+                    return Err(addr);
+                }
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Evaluates `expression` with the registers of the current thread, its variables being those `print` finds
+    fn evaluate(&self, expression: &str) -> Result<u64, expression::Error> {
+        let tid = self.current_thread;
+        let regs = ptrace::getregs(tid).unwrap();
+        expression::evaluate_with(expression, &regs, tid, |name, access| {
+            self.variable_number(name, access, &regs)
+        })
+    }
+
+    /// Reads the variable `name` of an expression as a number, its value, its address or the value it points to
+    /// depending on `access`
+    ///
+    /// Only the integers, characters, booleans and pointers are numbers.
+    fn variable_number(
+        &self,
+        name: &str,
+        access: Access,
+        regs: &user_regs_struct,
+    ) -> Result<u64, expression::Error> {
+        let unknown = || expression::Error::UnknownVariable(name.to_owned());
+        let debug_info = self.debug_info.as_ref().ok_or_else(unknown)?;
+        let function = debug_info.function_at(regs.rip as _);
+        let variable = debug_info
+            .variable(function, regs.rip as _, name)
+            .ok_or_else(unknown)?;
+        let place = self
+            .variable_place(function, variable, regs)
+            .map_err(|unavailable| {
+                expression::Error::Variable(unavailable.describe(name, "evaluate"))
+            })?;
+        let unknown_type = Type::unknown();
+        let type_ = variable.type_.as_ref().unwrap_or(&unknown_type);
+        let number = |type_: &Type, place: Place, name: &str| {
+            let bytes = self
+                .place_bytes(place, type_.byte_size)
+                .map_err(|addr| expression::Error::UnreadableMemory(addr as _))?;
+            type_.number(&bytes).ok_or_else(|| {
+                expression::Error::Variable(format!(
+                    "'{name}' of type '{}' is not a number",
+                    type_.name
+                ))
+            })
+        };
+        match access {
+            Access::Value => number(type_, place, name),
+            Access::Address => match place {
+                Place::Memory(addr) => Ok(addr as _),
+                Place::Register(register) => Err(expression::Error::Variable(format!(
+                    "'{name}' is in the register {register}, it has no address"
+                ))),
+            },
+            Access::Pointee => {
+                let pointee = type_
+                    .pointee
+                    .as_deref()
+                    .filter(|_| matches!(type_.kind, TypeKind::Pointer))
+                    .ok_or_else(|| {
+                        expression::Error::Variable(format!(
+                            "Cannot dereference '{name}' of type '{}'",
+                            type_.name
+                        ))
+                    })?;
+                let addr = number(type_, place, name)?;
+                number(pointee, Place::Memory(addr as _), &format!("*{name}"))
+            }
+        }
+    }
+
+    /// Finds the variable `name` in the scope of the current thread and where it is stored
+    ///
+    /// Reports an error mentioning `action`, like `set`, and returns `None` if the variable can't be found
+    /// or if its location is not supported.
+    fn find_variable(&self, name: &str, action: &str) -> Option<(&Variable, Place)> {
+        let Some(debug_info) = self.debug_info.as_ref() else {
+            self.reporter
+                .error(format!("No debugging information for '{}'", self.program));
+            return None;
+        };
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let function = debug_info.function_at(regs.rip as _);
+        let Some(variable) = debug_info.variable(function, regs.rip as _, name) else {
+            self.reporter
+                .error(format!("No symbol '{name}' in current context"));
+            return None;
+        };
+        match self.variable_place(function, variable, &regs) {
+            Ok(place) => Some((variable, place)),
+            Err(unavailable) => {
+                self.reporter.error(unavailable.describe(name, action));
+                None
+            }
+        }
+    }
+
+    /// Finds where `variable`, of `function` or global, is stored in the frame of the current thread with the registers `regs`
+    fn variable_place(
+        &self,
+        function: Option<&Function>,
+        variable: &Variable,
+        regs: &user_regs_struct,
+    ) -> Result<Place, Unavailable> {
+        match variable.location {
+            None => Err(Unavailable::OptimizedOut),
+            Some(Location::Unsupported) => Err(Unavailable::UnsupportedLocation),
+            Some(Location::Register(number)) => DWARF_REGISTERS
+                .get(number as usize)
+                .filter(|name| register(regs, name).is_some())
+                .map(|&name| Place::Register(name))
+                .ok_or(Unavailable::UnsupportedRegister(number)),
+            Some(Location::Address(addr)) => Ok(Place::Memory(addr)),
+            Some(Location::FrameOffset(_)) => {
+                let debug_info = self.debug_info.as_ref().unwrap();
+                if function.is_some_and(|function| {
+                    debug_info
+                        .prologue_end(function)
+                        .is_some_and(|end| (regs.rip as usize) < end)
+                }) {
+                    return Err(Unavailable::BeforePrologue);
+                }
+                self.unwind_frame(&Registers::new(regs), Some(regs))
+                    .zip(function)
+                    .and_then(|(unwound, function)| {
+                        variable_address(function, variable, unwound.cfa)
+                    })
+                    .map(Place::Memory)
+                    .ok_or(Unavailable::NoAddress)
+            }
+        }
+    }
+
+    /// Writes the word `value` at `addr` in the memory of the program
+    ///
+    /// Unlike the tracer, the program cannot write to read-only mappings, so writing to them is refused,
+    /// the breakpoints are still written to the executable code.
+    /// Writing over a breakpoint keeps the written bytes: the breakpoints are removed while the program
+    /// is stopped, and they save the data they replace when they are written again.
+    pub fn set_memory(&self, addr: usize, value: u64) {
+        let bytes = value.to_ne_bytes();
+        if !self.check_writable(addr, bytes.len()) {
+            return;
+        }
+        match write_data(self.current_thread, addr, &bytes) {
+            Ok(()) => self.reporter.info(format!("*{addr:#x} = {value:#x}")),
+            Err(_) => self
+                .reporter
+                .error(format!("Cannot write memory at {addr:#x}")),
+        }
+    }
+
+    /// Checks if the program can write the `len` bytes at `addr`, reporting the first address it can't write
+    ///
+    /// The bytes may span several mappings, all of them must be writable.
+    fn check_writable(&self, addr: usize, len: usize) -> bool {
+        let maps = read_maps(self.current_thread).unwrap_or_default();
+        let Some(end) = addr.checked_add(len) else {
+            self.reporter
+                .error(format!("Cannot access memory at {addr:#x}"));
+            return false;
+        };
+        let mut next = addr;
+        while next < end {
+            match maps.iter().find(|map| map.contains(next)) {
+                Some(map) if map.is_writable() => next = map.end,
+                Some(_) => {
+                    self.reporter
+                        .error(format!("Cannot write to read-only region {next:#x}"));
+                    return false;
+                }
+                None => {
+                    self.reporter
+                        .error(format!("Cannot access memory at {next:#x}"));
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Writes the contents of the file at `path` at `addr` in the memory of the program, like a region saved by `dump`
+    ///
+    /// As for `set_memory`, writing to read-only mappings is refused.
+    pub fn restore(&self, path: &str, addr: usize) {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) => {
+                self.reporter.error(format!("Cannot read '{path}' : {err}"));
+                return;
+            }
+        };
+        if !self.check_writable(addr, data.len()) {
+            return;
+        }
+        let len = match write_data(self.current_thread, addr, &data) {
+            Ok(()) => data.len(),
+            Err(written) => written,
+        };
+        self.reporter.report(Event::Restored {
+            path,
+            addr,
+            len,
+            requested: data.len(),
+        });
+    }
+
+    /// Moves the instruction pointer of the current thread to `addr`, then continues if `resume` is set
+    ///
+    /// The instructions that are skipped are not executed, which can leave the stack and registers
+    /// in a state the program doesn't expect.
+    pub fn jump(&mut self, addr: usize, resume: bool) {
+        let executable = read_maps(self.current_thread)
+            .and_then(|maps| maps.into_iter().find(|map| map.contains(addr)))
+            .is_some_and(|map| map.is_executable());
+        if !executable {
+            self.reporter
+                .error(format!("{addr:#x} is not in an executable mapping"));
+            return;
+        }
+        let mut regs = ptrace::getregs(self.current_thread).unwrap();
+        regs.rip = addr as _;
+        ptrace::setregs(self.current_thread, regs).unwrap();
+        self.reporter.info(
+            "Jumping skips or repeats instructions, the stack and registers may not be what the program expects",
+        );
+        if resume {
+            self.cont();
+        } else {
+            self.print_location(addr);
+        }
+    }
+
+    /// Pops the frame of the current function of the current thread, as if it returned `value`
+    ///
+    /// The frame is unwound like for `backtrace`: the stack pointer, the instruction pointer
+    /// and the registers saved by the function are set to their values in the caller.
+    /// Without `value`, `rax` is left as it is.
+    pub fn force_return(&mut self, value: Option<&str>) {
+        let mut regs = ptrace::getregs(self.current_thread).unwrap();
+        let value = value.map(|value| expression::evaluate(value, &regs, self.current_thread));
+        let value = match value.transpose() {
+            Ok(value) => value,
+            Err(err) => {
+                self.reporter.error(err.to_string());
+                return;
+            }
+        };
+        let Some(caller) = self
+            .unwind_frame(&Registers::new(&regs), Some(&regs))
+            .map(|unwound| unwound.caller)
+            .filter(|caller| caller.pc().is_some() && caller.get(X86_64::RSP).is_some())
+        else {
+            self.reporter
+                .error("Cannot find the frame of the current function");
+            return;
+        };
+        for (register, value) in [
+            (&mut regs.rbx, X86_64::RBX),
+            (&mut regs.rbp, X86_64::RBP),
+            (&mut regs.rsp, X86_64::RSP),
+            (&mut regs.r12, X86_64::R12),
+            (&mut regs.r13, X86_64::R13),
+            (&mut regs.r14, X86_64::R14),
+            (&mut regs.r15, X86_64::R15),
+            (&mut regs.rip, X86_64::RA),
+        ] {
+            if let Some(value) = caller.get(value) {
+                *register = value;
+            }
+        }
+        if let Some(value) = value {
+            regs.rax = value;
+        }
+        ptrace::setregs(self.current_thread, regs).unwrap();
+        self.reporter.info(
+            "The rest of the function is skipped, the program may not be in the state it expects",
+        );
+        self.print_location(regs.rip as _);
+    }
+
+    /// Encodes the instructions `text` and writes them at `location`, given as for `breakpoint`
+    ///
+    /// The overwritten bytes are saved to be restored by `unpatch`. The instructions following the patch
+    /// are left as they are, the end of an instruction partly overwritten is reported.
+    pub fn assemble(&mut self, location: &str, text: &str) {
+        let Some(addr) = BreakpointArg::parse(location).and_then(|arg| {
+            arg.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            )
+        }) else {
+            self.reporter
+                .error(format!("Cannot resolve location '{location}'"));
+            return;
+        };
+        let resolve = |location: &str| {
+            BreakpointArg::parse(location).and_then(|arg| {
+                arg.to_address(
+                    self.symbols.as_ref(),
+                    self.debug_info.as_ref(),
+                    &self.libraries,
+                )
+            })
+        };
+        let bytes = match assembler::assemble(text, addr, resolve) {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            Ok(_) => {
+                self.reporter.error("No instruction to assemble");
+                return;
+            }
+            Err(err) => {
+                self.reporter.error(err.to_string());
+                return;
+            }
+        };
+        let Some(end) = addr.checked_add(bytes.len()) else {
+            self.reporter
+                .error(format!("Cannot access memory at {addr:#x}"));
+            return;
+        };
+        let maps = read_maps(self.current_thread).unwrap_or_default();
+        let executable = |addr| {
+            maps.iter()
+                .find(|map| map.contains(addr))
+                .is_some_and(|map| map.is_executable())
+        };
+        if !executable(addr) || !executable(end - 1) {
+            self.reporter
+                .error(format!("{addr:#x} is not in an executable mapping"));
+            return;
+        }
+        // The last overwritten instruction may end after the patch
+        let code = read_data(self.current_thread, addr, bytes.len() + 15);
+        if code.len() < bytes.len() || write_data(self.current_thread, addr, &bytes).is_err() {
+            self.reporter
+                .error(format!("Cannot write memory at {addr:#x}"));
+            return;
+        }
+        // The instructions are before `end`, which doesn't overflow
+        let mut offset = 0;
+        while offset < bytes.len() {
+            offset += disassembler::decode(&code[offset..], addr + offset)
+                .map_or(1, |instruction| instruction.len);
+        }
+        self.patches.push(Patch {
+            addr,
+            original: code[..bytes.len()].to_vec(),
+        });
+        self.reporter.report(Event::Patched {
+            addr,
+            len: bytes.len(),
+        });
+        self.reporter.info(
+            "Patching the code of a running program can crash it, unpatch restores the original bytes",
+        );
+        if offset > bytes.len() {
+            self.reporter.info(format!(
+                "The patch ends in the middle of the instruction at {end:#x}, pad it with nop"
+            ));
+        }
+        self.disassemble(Some(&format!("{addr:#x}")), Some(bytes.len()), false);
+    }
+
+    /// Restores the bytes overwritten by the last patch written at `location`, given as for `breakpoint`
+    ///
+    /// Patches written over each other should be restored in the reverse order.
+    pub fn unpatch(&mut self, location: &str) {
+        let Some(addr) = BreakpointArg::parse(location).and_then(|arg| {
+            arg.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            )
+        }) else {
+            self.reporter
+                .error(format!("Cannot resolve location '{location}'"));
+            return;
+        };
+        let Some(index) = self.patches.iter().rposition(|patch| patch.addr == addr) else {
+            self.reporter.error(format!("No patch at {addr:#x}"));
+            return;
+        };
+        let patch = self.patches.remove(index);
+        match write_data(self.current_thread, addr, &patch.original) {
+            Ok(()) => self.reporter.report(Event::Unpatched {
+                addr,
+                len: patch.original.len(),
+            }),
+            Err(_) => self
+                .reporter
+                .error(format!("Cannot write memory at {addr:#x}")),
+        }
+    }
+
+    /// Resumes all the threads until one of them reaches `arg`, given as for `breakpoint`
+    ///
+    /// A temporary breakpoint is planted at the location and removed when the program stops,
+    /// the program also stops if another breakpoint is reached first.
+    pub fn until(&mut self, arg: &str) {
+        let Some(addr) = BreakpointArg::parse(arg).and_then(|arg| {
+            arg.to_address(
+                self.symbols.as_ref(),
+                self.debug_info.as_ref(),
+                &self.libraries,
+            )
+        }) else {
+            self.reporter
+                .error(format!("Cannot resolve location '{arg}'"));
+            return;
+        };
+        let executable = read_maps(self.current_thread)
+            .and_then(|maps| maps.into_iter().find(|map| map.contains(addr)))
+            .is_some_and(|map| map.is_executable());
+        if !executable {
+            self.reporter
+                .error(format!("{addr:#x} is not in an executable mapping"));
+            return;
+        }
+        self.temporary = Breakpoint::create(addr, self.current_thread);
+        let waitstatus = match self.resume() {
+            Some(waitstatus) => waitstatus,
+            None => self.wait(),
+        };
+        self.stop(&waitstatus);
+        self.temporary = None;
+        if let WaitStatus::Stopped(tid, Signal::SIGTRAP) = waitstatus
+            && ptrace::getregs(tid).unwrap().rip as usize == addr
+            && !self.breakpoints.iter().any(|bp| bp.addr == addr)
+        {
+            self.switch_thread(tid);
+            self.print_location(addr);
+            return;
+        }
+        self.report(&waitstatus);
+        if self.child.is_none() {
+            self.reporter
+                .info(format!("The program exited before reaching {addr:#x}"));
+        }
+    }
+
+    /// Evaluates `expression` with the registers of the current thread and prints its value
+    pub fn print(&self, expression: &str) {
+        if is_identifier(expression) {
+            self.print_variable(expression);
+            return;
+        }
+        if let Some((type_name, addr)) = parse_cast(expression) {
+            self.print_typed_memory(type_name, addr);
+            return;
+        }
+        match self.evaluate(expression) {
+            Ok(value) => self.reporter.report(Event::Value(value)),
+            Err(err) => self.reporter.error(err.to_string()),
+        }
+    }
+
+    /// Prints the memory at the address `addr`, an expression, as a value of the type `type_name`
+    ///
+    /// A structure is printed member by member with their offset and type, the structures inside it as raw bytes.
+    fn print_typed_memory(&self, type_name: &str, addr: &str) {
+        let Some(debug_info) = self.debug_info.as_ref() else {
+            self.reporter
+                .error(format!("No debugging information for '{}'", self.program));
+            return;
+        };
+        let Some(type_) = debug_info.type_named(type_name) else {
+            self.reporter.error(format!("Unknown type '{type_name}'"));
+            return;
+        };
+        if type_.byte_size == 0 {
+            self.reporter
+                .error(format!("The size of '{}' is unknown", type_.name));
+            return;
+        }
+        let tid = self.current_thread;
+        let addr = match self.evaluate(addr) {
+            Ok(addr) => addr as usize,
+            Err(err) => {
+                self.reporter.error(err.to_string());
+                return;
+            }
+        };
+        let bytes = read_data(tid, addr, type_.byte_size);
+        if bytes.len() < type_.byte_size {
+            self.reporter
+                .error(format!("Cannot access memory at {addr:#x}"));
+            return;
+        }
+        let members = type_
+            .members
+            .iter()
+            .map(|member| MemberValue {
+                name: &member.name,
+                offset: member.offset,
+                type_name: &member.type_.name,
+                value: member
+                    .type_
+                    .format_bytes(bytes.get(member.offset..).unwrap_or_default()),
+            })
+            .collect();
+        self.reporter.report(Event::TypedMemory {
+            addr,
+            type_name: &type_.name,
+            value: type_.format_bytes(&bytes),
+            members,
+        });
+    }
+
+    /// Adds `expression` to the expressions printed each time the program stops
+    pub fn display(&mut self, expression: &str) {
+        self.next_display += 1;
+        self.displays
+            .push((self.next_display, expression.to_owned()));
+        if self.child.is_some() && !self.background {
+            self.show_display(self.next_display, expression);
+        }
+    }
+
+    /// Removes the displayed expression `index`
+    pub fn undisplay(&mut self, index: usize) {
+        match self
+            .displays
+            .iter()
+            .position(|(number, _)| *number == index)
+        {
+            Some(position) => {
+                self.displays.remove(position);
+            }
+            None => self.reporter.error(format!("No display number {index}")),
+        }
+    }
+
+    /// Prints all the displayed expressions
+    pub fn show_displays(&self) {
+        for (index, expression) in &self.displays {
+            self.show_display(*index, expression);
+        }
+    }
+
+    /// Evaluates and prints the displayed expression `index`
+    ///
+    /// An expression that can't be evaluated, like one reading unmapped memory, is printed with the error.
+    fn show_display(&self, index: usize, expression: &str) {
+        let value = self.evaluate(expression).map_err(|err| err.to_string());
+        self.reporter.report(Event::Display {
+            index,
+            expression,
+            value,
+        });
+    }
+
+    /// Prints the auxiliary vector of the program, the strings it points to being read from the program's memory
+    pub fn info_auxv(&self) {
+        let pid = self.child.unwrap();
+        let Some(auxv) = read_auxv(pid) else {
+            self.reporter
+                .error(format!("Cannot read the auxiliary vector of process {pid}"));
+            return;
+        };
+        let lines = auxv
+            .iter()
+            .map(|entry| {
+                let (name, kind) = entry.describe().unzip();
+                let string = (kind == Some(ValueKind::String))
+                    .then(|| read_cstring(pid, entry.value as _, STRING_MAX))
+                    .flatten()
+                    .map(|bytes| format_string(&bytes, false));
+                AuxvLine {
+                    key: entry.key,
+                    name,
+                    value: entry.value,
+                    hex: kind != Some(ValueKind::Decimal),
+                    string,
+                }
+            })
+            .collect();
+        self.reporter.report(Event::Auxv(lines));
+    }
+
+    /// Lists the displayed expressions with their number
+    ///
+    /// They are kept across runs of the program, and printed in the order of their numbers at each stop.
+    pub fn info_display(&self) {
+        self.reporter.report(Event::Displays(&self.displays));
+    }
+
+    /// Saves the general purpose registers of the current thread as `name`
+    pub fn save_registers(&mut self, name: &str) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        self.snapshots.insert(name.to_owned(), regs);
+        self.reporter.info(format!("Registers saved as '{name}'"));
+    }
+
+    /// Sets the general purpose registers of the current thread to the registers saved as `name`
+    pub fn restore_registers(&self, name: &str) {
+        let Some(regs) = self.snapshots.get(name) else {
+            self.reporter
+                .error(format!("No registers saved as '{name}'"));
+            return;
+        };
+        ptrace::setregs(self.current_thread, *regs).unwrap();
+        self.reporter
+            .info(format!("Registers restored from '{name}'"));
+    }
+
+    /// Lists the saved registers
+    pub fn list_registers(&self) {
+        let mut snapshots: Vec<(&str, u64)> = self
+            .snapshots
+            .iter()
+            .map(|(name, regs)| (name.as_str(), regs.rip))
+            .collect();
+        snapshots.sort();
+        self.reporter.report(Event::Snapshots(snapshots));
+    }
+
+    /// Prints the x87 and SSE registers of the current thread
+    pub fn info_fp_registers(&self) {
+        match get_fpregs(self.current_thread) {
+            Some(fpregs) => self.reporter.report(Event::FpRegisters(&fpregs)),
+            None => self
+                .reporter
+                .error("Cannot read the floating point registers"),
+        }
+    }
+
+    /// Prints the vector registers of the current thread, split in elements of `size` bytes
+    pub fn info_vector_registers(&self, size: usize) {
+        match xstate::get_vector_registers(self.current_thread) {
+            Some(registers) => self.reporter.report(Event::VectorRegisters {
+                registers: &registers,
+                size,
+            }),
+            None => self.reporter.error("Cannot read the vector registers"),
+        }
+    }
+
+    /// Lists the functions of the symbol table and of the debugging information whose name matches `regex`
+    ///
+    /// The functions are sorted by address, or by name with `by_name`.
+    pub fn info_functions(&self, regex: Option<&Regex>, by_name: bool) {
+        let mut functions: Vec<(Cow<str>, usize)> = self
+            .symbols
+            .iter()
+            .flat_map(SymbolTable::functions)
+            .map(|(symbol, addr)| (symbol.display_name(), addr))
+            .collect();
+        // The functions without symbol, like the static functions of a stripped symbol table
+        for function in self.debug_info.iter().flat_map(DebugInfo::functions) {
+            if !functions.iter().any(|&(_, addr)| addr == function.low_pc) {
+                functions.push((Cow::Borrowed(&function.name), function.low_pc));
+            }
+        }
+        functions.retain(|(name, _)| regex.is_none_or(|regex| regex.is_match(name)));
+        if by_name {
+            functions.sort();
+        } else {
+            functions.sort_by(|(name, addr), (other, other_addr)| {
+                (addr, name).cmp(&(other_addr, other))
+            });
+        }
+        functions.dedup();
+        let functions = functions
+            .iter()
+            .map(|(name, addr)| (name.as_ref(), *addr))
+            .collect();
+        self.reporter.report(Event::Functions(functions));
+    }
+
+    /// Lists the variables of the symbol table and of the debugging information whose name matches `regex`
+    ///
+    /// The variables are sorted by address, or by name with `by_name`.
+    pub fn info_global_variables(&self, regex: Option<&Regex>, by_name: bool) {
+        let mut variables: Vec<(Cow<str>, usize, Option<usize>)> = self
+            .symbols
+            .iter()
+            .flat_map(SymbolTable::variables)
+            .map(|(symbol, addr)| (symbol.display_name(), addr, Some(symbol.size)))
+            .collect();
+        // The variables without symbol, like the static variables of a stripped symbol table
+        for variable in self.debug_info.iter().flat_map(DebugInfo::globals) {
+            let Some(Location::Address(addr)) = variable.location else {
+                continue;
+            };
+            if !variables.iter().any(|&(_, other, _)| other == addr) {
+                let size = variable.type_.as_ref().and_then(Type::size);
+                variables.push((Cow::Borrowed(&variable.name), addr, size));
+            }
+        }
+        variables.retain(|(name, _, _)| regex.is_none_or(|regex| regex.is_match(name)));
+        if by_name {
+            variables.sort();
+        } else {
+            variables.sort_by(|(name, addr, _), (other, other_addr, _)| {
+                (addr, name).cmp(&(other_addr, other))
+            });
+        }
+        variables.dedup();
+        let variables = variables
+            .iter()
+            .map(|(name, addr, size)| (name.as_ref(), *addr, *size))
+            .collect();
+        self.reporter.report(Event::GlobalVariables(variables));
+    }
+
+    /// Lists the shared libraries mapped by the program, with their load address and if their symbols were read
+    ///
+    /// The symbols are read when the program reaches its entry point, not for the libraries loaded later.
+    pub fn info_shared_libraries(&self) {
+        let libraries: Vec<(String, usize, bool)> = self
+            .mapped_libraries(self.current_thread)
+            .into_iter()
+            .map(|(path, bias)| {
+                let loaded = self.libraries.iter().any(|library| library.path == path);
+                (path, bias, loaded)
+            })
+            .collect();
+        self.reporter.report(Event::SharedLibraries(libraries));
+    }
+
+    /// Lists the threads of the program, the current thread is marked with a `*`
+    pub fn info_threads(&self) {
+        self.reporter.report(Event::Threads {
+            threads: self
+                .threads
+                .iter()
+                .map(|thread| (thread.id, thread.pid))
+                .collect(),
+            current: self.current_thread,
+        });
+    }
+
+    /// Prints the source location of the instruction at `addr`, or at the instruction pointer of the current thread
+    pub fn info_line(&self, addr: Option<usize>) {
+        if self.debug_info.is_none() {
+            self.reporter
+                .error(format!("No debugging information for '{}'", self.program));
+            return;
+        }
+        let addr = addr.unwrap_or_else(|| ptrace::getregs(self.current_thread).unwrap().rip as _);
+        self.reporter.report(Event::Line {
+            addr,
+            location: self.line_at(addr),
+        });
+    }
+
+    /// Prints the symbol containing `addr` and the offset of `addr` in it
+    pub fn info_symbol(&self, addr: usize) {
+        let symbol = self
+            .symbols
+            .as_ref()
+            .and_then(|symbols| symbols.symbol_at(addr))
+            .map(|(symbol, start)| (symbol.display_name(), addr - start));
+        let symbol = symbol
+            .as_ref()
+            .map(|(name, offset)| (name.as_ref(), *offset));
+        self.reporter.report(Event::Symbol { addr, symbol });
+    }
+
+    /// Prints the instructions of the function containing `location`, or of the `len` bytes at `location`
+    ///
+    /// Without location, the function containing the instruction pointer of the current thread is printed.
+    /// With `with_source`, the instructions are grouped under the source line they were generated for.
+    pub fn disassemble(&self, location: Option<&str>, len: Option<usize>, with_source: bool) {
+        let rip = ptrace::getregs(self.current_thread).unwrap().rip as usize;
+        let addr = match location {
+            Some(location) => {
+                match BreakpointArg::parse(location).and_then(|arg| {
+                    arg.to_address(
+                        self.symbols.as_ref(),
+                        self.debug_info.as_ref(),
+                        &self.libraries,
+                    )
+                }) {
+                    Some(addr) => addr,
+                    None => {
+                        self.reporter
+                            .error(format!("Cannot resolve location '{location}'"));
+                        return;
+                    }
+                }
+            }
+            None => rip,
+        };
+        let range = match len {
+            Some(len) => Some((addr, addr.saturating_add(len))),
+            None => self.function_range(addr),
+        };
+        let Some((start, end)) = range else {
+            self.reporter
+                .error(format!("Cannot find the function containing {addr:#x}"));
+            return;
+        };
+        // The last instruction may end after the range
+        let code = read_data(self.current_thread, start, (end - start).saturating_add(15));
+        if code.is_empty() {
+            self.reporter
+                .error(format!("Cannot read the memory at {start:#x}"));
+            return;
+        }
+        let instructions = disassembler::decode_range(&code, start, end - start);
+
+        let locations: Vec<Option<SourceLocation>> = instructions
+            .iter()
+            .map(|instruction| self.line_at(instruction.addr))
+            .collect();
+        if !with_source || locations.iter().all(Option::is_none) {
+            self.reporter.report(Event::Instructions {
+                instructions: &instructions,
+                current: rip,
+            });
+            return;
+        }
+        let mut first = 0;
+        while first < instructions.len() {
+            let location = &locations[first];
+            let count = locations[first..]
+                .iter()
+                .take_while(|other| *other == location)
+                .count();
+            if let Some(location) = location {
+                match source::read_lines(&location.file, location.line, location.line) {
+                    Some(lines) if !lines.is_empty() => self.reporter.report(Event::Source {
+                        file: &location.file,
+                        lines,
+                    }),
+                    _ => self.reporter.info(location.to_string()),
+                }
+            }
+            self.reporter.report(Event::Instructions {
+                instructions: &instructions[first..first + count],
+                current: rip,
+            });
+            first += count;
+        }
+    }
+
+    /// Prints the source of the function `name`
+    ///
+    /// Without a function, the lines following the last listed lines are printed,
+    /// or the lines around the current location if nothing has been listed.
+    pub fn list(&mut self, name: Option<&str>) {
+        let Some(debug_info) = self.debug_info.as_ref() else {
+            self.reporter
+                .error(format!("No debugging information for '{}'", self.program));
+            return;
+        };
+        let (start, last) = match name {
+            Some(name) => {
+                let Some(addr) = self
+                    .symbols
+                    .as_ref()
+                    .and_then(|symbols| symbols.lookup(name))
+                else {
+                    self.reporter.error(format!("Unknown function '{name}'"));
+                    return;
+                };
+                let Some((start, last)) = debug_info
+                    .function_at(addr)
+                    .and_then(|function| debug_info.function_lines(function))
+                else {
+                    self.reporter.error(format!("No source for '{name}'"));
+                    return;
+                };
+                (start, last)
+            }
+            None => match self.next_listed.take() {
+                Some(start) => {
+                    let last = start.line + LISTED_LINES - 1;
+                    (start, last)
+                }
+                None => {
+                    let location = self.child.and_then(|_| {
+                        let rip = ptrace::getregs(self.current_thread).unwrap().rip;
+                        self.line_at(rip as _)
+                    });
+                    let Some(SourceLocation { file, line }) = location else {
+                        self.reporter
+                            .error("No source listed yet, use list <function>");
+                        return;
+                    };
+                    let first = line.saturating_sub(LISTED_LINES / 2).max(1);
+                    let start = SourceLocation { file, line: first };
+                    (start, first + LISTED_LINES - 1)
+                }
+            },
+        };
+        let Some(lines) = source::read_lines(&start.file, start.line, last) else {
+            self.reporter.error(format!("Cannot read '{}'", start.file));
+            return;
+        };
+        if lines.is_empty() {
+            self.reporter.error(format!(
+                "Line {} is out of range for '{}'",
+                start.line, start.file
+            ));
+            return;
+        }
+        self.next_listed = Some(SourceLocation {
+            file: start.file.clone(),
+            line: last + 1,
+        });
+        self.reporter.report(Event::Source {
+            file: &start.file,
+            lines,
+        });
+    }
+
+    /// Makes the thread numbered `id` the current thread
+    pub fn select_thread(&mut self, id: usize) {
+        match self.threads.iter().find(|thread| thread.id == id) {
+            Some(thread) => {
+                self.current_thread = thread.pid;
+                self.reporter.report(Event::SwitchedThread {
+                    id,
+                    pid: thread.pid,
+                });
+            }
+            None if id > 0 && id < self.next_thread_id => {
+                self.reporter.error(format!("Thread {id} has exited"))
+            }
+            None => self.reporter.error(format!("Unknown thread {id}")),
+        }
+    }
+
+    /// Writes the `len` bytes of memory at `addr` to the file `path`
+    ///
+    /// Only the readable prefix of the range is written. An existing file is overwritten only if `force` is set.
+    pub fn dump(&self, addr: usize, len: usize, path: &str, force: bool) {
+        let data = read_data(self.current_thread, addr, len);
+        let file = if force {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)
+        } else {
+            OpenOptions::new().write(true).create_new(true).open(path)
+        };
+        let result = file.and_then(|mut file| file.write_all(&data));
+        match result {
+            Ok(()) => self.reporter.report(Event::Dumped {
+                path,
+                addr,
+                len: data.len(),
+                requested: len,
+            }),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => self.reporter.error(format!(
+                "'{path}' already exists, use --force to overwrite it"
+            )),
+            Err(err) => self
+                .reporter
+                .error(format!("Cannot write '{path}' : {err}")),
+        }
+    }
+
+    /// Writes an ELF core file of the program to `path`, that other debuggers can load
+    ///
+    /// The current thread is the first thread of the core file.
+    pub fn generate_core_file(&self, path: &str) {
+        let mut threads = vec![self.current_thread];
+        threads.extend(
+            self.threads
+                .iter()
+                .filter(|thread| thread.attached && thread.pid != self.current_thread)
+                .map(|thread| thread.pid),
+        );
+        let Some(core) = coredump::build(self.child.unwrap(), &threads) else {
+            self.reporter
+                .error("Cannot read the memory mappings of the program");
+            return;
+        };
+        match fs::write(path, core) {
+            Ok(()) => self.reporter.report(Event::CoreFile(path)),
+            Err(err) => self
+                .reporter
+                .error(format!("Cannot write '{path}' : {err}")),
+        }
+    }
+
+    /// Prints the addresses where `pattern` occurs in the `len` bytes of memory at `start`
+    ///
+    /// Only the readable prefix of the range is searched.
+    pub fn search(&self, start: usize, len: usize, pattern: &[u8]) {
+        let data = read_data(self.current_thread, start, len);
+        if data.len() < len {
+            self.reporter.info(format!(
+                "The memory at {:#x} is not readable, searching only {} bytes",
+                start + data.len(),
+                data.len()
+            ));
+        }
+        let matches: Vec<usize> = data
+            .windows(pattern.len())
+            .enumerate()
+            .filter(|(_, window)| *window == pattern)
+            .map(|(offset, _)| start + offset)
+            .collect();
+        self.reporter.report(Event::SearchMatches(matches));
+    }
+
+    /// Prints `format.count` units of the memory at `location`, an expression or a location given as for `breakpoint`
+    ///
+    /// The units are printed up to the first one that can't be read, however large the count.
+    pub fn examine(&self, format: &Format, location: &str) {
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let addr = match expression::evaluate(location, &regs, self.current_thread) {
+            Ok(addr) => addr as usize,
+            Err(err) => match BreakpointArg::parse(location).and_then(|arg| {
+                arg.to_address(
+                    self.symbols.as_ref(),
+                    self.debug_info.as_ref(),
+                    &self.libraries,
+                )
+            }) {
+                Some(addr) => addr,
+                None => {
+                    self.reporter.error(err.to_string());
+                    return;
+                }
+            },
+        };
+        let mut end = addr;
+        let printed = match format.letter {
+            Letter::String => {
+                let mut strings = Vec::new();
+                while strings.len() < format.count {
+                    let Some(string) = read_cstring(self.current_thread, end, STRING_MAX) else {
+                        break;
+                    };
+                    strings.push((end, format_string(&string, string.len() == STRING_MAX)));
+                    end += string.len() + 1;
+                }
+                let printed = strings.len();
+                if printed > 0 {
+                    self.reporter.report(Event::Strings(strings));
+                }
+                printed
+            }
+            Letter::Instruction => {
+                // The instructions are at most 15 bytes long
+                let code = read_data(self.current_thread, addr, format.count.saturating_mul(15));
+                let mut instructions = Vec::new();
+                while instructions.len() < format.count && end - addr < code.len() {
+                    let offset = end - addr;
+                    let instruction = disassembler::decode(&code[offset..], end)
+                        .unwrap_or_else(|| Instruction::unknown(end, code[offset]));
+                    end += instruction.len;
+                    instructions.push(instruction);
+                }
+                if !instructions.is_empty() {
+                    self.reporter.report(Event::Instructions {
+                        instructions: &instructions,
+                        current: regs.rip as _,
+                    });
+                }
+                instructions.len()
+            }
+            letter => {
+                let data = read_data(
+                    self.current_thread,
+                    addr,
+                    format.count.saturating_mul(format.size),
+                );
+                let values: Vec<String> = data
+                    .chunks_exact(format.size)
+                    .map(|unit| examine::format_unit(unit, letter))
+                    .collect();
+                end += values.len() * format.size;
+                let printed = values.len();
+                if printed > 0 {
+                    self.reporter.report(Event::Memory {
+                        addr,
+                        size: format.size,
+                        per_line: format.per_line(),
+                        values,
+                    });
+                }
+                printed
+            }
+        };
+        if printed < format.count {
+            self.reporter
+                .error(format!("Cannot read memory at {end:#x}"));
+        }
+    }
+
+    /// Prints the `count` words at the top of the stack of the current thread
+    ///
+    /// The words pointing into a symbol of the program or of its libraries are printed with the symbol,
+    /// and marked as return addresses if they point just after a call in an executable mapping.
+    pub fn stack(&self, count: usize) {
+        let tid = self.current_thread;
+        let sp = ptrace::getregs(tid).unwrap().rsp as usize;
+        let data = read_data(tid, sp, count.saturating_mul(8));
+        let maps = read_maps(tid).unwrap_or_default();
+        let values: Vec<u64> = data
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let symbols: Vec<Option<(Cow<str>, usize)>> = values
+            .iter()
+            .map(|&value| self.symbol_at(value as _))
+            .collect();
+        let words: Vec<StackWord> = values
+            .iter()
+            .zip(&symbols)
+            .enumerate()
+            .map(|(index, (&value, symbol))| {
+                let executable = maps
+                    .iter()
+                    .any(|map| map.contains(value as _) && map.is_executable());
+                StackWord {
+                    addr: sp + 8 * index,
+                    value,
+                    symbol: symbol
+                        .as_ref()
+                        .map(|(name, offset)| (name.as_ref(), *offset)),
+                    return_address: executable && self.follows_call(value as _),
+                }
+            })
+            .collect();
+        let read = words.len();
+        self.reporter.report(Event::Stack { sp, words });
+        if read < count {
+            self.reporter
+                .error(format!("Cannot read memory at {:#x}", sp + 8 * read));
+        }
+    }
+
+    /// Returns the symbol of the program or of a loaded library containing `addr`, with the offset of `addr` in it
+    fn symbol_at(&self, addr: usize) -> Option<(Cow<'_, str>, usize)> {
+        self.symbols
+            .iter()
+            .chain(self.libraries.iter().map(|library| &library.symbols))
+            .find_map(|symbols| symbols.symbol_at(addr))
+            .map(|(symbol, start)| (symbol.display_name(), addr - start))
+    }
+
+    /// Checks if the instruction before `addr` is a call, as for a return address
+    ///
+    /// The calls are 2 to 7 bytes long, like `call *%rax` or `call 0x401136`.
+    fn follows_call(&self, addr: usize) -> bool {
+        let code = read_data(self.current_thread, addr.saturating_sub(7), 7);
+        (2..=7.min(code.len())).any(|len| {
+            disassembler::decode(&code[code.len() - len..], addr - len)
+                .is_some_and(|instruction| instruction.len == len && instruction.is_call())
+        })
+    }
+
+    /// Returns the instruction ending at `addr`, like the one accessing the memory of a watchpoint
+    ///
+    /// The instructions can't be decoded backward, they are decoded from the start of the symbol containing `addr`.
+    fn instruction_before(&self, addr: usize) -> Option<Instruction> {
+        let (_, offset) = self.symbol_at(addr)?;
+        let start = addr - offset;
+        let code = read_data(self.current_thread, start, offset);
+        let mut offset = 0;
+        while offset < code.len() {
+            let instruction = disassembler::decode(&code[offset..], start + offset)?;
+            offset += instruction.len;
+            if offset == code.len() {
+                return Some(instruction);
+            }
+        }
+        None
+    }
+
+    /// Returns the `rows` lines of the code pane of the TUI and the registers of the current thread
+    ///
+    /// The source around the current line is shown if it can be read, or else the instructions around `rip`.
+    pub fn code_view(&self, rows: usize) -> CodeView {
+        if self.child.is_none() || self.background {
+            return CodeView {
+                title: String::from(if self.background {
+                    "Running"
+                } else {
+                    "No program running"
+                }),
+                lines: Vec::new(),
+                registers: None,
+            };
+        }
+        let regs = ptrace::getregs(self.current_thread).unwrap();
+        let rip = regs.rip as usize;
+        if let Some(SourceLocation { file, line }) = self.line_at(rip) {
+            let first = line.saturating_sub(rows as u64 / 2).max(1);
+            if let Some(lines) = source::read_lines(&file, first, first + rows as u64 - 1)
+                && !lines.is_empty()
+            {
+                return CodeView {
+                    title: file,
+                    lines: lines
+                        .into_iter()
+                        .map(|(number, text)| (number == line, format!("{number:<4} {text}")))
+                        .collect(),
+                    registers: Some(regs),
+                };
+            }
+        }
+        // The instructions are decoded from the start of the function, as they cannot be decoded backwards
+        let start = self
+            .function_range(rip)
+            .map_or(rip, |(start, _)| start)
+            .max(rip.saturating_sub(0x1000));
+        let code = read_data(self.current_thread, start, rip - start + 15 * rows);
+        let mut instructions: Vec<Instruction> = Vec::new();
+        let mut offset = 0;
+        while offset < code.len() {
+            let addr = start + offset;
+            let instruction = disassembler::decode(&code[offset..], addr)
+                .unwrap_or_else(|| Instruction::unknown(addr, code[offset]));
+            offset += instruction.len;
+            instructions.push(instruction);
+        }
+        let current = instructions
+            .iter()
+            .position(|instruction| instruction.addr == rip)
+            .unwrap_or(0);
+        let first = current.saturating_sub(rows / 2);
+        CodeView {
+            title: String::from("disassembly"),
+            lines: instructions
+                .iter()
+                .skip(first)
+                .take(rows)
+                .map(|instruction| {
+                    (
+                        instruction.addr == rip,
+                        format!("{:#x}:\t{instruction}", instruction.addr),
+                    )
+                })
+                .collect(),
+            registers: Some(regs),
+        }
+    }
+
+    /// Returns the source location of the instruction at `addr`
+    fn line_at(&self, addr: usize) -> Option<SourceLocation> {
+        self.debug_info.as_ref()?.line_at(addr)
+    }
+
+    /// Returns the start and end addresses of the function containing the instruction at `addr`
+    ///
+    /// The function is found in the debugging information, or else in the symbols.
+    fn function_range(&self, addr: usize) -> Option<(usize, usize)> {
+        if let Some(function) = self
+            .debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.function_at(addr))
+        {
+            return Some((function.low_pc, function.high_pc));
+        }
+        let (symbol, start) = self.symbols.as_ref()?.symbol_at(addr)?;
+        Some((start, start + symbol.size))
+    }
+
+    /// Returns the entry of the function containing the instruction at `addr`
+    fn function_entry(&self, addr: usize) -> Option<usize> {
+        let function = self.debug_info.as_ref()?.function_at(addr)?;
+        Some(function.low_pc)
+    }
+
+    /// Returns the indices of the breakpoints at `addr`, starting from 1
+    fn breakpoint_indices(&self, addr: usize) -> Vec<usize> {
+        self.user_breakpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, breakpoint)| {
+                breakpoint.location.to_address(
+                    self.symbols.as_ref(),
+                    self.debug_info.as_ref(),
+                    &self.libraries,
+                ) == Some(addr)
+            })
+            .map(|(index, _)| index + 1)
+            .collect()
+    }
+
+    /// Counts a hit of the breakpoints `indices`
+    fn count_hits(&mut self, indices: &[usize]) {
+        for index in indices {
+            self.user_breakpoints[index - 1].hits += 1;
+        }
+    }
+
+    /// Prints how many times each breakpoint was hit since the program was run
+    pub fn info_breakpoint_hits(&self) {
+        let addrs: Vec<Option<usize>> = self
+            .user_breakpoints
+            .iter()
+            .map(|breakpoint| {
+                breakpoint.location.to_address(
+                    self.symbols.as_ref(),
+                    self.debug_info.as_ref(),
+                    &self.libraries,
+                )
+            })
+            .collect();
+        let symbols: Vec<Option<(Cow<str>, usize)>> = addrs
+            .iter()
+            .map(|addr| {
+                let addr = (*addr)?;
+                let (symbol, start) = self.symbols.as_ref()?.symbol_at(addr)?;
+                Some((symbol.display_name(), addr - start))
+            })
+            .collect();
+        let breakpoints = self
+            .user_breakpoints
+            .iter()
+            .zip(addrs.iter().zip(&symbols))
+            .enumerate()
+            .map(|(index, (breakpoint, (addr, symbol)))| BreakpointHits {
+                index: index + 1,
+                addr: *addr,
+                symbol: symbol
+                    .as_ref()
+                    .map(|(name, offset)| (name.as_ref(), *offset)),
+                hits: breakpoint.hits,
+            })
+            .collect();
+        self.reporter.report(Event::BreakpointHits(breakpoints));
+    }
+
+    /// Prints the function and source location of the instruction at `addr`
+    fn print_location(&self, addr: usize) {
+        let function = self
+            .debug_info
+            .as_ref()
+            .and_then(|debug_info| debug_info.function_at(addr));
+        self.reporter.report(Event::Location {
+            addr,
+            function: function.map(|function| function.name.as_str()),
+            location: self.line_at(addr),
+        });
+        self.show_displays();
+    }
+
+    /// Tracks a new thread of the program
+    fn add_thread(&mut self, pid: Pid, attached: bool) {
+        let id = self.next_thread_id;
+        self.next_thread_id += 1;
+        if id > 1 {
+            self.reporter.report(Event::NewThread { id, pid });
+        }
+        self.threads.push(Thread {
+            id,
+            pid,
+            attached,
+            running: false,
+            pending_signal: None,
+            stop_regs: attached.then(|| ptrace::getregs(pid).ok()).flatten(),
+            previous_regs: None,
+            syscall_stop: None,
+        });
+        if attached {
+            self.install_watchpoints(pid);
+        }
+    }
+
+    /// Sets the watchpoints in the debug registers of the stopped thread `tid`
+    ///
+    /// The debug registers of a thread are not inherited by the threads it creates.
+    fn install_watchpoints(&mut self, tid: Pid) {
+        if self.watchpoints.is_empty() || self.software_watchpoints {
+            return;
+        }
+        if watchpoint::install(tid, &self.watchpoints).is_err() && !self.fall_back_to_software() {
+            self.reporter
+                .error(format!("Cannot set the watchpoints of thread {tid}"));
+        }
+    }
+
+    /// Handles the creation of a thread and the exit of a thread other than the thread group leader
+    ///
+    /// Returns `true` if `waitstatus` was such an event, the threads concerned by the event are left stopped.
+    fn handle_thread_event(&mut self, waitstatus: &WaitStatus) -> bool {
+        match *waitstatus {
+            WaitStatus::PtraceEvent(tid, _, event)
+                if event == ptrace::Event::PTRACE_EVENT_CLONE as i32 =>
+            {
+                let new = Pid::from_raw(ptrace::getevent(tid).unwrap() as _);
+                // The stop of the new thread can be reported before the creation
+                if !self.threads.iter().any(|thread| thread.pid == new) {
+                    self.add_thread(new, false);
+                }
+                true
+            }
+            WaitStatus::PtraceEvent(tid, _, event)
+                if event == ptrace::Event::PTRACE_EVENT_FORK as i32 =>
+            {
+                let child = Pid::from_raw(ptrace::getevent(tid).unwrap() as _);
+                self.detach_fork_child(child);
+                true
+            }
+            WaitStatus::Stopped(tid, Signal::SIGSTOP) => {
+                match self.threads.iter_mut().find(|thread| thread.pid == tid) {
+                    Some(thread) if thread.attached => return false,
+                    Some(thread) => {
+                        thread.attached = true;
+                        self.install_watchpoints(tid);
+                    }
+                    None => self.add_thread(tid, true),
+                }
+                true
+            }
+            WaitStatus::Exited(tid, _) | WaitStatus::Signaled(tid, _, _)
+                if Some(tid) != self.child =>
+            {
+                if let Some(index) = self.threads.iter().position(|thread| thread.pid == tid) {
+                    self.reporter.report(Event::ThreadExited {
+                        id: self.threads[index].id,
+                        pid: tid,
+                    });
+                    self.threads.remove(index);
+                }
+                if self.current_thread == tid {
+                    self.current_thread = self.child.unwrap();
+                }
+                if self
+                    .history
+                    .as_ref()
+                    .is_some_and(|history| history.thread == tid)
+                {
+                    self.reporter
+                        .info("The recorded thread exited, recording stopped");
+                    self.history = None;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks if the unknown thread `tid` is the child of a fork of the program rather than one of its threads
+    fn is_fork_child(&self, tid: Pid) -> bool {
+        !self.threads.iter().any(|thread| thread.pid == tid)
+            && self
+                .child
+                .is_some_and(|pid| !Path::new(&format!("/proc/{pid}/task/{tid}")).exists())
+    }
+
+    /// Waits for the first stop of `child`, forked by the program, unless it was reported before the fork
+    fn wait_fork_child(&mut self, child: Pid) {
+        match self.forked.iter().position(|&pid| pid == child) {
+            Some(index) => {
+                self.forked.remove(index);
+            }
+            None => {
+                wait_thread(child);
+            }
+        }
+    }
+
+    /// Lets `child`, forked by the program, run on its own, after removing the breakpoints from its memory
+    fn detach_fork_child(&mut self, child: Pid) {
+        self.wait_fork_child(child);
+        for breakpoint in self
+            .breakpoints
+            .iter()
+            .chain(&self.temporary)
+            .chain(&self.linker)
+        {
+            let _ = breakpoint.forked(child).restore_data();
+        }
+        let _ = ptrace::detach(child, None);
+        self.reporter
+            .info(format!("Detaching after fork from child process {child}"));
+    }
+
+    /// Debugs `child`, forked by the thread `tid`, instead of the program, which is detached
+    ///
+    /// The breakpoints are removed from the memory of the program and kept in the memory of the child,
+    /// where they are as in the program when it forked. The threads of the child are numbered from 1 again.
+    fn follow_fork_child(&mut self, tid: Pid, child: Pid) {
+        self.wait_fork_child(child);
+        self.stop_others(tid);
+        for breakpoint in self
+            .breakpoints
+            .iter_mut()
+            .chain(&mut self.temporary)
+            .chain(&mut self.linker)
+        {
+            let forked = breakpoint.forked(child);
+            let _ = breakpoint.restore_data();
+            *breakpoint = forked;
+        }
+        for thread in self.threads.iter().filter(|thread| thread.attached) {
+            let _ = watchpoint::install(thread.pid, &[]);
+            let _ = ptrace::detach(thread.pid, thread.pending_signal);
+        }
+        let parent = self.child.replace(child).unwrap();
+        self.reporter.info(format!(
+            "Attaching after process {parent} fork to child process {child}"
+        ));
+        self.threads.clear();
+        self.next_thread_id = 1;
+        self.add_thread(child, true);
+        self.current_thread = child;
+        self.history = None;
+    }
+
+    /// Resumes the thread `tid`, until the next syscall if syscalls are caught
+    fn resume_thread(&self, tid: Pid, signal: Option<Signal>) {
+        match self.catch {
+            Some(_) => ptrace::syscall(tid, signal).unwrap(),
+            None => ptrace::cont(tid, signal).unwrap(),
+        }
+    }
+
+    /// Checks if the thread `tid`, stopped at a syscall entry or exit, stops the program
+    fn is_caught(&mut self, tid: Pid) -> bool {
+        let regs = ptrace::getregs(tid).unwrap();
+        let stop = self.track_syscall(tid, &regs);
+        self.catch
+            .as_ref()
+            .is_some_and(|catch| catch.matches(regs.orig_rax))
+            && (matches!(stop, SyscallStop::Exit(_)) || !self.catch_exits_only)
+    }
+
+    /// Records the syscall stop of the thread `tid` whose registers are `regs`, and returns it
+    fn track_syscall(&mut self, tid: Pid, regs: &user_regs_struct) -> SyscallStop {
+        let Some(thread) = self.threads.iter_mut().find(|thread| thread.pid == tid) else {
+            return SyscallStop::next(None, regs);
+        };
+        let stop = SyscallStop::next(thread.syscall_stop.as_ref(), regs);
+        thread.syscall_stop = Some(stop);
+        stop
+    }
+
+    /// Waits for a stop of the running program that must be handled
+    ///
+    /// The creation and exit of threads, and the syscalls that are not caught are handled while waiting.
+    fn wait(&mut self) -> WaitStatus {
+        self.wait_with(WaitPidFlag::__WALL)
+    }
+
+    /// Same as `wait`, with the flags `flags` of `waitpid`
+    ///
+    /// With `WNOHANG`, `WaitStatus::StillAlive` is returned if the program is still running.
+    ///
+    /// A Ctrl-C while blocked stops the program with a `SIGSTOP`, instead of terminating the debugger.
+    fn wait_with(&mut self, flags: WaitPidFlag) -> WaitStatus {
+        let ctrl_c = (!flags.contains(WaitPidFlag::WNOHANG)).then(CtrlC::catch);
+        let mut interrupted = false;
+        loop {
+            if ctrl_c.as_ref().is_some_and(CtrlC::interrupted) {
+                signal::kill(self.child.unwrap(), Signal::SIGSTOP).unwrap();
+                interrupted = true;
+            }
+            let waitstatus = match self.wait_own(flags) {
+                Err(Errno::EINTR) => continue,
+                waitstatus => waitstatus.unwrap(),
+            };
+            let tid = match waitstatus {
+                WaitStatus::PtraceSyscall(tid) if !self.is_caught(tid) => tid,
+                // The other threads keep running while the thread steps over the breakpoint of the linker
+                WaitStatus::Stopped(tid, Signal::SIGTRAP) if self.is_linker_hit(tid) => {
+                    self.linker.as_ref().unwrap().restore_rip(tid).unwrap();
+                    let waitstatus = self.step_thread(tid);
+                    if !is_step_completed(&waitstatus, tid) {
+                        return waitstatus;
+                    }
+                    tid
+                }
+                WaitStatus::Stopped(tid, Signal::SIGSTOP) if self.is_fork_child(tid) => {
+                    self.forked.push(tid);
+                    continue;
+                }
+                WaitStatus::PtraceEvent(tid, _, event)
+                    if event == ptrace::Event::PTRACE_EVENT_FORK as i32
+                        && FOLLOW_CHILD.load(Ordering::Relaxed) =>
+                {
+                    let child = Pid::from_raw(ptrace::getevent(tid).unwrap() as _);
+                    self.follow_fork_child(tid, child);
+                    child
+                }
+                WaitStatus::PtraceEvent(tid, _, _) | WaitStatus::Stopped(tid, _)
+                    if self.handle_thread_event(&waitstatus) =>
+                {
+                    tid
+                }
+                _ if self.handle_thread_event(&waitstatus) => continue,
+                // The program shares the terminal of the debugger and received the Ctrl-C too,
+                // it stops with the `SIGSTOP` that follows
+                WaitStatus::Stopped(tid, Signal::SIGINT) if interrupted => {
+                    let pass = self.signal_handling(Signal::SIGINT).pass;
+                    self.resume_thread(tid, pass.then_some(Signal::SIGINT));
+                    continue;
+                }
+                WaitStatus::Stopped(tid, signal)
+                    if signal != Signal::SIGTRAP && !self.signal_handling(signal).stop =>
+                {
+                    let handling = self.signal_handling(signal);
+                    if handling.print {
+                        self.reporter.report(Event::SignalReceived { signal });
+                    }
+                    self.resume_thread(tid, handling.pass.then_some(signal));
+                    continue;
+                }
+                _ => return waitstatus,
+            };
+            self.resume_thread(tid, None);
+            if let Some(thread) = self.threads.iter_mut().find(|thread| thread.pid == tid) {
+                thread.running = true;
+            }
+        }
+    }
+
+    /// Waits for a thread of the program, or a process it forked that is traced, with the flags `flags` of `waitpid`
+    ///
+    /// The stops of the other inferiors are kept for them, and the stops kept by the other inferiors
+    /// for the program are reported first.
+    fn wait_own(&self, flags: WaitPidFlag) -> nix::Result<WaitStatus> {
+        let mut others = OTHER_STOPS.lock().unwrap();
+        let kept = others
+            .iter()
+            .position(|waitstatus| waitstatus.pid().is_some_and(|tid| self.is_own(tid)));
+        if let Some(index) = kept {
+            return Ok(others.remove(index));
+        }
+        loop {
+            let waitstatus = waitpid(None, Some(flags))?;
+            match waitstatus.pid() {
+                Some(tid) if !self.is_own(tid) => others.push(waitstatus),
+                _ => return Ok(waitstatus),
+            }
+        }
+    }
+
+    /// Checks if `tid` is a thread of the program or a process it forked, rather than a thread of another inferior
+    fn is_own(&self, tid: Pid) -> bool {
+        let Some(pid) = self.child else {
+            return false;
+        };
+        tid == pid
+            || self.threads.iter().any(|thread| thread.pid == tid)
+            || self.forked.contains(&tid)
+            // A new thread or a forked process can stop before its creation is reported
+            || Path::new(&format!("/proc/{pid}/task/{tid}")).exists()
+            || parent(tid) == Some(pid)
+    }
+
+    /// Executes a single instruction of the thread `tid`, the other threads stay stopped
+    ///
+    /// The breakpoints are not planted, so the original instruction is executed
+    /// even if the thread is stopped at a breakpoint.
+    /// A syscall instruction is executed by running the thread to the exit of the syscall,
+    /// where the stop is made to look like a single step.
+    fn step_thread(&mut self, tid: Pid) -> WaitStatus {
+        let recorded = self
+            .history
+            .as_mut()
+            .filter(|history| history.thread == tid)
+            .map(History::record);
+        if let Some(Err(err)) = recorded {
+            self.stop_recording(err);
+        }
+        let rip = ptrace::getregs(tid).unwrap().rip as usize;
+        let linker = self.linker.as_mut().filter(|linker| linker.addr == rip);
+        let at_linker = linker.is_some();
+        if let Some(linker) = linker {
+            linker.restore_data().unwrap();
+        }
+        let syscall = read_data_fixed::<2>(tid, rip)
+            .is_some_and(|code| matches!(code, [0x0f, 0x05] | [0xcd, 0x80]));
+        let step = |tid| match syscall {
+            true => ptrace::syscall(tid, None),
+            false => ptrace::step(tid, None),
+        };
+        step(tid).unwrap();
+        let mut entered = false;
+        let waitstatus = loop {
+            let waitstatus = wait_thread(tid);
+            match waitstatus {
+                WaitStatus::PtraceEvent(..) if self.handle_thread_event(&waitstatus) => {
+                    step(tid).unwrap();
+                }
+                WaitStatus::PtraceSyscall(_) if !entered => {
+                    entered = true;
+                    step(tid).unwrap();
+                }
+                WaitStatus::PtraceSyscall(_) => {
+                    make_single_step(tid);
+                    break WaitStatus::Stopped(tid, Signal::SIGTRAP);
+                }
+                // A thread stopped at the exit of a syscall, like after its `execve`, reports
+                // the exit of the syscall as a breakpoint trap, without executing an instruction
+                WaitStatus::Stopped(_, Signal::SIGTRAP)
+                    if ptrace::getsiginfo(tid)
+                        .is_ok_and(|siginfo| siginfo.si_code == TRAP_BRKPT)
+                        && ptrace::getregs(tid).unwrap().rip as usize == rip =>
+                {
+                    make_single_step(tid);
+                    break waitstatus;
+                }
+                _ => break waitstatus,
+            }
+        };
+        if at_linker {
+            self.linker.as_mut().unwrap().write().unwrap();
+            // The linker also stops there before adding or removing libraries, when its list is being changed
+            let consistent = self
+                .rendezvous(tid)
+                .is_none_or(|rendezvous| rendezvous.is_consistent(tid));
+            if consistent {
+                let running = self.threads.iter().any(|thread| thread.running);
+                self.load_libraries(tid, running);
+            }
+        }
+        waitstatus
+    }
+
+    /// Resumes all the threads of the program with the breakpoints planted
+    ///
+    /// The threads stopped at a breakpoint first execute the instruction at the breakpoint.
+    /// As no breakpoint is planted meanwhile, this single step can't stop at another breakpoint,
+    /// and a thread stepping onto the address of another one executes its trap once it is planted and the thread resumed.
+    /// Returns the status of a thread if the program stopped while doing so.
+    fn resume(&mut self) -> Option<WaitStatus> {
+        if let Some(history) = self.history.as_mut().filter(|history| history.len() > 0) {
+            // The instructions executed by the running program can't be recorded
+            self.reporter
+                .info("The program runs without being recorded, the recorded history is cleared");
+            history.clear();
+        }
+        self.remove_breakpoints();
+        let at_breakpoint: Vec<Pid> = self
+            .threads
+            .iter()
+            .filter(|thread| thread.attached)
+            .map(|thread| thread.pid)
+            .filter(|&tid| {
+                let rip = ptrace::getregs(tid).unwrap().rip as usize;
+                self.breakpoints
+                    .iter()
+                    .chain(self.temporary.iter())
+                    .any(|bp| bp.addr == rip)
+            })
+            .collect();
+        for tid in at_breakpoint {
+            let waitstatus = self.step_thread(tid);
+            if is_step_completed(&waitstatus, tid) || self.handle_thread_event(&waitstatus) {
+                continue;
+            }
+            match waitstatus {
+                WaitStatus::Stopped(_, signal) if signal != Signal::SIGTRAP => {
+                    let pass = self.signal_handling(signal).pass;
+                    let thread = self.threads.iter_mut().find(|thread| thread.pid == tid);
+                    thread.unwrap().pending_signal = pass.then_some(signal);
+                }
+                _ => return Some(waitstatus),
+            }
+        }
+
+        self.breakpoints
+            .iter_mut()
+            .chain(self.temporary.iter_mut())
+            .for_each(|bp| bp.write().unwrap());
+        let catch = self.catch.is_some();
+        for thread in self.threads.iter_mut().filter(|thread| thread.attached) {
+            let signal = thread.pending_signal.take();
+            if catch {
+                ptrace::syscall(thread.pid, signal).unwrap();
+            } else {
+                ptrace::cont(thread.pid, signal).unwrap();
+            }
+            thread.running = true;
+        }
+        None
+    }
+
+    /// Removes the breakpoints from the program's memory
+    ///
+    /// They are removed in the reverse order of their planting, so that when the temporary breakpoint
+    /// shares the address of a breakpoint, the original data saved by the first one is restored last.
+    fn remove_breakpoints(&mut self) {
+        self.breakpoints
+            .iter_mut()
+            .chain(self.temporary.iter_mut())
+            .rev()
+            .for_each(|bp| bp.restore_data().unwrap());
+    }
+
+    /// Moves the instruction pointer of `tid` back to the address of the breakpoint it hit, if any
+    ///
+    /// A trap of the program itself, like an `int3` at a breakpoint executed while stepping over the breakpoint,
+    /// is not rewound, so that the program goes past it instead of executing it again forever.
+    fn rewind_breakpoint(&self, tid: Pid) {
+        // Watchpoints stop the thread after the instruction accessing the memory
+        if is_single_step(tid) || watchpoint::is_hit(tid) {
+            return;
+        }
+        let rip = ptrace::getregs(tid).unwrap().rip as usize;
+        if let Some(bp) = self
+            .breakpoints
+            .iter()
+            .chain(self.temporary.iter())
+            .chain(self.linker.iter())
+            .find(|bp| bp.addr == rip - 1 && bp.is_inserted())
+        {
+            bp.restore_rip(tid).unwrap();
+        }
+    }
+
+    /// Stops all the running threads of the program but `tid`
+    ///
+    /// The threads hitting a breakpoint meanwhile are moved back to the breakpoint so that it is hit again
+    /// when they are resumed, and the signals they receive are delivered when they are resumed.
+    fn stop_others(&mut self, tid: Pid) {
+        let leader = self.child.unwrap();
+        let mut to_wait = Vec::new();
+        for thread in self.threads.iter_mut() {
+            if thread.pid == tid || (thread.attached && !thread.running) {
+                thread.running = false;
+                continue;
+            }
+            if thread.attached {
+                unsafe {
+                    libc::syscall(
+                        libc::SYS_tgkill,
+                        leader.as_raw(),
+                        thread.pid.as_raw(),
+                        libc::SIGSTOP,
+                    );
+                }
+            }
+            // New threads are stopped when they report their creation
+            to_wait.push(thread.pid);
+        }
+
+        while let Some(pid) = to_wait.pop() {
+            loop {
+                let waitstatus = wait_thread(pid);
+                match waitstatus {
+                    WaitStatus::Stopped(_, Signal::SIGSTOP) => {
+                        if let Some(thread) = self.threads.iter_mut().find(|t| t.pid == pid) {
+                            let attached = thread.attached;
+                            thread.attached = true;
+                            thread.running = false;
+                            if !attached {
+                                self.install_watchpoints(pid);
+                            }
+                        }
+                        break;
+                    }
+                    WaitStatus::Stopped(_, Signal::SIGTRAP) => {
+                        self.rewind_breakpoint(pid);
+                        self.resume_thread(pid, None);
+                    }
+                    WaitStatus::Stopped(_, signal) => {
+                        let pass = self.signal_handling(signal).pass;
+                        if let Some(thread) = self.threads.iter_mut().find(|t| t.pid == pid) {
+                            thread.pending_signal = pass.then_some(signal);
+                        }
+                        self.resume_thread(pid, None);
+                    }
+                    WaitStatus::PtraceEvent(_, _, _) => {
+                        let known = self.threads.len();
+                        self.handle_thread_event(&waitstatus);
+                        to_wait.extend(self.threads[known..].iter().map(|thread| thread.pid));
+                        self.resume_thread(pid, None);
+                    }
+                    WaitStatus::PtraceSyscall(_) => {
+                        let regs = ptrace::getregs(pid).unwrap();
+                        self.track_syscall(pid, &regs);
+                        self.resume_thread(pid, None);
+                    }
+                    WaitStatus::Exited(_, _) | WaitStatus::Signaled(_, _, _) => {
+                        self.handle_thread_event(&waitstatus);
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+
+    /// Brings the whole program to a stop after a thread stopped with `waitstatus`
+    ///
+    /// The other threads are stopped, the breakpoints are removed from memory and if the thread
+    /// hit a breakpoint, its instruction pointer is moved back to the breakpoint.
+    fn stop(&mut self, waitstatus: &WaitStatus) {
+        let tid = match *waitstatus {
+            WaitStatus::Stopped(tid, _)
+            | WaitStatus::PtraceEvent(tid, _, _)
+            | WaitStatus::PtraceSyscall(tid) => tid,
+            _ => return,
+        };
+        self.stop_others(tid);
+        if let WaitStatus::Stopped(_, Signal::SIGTRAP) = waitstatus {
+            self.rewind_breakpoint(tid);
+        }
+        self.remove_breakpoints();
+        if let Some(from) = self.unplanted.take() {
+            self.plant_breakpoints(from);
+        }
+        for thread in self.threads.iter_mut().filter(|thread| thread.attached) {
+            if let Ok(regs) = ptrace::getregs(thread.pid) {
+                thread.previous_regs = thread.stop_regs.replace(regs);
+            }
+        }
+    }
+
+    /// Runs the program until the current thread returns to `return_addr` from the function
+    /// called with the stack pointer `sp`
+    ///
+    /// A temporary breakpoint is planted at `return_addr`, hits of that breakpoint by other threads
+    /// or by deeper recursive calls are skipped.
+    /// Returns the `WaitStatus` if the program stopped for another reason, like hitting a breakpoint.
+    fn run_until_return(&mut self, return_addr: usize, sp: usize) -> Option<WaitStatus> {
+        let tid = self.current_thread;
+        self.temporary = Breakpoint::create(return_addr, self.child.unwrap());
+        loop {
+            let waitstatus = match self.resume() {
+                Some(waitstatus) => waitstatus,
+                None => self.wait(),
+            };
+            self.stop(&waitstatus);
+            if let WaitStatus::Stopped(stopped, Signal::SIGTRAP) = waitstatus {
+                let regs = ptrace::getregs(stopped).unwrap();
+                if regs.rip as usize == return_addr
+                    && !self.breakpoints.iter().any(|bp| bp.addr == return_addr)
+                {
+                    if stopped == tid && regs.rsp as usize > sp {
+                        self.temporary = None;
+                        return None;
+                    }
+                    continue;
+                }
+            }
+            self.temporary = None;
+            return Some(waitstatus);
+        }
+    }
+
+    /// Makes the thread `tid` the current thread, telling the user if it changed
+    fn switch_thread(&mut self, tid: Pid) {
+        if tid == self.current_thread {
+            return;
+        }
+        if let Some(thread) = self.threads.iter().find(|thread| thread.pid == tid) {
+            self.reporter.report(Event::SwitchedThread {
+                id: thread.id,
+                pid: tid,
+            });
+            self.current_thread = tid;
+        }
+    }
+
+    /// Reports a stop of the program to the user, followed by the displayed expressions
+    ///
+    /// The thread that stopped becomes the current thread.
+    fn report(&mut self, waitstatus: &WaitStatus) {
+        self.report_status(waitstatus);
+        self.delete_left_watchpoints();
+        if self.child.is_some() {
+            self.show_displays();
+        }
+    }
+
+    /// Reports the status of a stop of the program
+    fn report_status(&mut self, waitstatus: &WaitStatus) {
+        if let Some(tid) = waitstatus.pid()
+            && !matches!(
+                waitstatus,
+                WaitStatus::Exited(_, _) | WaitStatus::Signaled(_, _, _)
+            )
+        {
+            self.switch_thread(tid);
+        }
+        match waitstatus {
+            WaitStatus::Exited(_, exitcode) => {
+                self.reporter.report(Event::Exited { code: *exitcode });
+                self.exit_code = Some(*exitcode);
+                if !self.user_breakpoints.is_empty() {
+                    self.info_breakpoint_hits();
+                }
+                self.clear_process();
+            }
+            WaitStatus::Signaled(_, signal, _) => {
+                self.reporter.report(Event::Signaled { signal: *signal });
+                self.exit_code = Some(128 + *signal as i32);
+                if !self.user_breakpoints.is_empty() {
+                    self.info_breakpoint_hits();
+                }
+                self.clear_process();
+            }
+            WaitStatus::Stopped(tid, Signal::SIGTRAP) => {
+                let regs = ptrace::getregs(*tid).unwrap();
+                let slots = match self.software_watchpoints {
+                    true => self.changed_watchpoints(*tid),
+                    false => watchpoint::triggered(*tid),
+                };
+                if !slots.is_empty() {
+                    // The thread stops after the instruction accessing the memory
+                    let instruction = self.instruction_before(regs.rip as _);
+                    for slot in slots {
+                        // The memory of a returned frame is reused by the next calls
+                        if self
+                            .watchpoints
+                            .get(slot)
+                            .is_some_and(|watchpoint| self.left_scope(watchpoint))
+                        {
+                            continue;
+                        }
+                        let Some(watchpoint) = self.watchpoints.get_mut(slot) else {
+                            continue;
+                        };
+                        let old = watchpoint.value;
+                        let new = watchpoint.read_value(*tid).unwrap_or(old);
+                        watchpoint.value = new;
+                        watchpoint.hits += 1;
+                        self.reporter.report(Event::WatchpointHit {
+                            index: slot + 1,
+                            kind: watchpoint.kind,
+                            software: watchpoint.software,
+                            addr: watchpoint.addr,
+                            written: watchpoint.kind == WatchKind::Write || new != old,
+                            old,
+                            new,
+                            instruction: instruction.as_ref(),
+                        });
+                    }
+                    self.print_location(regs.rip as _);
+                    return;
+                }
+                let indices = self.breakpoint_indices(regs.rip as _);
+                if !indices.is_empty() {
+                    self.count_hits(&indices);
+                    self.hit_commands = indices
+                        .iter()
+                        .flat_map(|index| self.user_breakpoints[index - 1].commands.iter().cloned())
+                        .collect();
+                    self.reporter.report(Event::BreakpointHit {
+                        indices,
+                        addr: regs.rip as _,
+                    });
+                    return;
+                }
+                self.reporter.report(Event::Location {
+                    addr: regs.rip as _,
+                    function: None,
+                    location: None,
+                });
+            }
+            WaitStatus::PtraceSyscall(tid) => {
+                let regs = ptrace::getregs(*tid).unwrap();
+                let stop = self
+                    .threads
+                    .iter()
+                    .find(|thread| thread.pid == *tid)
+                    .and_then(|thread| thread.syscall_stop);
+                match stop {
+                    Some(SyscallStop::Entry(_)) => self.reporter.report(Event::SyscallEntry {
+                        regs: &regs,
+                        call: syscalls::format_call(&regs, *tid, None),
+                    }),
+                    Some(SyscallStop::Exit(entry)) => self.reporter.report(Event::SyscallExit {
+                        number: regs.orig_rax,
+                        value: regs.rax as _,
+                        entry: entry.as_ref(),
+                        call: entry
+                            .map(|entry| syscalls::format_call(&entry, *tid, Some(regs.rax as _))),
+                    }),
+                    None => {}
+                }
+            }
+            WaitStatus::StillAlive => {
+                panic!("Program never stopped")
+            }
+            WaitStatus::Stopped(tid, signal) => {
+                self.reporter.report(Event::Stopped { signal: *signal });
+                if self.signal_handling(*signal).pass
+                    && let Some(thread) = self.threads.iter_mut().find(|thread| thread.pid == *tid)
+                {
+                    thread.pending_signal = Some(*signal);
+                }
+            }
+            WaitStatus::PtraceEvent(_, _, code) => {
+                self.reporter.report(Event::PtraceStop { code: *code });
+            }
+            WaitStatus::Continued(_) => self.reporter.report(Event::Continued),
+        }
+    }
+
+    /// Handles a stop of the program and reports it
+    ///
+    /// When only probes are hit, they are printed and the program is resumed until it stops again,
+    /// a program running in the background keeps running once resumed.
+    fn wait_and_check(&mut self, waitstatus: &WaitStatus) {
+        let mut waitstatus = *waitstatus;
+        loop {
+            if self.handle_thread_event(&waitstatus) {
+                // Only a thread exited, the rest of the program is still stopped
+                self.background = false;
+                return;
+            }
+            self.stop(&waitstatus);
+            if !self.report_probes(&waitstatus) {
+                break;
+            }
+            waitstatus = match self.resume() {
+                Some(waitstatus) => waitstatus,
+                None if self.background => return,
+                None => self.wait(),
+            };
+        }
+        self.background = false;
+        self.report(&waitstatus);
+    }
+
+    /// Prints the probes hit at the stop `waitstatus`, returns `false` if it isn't a stop at probes only
+    fn report_probes(&mut self, waitstatus: &WaitStatus) -> bool {
+        let WaitStatus::Stopped(tid, Signal::SIGTRAP) = *waitstatus else {
+            return false;
+        };
+        if is_single_step(tid) || watchpoint::is_hit(tid) {
+            return false;
+        }
+        let regs = ptrace::getregs(tid).unwrap();
+        let indices = self.breakpoint_indices(regs.rip as _);
+        if indices.is_empty()
+            || indices
+                .iter()
+                .any(|index| self.user_breakpoints[index - 1].probe.is_none())
+        {
+            return false;
+        }
+        self.count_hits(&indices);
+        for index in indices {
+            let breakpoint = &self.user_breakpoints[index - 1];
+            let expression = breakpoint.probe.as_deref().unwrap();
+            match expression::evaluate(expression, &regs, tid) {
+                Ok(value) => self.reporter.report(Event::ProbeHit {
+                    index,
+                    hits: breakpoint.hits,
+                    expression,
+                    value,
+                }),
+                Err(err) => self
+                    .reporter
+                    .error(format!("Probe {index}: {expression} : {err}")),
+            }
+        }
+        true
+    }
+
+    /// Forgets the state of the program after it exited
+    fn clear_process(&mut self) {
+        self.child = None;
+        self.threads.clear();
+        self.breakpoints.clear();
+        self.temporary = None;
+        self.linker = None;
+        self.history = None;
+        self.patches.clear();
+    }
+}
@@ -0,0 +1,1312 @@
+use std::{
+    fmt::{Display, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The size in bytes of an operand
+type Size = u8;
+
+/// Whether the instructions are printed in Intel syntax rather than AT&T syntax, changed by `set disassembly-flavor`
+static INTEL: AtomicBool = AtomicBool::new(true);
+
+/// The syntax the instructions are printed in
+#[derive(Clone, Copy)]
+pub enum Flavor {
+    Att,
+    Intel,
+}
+
+impl Flavor {
+    /// Parses the name of a flavor, `att` or `intel`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "att" => Some(Flavor::Att),
+            "intel" => Some(Flavor::Intel),
+            _ => None,
+        }
+    }
+}
+
+/// Prints the instructions in the syntax `flavor`
+pub fn set_flavor(flavor: Flavor) {
+    INTEL.store(matches!(flavor, Flavor::Intel), Ordering::Relaxed);
+}
+
+pub const GPR64: [&str; 16] = [
+    "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+pub const GPR32: [&str; 16] = [
+    "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d", "r12d",
+    "r13d", "r14d", "r15d",
+];
+const GPR16: [&str; 16] = [
+    "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w", "r13w",
+    "r14w", "r15w",
+];
+const GPR8: [&str; 16] = [
+    "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+    "r13b", "r14b", "r15b",
+];
+/// The byte registers 4 to 7 without a REX prefix
+const GPR8_HIGH: [&str; 4] = ["ah", "ch", "dh", "bh"];
+const XMM: [&str; 16] = [
+    "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10",
+    "xmm11", "xmm12", "xmm13", "xmm14", "xmm15",
+];
+
+/// The operations of the opcodes `00` to `3d` and of the group 1 (`80` to `83`), by opcode extension
+pub const ARITHMETIC: [&str; 8] = ["add", "or", "adc", "sbb", "and", "sub", "xor", "cmp"];
+const SHIFTS: [&str; 8] = ["rol", "ror", "rcl", "rcr", "shl", "shr", "sal", "sar"];
+/// The condition codes of `jcc`, `setcc` and `cmovcc`, by the low nibble of the opcode
+pub const CONDITIONS: [&str; 16] = [
+    "o", "no", "b", "ae", "e", "ne", "be", "a", "s", "ns", "p", "np", "l", "ge", "le", "g",
+];
+
+/// A memory operand, `segment:[base + index * scale + disp]`
+struct Memory {
+    /// The size of the accessed value, 0 if no value is accessed like for `lea`
+    size: Size,
+    segment: Option<&'static str>,
+    /// The base register, `rip` for a RIP relative address
+    base: Option<&'static str>,
+    index: Option<(&'static str, u8)>,
+    /// The displacement and whether it is encoded in the instruction
+    disp: (i64, bool),
+}
+
+/// An operand of an instruction
+enum Operand {
+    Register(&'static str),
+    /// An immediate value sign extended to the operand size
+    Immediate(i64, Size),
+    Memory(Memory),
+    /// The address targeted by a relative jump or call
+    Target(usize),
+}
+
+impl Operand {
+    fn is_general_register(&self) -> bool {
+        matches!(self, Operand::Register(name) if !name.starts_with("xmm"))
+    }
+}
+
+/// A decoded x86_64 instruction
+pub struct Instruction {
+    pub addr: usize,
+    pub len: usize,
+    /// The prefix printed before the mnemonic, like `rep` or `lock`
+    prefix: Option<&'static str>,
+    mnemonic: String,
+    /// The operands in Intel order, the destination first
+    operands: Vec<Operand>,
+    /// The size of the memory operand is told by a suffix in AT&T syntax when no register operand gives it
+    suffixed: bool,
+    /// The operand is the address of an indirect jump or call
+    indirect: bool,
+}
+
+/// The ModRM byte, the register operand and the register or memory operand
+struct ModRm {
+    reg: u8,
+    rm: Rm,
+}
+
+enum Rm {
+    Register(u8),
+    Memory {
+        base: Option<u8>,
+        index: Option<(u8, u8)>,
+        disp: (i64, bool),
+        rip_relative: bool,
+    },
+}
+
+/// Decodes an instruction from its bytes, keeping track of the prefixes
+struct Decoder<'a> {
+    code: &'a [u8],
+    pos: usize,
+    rex: u8,
+    operand_size_prefix: bool,
+    address_size_prefix: bool,
+    /// The `f2` or `f3` prefix
+    repeat: Option<u8>,
+    segment: Option<u8>,
+    lock: bool,
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.code.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    /// Reads an immediate of `size` bytes, sign extended
+    fn immediate(&mut self, size: Size) -> Option<i64> {
+        let bytes = self.code.get(self.pos..self.pos + size as usize)?;
+        self.pos += size as usize;
+        let mut value = [0; 8];
+        value[..size as usize].copy_from_slice(bytes);
+        let shift = 64 - 8 * size as u32;
+        Some(i64::from_le_bytes(value) << shift >> shift)
+    }
+
+    fn rex_w(&self) -> bool {
+        self.rex & 8 != 0
+    }
+
+    /// The size of the operands of the instructions that default to 32 bits
+    fn operand_size(&self) -> Size {
+        if self.rex_w() {
+            8
+        } else if self.operand_size_prefix {
+            2
+        } else {
+            4
+        }
+    }
+
+    /// The size of the operands of the instructions that default to 64 bits, like `push`
+    fn stack_size(&self) -> Size {
+        if self.operand_size_prefix { 2 } else { 8 }
+    }
+
+    fn prefixes(&mut self) -> Option<()> {
+        loop {
+            match *self.code.get(self.pos)? {
+                0x66 => self.operand_size_prefix = true,
+                0x67 => self.address_size_prefix = true,
+                byte @ (0xf2 | 0xf3) => self.repeat = Some(byte),
+                0xf0 => self.lock = true,
+                byte @ (0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65) => self.segment = Some(byte),
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        if self.code.get(self.pos)? & 0xf0 == 0x40 {
+            self.rex = self.byte()?;
+        }
+        Some(())
+    }
+
+    fn modrm(&mut self) -> Option<ModRm> {
+        let modrm = self.byte()?;
+        let (mode, reg, rm) = (modrm >> 6, (modrm >> 3) & 7, modrm & 7);
+        let reg = reg | (self.rex & 4) << 1;
+        if mode == 3 {
+            return Some(ModRm {
+                reg,
+                rm: Rm::Register(rm | (self.rex & 1) << 3),
+            });
+        }
+        let mut base = Some(rm | (self.rex & 1) << 3);
+        let mut index = None;
+        let mut rip_relative = false;
+        let mut disp_size = match mode {
+            1 => 1,
+            2 => 4,
+            _ => 0,
+        };
+        if rm == 4 {
+            let sib = self.byte()?;
+            let (scale, sib_index, sib_base) = (sib >> 6, (sib >> 3) & 7, sib & 7);
+            let sib_index = sib_index | (self.rex & 2) << 2;
+            // Without REX.X, the index 4 means no index
+            if sib_index != 4 {
+                index = Some((sib_index, 1 << scale));
+            }
+            base = Some(sib_base | (self.rex & 1) << 3);
+            if mode == 0 && sib_base == 5 {
+                base = None;
+                disp_size = 4;
+            }
+        } else if mode == 0 && rm == 5 {
+            base = None;
+            rip_relative = true;
+            disp_size = 4;
+        }
+        let disp = match disp_size {
+            0 => (0, false),
+            size => (self.immediate(size)?, true),
+        };
+        Some(ModRm {
+            reg,
+            rm: Rm::Memory {
+                base,
+                index,
+                disp,
+                rip_relative,
+            },
+        })
+    }
+
+    /// The name of the general purpose register `index` of `size` bytes
+    fn register(&self, index: u8, size: Size) -> &'static str {
+        let index = index as usize;
+        match size {
+            1 if self.rex == 0 && (4..8).contains(&index) => GPR8_HIGH[index - 4],
+            1 => GPR8[index],
+            2 => GPR16[index],
+            4 => GPR32[index],
+            _ => GPR64[index],
+        }
+    }
+
+    fn segment(&self) -> Option<&'static str> {
+        match self.segment? {
+            0x26 => Some("es"),
+            0x2e => Some("cs"),
+            0x36 => Some("ss"),
+            0x3e => Some("ds"),
+            0x64 => Some("fs"),
+            _ => Some("gs"),
+        }
+    }
+
+    /// The register or memory operand of `modrm`, a general purpose register of `size` bytes
+    fn rm(&self, modrm: &ModRm, size: Size) -> Operand {
+        self.rm_with(modrm, size, |index| self.register(index, size))
+    }
+
+    /// The register or memory operand of `modrm`, a SSE register or `size` bytes of memory
+    fn rm_xmm(&self, modrm: &ModRm, size: Size) -> Operand {
+        self.rm_with(modrm, size, |index| XMM[index as usize])
+    }
+
+    fn rm_with(&self, modrm: &ModRm, size: Size, register: impl Fn(u8) -> &'static str) -> Operand {
+        match modrm.rm {
+            Rm::Register(index) => Operand::Register(register(index)),
+            Rm::Memory {
+                base,
+                index,
+                disp,
+                rip_relative,
+            } => {
+                let address_size = if self.address_size_prefix { 4 } else { 8 };
+                let base = match base {
+                    _ if rip_relative => Some(if address_size == 4 { "eip" } else { "rip" }),
+                    Some(base) => Some(self.register(base, address_size)),
+                    None => None,
+                };
+                Operand::Memory(Memory {
+                    size,
+                    segment: self.segment(),
+                    base,
+                    index: index.map(|(index, scale)| (self.register(index, address_size), scale)),
+                    disp,
+                })
+            }
+        }
+    }
+
+    /// The memory operand of the string instructions, `segment:[base]`
+    fn string_memory(&self, segment: &'static str, base: u8, size: Size) -> Operand {
+        let address_size = if self.address_size_prefix { 4 } else { 8 };
+        Operand::Memory(Memory {
+            size,
+            segment: Some(segment),
+            base: Some(self.register(base, address_size)),
+            index: None,
+            disp: (0, false),
+        })
+    }
+}
+
+/// The parts of an instruction found by decoding its opcode
+struct Decoded {
+    mnemonic: String,
+    operands: Vec<Operand>,
+    suffixed: bool,
+    indirect: bool,
+}
+
+impl Decoded {
+    /// An instruction whose size is told by the suffix in AT&T syntax if needed
+    fn sized(mnemonic: impl Into<String>, operands: Vec<Operand>) -> Self {
+        Self {
+            mnemonic: mnemonic.into(),
+            operands,
+            suffixed: true,
+            indirect: false,
+        }
+    }
+
+    /// An instruction whose size is implied by the mnemonic
+    fn plain(mnemonic: impl Into<String>, operands: Vec<Operand>) -> Self {
+        Self {
+            mnemonic: mnemonic.into(),
+            operands,
+            suffixed: false,
+            indirect: false,
+        }
+    }
+}
+
+/// Decodes the instruction at the beginning of `code`, located at `addr`
+///
+/// Returns `None` if the bytes are not an instruction known by the disassembler. Only the general purpose,
+/// the usual SSE instructions and the instructions commonly generated by compilers are known.
+pub fn decode(code: &[u8], addr: usize) -> Option<Instruction> {
+    let mut decoder = Decoder {
+        code: &code[..code.len().min(15)],
+        pos: 0,
+        rex: 0,
+        operand_size_prefix: false,
+        address_size_prefix: false,
+        repeat: None,
+        segment: None,
+        lock: false,
+    };
+    decoder.prefixes()?;
+    let opcode = decoder.byte()?;
+    let decoded = match opcode {
+        0x0f => two_bytes(&mut decoder)?,
+        _ => one_byte(&mut decoder, opcode)?,
+    };
+    let string = matches!(opcode, 0xa4..=0xa7 | 0xaa..=0xaf);
+    let branch = matches!(opcode, 0x70..=0x7f | 0xc3 | 0xe8 | 0xe9 | 0xeb)
+        || (opcode == 0x0f && decoded.mnemonic.starts_with('j'))
+        || decoded.indirect;
+    let prefix = match decoder.repeat {
+        _ if decoder.lock => Some("lock"),
+        Some(0xf3) if string && matches!(opcode, 0xa6 | 0xa7 | 0xae | 0xaf) => Some("repz"),
+        Some(0xf3) if string => Some("rep"),
+        Some(0xf2) if string => Some("repnz"),
+        Some(0xf2) if branch => Some("bnd"),
+        _ if decoded.indirect && decoder.segment == Some(0x3e) => Some("notrack"),
+        _ if decoder.address_size_prefix
+            && !decoded
+                .operands
+                .iter()
+                .any(|operand| matches!(operand, Operand::Memory(_))) =>
+        {
+            Some("addr32")
+        }
+        _ => None,
+    };
+    Some(Instruction {
+        addr,
+        len: decoder.pos,
+        prefix,
+        mnemonic: decoded.mnemonic,
+        operands: decoded.operands,
+        suffixed: decoded.suffixed,
+        indirect: decoded.indirect,
+    })
+}
+
+/// Decodes the instructions of `code`, located at `start`, that begin in its `len` first bytes
+///
+/// The bytes that are not an instruction are decoded as unknown instructions of one byte.
+/// The decoding stops at the end of the address space.
+pub fn decode_range(code: &[u8], start: usize, len: usize) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < len.min(code.len()) {
+        let Some(addr) = start.checked_add(offset) else {
+            break;
+        };
+        let instruction = decode(&code[offset..], addr)
+            .unwrap_or_else(|| Instruction::unknown(addr, code[offset]));
+        offset += instruction.len;
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+fn one_byte(decoder: &mut Decoder, opcode: u8) -> Option<Decoded> {
+    let size = decoder.operand_size();
+    let decoded = match opcode {
+        0x00..=0x3f if opcode & 7 < 6 => {
+            let name = ARITHMETIC[opcode as usize >> 3];
+            let size = if opcode & 1 == 0 { 1 } else { size };
+            match opcode & 7 {
+                0 | 1 => {
+                    let modrm = decoder.modrm()?;
+                    let reg = Operand::Register(decoder.register(modrm.reg, size));
+                    Decoded::sized(name, vec![decoder.rm(&modrm, size), reg])
+                }
+                2 | 3 => {
+                    let modrm = decoder.modrm()?;
+                    let rm = decoder.rm(&modrm, size);
+                    let reg = Operand::Register(decoder.register(modrm.reg, size));
+                    Decoded::sized(name, vec![reg, rm])
+                }
+                _ => {
+                    let value = decoder.immediate(size.min(4))?;
+                    let accumulator = Operand::Register(decoder.register(0, size));
+                    Decoded::sized(name, vec![accumulator, Operand::Immediate(value, size)])
+                }
+            }
+        }
+        0x50..=0x5f => {
+            let name = if opcode < 0x58 { "push" } else { "pop" };
+            let index = (opcode & 7) | (decoder.rex & 1) << 3;
+            let register = decoder.register(index, decoder.stack_size());
+            Decoded::plain(name, vec![Operand::Register(register)])
+        }
+        0x63 => {
+            let modrm = decoder.modrm()?;
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            Decoded::plain("movslq", vec![reg, decoder.rm(&modrm, 4)])
+        }
+        0x68 | 0x6a => {
+            let value = decoder.immediate(if opcode == 0x68 { 4 } else { 1 })?;
+            let size = decoder.stack_size();
+            Decoded::plain("push", vec![Operand::Immediate(value, size)])
+        }
+        0x69 | 0x6b => {
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, size);
+            let value = decoder.immediate(if opcode == 0x69 { size.min(4) } else { 1 })?;
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            Decoded::sized("imul", vec![reg, rm, Operand::Immediate(value, size)])
+        }
+        0x70..=0x7f => {
+            let offset = decoder.immediate(1)?;
+            let name = format!("j{}", CONDITIONS[opcode as usize & 0xf]);
+            Decoded::plain(name, vec![Operand::Target(offset as usize)])
+        }
+        0x80 | 0x81 | 0x83 => {
+            let size = if opcode == 0x80 { 1 } else { size };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, size);
+            let value = decoder.immediate(if opcode == 0x81 { size.min(4) } else { 1 })?;
+            let name = ARITHMETIC[modrm.reg as usize & 7];
+            Decoded::sized(name, vec![rm, Operand::Immediate(value, size)])
+        }
+        0x84..=0x8b => {
+            let name = match opcode {
+                0x84 | 0x85 => "test",
+                0x86 | 0x87 => "xchg",
+                _ => "mov",
+            };
+            let size = if opcode & 1 == 0 { 1 } else { size };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, size);
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            match opcode {
+                0x8a | 0x8b => Decoded::sized(name, vec![reg, rm]),
+                _ => Decoded::sized(name, vec![rm, reg]),
+            }
+        }
+        0x8d => {
+            let modrm = decoder.modrm()?;
+            if let Rm::Register(_) = modrm.rm {
+                return None;
+            }
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            Decoded::plain("lea", vec![reg, decoder.rm(&modrm, 0)])
+        }
+        0x8f => {
+            let modrm = decoder.modrm()?;
+            if modrm.reg & 7 != 0 {
+                return None;
+            }
+            let size = decoder.stack_size();
+            Decoded::plain("pop", vec![decoder.rm(&modrm, size)])
+        }
+        0x90 if decoder.rex & 1 == 0 && !decoder.operand_size_prefix => match decoder.repeat {
+            Some(0xf3) => Decoded::plain("pause", vec![]),
+            _ => Decoded::plain("nop", vec![]),
+        },
+        0x90..=0x97 => {
+            let index = (opcode & 7) | (decoder.rex & 1) << 3;
+            let register = Operand::Register(decoder.register(index, size));
+            let accumulator = Operand::Register(decoder.register(0, size));
+            Decoded::plain("xchg", vec![register, accumulator])
+        }
+        0x98 => Decoded::plain(
+            match size {
+                8 => "cltq",
+                2 => "cbtw",
+                _ => "cwtl",
+            },
+            vec![],
+        ),
+        0x99 => Decoded::plain(
+            match size {
+                8 => "cqto",
+                2 => "cwtd",
+                _ => "cltd",
+            },
+            vec![],
+        ),
+        0xa4..=0xa7 => {
+            let size = if opcode & 1 == 0 { 1 } else { size };
+            let name = if opcode < 0xa6 { "movs" } else { "cmps" };
+            let destination = decoder.string_memory("es", 7, size);
+            let source = decoder.string_memory("ds", 6, size);
+            match opcode {
+                0xa4 | 0xa5 => Decoded::sized(name, vec![destination, source]),
+                _ => Decoded::sized(name, vec![source, destination]),
+            }
+        }
+        0xa8 | 0xa9 => {
+            let size = if opcode == 0xa8 { 1 } else { size };
+            let value = decoder.immediate(size.min(4))?;
+            let accumulator = Operand::Register(decoder.register(0, size));
+            Decoded::sized("test", vec![accumulator, Operand::Immediate(value, size)])
+        }
+        0xaa..=0xaf => {
+            let size = if opcode & 1 == 0 { 1 } else { size };
+            let accumulator = Operand::Register(decoder.register(0, size));
+            match opcode {
+                0xaa | 0xab => {
+                    let destination = decoder.string_memory("es", 7, size);
+                    Decoded::sized("stos", vec![destination, accumulator])
+                }
+                0xac | 0xad => {
+                    let source = decoder.string_memory("ds", 6, size);
+                    Decoded::sized("lods", vec![accumulator, source])
+                }
+                _ => {
+                    let destination = decoder.string_memory("es", 7, size);
+                    Decoded::sized("scas", vec![accumulator, destination])
+                }
+            }
+        }
+        0xb0..=0xb7 => {
+            let index = (opcode & 7) | (decoder.rex & 1) << 3;
+            let register = Operand::Register(decoder.register(index, 1));
+            let value = decoder.immediate(1)?;
+            Decoded::sized("mov", vec![register, Operand::Immediate(value, 1)])
+        }
+        0xb8..=0xbf => {
+            let index = (opcode & 7) | (decoder.rex & 1) << 3;
+            let register = Operand::Register(decoder.register(index, size));
+            let value = decoder.immediate(size)?;
+            let name = if size == 8 { "movabs" } else { "mov" };
+            Decoded::sized(name, vec![register, Operand::Immediate(value, size)])
+        }
+        0xc0 | 0xc1 | 0xd0..=0xd3 => {
+            let size = if opcode & 1 == 0 { 1 } else { size };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, size);
+            let name = SHIFTS[modrm.reg as usize & 7];
+            let operands = match opcode {
+                0xc0 | 0xc1 => vec![rm, Operand::Immediate(decoder.immediate(1)?, 1)],
+                0xd0 | 0xd1 => vec![rm],
+                _ => vec![rm, Operand::Register("cl")],
+            };
+            Decoded::sized(name, operands)
+        }
+        0xc2 => {
+            let value = decoder.immediate(2)? as u16;
+            Decoded::plain("ret", vec![Operand::Immediate(value as _, 2)])
+        }
+        0xc3 => Decoded::plain("ret", vec![]),
+        0xc6 | 0xc7 => {
+            let size = if opcode == 0xc6 { 1 } else { size };
+            let modrm = decoder.modrm()?;
+            if modrm.reg & 7 != 0 {
+                return None;
+            }
+            let rm = decoder.rm(&modrm, size);
+            let value = decoder.immediate(size.min(4))?;
+            Decoded::sized("mov", vec![rm, Operand::Immediate(value, size)])
+        }
+        0xc9 => Decoded::plain("leave", vec![]),
+        0xcc => Decoded::plain("int3", vec![]),
+        0xcd => {
+            let value = decoder.immediate(1)? as u8;
+            Decoded::plain("int", vec![Operand::Immediate(value as _, 1)])
+        }
+        0xe8 | 0xe9 => {
+            let offset = decoder.immediate(4)?;
+            let name = if opcode == 0xe8 { "call" } else { "jmp" };
+            Decoded::plain(name, vec![Operand::Target(offset as usize)])
+        }
+        0xeb => {
+            let offset = decoder.immediate(1)?;
+            Decoded::plain("jmp", vec![Operand::Target(offset as usize)])
+        }
+        0xf4 => Decoded::plain("hlt", vec![]),
+        0xf5 => Decoded::plain("cmc", vec![]),
+        0xf6 | 0xf7 => {
+            let size = if opcode == 0xf6 { 1 } else { size };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, size);
+            match modrm.reg & 7 {
+                0 => {
+                    let value = decoder.immediate(size.min(4))?;
+                    Decoded::sized("test", vec![rm, Operand::Immediate(value, size)])
+                }
+                1 => return None,
+                extension => {
+                    let name = ["", "", "not", "neg", "mul", "imul", "div", "idiv"];
+                    Decoded::sized(name[extension as usize], vec![rm])
+                }
+            }
+        }
+        0xf8 => Decoded::plain("clc", vec![]),
+        0xf9 => Decoded::plain("stc", vec![]),
+        0xfc => Decoded::plain("cld", vec![]),
+        0xfd => Decoded::plain("std", vec![]),
+        0xfe | 0xff => {
+            let size = if opcode == 0xfe { 1 } else { size };
+            let modrm = decoder.modrm()?;
+            match (opcode, modrm.reg & 7) {
+                (_, 0) => Decoded::sized("inc", vec![decoder.rm(&modrm, size)]),
+                (_, 1) => Decoded::sized("dec", vec![decoder.rm(&modrm, size)]),
+                (0xff, 2 | 4) => {
+                    let name = if modrm.reg & 7 == 2 { "call" } else { "jmp" };
+                    Decoded {
+                        indirect: true,
+                        ..Decoded::plain(name, vec![decoder.rm(&modrm, 8)])
+                    }
+                }
+                (0xff, 6) => {
+                    let size = decoder.stack_size();
+                    Decoded::plain("push", vec![decoder.rm(&modrm, size)])
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+/// Decodes the instructions whose opcode starts with `0f`
+fn two_bytes(decoder: &mut Decoder) -> Option<Decoded> {
+    let opcode = decoder.byte()?;
+    let size = decoder.operand_size();
+    // The SSE instructions are selected by a mandatory prefix, none, `66`, `f3` or `f2`
+    let sse = match (decoder.repeat, decoder.operand_size_prefix) {
+        (Some(0xf3), _) => 2,
+        (Some(0xf2), _) => 3,
+        (None, true) => 1,
+        _ => 0,
+    };
+    let decoded = match opcode {
+        0x05 => Decoded::plain("syscall", vec![]),
+        0x0b => Decoded::plain("ud2", vec![]),
+        0x1e if decoder.repeat == Some(0xf3) && decoder.code.get(decoder.pos) == Some(&0xfa) => {
+            decoder.pos += 1;
+            Decoded::plain("endbr64", vec![])
+        }
+        0x1f => {
+            let modrm = decoder.modrm()?;
+            // A segment prefix only pads the instruction, it is printed before the mnemonic
+            let mut name = match decoder.segment.take() {
+                Some(0x2e) => String::from("cs nop"),
+                _ => String::from("nop"),
+            };
+            let padding = decoder.code[..decoder.pos]
+                .iter()
+                .filter(|&&byte| byte == 0x66)
+                .count();
+            if padding > 1 {
+                name.insert_str(0, &"data16 ".repeat(padding - 1));
+            }
+            Decoded::sized(name, vec![decoder.rm(&modrm, size)])
+        }
+        0x10 | 0x11 | 0x28 | 0x29 | 0x2e | 0x2f | 0x51 | 0x54..=0x59 | 0x5c..=0x5f => {
+            // The names without prefix, with `66`, `f3` and `f2`
+            let names = match opcode {
+                0x10 | 0x11 => ["movups", "movupd", "movss", "movsd"],
+                0x28 | 0x29 => ["movaps", "movapd", "", ""],
+                0x2e => ["ucomiss", "ucomisd", "", ""],
+                0x2f => ["comiss", "comisd", "", ""],
+                0x51 => ["sqrtps", "sqrtpd", "sqrtss", "sqrtsd"],
+                0x54 => ["andps", "andpd", "", ""],
+                0x55 => ["andnps", "andnpd", "", ""],
+                0x56 => ["orps", "orpd", "", ""],
+                0x57 => ["xorps", "xorpd", "", ""],
+                0x58 => ["addps", "addpd", "addss", "addsd"],
+                0x59 => ["mulps", "mulpd", "mulss", "mulsd"],
+                0x5c => ["subps", "subpd", "subss", "subsd"],
+                0x5d => ["minps", "minpd", "minss", "minsd"],
+                0x5e => ["divps", "divpd", "divss", "divsd"],
+                _ => ["maxps", "maxpd", "maxss", "maxsd"],
+            };
+            let name = names[sse];
+            let memory_size = match &name[name.len().saturating_sub(2)..] {
+                "ss" => 4,
+                "sd" => 8,
+                _ => 16,
+            };
+            if name.is_empty() {
+                return None;
+            }
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, memory_size);
+            let reg = Operand::Register(XMM[modrm.reg as usize]);
+            match opcode {
+                0x11 | 0x29 => Decoded::plain(name, vec![rm, reg]),
+                _ => Decoded::plain(name, vec![reg, rm]),
+            }
+        }
+        0x2a if sse >= 2 => {
+            let modrm = decoder.modrm()?;
+            let size = if decoder.rex_w() { 8 } else { 4 };
+            let name = if sse == 2 { "cvtsi2ss" } else { "cvtsi2sd" };
+            let rm = decoder.rm(&modrm, size);
+            Decoded::sized(name, vec![Operand::Register(XMM[modrm.reg as usize]), rm])
+        }
+        0x2c | 0x2d if sse >= 2 => {
+            let modrm = decoder.modrm()?;
+            let size = if decoder.rex_w() { 8 } else { 4 };
+            let name = match (opcode, sse) {
+                (0x2c, 2) => "cvttss2si",
+                (0x2c, _) => "cvttsd2si",
+                (_, 2) => "cvtss2si",
+                _ => "cvtsd2si",
+            };
+            let rm = decoder.rm_xmm(&modrm, if sse == 2 { 4 } else { 8 });
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            Decoded::plain(name, vec![reg, rm])
+        }
+        0x5a | 0x5b => {
+            let (name, memory_size) = match (opcode, sse) {
+                (0x5a, 0) => ("cvtps2pd", 8),
+                (0x5a, 1) => ("cvtpd2ps", 16),
+                (0x5a, 2) => ("cvtss2sd", 4),
+                (0x5a, _) => ("cvtsd2ss", 8),
+                (_, 0) => ("cvtdq2ps", 16),
+                (_, 1) => ("cvtps2dq", 16),
+                (_, 2) => ("cvttps2dq", 16),
+                _ => return None,
+            };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, memory_size);
+            Decoded::plain(name, vec![Operand::Register(XMM[modrm.reg as usize]), rm])
+        }
+        0x6e | 0x7e if sse == 1 => {
+            let modrm = decoder.modrm()?;
+            let size = if decoder.rex_w() { 8 } else { 4 };
+            let name = if size == 8 { "movq" } else { "movd" };
+            let rm = decoder.rm(&modrm, size);
+            let reg = Operand::Register(XMM[modrm.reg as usize]);
+            match opcode {
+                0x6e => Decoded::plain(name, vec![reg, rm]),
+                _ => Decoded::plain(name, vec![rm, reg]),
+            }
+        }
+        0x7e if sse == 2 => {
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, 8);
+            Decoded::plain("movq", vec![Operand::Register(XMM[modrm.reg as usize]), rm])
+        }
+        0xd6 if sse == 1 => {
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, 8);
+            Decoded::plain("movq", vec![rm, Operand::Register(XMM[modrm.reg as usize])])
+        }
+        0x6f | 0x7f if sse == 1 || sse == 2 => {
+            let name = if sse == 1 { "movdqa" } else { "movdqu" };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, 16);
+            let reg = Operand::Register(XMM[modrm.reg as usize]);
+            match opcode {
+                0x6f => Decoded::plain(name, vec![reg, rm]),
+                _ => Decoded::plain(name, vec![rm, reg]),
+            }
+        }
+        0x12 | 0x13 | 0x16 | 0x17 if sse <= 1 => {
+            let modrm = decoder.modrm()?;
+            let register = matches!(modrm.rm, Rm::Register(_));
+            let name = match (opcode, sse, register) {
+                (0x12, 0, true) => "movhlps",
+                (0x16, 0, true) => "movlhps",
+                (_, _, true) => return None,
+                (0x12 | 0x13, 0, _) => "movlps",
+                (0x12 | 0x13, _, _) => "movlpd",
+                (_, 0, _) => "movhps",
+                _ => "movhpd",
+            };
+            let rm = decoder.rm_xmm(&modrm, 8);
+            let reg = Operand::Register(XMM[modrm.reg as usize]);
+            match opcode {
+                0x13 | 0x17 => Decoded::plain(name, vec![rm, reg]),
+                _ => Decoded::plain(name, vec![reg, rm]),
+            }
+        }
+        0x70 | 0xc6 if sse <= 1 => {
+            let name = match (opcode, sse) {
+                (0x70, 1) => "pshufd",
+                (0x70, _) => return None,
+                (_, 0) => "shufps",
+                _ => "shufpd",
+            };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, 16);
+            let value = decoder.immediate(1)? as u8;
+            let reg = Operand::Register(XMM[modrm.reg as usize]);
+            Decoded::plain(name, vec![reg, rm, Operand::Immediate(value as _, 1)])
+        }
+        0xae if matches!(decoder.code.get(decoder.pos), Some(modrm) if modrm >> 6 != 3) => {
+            let modrm = decoder.modrm()?;
+            let name = match modrm.reg & 7 {
+                2 => "ldmxcsr",
+                3 => "stmxcsr",
+                _ => return None,
+            };
+            Decoded::plain(name, vec![decoder.rm(&modrm, 4)])
+        }
+        0xae => match decoder.byte()? {
+            0xe8 => Decoded::plain("lfence", vec![]),
+            0xf0 => Decoded::plain("mfence", vec![]),
+            0xf8 => Decoded::plain("sfence", vec![]),
+            _ => return None,
+        },
+        0x01 if decoder.code.get(decoder.pos) == Some(&0xd0) => {
+            decoder.pos += 1;
+            Decoded::plain("xgetbv", vec![])
+        }
+        0x18 => {
+            let modrm = decoder.modrm()?;
+            let name = match modrm.reg & 7 {
+                0 => "prefetchnta",
+                1 => "prefetcht0",
+                2 => "prefetcht1",
+                3 => "prefetcht2",
+                _ => return None,
+            };
+            Decoded::plain(name, vec![decoder.rm(&modrm, 1)])
+        }
+        0x2b | 0xe7 if sse <= 1 => {
+            let name = match (opcode, sse) {
+                (0x2b, 0) => "movntps",
+                (0x2b, _) => "movntpd",
+                (_, 1) => "movntdq",
+                _ => return None,
+            };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, 16);
+            Decoded::plain(name, vec![rm, Operand::Register(XMM[modrm.reg as usize])])
+        }
+        0x71..=0x73 if sse == 1 => {
+            let modrm = decoder.modrm()?;
+            let names = match opcode {
+                0x71 => ["", "", "psrlw", "", "psraw", "", "psllw", ""],
+                0x72 => ["", "", "psrld", "", "psrad", "", "pslld", ""],
+                _ => ["", "", "psrlq", "psrldq", "", "", "psllq", "pslldq"],
+            };
+            let name = names[modrm.reg as usize & 7];
+            if name.is_empty() {
+                return None;
+            }
+            let rm = decoder.rm_xmm(&modrm, 16);
+            let value = decoder.immediate(1)? as u8;
+            Decoded::plain(name, vec![rm, Operand::Immediate(value as _, 1)])
+        }
+        0x60..=0x62
+        | 0x64..=0x66
+        | 0x68..=0x6a
+        | 0x6c
+        | 0x6d
+        | 0x74..=0x76
+        | 0xd4
+        | 0xda
+        | 0xdb
+        | 0xde
+        | 0xdf
+        | 0xeb
+        | 0xef
+        | 0xf8..=0xfe
+            if sse == 1 =>
+        {
+            let name = match opcode {
+                0x64 => "pcmpgtb",
+                0x65 => "pcmpgtw",
+                0x66 => "pcmpgtd",
+                0x75 => "pcmpeqw",
+                0xda => "pminub",
+                0xde => "pmaxub",
+                0xdf => "pandn",
+                0xf8 => "psubb",
+                0xf9 => "psubw",
+                0xfc => "paddb",
+                0xfd => "paddw",
+                0x60 => "punpcklbw",
+                0x61 => "punpcklwd",
+                0x62 => "punpckldq",
+                0x68 => "punpckhbw",
+                0x69 => "punpckhwd",
+                0x6a => "punpckhdq",
+                0x6c => "punpcklqdq",
+                0x6d => "punpckhqdq",
+                0x74 => "pcmpeqb",
+                0x76 => "pcmpeqd",
+                0xd4 => "paddq",
+                0xfa => "psubd",
+                0xfb => "psubq",
+                0xdb => "pand",
+                0xeb => "por",
+                0xef => "pxor",
+                _ => "paddd",
+            };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm_xmm(&modrm, 16);
+            Decoded::plain(name, vec![Operand::Register(XMM[modrm.reg as usize]), rm])
+        }
+        0xd7 if sse == 1 => {
+            let modrm = decoder.modrm()?;
+            let reg = Operand::Register(decoder.register(modrm.reg, 4));
+            Decoded::plain("pmovmskb", vec![reg, decoder.rm_xmm(&modrm, 16)])
+        }
+        0x31 => Decoded::plain("rdtsc", vec![]),
+        0x40..=0x4f => {
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, size);
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            let name = format!("cmov{}", CONDITIONS[opcode as usize & 0xf]);
+            Decoded::sized(name, vec![reg, rm])
+        }
+        0x80..=0x8f => {
+            let offset = decoder.immediate(4)?;
+            let name = format!("j{}", CONDITIONS[opcode as usize & 0xf]);
+            Decoded::plain(name, vec![Operand::Target(offset as usize)])
+        }
+        0x90..=0x9f => {
+            let modrm = decoder.modrm()?;
+            let name = format!("set{}", CONDITIONS[opcode as usize & 0xf]);
+            Decoded::plain(name, vec![decoder.rm(&modrm, 1)])
+        }
+        0xa2 => Decoded::plain("cpuid", vec![]),
+        0xa3 | 0xab | 0xb0 | 0xb1 | 0xc0 | 0xc1 => {
+            let name = match opcode {
+                0xa3 => "bt",
+                0xab => "bts",
+                0xb0 | 0xb1 => "cmpxchg",
+                _ => "xadd",
+            };
+            let size = if opcode == 0xb0 || opcode == 0xc0 {
+                1
+            } else {
+                size
+            };
+            let modrm = decoder.modrm()?;
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            Decoded::sized(name, vec![decoder.rm(&modrm, size), reg])
+        }
+        0xaf | 0xbc | 0xbd => {
+            let name = match (opcode, decoder.repeat) {
+                (0xaf, _) => "imul",
+                (0xbc, Some(0xf3)) => "tzcnt",
+                (0xbc, _) => "bsf",
+                (_, Some(0xf3)) => "lzcnt",
+                _ => "bsr",
+            };
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, size);
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            Decoded::sized(name, vec![reg, rm])
+        }
+        0xb6 | 0xb7 | 0xbe | 0xbf => {
+            let source_size = if opcode & 1 == 0 { 1 } else { 2 };
+            let extension = if opcode < 0xb8 { "movz" } else { "movs" };
+            let name = format!("{extension}{}{}", suffix(source_size), suffix(size));
+            let modrm = decoder.modrm()?;
+            let rm = decoder.rm(&modrm, source_size);
+            let reg = Operand::Register(decoder.register(modrm.reg, size));
+            Decoded::plain(name, vec![reg, rm])
+        }
+        0xc8..=0xcf => {
+            let index = (opcode & 7) | (decoder.rex & 1) << 3;
+            let register = Operand::Register(decoder.register(index, size));
+            Decoded::plain("bswap", vec![register])
+        }
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+/// The AT&T suffix of an operand of `size` bytes
+fn suffix(size: Size) -> char {
+    match size {
+        1 => 'b',
+        2 => 'w',
+        4 => 'l',
+        _ => 'q',
+    }
+}
+
+/// The Intel name of the size of a memory operand of `size` bytes
+fn size_name(size: Size) -> &'static str {
+    match size {
+        1 => "BYTE",
+        2 => "WORD",
+        4 => "DWORD",
+        8 => "QWORD",
+        10 => "TBYTE",
+        _ => "XMMWORD",
+    }
+}
+
+/// Returns the Intel mnemonic of the AT&T mnemonic `mnemonic`, when they differ
+///
+/// The mnemonics of the sign and zero extensions hold the operand sizes in AT&T syntax, like `movzbl`.
+fn intel_mnemonic(mnemonic: &str) -> Option<&'static str> {
+    let renamed = match mnemonic {
+        "cbtw" => "cbw",
+        "cwtl" => "cwde",
+        "cltq" => "cdqe",
+        "cwtd" => "cwd",
+        "cltd" => "cdq",
+        "cqto" => "cqo",
+        "movslq" => "movsxd",
+        _ => {
+            let sizes = mnemonic.get(4..)?;
+            let sized = sizes.len() == 2
+                && matches!(&sizes[..1], "b" | "w")
+                && matches!(&sizes[1..], "w" | "l" | "q");
+            return match &mnemonic[..4] {
+                "movz" if sized => Some("movzx"),
+                "movs" if sized => Some("movsx"),
+                _ => None,
+            };
+        }
+    };
+    Some(renamed)
+}
+
+/// Writes `value` as a signed hexadecimal number
+fn signed_hex(text: &mut String, value: i64) {
+    if value < 0 {
+        write!(text, "-{:#x}", value.unsigned_abs()).unwrap();
+    } else {
+        write!(text, "{value:#x}").unwrap();
+    }
+}
+
+impl Instruction {
+    /// An instruction that the disassembler doesn't know, starting with the byte `byte`
+    ///
+    /// The disassembly continues with the next byte.
+    pub fn unknown(addr: usize, byte: u8) -> Self {
+        Self {
+            addr,
+            len: 1,
+            prefix: None,
+            mnemonic: format!("(bad) {byte:#04x}"),
+            operands: Vec::new(),
+            suffixed: false,
+            indirect: false,
+        }
+    }
+
+    /// Checks if the instruction is a direct or indirect call
+    pub fn is_call(&self) -> bool {
+        self.mnemonic == "call"
+    }
+
+    /// Computes the address and size of the memory accessed by the instruction, if it has a memory operand
+    ///
+    /// `register` gives the value of a 64 bits register, or of `fs_base` and `gs_base` for the segments.
+    pub fn memory_access(&self, register: impl Fn(&str) -> Option<u64>) -> Option<(usize, usize)> {
+        let memory = self.operands.iter().find_map(|operand| match operand {
+            Operand::Memory(memory) if memory.size != 0 => Some(memory),
+            _ => None,
+        })?;
+        let mut addr = memory.disp.0 as u64;
+        match memory.base {
+            Some("rip") => addr = addr.wrapping_add(self.end() as _),
+            Some(base) => addr = addr.wrapping_add(register(base)?),
+            None => {}
+        }
+        if let Some((index, scale)) = memory.index {
+            addr = addr.wrapping_add(register(index)?.wrapping_mul(scale as _));
+        }
+        match memory.segment {
+            Some("fs") => addr = addr.wrapping_add(register("fs_base")?),
+            Some("gs") => addr = addr.wrapping_add(register("gs_base")?),
+            _ => {}
+        }
+        Some((addr as _, memory.size as _))
+    }
+
+    /// The address of the next instruction, relative jumps and RIP relative addresses start from there
+    fn end(&self) -> usize {
+        self.addr.wrapping_add(self.len)
+    }
+
+    /// Formats `operand` in AT&T syntax
+    fn format_operand(&self, operand: &Operand) -> String {
+        let mut text = String::new();
+        match operand {
+            Operand::Register(name) => write!(text, "%{name}").unwrap(),
+            Operand::Immediate(value, size) => {
+                let mask = u64::MAX >> (64 - 8 * *size as u32);
+                write!(text, "${:#x}", *value as u64 & mask).unwrap();
+            }
+            Operand::Memory(memory) => {
+                if let Some(segment) = memory.segment {
+                    write!(text, "%{segment}:").unwrap();
+                }
+                let (disp, explicit) = memory.disp;
+                if memory.base.is_none() && memory.index.is_none() {
+                    // An absolute address
+                    write!(text, "{:#x}", disp as u64).unwrap();
+                } else if explicit || memory.base.is_none() {
+                    signed_hex(&mut text, disp);
+                }
+                if memory.base.is_some() || memory.index.is_some() {
+                    text.push('(');
+                    if let Some(base) = memory.base {
+                        write!(text, "%{base}").unwrap();
+                    }
+                    if let Some((index, scale)) = memory.index {
+                        write!(text, ",%{index},{scale}").unwrap();
+                    }
+                    text.push(')');
+                }
+            }
+            Operand::Target(offset) => {
+                write!(text, "{:#x}", self.end().wrapping_add(*offset)).unwrap()
+            }
+        }
+        if self.indirect {
+            text.insert(0, '*');
+        }
+        text
+    }
+
+    /// Formats `operand` in Intel syntax
+    fn format_intel_operand(&self, operand: &Operand) -> String {
+        let mut text = String::new();
+        match operand {
+            Operand::Register(name) => text.push_str(name),
+            Operand::Immediate(value, size) => {
+                let mask = u64::MAX >> (64 - 8 * *size as u32);
+                write!(text, "{:#x}", *value as u64 & mask).unwrap();
+            }
+            Operand::Memory(memory) => {
+                if memory.size != 0 {
+                    write!(text, "{} PTR ", size_name(memory.size)).unwrap();
+                }
+                let (disp, explicit) = memory.disp;
+                if memory.base.is_none() && memory.index.is_none() {
+                    // An absolute address
+                    let segment = memory.segment.unwrap_or("ds");
+                    write!(text, "{segment}:{:#x}", disp as u64).unwrap();
+                    return text;
+                }
+                if let Some(segment) = memory.segment {
+                    write!(text, "{segment}:").unwrap();
+                }
+                text.push('[');
+                if let Some(base) = memory.base {
+                    text.push_str(base);
+                }
+                if let Some((index, scale)) = memory.index {
+                    if memory.base.is_some() {
+                        text.push('+');
+                    }
+                    write!(text, "{index}*{scale}").unwrap();
+                }
+                if explicit || memory.base.is_none() {
+                    if disp >= 0 {
+                        text.push('+');
+                    }
+                    signed_hex(&mut text, disp);
+                }
+                text.push(']');
+            }
+            Operand::Target(offset) => {
+                write!(text, "{:#x}", self.end().wrapping_add(*offset)).unwrap()
+            }
+        }
+        text
+    }
+
+    /// Formats the instruction in Intel syntax, the destination operand first
+    fn format_intel(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = intel_mnemonic(&self.mnemonic).unwrap_or(&self.mnemonic);
+        let mnemonic = match self.prefix {
+            Some(prefix) => format!("{prefix} {name}"),
+            None => name.to_owned(),
+        };
+        if self.operands.is_empty() {
+            return write!(f, "{mnemonic}");
+        }
+        let operands: Vec<String> = self
+            .operands
+            .iter()
+            .map(|operand| self.format_intel_operand(operand))
+            .collect();
+        write!(f, "{mnemonic:<6} {}", operands.join(","))?;
+        if let Some(addr) = self.rip_relative_address() {
+            write!(f, "        # {addr:#x}")?;
+        }
+        Ok(())
+    }
+
+    /// The address of the RIP relative memory operand, if any
+    fn rip_relative_address(&self) -> Option<usize> {
+        self.operands.iter().find_map(|operand| match operand {
+            Operand::Memory(Memory {
+                base: Some("rip"),
+                disp: (disp, _),
+                ..
+            }) => Some(self.end().wrapping_add(*disp as usize)),
+            _ => None,
+        })
+    }
+}
+
+impl Display for Instruction {
+    /// Formats the instruction in the syntax set by `set_flavor`, like `objdump` and `gdb`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if INTEL.load(Ordering::Relaxed) {
+            return self.format_intel(f);
+        }
+        let mut mnemonic = match self.prefix {
+            Some(prefix) => format!("{prefix} {}", self.mnemonic),
+            None => self.mnemonic.clone(),
+        };
+        // The suffix tells the operand size when no register operand does
+        if self.suffixed
+            && !self.operands.iter().any(Operand::is_general_register)
+            && let Some(size) = self.operands.iter().find_map(|operand| match operand {
+                Operand::Memory(memory) => Some(memory.size),
+                _ => None,
+            })
+        {
+            mnemonic.push(suffix(size));
+        }
+        if self.operands.is_empty() {
+            return write!(f, "{mnemonic}");
+        }
+        let operands: Vec<String> = self
+            .operands
+            .iter()
+            .rev()
+            .map(|operand| self.format_operand(operand))
+            .collect();
+        write!(f, "{mnemonic:<6} {}", operands.join(","))?;
+        if let Some(addr) = self.rip_relative_address() {
+            write!(f, "        # {addr:#x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_range_stops_at_the_end_of_the_address_space() {
+        let instructions = decode_range(&[0x90; 4], usize::MAX - 1, 4);
+        let addrs: Vec<usize> = instructions
+            .iter()
+            .map(|instruction| instruction.addr)
+            .collect();
+        assert_eq!(addrs, [usize::MAX - 1, usize::MAX]);
+    }
+
+    #[test]
+    fn decode_range_decodes_unknown_bytes_one_by_one() {
+        // ud2 then the invalid opcode 0x06 and a ret
+        let instructions = decode_range(&[0x0f, 0x0b, 0x06, 0xc3], 0x401000, 4);
+        let lens: Vec<usize> = instructions
+            .iter()
+            .map(|instruction| instruction.len)
+            .collect();
+        assert_eq!(lens, [2, 1, 1]);
+    }
+}
@@ -0,0 +1,814 @@
+use std::{collections::HashMap, fmt::Display, fs, path::Path, rc::Rc};
+
+use gimli::{
+    AttributeValue, EndianRcSlice, Operation, Reader as _, RunTimeEndian, UnitOffset, UnitRef,
+    X86_64,
+};
+use object::{Object, ObjectSection};
+
+type Reader = EndianRcSlice<RunTimeEndian>;
+
+/// A location in the source code of the program
+#[derive(PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u64,
+}
+
+impl Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// A row of the DWARF line table, the source location applies until the address of the next row
+struct LineRow {
+    addr: usize,
+    file: usize,
+    line: u64,
+    end_sequence: bool,
+}
+
+/// The kind of a type, which tells how a value of this type is formatted
+#[derive(Clone, Copy)]
+pub enum TypeKind {
+    Signed(usize),
+    Unsigned(usize),
+    Char,
+    Bool,
+    Float(usize),
+    Pointer,
+    Other,
+}
+
+/// The names of the general purpose registers, indexed by their DWARF number
+pub const DWARF_REGISTERS: [&str; 16] = [
+    "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15",
+];
+
+/// A type described by a DWARF type entry
+#[derive(Clone)]
+pub struct Type {
+    pub name: String,
+    pub kind: TypeKind,
+    /// The size of a value of this type in memory, 0 if unknown
+    pub byte_size: usize,
+    /// The members of a structure, only for the type of a variable, see `type_of`
+    pub members: Vec<Member>,
+    /// The type a pointer points to, `None` for the other types and for `void *`
+    pub pointee: Option<Box<Type>>,
+}
+
+/// A member of a structure, from a `DW_TAG_member` entry
+#[derive(Clone)]
+pub struct Member {
+    pub name: String,
+    /// The offset of the member from the beginning of the structure
+    pub offset: usize,
+    pub type_: Type,
+}
+
+impl Type {
+    /// A type that can't be read from the debugging information
+    pub fn unknown() -> Self {
+        Self {
+            name: String::from("?"),
+            kind: TypeKind::Other,
+            byte_size: 0,
+            members: Vec::new(),
+            pointee: None,
+        }
+    }
+
+    /// The size of a value of this type, `None` for the types that can't be held in a register
+    pub fn size(&self) -> Option<usize> {
+        match self.kind {
+            TypeKind::Signed(size) | TypeKind::Unsigned(size) | TypeKind::Float(size) => {
+                Some(size).filter(|size| (1..=8).contains(size))
+            }
+            TypeKind::Char | TypeKind::Bool => Some(1),
+            TypeKind::Pointer => Some(8),
+            TypeKind::Other => None,
+        }
+    }
+
+    /// Formats a value of this type from its raw bits
+    ///
+    /// Returns `None` for types that can't be held in a register like structures
+    pub fn format(&self, value: u64) -> Option<String> {
+        let formatted = match self.kind {
+            TypeKind::Signed(1) => (value as i8).to_string(),
+            TypeKind::Signed(2) => (value as i16).to_string(),
+            TypeKind::Signed(4) => (value as i32).to_string(),
+            TypeKind::Signed(_) => (value as i64).to_string(),
+            TypeKind::Unsigned(1) => (value as u8).to_string(),
+            TypeKind::Unsigned(2) => (value as u16).to_string(),
+            TypeKind::Unsigned(4) => (value as u32).to_string(),
+            TypeKind::Unsigned(_) => value.to_string(),
+            TypeKind::Char if (value as u8).is_ascii_graphic() => {
+                format!("{} '{}'", value as u8, value as u8 as char)
+            }
+            TypeKind::Char => (value as u8).to_string(),
+            TypeKind::Bool => (value as u8 != 0).to_string(),
+            TypeKind::Float(4) => f32::from_bits(value as u32).to_string(),
+            TypeKind::Float(8) => f64::from_bits(value).to_string(),
+            TypeKind::Pointer => format!("{value:#x}"),
+            TypeKind::Float(_) | TypeKind::Other => return None,
+        };
+        Some(formatted)
+    }
+
+    /// Reads a value of this type from its bytes in memory as a number, the signed integers being sign extended
+    ///
+    /// Returns `None` for the floating point numbers and the types that can't be held in a register.
+    pub fn number(&self, bytes: &[u8]) -> Option<u64> {
+        let size = self
+            .size()
+            .filter(|_| !matches!(self.kind, TypeKind::Float(_)))?;
+        let mut word = [0; 8];
+        word[..size].copy_from_slice(bytes.get(..size)?);
+        let value = u64::from_le_bytes(word);
+        let shift = 64 - 8 * size as u32;
+        Some(match self.kind {
+            TypeKind::Signed(_) => ((value << shift) as i64 >> shift) as u64,
+            _ => value,
+        })
+    }
+
+    /// Formats a value of this type from its bytes in memory
+    ///
+    /// Structures are formatted as their members, and the values no type can format are printed as raw bytes.
+    pub fn format_bytes(&self, bytes: &[u8]) -> String {
+        if !self.members.is_empty() {
+            let members: Vec<String> = self
+                .members
+                .iter()
+                .map(|member| {
+                    let bytes = bytes.get(member.offset..).unwrap_or_default();
+                    format!("{} = {}", member.name, member.type_.format_bytes(bytes))
+                })
+                .collect();
+            return format!("{{{}}}", members.join(", "));
+        }
+        let formatted = self
+            .size()
+            .filter(|&size| size <= bytes.len())
+            .and_then(|size| {
+                let mut word = [0; 8];
+                word[..size].copy_from_slice(&bytes[..size]);
+                self.format(u64::from_le_bytes(word))
+            });
+        formatted.unwrap_or_else(|| {
+            let len = self.byte_size.min(bytes.len());
+            let bytes: Vec<String> = bytes[..len]
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            format!("[{}]", bytes.join(" "))
+        })
+    }
+}
+
+/// The register the addresses of the variables of a function are relative to, from `DW_AT_frame_base`
+#[derive(Clone, Copy)]
+pub enum FrameBase {
+    /// The canonical frame address, the stack pointer before the call instruction
+    Cfa,
+    Rbp,
+}
+
+/// Where the value of a variable is stored, from its `DW_AT_location`
+#[derive(Clone, Copy)]
+pub enum Location {
+    /// At an offset from the frame base of the function, `DW_OP_fbreg`
+    FrameOffset(i64),
+    /// At a fixed address, `DW_OP_addr`
+    Address(usize),
+    /// In the register with this DWARF number, `DW_OP_reg`
+    Register(u16),
+    /// Described by another expression or by a location list
+    Unsupported,
+}
+
+/// A parameter or a variable of a function, or a global variable
+#[derive(Clone)]
+pub struct Variable {
+    pub name: String,
+    pub type_: Option<Type>,
+    /// `None` if the variable has no location, when it is optimized out
+    pub location: Option<Location>,
+    /// The address ranges of the lexical block declaring the variable, empty if it is visible in the whole function
+    pub scope: Vec<(usize, usize)>,
+}
+
+impl Variable {
+    /// Checks if the variable is visible from the instruction at `addr` of its function
+    pub fn in_scope(&self, addr: usize) -> bool {
+        self.scope.is_empty()
+            || self
+                .scope
+                .iter()
+                .any(|&(low_pc, high_pc)| low_pc <= addr && addr < high_pc)
+    }
+}
+
+/// A contiguous range of instructions of a function, as described by a `DW_TAG_subprogram` entry
+pub struct Function {
+    pub name: String,
+    pub low_pc: usize,
+    pub high_pc: usize,
+    /// The type returned by the function, `None` if it returns `void`
+    pub return_type: Option<Type>,
+    pub frame_base: Option<FrameBase>,
+    pub parameters: Vec<Variable>,
+    /// The local variables, including those of the nested blocks
+    pub variables: Vec<Variable>,
+}
+
+/// The debugging information of an ELF executable read from its DWARF sections
+///
+/// Addresses are stored as found in the file until `relocate` is called with the load bias of the executable.
+pub struct DebugInfo {
+    files: Vec<String>,
+    lines: Vec<LineRow>,
+    functions: Vec<Function>,
+    globals: Vec<Variable>,
+    /// The named types of the compilation units, the structures with their members
+    types: Vec<Type>,
+    bias: usize,
+}
+
+impl DebugInfo {
+    /// Reads the line table and the functions from the DWARF sections of the ELF file at `path`
+    ///
+    /// Returns `None` if the file has no debugging information
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        let endian = if file.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let dwarf = gimli::Dwarf::load(|id| -> Result<Reader, gimli::Error> {
+            let data = file
+                .section_by_name(id.name())
+                .and_then(|section| section.data().ok())
+                .unwrap_or(&[]);
+            Ok(EndianRcSlice::new(Rc::from(data), endian))
+        })
+        .ok()?;
+
+        let mut debug_info = Self {
+            files: Vec::new(),
+            lines: Vec::new(),
+            functions: Vec::new(),
+            globals: Vec::new(),
+            types: Vec::new(),
+            bias: 0,
+        };
+        let mut units = dwarf.units();
+        while let Ok(Some(header)) = units.next() {
+            let Ok(unit) = dwarf.unit(header) else {
+                continue;
+            };
+            debug_info.read_lines(&dwarf, &unit);
+            debug_info.read_functions(&dwarf, &unit);
+        }
+        if debug_info.lines.is_empty() {
+            return None;
+        }
+
+        // An end of sequence is sorted before a sequence starting at the same address
+        debug_info
+            .lines
+            .sort_by_key(|row| (row.addr, !row.end_sequence));
+        debug_info.functions.sort_by_key(|function| function.low_pc);
+        Some(debug_info)
+    }
+
+    /// Reads the rows of the line program of `unit`
+    fn read_lines(&mut self, dwarf: &gimli::Dwarf<Reader>, unit: &gimli::Unit<Reader>) {
+        let Some(program) = unit.line_program.clone() else {
+            return;
+        };
+        let unit = unit.unit_ref(dwarf);
+        let comp_dir = unit
+            .comp_dir
+            .as_ref()
+            .and_then(|dir| Some(dir.to_string_lossy().ok()?.into_owned()));
+        // Maps the file indices of this unit to indices in `self.files`
+        let mut files = HashMap::new();
+
+        let mut rows = program.rows();
+        while let Ok(Some((header, row))) = rows.next_row() {
+            let file = match files.get(&row.file_index()) {
+                Some(file) => *file,
+                None => {
+                    let path = row
+                        .file(header)
+                        .and_then(|file| {
+                            let name = attr_string(unit, file.path_name())?;
+                            let dir = file
+                                .directory(header)
+                                .and_then(|dir| attr_string(unit, dir));
+                            let path = match (&comp_dir, dir) {
+                                (_, Some(dir)) if Path::new(&dir).is_absolute() => {
+                                    Path::new(&dir).join(name)
+                                }
+                                (Some(comp_dir), Some(dir)) => {
+                                    Path::new(comp_dir).join(dir).join(name)
+                                }
+                                (_, Some(dir)) => Path::new(&dir).join(name),
+                                (Some(comp_dir), None) => Path::new(comp_dir).join(name),
+                                (None, None) => Path::new(&name).to_owned(),
+                            };
+                            Some(path.to_string_lossy().into_owned())
+                        })
+                        .unwrap_or_else(|| String::from("??"));
+                    self.files.push(path);
+                    files.insert(row.file_index(), self.files.len() - 1);
+                    self.files.len() - 1
+                }
+            };
+            self.lines.push(LineRow {
+                addr: row.address() as _,
+                file,
+                line: row.line().map_or(0, |line| line.get()),
+                end_sequence: row.end_sequence(),
+            });
+        }
+    }
+
+    /// Reads the address ranges of the functions of `unit`
+    fn read_functions(&mut self, dwarf: &gimli::Dwarf<Reader>, unit: &gimli::Unit<Reader>) {
+        let unit = unit.unit_ref(dwarf);
+        let mut entries = unit.entries();
+        while let Ok(Some(entry)) = entries.next_dfs() {
+            // The variables of the compilation unit, those of the functions are read with them
+            if entry.tag() == gimli::DW_TAG_variable && entry.depth() == 1 {
+                self.globals.extend(read_variable(unit, entry));
+                continue;
+            }
+            if is_named_type(entry) && entry.depth() == 1 {
+                self.types.push(read_type(unit, entry.offset(), true));
+                continue;
+            }
+            if entry.tag() != gimli::DW_TAG_subprogram {
+                continue;
+            }
+            let Some(name) = entry
+                .attr_value(gimli::DW_AT_name)
+                .and_then(|name| attr_string(unit, name))
+            else {
+                continue;
+            };
+            let return_type = type_of(unit, entry, true);
+            let frame_base = entry
+                .attr_value(gimli::DW_AT_frame_base)
+                .and_then(|base| single_operation(unit, base))
+                .and_then(|operation| match operation {
+                    Operation::CallFrameCFA => Some(FrameBase::Cfa),
+                    Operation::Register { register } if register == X86_64::RBP => {
+                        Some(FrameBase::Rbp)
+                    }
+                    _ => None,
+                });
+            let (parameters, variables) = read_variables(unit, entry.offset());
+            let Ok(mut ranges) = unit.die_ranges(entry) else {
+                continue;
+            };
+            while let Ok(Some(range)) = ranges.next() {
+                self.functions.push(Function {
+                    name: name.clone(),
+                    low_pc: range.begin as _,
+                    high_pc: range.end as _,
+                    return_type: return_type.clone(),
+                    frame_base,
+                    parameters: parameters.clone(),
+                    variables: variables.clone(),
+                });
+            }
+        }
+    }
+
+    /// Moves all addresses to the load bias `bias`, see `SymbolTable::relocate`
+    pub fn relocate(&mut self, bias: usize) {
+        let offset = bias.wrapping_sub(self.bias);
+        self.lines
+            .iter_mut()
+            .for_each(|row| row.addr = row.addr.wrapping_add(offset));
+        let relocate_variable = |variable: &mut Variable| {
+            if let Some(Location::Address(addr)) = &mut variable.location {
+                *addr = addr.wrapping_add(offset);
+            }
+            variable.scope.iter_mut().for_each(|(low_pc, high_pc)| {
+                *low_pc = low_pc.wrapping_add(offset);
+                *high_pc = high_pc.wrapping_add(offset);
+            });
+        };
+        self.functions.iter_mut().for_each(|function| {
+            function.low_pc = function.low_pc.wrapping_add(offset);
+            function.high_pc = function.high_pc.wrapping_add(offset);
+            function.variables.iter_mut().for_each(relocate_variable);
+        });
+        self.globals.iter_mut().for_each(relocate_variable);
+        self.bias = bias;
+    }
+
+    /// Returns the source location of the instruction at `addr`
+    pub fn line_at(&self, addr: usize) -> Option<SourceLocation> {
+        let index = self.lines.partition_point(|row| row.addr <= addr);
+        let row = &self.lines[index.checked_sub(1)?];
+        if row.end_sequence || row.line == 0 {
+            return None;
+        }
+        Some(SourceLocation {
+            file: self.files[row.file].clone(),
+            line: row.line,
+        })
+    }
+
+    /// Returns the source location of the entry of `function` and the last line of its body
+    ///
+    /// Only the lines of the file containing the entry are considered.
+    pub fn function_lines(&self, function: &Function) -> Option<(SourceLocation, u64)> {
+        let start = self.lines.partition_point(|row| row.addr < function.low_pc);
+        let end = self
+            .lines
+            .partition_point(|row| row.addr < function.high_pc);
+        let rows = &self.lines[start..end];
+        let file = rows.first()?.file;
+        let rows = rows
+            .iter()
+            .filter(|row| row.file == file && !row.end_sequence && row.line != 0);
+        let first = rows.clone().map(|row| row.line).min()?;
+        let last = rows.map(|row| row.line).max()?;
+        let location = SourceLocation {
+            file: self.files[file].clone(),
+            line: first,
+        };
+        Some((location, last))
+    }
+
+    /// Returns the address where the prologue of `function` ends, the address of its second line row
+    ///
+    /// The parameters are stored at their location once the prologue has been executed.
+    pub fn prologue_end(&self, function: &Function) -> Option<usize> {
+        let start = self
+            .lines
+            .partition_point(|row| row.addr <= function.low_pc);
+        self.lines
+            .get(start)
+            .map(|row| row.addr)
+            .filter(|&addr| addr < function.high_pc)
+    }
+
+    /// Returns the functions of the executable, sorted by address
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// Returns the global and static variables declared outside of the functions
+    pub fn globals(&self) -> &[Variable] {
+        &self.globals
+    }
+
+    /// Returns the type named `name`, like `int`, `struct point` or a typedef
+    pub fn type_named(&self, name: &str) -> Option<&Type> {
+        let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+        self.types.iter().find(|type_| type_.name == name)
+    }
+
+    /// Returns the function containing the instruction at `addr`
+    pub fn function_at(&self, addr: usize) -> Option<&Function> {
+        self.functions
+            .iter()
+            .find(|function| function.low_pc <= addr && addr < function.high_pc)
+    }
+
+    /// Returns the lowest address of the line `line` of the source file `file`, or of the next line with instructions
+    ///
+    /// `file` is matched against the end of the paths of the source files, like `main.c` or `src/main.c`.
+    pub fn line_address(&self, file: &str, line: u64) -> Option<usize> {
+        let rows = self.lines.iter().filter(|row| {
+            !row.end_sequence
+                && row.line >= line
+                && Path::new(&self.files[row.file]).ends_with(file)
+        });
+        let first = rows.clone().map(|row| row.line).min()?;
+        rows.filter(|row| row.line == first)
+            .map(|row| row.addr)
+            .min()
+    }
+
+    /// Finds the variable `name` visible from the instruction at `addr` of `function`:
+    /// a local variable, a parameter or a global variable
+    ///
+    /// The variables of the innermost blocks hide those of the outer blocks, the parameters and the global variables.
+    pub fn variable<'a>(
+        &'a self,
+        function: Option<&'a Function>,
+        addr: usize,
+        name: &str,
+    ) -> Option<&'a Variable> {
+        let locals = function.into_iter().flat_map(|function| {
+            function
+                .variables
+                .iter()
+                .rev()
+                .filter(|variable| variable.in_scope(addr))
+                .chain(&function.parameters)
+        });
+        locals
+            .chain(&self.globals)
+            .find(|variable| variable.name == name)
+    }
+}
+
+/// Checks if `entry` defines a named type that `print` can cast memory to, declarations being skipped
+fn is_named_type(entry: &gimli::DebuggingInformationEntry<Reader>) -> bool {
+    matches!(
+        entry.tag(),
+        gimli::DW_TAG_base_type
+            | gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_enumeration_type
+            | gimli::DW_TAG_typedef
+    ) && entry.attr(gimli::DW_AT_name).is_some()
+        && entry.attr(gimli::DW_AT_declaration).is_none()
+}
+
+/// Reads the string value of an attribute
+fn attr_string(unit: UnitRef<Reader>, attr: AttributeValue<Reader>) -> Option<String> {
+    Some(
+        unit.attr_string(attr)
+            .ok()?
+            .to_string_lossy()
+            .ok()?
+            .into_owned(),
+    )
+}
+
+/// Reads the expression of an attribute made of a single operation
+fn single_operation(
+    unit: UnitRef<Reader>,
+    attr: AttributeValue<Reader>,
+) -> Option<Operation<Reader>> {
+    let mut operations = attr.exprloc_value()?.operations(unit.encoding());
+    match (operations.next(), operations.next()) {
+        (Ok(Some(operation)), Ok(None)) => Some(operation),
+        _ => None,
+    }
+}
+
+/// Reads the parameters and the local variables of the function entry at `offset`
+///
+/// The variables of the nested blocks are read too, in the order of the entries,
+/// with the address ranges of their block as scope.
+fn read_variables(unit: UnitRef<Reader>, offset: UnitOffset) -> (Vec<Variable>, Vec<Variable>) {
+    let mut parameters = Vec::new();
+    let mut variables = Vec::new();
+    let Ok(mut entries) = unit.entries_at_offset(offset) else {
+        return (parameters, variables);
+    };
+    // Skip the function entry, the depth is then relative to it
+    let _ = entries.next_dfs();
+    // The depth and the ranges of the enclosing lexical blocks, innermost last
+    let mut blocks: Vec<(isize, Vec<(usize, usize)>)> = Vec::new();
+    while let Ok(Some(entry)) = entries.next_dfs() {
+        if entry.depth() <= 0 {
+            break;
+        }
+        while blocks
+            .last()
+            .is_some_and(|(depth, _)| *depth >= entry.depth())
+        {
+            blocks.pop();
+        }
+        let scope = blocks
+            .last()
+            .map(|(_, scope)| scope.clone())
+            .unwrap_or_default();
+        match entry.tag() {
+            gimli::DW_TAG_formal_parameter if entry.depth() == 1 => {
+                parameters.extend(read_variable(unit, entry));
+            }
+            gimli::DW_TAG_variable => {
+                variables.extend(
+                    read_variable(unit, entry).map(|variable| Variable { scope, ..variable }),
+                );
+            }
+            gimli::DW_TAG_lexical_block => {
+                let mut ranges = Vec::new();
+                if let Ok(mut die_ranges) = unit.die_ranges(entry) {
+                    while let Ok(Some(range)) = die_ranges.next() {
+                        ranges.push((range.begin as usize, range.end as usize));
+                    }
+                }
+                // A block without addresses has the scope of the enclosing block
+                if ranges.is_empty() {
+                    ranges = scope;
+                }
+                blocks.push((entry.depth(), ranges));
+            }
+            _ => {}
+        }
+    }
+    (parameters, variables)
+}
+
+/// Reads a `DW_TAG_formal_parameter` or `DW_TAG_variable` entry, `None` if it has no name
+fn read_variable(
+    unit: UnitRef<Reader>,
+    entry: &gimli::DebuggingInformationEntry<Reader>,
+) -> Option<Variable> {
+    let name = entry
+        .attr_value(gimli::DW_AT_name)
+        .and_then(|name| attr_string(unit, name))?;
+    let location = entry.attr_value(gimli::DW_AT_location).map(|location| {
+        match single_operation(unit, location) {
+            Some(Operation::FrameOffset { offset }) => Location::FrameOffset(offset),
+            Some(Operation::Address { address }) => Location::Address(address as _),
+            Some(Operation::Register { register }) => Location::Register(register.0),
+            _ => Location::Unsupported,
+        }
+    });
+    Some(Variable {
+        name,
+        type_: type_of(unit, entry, true),
+        location,
+        scope: Vec::new(),
+    })
+}
+
+/// Reads the type referenced by the `DW_AT_type` attribute of `entry`
+///
+/// The members of a structure are read only if `members` is set, so that the types of the members
+/// are read without their own members and a structure pointing to itself is read once.
+/// Returns `None` if there is no such attribute, which means `void`
+fn type_of(
+    unit: UnitRef<Reader>,
+    entry: &gimli::DebuggingInformationEntry<Reader>,
+    members: bool,
+) -> Option<Type> {
+    match entry.attr_value(gimli::DW_AT_type)? {
+        AttributeValue::UnitRef(offset) => Some(read_type(unit, offset, members)),
+        _ => Some(Type::unknown()),
+    }
+}
+
+/// Reads the type entry at `offset`, with its members if `members` is set, see `type_of`
+///
+/// Typedefs and qualifiers keep the kind, the size and the members of the type they refer to.
+fn read_type(unit: UnitRef<Reader>, offset: UnitOffset, members: bool) -> Type {
+    let Ok(entry) = unit.entry(offset) else {
+        return Type::unknown();
+    };
+    let name = entry
+        .attr_value(gimli::DW_AT_name)
+        .and_then(|name| attr_string(unit, name));
+    let size = entry
+        .attr_value(gimli::DW_AT_byte_size)
+        .and_then(|size| size.udata_value())
+        .unwrap_or(0) as usize;
+    let qualified = matches!(
+        entry.tag(),
+        gimli::DW_TAG_typedef | gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type
+    );
+    let inner = type_of(unit, &entry, members && qualified);
+    let inner_name = || inner.as_ref().map_or("void", |inner| &inner.name);
+
+    let (name, kind) = match entry.tag() {
+        gimli::DW_TAG_base_type => {
+            let kind = match entry.attr_value(gimli::DW_AT_encoding) {
+                Some(AttributeValue::Encoding(gimli::DW_ATE_signed)) => TypeKind::Signed(size),
+                Some(AttributeValue::Encoding(gimli::DW_ATE_unsigned)) => TypeKind::Unsigned(size),
+                Some(AttributeValue::Encoding(
+                    gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char,
+                )) => TypeKind::Char,
+                Some(AttributeValue::Encoding(gimli::DW_ATE_boolean)) => TypeKind::Bool,
+                Some(AttributeValue::Encoding(gimli::DW_ATE_float)) => TypeKind::Float(size),
+                _ => TypeKind::Other,
+            };
+            (name.unwrap_or_else(|| String::from("?")), kind)
+        }
+        gimli::DW_TAG_pointer_type => {
+            return Type {
+                name: format!("{} *", inner_name()),
+                kind: TypeKind::Pointer,
+                byte_size: 8,
+                members: Vec::new(),
+                pointee: inner.map(Box::new),
+            };
+        }
+        gimli::DW_TAG_typedef | gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type => {
+            let name = match entry.tag() {
+                gimli::DW_TAG_typedef => name.unwrap_or_else(|| String::from("?")),
+                gimli::DW_TAG_const_type => format!("const {}", inner_name()),
+                _ => format!("volatile {}", inner_name()),
+            };
+            return match inner {
+                Some(inner) => Type { name, ..inner },
+                None => Type {
+                    name,
+                    ..Type::unknown()
+                },
+            };
+        }
+        gimli::DW_TAG_enumeration_type => (
+            format!("enum {}", name.as_deref().unwrap_or("")),
+            TypeKind::Signed(size),
+        ),
+        gimli::DW_TAG_structure_type => (
+            format!("struct {}", name.as_deref().unwrap_or("")),
+            TypeKind::Other,
+        ),
+        gimli::DW_TAG_union_type => (
+            format!("union {}", name.as_deref().unwrap_or("")),
+            TypeKind::Other,
+        ),
+        gimli::DW_TAG_array_type => {
+            let len = array_len(unit, offset);
+            return Type {
+                name: format!("{} [{}]", inner_name(), len.unwrap_or(0)),
+                kind: TypeKind::Other,
+                byte_size: len
+                    .zip(inner.as_ref())
+                    .map_or(0, |(len, inner)| len * inner.byte_size),
+                members: Vec::new(),
+                pointee: None,
+            };
+        }
+        _ => (name.unwrap_or_else(|| String::from("?")), TypeKind::Other),
+    };
+    let members = match entry.tag() {
+        gimli::DW_TAG_structure_type if members => read_members(unit, offset),
+        _ => Vec::new(),
+    };
+    Type {
+        name,
+        kind,
+        byte_size: size,
+        members,
+        pointee: None,
+    }
+}
+
+/// Reads the number of elements of the array entry at `offset`, from its `DW_TAG_subrange_type` child
+///
+/// Only the first dimension is read, `None` if its length is not known.
+fn array_len(unit: UnitRef<Reader>, offset: UnitOffset) -> Option<usize> {
+    let mut tree = unit.entries_tree(Some(offset)).ok()?;
+    let root = tree.root().ok()?;
+    let mut children = root.children();
+    while let Ok(Some(child)) = children.next() {
+        let entry = child.entry();
+        if entry.tag() != gimli::DW_TAG_subrange_type {
+            continue;
+        }
+        if let Some(count) = entry.attr_value(gimli::DW_AT_count) {
+            return count.udata_value().map(|count| count as _);
+        }
+        return entry
+            .attr_value(gimli::DW_AT_upper_bound)
+            .and_then(|bound| bound.udata_value())
+            .map(|bound| bound as usize + 1);
+    }
+    None
+}
+
+/// Reads the `DW_TAG_member` children of the structure entry at `offset`, without their own members
+///
+/// Bit fields and members without a constant offset are skipped.
+fn read_members(unit: UnitRef<Reader>, offset: UnitOffset) -> Vec<Member> {
+    let mut members = Vec::new();
+    let Ok(mut tree) = unit.entries_tree(Some(offset)) else {
+        return members;
+    };
+    let Ok(root) = tree.root() else {
+        return members;
+    };
+    let mut children = root.children();
+    while let Ok(Some(child)) = children.next() {
+        let entry = child.entry();
+        if entry.tag() != gimli::DW_TAG_member || entry.attr(gimli::DW_AT_bit_size).is_some() {
+            continue;
+        }
+        let name = entry
+            .attr_value(gimli::DW_AT_name)
+            .and_then(|name| attr_string(unit, name));
+        let offset = entry
+            .attr_value(gimli::DW_AT_data_member_location)
+            .and_then(|offset| offset.udata_value());
+        if let (Some(name), Some(offset)) = (name, offset) {
+            members.push(Member {
+                name,
+                offset: offset as _,
+                type_: type_of(unit, entry, false).unwrap_or_else(Type::unknown),
+            });
+        }
+    }
+    members
+}
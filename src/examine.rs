@@ -0,0 +1,110 @@
+use crate::utils::format_string;
+
+/// How the units of memory are printed by `x`
+#[derive(Clone, Copy, PartialEq)]
+pub enum Letter {
+    /// `x`, hexadecimal
+    Hex,
+    /// `d`, signed decimal
+    Decimal,
+    /// `u`, unsigned decimal
+    Unsigned,
+    /// `c`, a byte as a number and a character
+    Char,
+    /// `s`, a null-terminated string
+    String,
+    /// `i`, an instruction
+    Instruction,
+}
+
+/// The format of `x`, parsed from `/<count><letter><size>` like `/4xw`
+///
+/// The letter and the size can be given in any order after the count, they default to `x` and `w`,
+/// `c` defaults to the size `b`.
+pub struct Format {
+    pub count: usize,
+    pub letter: Letter,
+    /// The unit size in bytes, 1 (`b`), 2 (`h`), 4 (`w`) or 8 (`g`)
+    pub size: usize,
+}
+
+impl Format {
+    /// Parses the format following the `/` of `x`
+    pub fn parse(spec: &str) -> Option<Format> {
+        let digits = spec
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(spec.len());
+        let count = match digits {
+            0 => 1,
+            _ => spec[..digits].parse().ok().filter(|&count| count > 0)?,
+        };
+        let mut letter = None;
+        let mut size = None;
+        for c in spec[digits..].chars() {
+            match c {
+                'x' | 'd' | 'u' | 'c' | 's' | 'i' if letter.is_none() => {
+                    letter = Some(match c {
+                        'x' => Letter::Hex,
+                        'd' => Letter::Decimal,
+                        'u' => Letter::Unsigned,
+                        'c' => Letter::Char,
+                        's' => Letter::String,
+                        _ => Letter::Instruction,
+                    })
+                }
+                'b' | 'h' | 'w' | 'g' if size.is_none() => {
+                    size = Some(match c {
+                        'b' => 1,
+                        'h' => 2,
+                        'w' => 4,
+                        _ => 8,
+                    })
+                }
+                _ => return None,
+            }
+        }
+        let letter = letter.unwrap_or(Letter::Hex);
+        let size = size.unwrap_or(if letter == Letter::Char { 1 } else { 4 });
+        Some(Format {
+            count,
+            letter,
+            size,
+        })
+    }
+
+    /// How many units are printed on a line
+    pub fn per_line(&self) -> usize {
+        match self.size {
+            8 => 2,
+            4 => 4,
+            _ => 8,
+        }
+    }
+}
+
+/// Formats the unit `bytes`, in little endian, with `letter`
+pub fn format_unit(bytes: &[u8], letter: Letter) -> String {
+    let mut value = [0; 8];
+    value[..bytes.len()].copy_from_slice(bytes);
+    let value = u64::from_le_bytes(value);
+    let bits = bytes.len() * 8;
+    // Sign extends the unit
+    let signed = ((value << (64 - bits)) as i64) >> (64 - bits);
+    match letter {
+        Letter::Decimal => signed.to_string(),
+        Letter::Unsigned => value.to_string(),
+        Letter::Char => format!("{signed} {}", format_char(value as u8)),
+        _ => format!("{value:#0width$x}", width = 2 + 2 * bytes.len()),
+    }
+}
+
+/// Formats `byte` as a quoted character, escaped like in `format_string`
+fn format_char(byte: u8) -> String {
+    let text = format_string(&[byte], false);
+    let text = &text[1..text.len() - 1];
+    match text {
+        "'" => String::from("'\\''"),
+        "\\\"" => String::from("'\"'"),
+        _ => format!("'{text}'"),
+    }
+}
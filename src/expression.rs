@@ -0,0 +1,261 @@
+use std::{fmt::Display, iter::Peekable, str::Chars};
+
+use nix::{libc::user_regs_struct, unistd::Pid};
+
+use crate::utils::{parse_address, read_data_fixed, register};
+
+/// A token of an expression
+enum Token {
+    /// A number and its text
+    Number(u64, String),
+    Register(String),
+    /// The name of a variable
+    Identifier(String),
+    Plus,
+    Minus,
+    Star,
+    Ampersand,
+    LeftParen,
+    RightParen,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(_, text) => write!(f, "{text}"),
+            Token::Register(name) => write!(f, "${name}"),
+            Token::Identifier(name) => write!(f, "{name}"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Ampersand => write!(f, "&"),
+            Token::LeftParen => write!(f, "("),
+            Token::RightParen => write!(f, ")"),
+        }
+    }
+}
+
+/// The reason an expression cannot be evaluated
+pub enum Error {
+    /// A part of the expression is not a token
+    InvalidToken(String),
+    UnexpectedToken(String),
+    UnexpectedEnd,
+    UnknownRegister(String),
+    /// No variable of this name is visible from the current instruction
+    UnknownVariable(String),
+    /// The variable cannot be read as a number, the reason being the message
+    Variable(String),
+    /// The memory at the address cannot be read
+    UnreadableMemory(u64),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::InvalidToken(token) => write!(f, "Invalid token '{token}'"),
+            Error::UnexpectedToken(token) => write!(f, "Unexpected token '{token}'"),
+            Error::UnexpectedEnd => write!(f, "Unexpected end of expression"),
+            Error::UnknownRegister(name) => write!(f, "Unknown register '${name}'"),
+            Error::UnknownVariable(name) => write!(f, "No symbol '{name}' in current context"),
+            Error::Variable(message) => write!(f, "{message}"),
+            Error::UnreadableMemory(addr) => write!(f, "Cannot read memory at {addr:#x}"),
+        }
+    }
+}
+
+/// Reads a word made of the characters matching `predicate`
+fn take_word(chars: &mut Peekable<Chars>, predicate: impl Fn(char) -> bool) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek().filter(|&&c| predicate(c)) {
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+/// Splits `expression` into tokens
+fn tokenize(expression: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '&' => Token::Ampersand,
+            '(' => Token::LeftParen,
+            ')' => Token::RightParen,
+            '$' => {
+                chars.next();
+                let name = take_word(&mut chars, |c| c.is_ascii_alphanumeric() || c == '_');
+                tokens.push(Token::Register(name));
+                continue;
+            }
+            c if c.is_ascii_digit() => {
+                let word = take_word(&mut chars, |c| c.is_ascii_alphanumeric());
+                let Some(number) = parse_address(&word) else {
+                    return Err(Error::InvalidToken(word));
+                };
+                tokens.push(Token::Number(number as _, word));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let name = take_word(&mut chars, |c| c.is_ascii_alphanumeric() || c == '_');
+                tokens.push(Token::Identifier(name));
+                continue;
+            }
+            _ => {
+                let word = take_word(&mut chars, |c| !c.is_whitespace());
+                return Err(Error::InvalidToken(word));
+            }
+        };
+        chars.next();
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Evaluates an expression by recursive descent
+///
+/// The grammar is
+/// ```text
+/// sum     = product (("+" | "-") product)*
+/// product = unary ("*" unary)*
+/// unary   = "*" identifier | "&" identifier | "*" unary | "-" unary | primary
+/// primary = number | register | identifier | "(" sum ")"
+/// ```
+/// where `*` before a value reads the word of memory at this address,
+/// and `*` before a variable reads the value it points to.
+struct Evaluator<'a> {
+    tokens: Peekable<std::vec::IntoIter<Token>>,
+    regs: &'a user_regs_struct,
+    pid: Pid,
+    variables: &'a dyn Fn(&str, Access) -> Result<u64, Error>,
+}
+
+impl Evaluator<'_> {
+    fn next(&mut self) -> Result<Token, Error> {
+        self.tokens.next().ok_or(Error::UnexpectedEnd)
+    }
+
+    fn sum(&mut self) -> Result<u64, Error> {
+        let mut value = self.product()?;
+        loop {
+            match self.tokens.peek() {
+                Some(Token::Plus) => {
+                    self.tokens.next();
+                    value = value.wrapping_add(self.product()?);
+                }
+                Some(Token::Minus) => {
+                    self.tokens.next();
+                    value = value.wrapping_sub(self.product()?);
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<u64, Error> {
+        let mut value = self.unary()?;
+        while let Some(Token::Star) = self.tokens.peek() {
+            self.tokens.next();
+            value = value.wrapping_mul(self.unary()?);
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<u64, Error> {
+        match self.tokens.peek() {
+            Some(Token::Star) => {
+                self.tokens.next();
+                let variable = self
+                    .tokens
+                    .next_if(|token| matches!(token, Token::Identifier(_)));
+                if let Some(Token::Identifier(name)) = variable {
+                    return (self.variables)(&name, Access::Pointee);
+                }
+                let addr = self.unary()?;
+                let data =
+                    read_data_fixed(self.pid, addr as _).ok_or(Error::UnreadableMemory(addr))?;
+                Ok(u64::from_ne_bytes(data))
+            }
+            Some(Token::Minus) => {
+                self.tokens.next();
+                Ok(self.unary()?.wrapping_neg())
+            }
+            Some(Token::Ampersand) => {
+                self.tokens.next();
+                match self.next()? {
+                    Token::Identifier(name) => (self.variables)(&name, Access::Address),
+                    token => Err(Error::UnexpectedToken(token.to_string())),
+                }
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<u64, Error> {
+        match self.next()? {
+            Token::Number(number, _) => Ok(number),
+            Token::Register(name) => register(self.regs, &name).ok_or(Error::UnknownRegister(name)),
+            Token::Identifier(name) => (self.variables)(&name, Access::Value),
+            Token::LeftParen => {
+                let value = self.sum()?;
+                match self.next()? {
+                    Token::RightParen => Ok(value),
+                    token => Err(Error::UnexpectedToken(token.to_string())),
+                }
+            }
+            token => Err(Error::UnexpectedToken(token.to_string())),
+        }
+    }
+}
+
+/// How a variable of an expression is used
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// The value of the variable, like `n`
+    Value,
+    /// The address of the variable, like `&n`
+    Address,
+    /// The value the variable points to, like `*p`
+    Pointee,
+}
+
+/// Evaluates `expression` with the registers `regs` and the memory of the thread `pid`
+///
+/// Expressions are made of numbers, registers like `$rax`, `+`, `-`, `*`, parentheses
+/// and dereferences of a word of memory like `*0x601040`. There are no variables,
+/// see [`evaluate_with`].
+pub fn evaluate(expression: &str, regs: &user_regs_struct, pid: Pid) -> Result<u64, Error> {
+    evaluate_with(expression, regs, pid, |name, _| {
+        Err(Error::UnknownVariable(name.to_owned()))
+    })
+}
+
+/// Evaluates `expression` like [`evaluate`], the variables being read by `variables`
+///
+/// `variables` is given the name of a variable and how it is used, and returns the number it stands for.
+pub fn evaluate_with(
+    expression: &str,
+    regs: &user_regs_struct,
+    pid: Pid,
+    variables: impl Fn(&str, Access) -> Result<u64, Error>,
+) -> Result<u64, Error> {
+    let mut evaluator = Evaluator {
+        tokens: tokenize(expression)?.into_iter().peekable(),
+        regs,
+        pid,
+        variables: &variables,
+    };
+    let value = evaluator.sum()?;
+    match evaluator.tokens.next() {
+        Some(token) => Err(Error::UnexpectedToken(token.to_string())),
+        None => Ok(value),
+    }
+}
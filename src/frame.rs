@@ -0,0 +1,73 @@
+use nix::{libc::user_regs_struct, unistd::Pid};
+
+use crate::{dwarf::SourceLocation, utils::read_data_fixed};
+
+const ENDBR64: [u8; 4] = [0xf3, 0x0f, 0x1e, 0xfa];
+const PUSH_RBP: u8 = 0x55;
+const MOV_RBP_RSP: [u8; 3] = [0x48, 0x89, 0xe5];
+
+/// The stack frame of the function being executed
+pub struct Frame {
+    /// The stack pointer at the entry of the function, where the return address is stored
+    pub sp: usize,
+}
+
+/// A frame of the call stack, as printed by `backtrace`
+pub struct StackFrame {
+    /// The next instruction executed in the frame
+    pub pc: usize,
+    pub function: Option<String>,
+    /// The names and values of the parameters, `None` without debugging information
+    pub arguments: Option<Vec<(String, String)>>,
+    pub location: Option<SourceLocation>,
+}
+
+impl Frame {
+    /// Finds the frame of the function being executed by the thread
+    ///
+    /// The frame pointer convention is used: once the prologue `push rbp; mov rbp, rsp` has been executed,
+    /// the return address is at `rbp + 8`.
+    /// When the entry of the function is known, a thread stopped inside the prologue is also handled.
+    /// `None` is returned if the return address can't be read.
+    pub fn current(pid: Pid, regs: &user_regs_struct, entry: Option<usize>) -> Option<Frame> {
+        let rip = regs.rip as usize;
+        let sp = match entry.map(|entry| prologue_progress(pid, entry, rip)) {
+            Some(Some(Prologue::NotStarted)) => regs.rsp as usize,
+            Some(Some(Prologue::RbpPushed)) => regs.rsp as usize + 8,
+            _ => regs.rbp as usize + 8,
+        };
+        read_data_fixed::<8>(pid, sp)?;
+        Some(Frame { sp })
+    }
+}
+
+/// How much of the prologue of a function has been executed
+enum Prologue {
+    NotStarted,
+    RbpPushed,
+    Done,
+}
+
+/// Finds which instructions of the prologue of the function starting at `entry` executed before `rip`
+///
+/// Returns `None` if the function doesn't start with the frame pointer prologue
+fn prologue_progress(pid: Pid, entry: usize, rip: usize) -> Option<Prologue> {
+    let code: [u8; 8] = read_data_fixed(pid, entry)?;
+    let push = if code[..4] == ENDBR64 {
+        entry + 4
+    } else {
+        entry
+    };
+    if rip <= push {
+        return Some(Prologue::NotStarted);
+    }
+    let offset = push - entry;
+    if code[offset] != PUSH_RBP || code[offset + 1..offset + 4] != MOV_RBP_RSP {
+        return None;
+    }
+    if rip == push + 1 {
+        Some(Prologue::RbpPushed)
+    } else {
+        Some(Prologue::Done)
+    }
+}
@@ -1,307 +1,1154 @@
+mod assembler;
+mod auxv;
 mod breakpoint;
+mod config;
+mod control;
+mod coredump;
+mod debugger;
+mod disassembler;
+mod dwarf;
+mod examine;
+mod expression;
+mod frame;
+mod maps;
+mod record;
+mod regex;
+mod rendezvous;
+mod reporter;
+mod signals;
+mod source;
+mod symbols;
+mod syscalls;
+mod tui;
+mod unwind;
 mod utils;
+mod watchpoint;
+mod xstate;
 
 use std::{
-    env::{self, Args},
-    ffi::CString,
-    io::{Write, stdin, stdout},
+    collections::VecDeque,
+    env, fs,
+    io::{IsTerminal, stdin, stdout},
+    path::PathBuf,
     process::exit,
 };
 
-use breakpoint::Breakpoint;
+use config::Config;
+use control::ControlSocket;
+use debugger::{Debugger, ForkMode};
+use disassembler::Flavor;
 use nix::{
-    errno::Errno,
-    sys::{
-        personality::{self, Persona},
-        ptrace::{self},
-        signal::{Signal, raise},
-        wait::{WaitStatus, waitpid},
-    },
-    unistd::{ForkResult, Pid, execvp, fork},
+    sys::signal::{self, Signal},
+    unistd::Pid,
 };
+use regex::Regex;
+use reporter::{Event, Format, Reporter};
+use tui::Tui;
+use utils::{Charset, parse_address, parse_pattern, split_arguments};
+use watchpoint::WatchKind;
 
-/// Launches the tracee `program` and returns its Pid.
-/// ASLR is disabled for the tracee and the traces asks to be traced.
-/// For the tracer, this function guarantees that execve has already been called in the tracee.
-fn launch_program(program: &str) -> Result<Pid, Errno> {
-    match unsafe { fork() } {
-        Ok(ForkResult::Parent { child, .. }) => {
-            waitpid(child, None).unwrap();
-            ptrace::setoptions(child, ptrace::Options::PTRACE_O_TRACEEXEC).unwrap();
-            ptrace::cont(child, None).unwrap();
-            waitpid(child, None).unwrap();
-            Ok(child)
-        }
-        Ok(ForkResult::Child) => {
-            ptrace::traceme().unwrap();
-            personality::set(Persona::ADDR_NO_RANDOMIZE).unwrap();
-            raise(Signal::SIGSTOP).unwrap();
-            execvp(&CString::new(program).unwrap(), &[] as &[CString])?;
-            exit(1); // Unreachable
-        }
-        Err(errno) => Err(errno),
-    }
-}
+/// The subcommands of `info`, with the synopsis of their arguments and whether they need a running program
+const INFO_COMMANDS: [(&str, &str, bool); 15] = [
+    ("registers", "[raw|fpu|changed|vector|<name>...]", true),
+    ("threads", "", true),
+    ("inferiors", "", false),
+    ("line", "[<addr>]", false),
+    ("symbol", "<addr>", false),
+    ("frame", "", true),
+    ("args", "", true),
+    ("locals", "", true),
+    ("watchpoints", "", false),
+    ("display", "", false),
+    ("auxv", "", true),
+    ("all-breakpoints-hit", "", false),
+    ("sharedlibrary", "", true),
+    ("functions", "[--by-name] [<regex>]", false),
+    ("variables", "[--by-name] [<regex>]", false),
+];
 
-enum BreakpointArg {
-    Address(usize),
-    LineNumber(String, usize),
-    Symbol(String),
+/// The usage of `info`, listing its subcommands
+fn info_usage() -> String {
+    let subcommands: Vec<String> = INFO_COMMANDS
+        .iter()
+        .map(|(name, args, _)| match args.is_empty() {
+            true => name.to_string(),
+            false => format!("{name} {args}"),
+        })
+        .collect();
+    format!("Usage: info <{}>", subcommands.join("|"))
 }
 
-impl BreakpointArg {
-    fn parse(arg: &str) -> Option<BreakpointArg> {
-        if arg.starts_with("0x") {
-            let addr = arg.trim_start_matches("0x");
-            if let Ok(addr) = usize::from_str_radix(addr, 16) {
-                return Some(BreakpointArg::Address(addr));
-            }
-        }
-        todo!()
-    }
+/// The commands of the debugger, a command can also be called by a prefix matching only it
+const COMMANDS: [&str; 55] = [
+    "quit",
+    "exit",
+    "alias",
+    "add-inferior",
+    "inferior",
+    "breakpoint",
+    "clear",
+    "probe",
+    "commands",
+    "handle",
+    "watch-syscall-return",
+    "catch",
+    "set",
+    "unset",
+    "run",
+    "start",
+    "starti",
+    "continue",
+    "interrupt",
+    "kill",
+    "detach",
+    "info",
+    "list",
+    "tui",
+    "disassemble",
+    "thread",
+    "regs",
+    "print",
+    "display",
+    "undisplay",
+    "jump",
+    "assemble",
+    "unpatch",
+    "dump",
+    "restore",
+    "generate-core-file",
+    "dump-core",
+    "search",
+    "watch",
+    "rwatch",
+    "awatch",
+    "stepi",
+    "nexti",
+    "count",
+    "until",
+    "stack",
+    "backtrace",
+    "record",
+    "reverse-stepi",
+    "reverse-continue",
+    "return",
+    "finish",
+    "step",
+    "next",
+    "x",
+];
 
-    fn to_address(self: &Self) -> usize {
-        match self {
-            BreakpointArg::Address(addr) => *addr,
-            _ => todo!(),
-        }
+/// The short names of the common commands, before those defined by `alias`
+const DEFAULT_ALIASES: [(&str, &str); 9] = [
+    ("b", "breakpoint"),
+    ("r", "run"),
+    ("c", "continue"),
+    ("s", "stepi"),
+    ("si", "stepi"),
+    ("ni", "nexti"),
+    ("i", "info"),
+    ("bt", "backtrace"),
+    ("q", "quit"),
+];
+
+/// Replaces the alias or the prefix of a command starting `line` by the command
+///
+/// An unknown command is left as it is, an error is returned if it is the prefix of several commands.
+fn expand_command(line: &str, aliases: &[(String, String)]) -> Result<String, String> {
+    let line = line.trim();
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    if let Some((_, command)) = aliases.iter().find(|(alias, _)| alias == name) {
+        return Ok(format!("{command} {rest}"));
+    }
+    if name.is_empty() || COMMANDS.contains(&name) {
+        return Ok(line.to_owned());
+    }
+    let candidates: Vec<&str> = COMMANDS
+        .into_iter()
+        .filter(|command| command.starts_with(name))
+        .collect();
+    match candidates.as_slice() {
+        [] => Ok(line.to_owned()),
+        [command] => Ok(format!("{command} {rest}")),
+        _ => Err(format!(
+            "Ambiguous command '{name}': {}",
+            candidates.join(", ")
+        )),
     }
 }
 
-fn wait_and_check(
-    waitstatus: &WaitStatus,
-    child: &mut Option<Pid>,
-    breakpoints: &mut Vec<Breakpoint>,
-    hit_breakpoint_index: &mut Option<usize>,
-) {
-    let pid = child.unwrap();
-    match waitstatus {
-        nix::sys::wait::WaitStatus::Exited(_, exitcode) => {
-            println!("Program exited with exit code {exitcode}");
-            *child = None;
-            breakpoints.clear();
-        }
-        nix::sys::wait::WaitStatus::Stopped(_, signal) => {
-            if *signal == Signal::SIGTRAP {
-                breakpoints.iter().for_each(|bp| bp.restore_data().unwrap());
-                let regs = ptrace::getregs(pid).unwrap();
-                if let Some(index) = breakpoints
-                    .iter()
-                    .position(|bp| bp.addr == (regs.rip - 1) as _)
-                {
-                    // We've hit the breakpoint at index
-                    println!(
-                        "Reached breakpoint {} at {:#x}",
-                        index + 1,
-                        breakpoints[index].addr
-                    );
-                    breakpoints.get_mut(index).unwrap().restore_rip().unwrap();
-                    *hit_breakpoint_index = Some(index);
-                    return;
-                }
-                println!("Program interrupted at {:#x}", regs.rip);
-                return;
-            }
-            println!("Program stopped : {waitstatus:#?}");
-        }
-        nix::sys::wait::WaitStatus::StillAlive => {
-            panic!("Program never stopped")
-        }
-        other => {
-            println!("Program stopped : {other:#?}");
-        }
+/// Finds the file that `execvp` would execute for `program`
+///
+/// Like `execvp`, the `PATH` is searched only if `program` doesn't contain a slash.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        return Some(PathBuf::from(program));
     }
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
 }
 
-fn prompt_force_close(pid: Pid) {
+/// Asks the user to confirm quitting, which kills the running programs `pids`
+///
+/// The answer is case insensitive. At the end of the input, the programs are killed as nobody can answer.
+fn prompt_force_close(pids: &[Pid], reporter: Reporter) {
+    let kill = || {
+        for &pid in pids {
+            signal::kill(pid, Signal::SIGKILL).unwrap();
+        }
+        exit(0);
+    };
+    let pids: Vec<String> = pids.iter().map(Pid::to_string).collect();
+    let pids = pids.join(", ");
     let mut buf = String::new();
     loop {
-        println!(
-            "\nProcess {pid} is still running, are you sure you want to quit ?\nThis will kill process {pid}\n\nQuit ? (y/n)"
-        );
-        stdin().read_line(&mut buf).unwrap();
-        match buf.as_str().trim() {
-            "y" => {
-                ptrace::kill(pid).unwrap();
-                exit(0);
-            }
+        reporter.info(format!(
+            "\nProcess {pids} is still running, are you sure you want to quit ?\nThis will kill process {pids}\n\nQuit ? (y/n)"
+        ));
+        buf.clear();
+        if stdin().read_line(&mut buf).unwrap() == 0 {
+            kill();
+        }
+        match buf.trim().to_ascii_lowercase().as_str() {
+            "y" => kill(),
             "n" => {
                 return;
             }
-            _ => {
-                buf.clear();
-            }
+            _ => {}
         }
     }
 }
 
-fn main_loop(mut args: Args) {
+/// Kills the running programs and exits, for the end of `--batch`
+///
+/// The exit code is 1 if a command failed, otherwise that of the last run of `debugger`, or 0 if it was never run.
+fn exit_batch(inferiors: &mut [Debugger], debugger: usize) -> ! {
+    for inferior in inferiors.iter_mut() {
+        if inferior.child().is_some() {
+            inferior.kill();
+        }
+    }
+    if reporter::has_errored() {
+        exit(1);
+    }
+    exit(inferiors[debugger].exit_code().unwrap_or(0));
+}
+
+/// Runs the commands `startup`, then reads and runs commands, from `control` if the debugger is listening on a socket
+///
+/// With `batch`, the debugger exits once the commands `startup` have been run instead of reading commands.
+fn main_loop(
+    mut args: impl Iterator<Item = String>,
+    reporter: Reporter,
+    control: Option<ControlSocket>,
+    startup: Vec<String>,
+    batch: bool,
+) {
     let program = args.next().unwrap();
 
-    println!("Debugging {program}");
+    if let Some(control) = &control {
+        control.accept();
+    }
 
-    let mut breakpoints = Vec::new();
-    let mut breakpoints_args = Vec::new();
-    let mut child = None;
-    let mut hit_breakpoint_index = None;
+    reporter.info(format!("Debugging {program}"));
 
-    loop {
-        print!("> ");
-        stdout().flush().unwrap();
-        let mut buffer = String::new();
-        stdin().read_line(&mut buffer).unwrap();
-        let mut words = buffer.split_whitespace();
+    let program_path = find_program(&program);
+    let mut debugger = Debugger::new(program, program_path, reporter);
+    debugger.set_args(args.collect());
+    // The programs being debugged, commands act on the selected one
+    let mut inferiors = vec![debugger];
+    let mut current = 0;
+    // The startup commands and those of the breakpoints hit, run before reading the input again
+    let mut pending: VecDeque<String> = startup.into();
+    // The split screen of `tui enable`, redrawn before each prompt
+    let mut tui: Option<Tui> = None;
+    // The short names of the commands, with those defined by `alias`
+    let mut aliases: Vec<(String, String)> = DEFAULT_ALIASES
+        .iter()
+        .map(|&(alias, command)| (alias.to_owned(), command.to_owned()))
+        .collect();
 
-        let command = words.next();
-
-        let command = match command {
+    loop {
+        let debugger = &mut inferiors[current];
+        debugger.poll();
+        // The commands of the breakpoint run before the rest of the startup commands
+        for command in debugger.take_hit_commands().into_iter().rev() {
+            pending.push_front(command);
+        }
+        let buffer = match pending.pop_front() {
             Some(command) => command,
+            None if batch => exit_batch(&mut inferiors, current),
             None => {
-                match child {
-                    Some(pid) => {
-                        prompt_force_close(pid);
+                if let Some(tui) = tui.as_mut() {
+                    tui.draw(&debugger.code_view(tui.code_rows()));
+                }
+                reporter.prompt();
+                let mut buffer = String::new();
+                if stdin().read_line(&mut buffer).unwrap() == 0 {
+                    // When a client disconnects, the programs keep being debugged for the next one
+                    if let Some(control) = &control {
+                        control.accept();
                         continue;
                     }
-                    None => exit(0),
-                };
+                    buffer = String::from("quit");
+                }
+                buffer
+            }
+        };
+        if !buffer.trim().is_empty() {
+            reporter.log_command(buffer.trim());
+        }
+        let buffer = match expand_command(&buffer, &aliases) {
+            Ok(buffer) => buffer,
+            Err(message) => {
+                reporter.error(message);
+                continue;
             }
         };
+        let mut words = buffer.split_whitespace();
+
+        let command = words.next();
+        // The text following the command, for the commands that split it themselves
+        let rest = command.map_or("", |command| buffer.trim_start()[command.len()..].trim());
+
+        let Some(command) = command else {
+            continue;
+        };
+
+        if debugger.is_running()
+            && !matches!(
+                command,
+                "interrupt"
+                    | "quit"
+                    | "exit"
+                    | "kill"
+                    | "detach"
+                    | "breakpoint"
+                    | "probe"
+                    | "commands"
+                    | "add-inferior"
+            )
+        {
+            reporter.error("The program is running, use interrupt to stop it");
+            continue;
+        }
 
         match command {
+            "quit" | "exit" => {
+                let running: Vec<Pid> = inferiors.iter().filter_map(Debugger::child).collect();
+                if running.is_empty() {
+                    exit(0);
+                }
+                prompt_force_close(&running, reporter);
+            }
+            "alias" => match rest.split_once('=') {
+                Some((name, command)) if !name.trim().is_empty() && !command.trim().is_empty() => {
+                    let (name, command) = (name.trim(), command.trim());
+                    if name.contains(char::is_whitespace) || COMMANDS.contains(&name) {
+                        reporter.error(format!("Invalid alias name '{name}'"));
+                        continue;
+                    }
+                    aliases.retain(|(alias, _)| alias != name);
+                    aliases.push((name.to_owned(), command.to_owned()));
+                }
+                _ => reporter.error("Usage: alias <name>=<command>"),
+            },
+            "add-inferior" => {
+                let Some(program) = words.next() else {
+                    reporter.error("Usage: add-inferior <program>");
+                    continue;
+                };
+                let program_path = find_program(program);
+                inferiors.push(Debugger::new(program.to_owned(), program_path, reporter));
+                reporter.report(Event::InferiorAdded {
+                    id: inferiors.len(),
+                    program,
+                });
+            }
+            "inferior" => match words.next().and_then(|id| id.parse::<usize>().ok()) {
+                Some(id) if (1..=inferiors.len()).contains(&id) => {
+                    current = id - 1;
+                    reporter.report(Event::SwitchedInferior {
+                        id,
+                        program: inferiors[current].program(),
+                    });
+                }
+                Some(id) => reporter.error(format!("Unknown inferior {id}")),
+                None => reporter.error("Usage: inferior <id>"),
+            },
             "breakpoint" => {
                 let arg = words.next();
-                if let None = arg {
-                    println!("Usage: breakpoint <arg>");
+                if arg.is_none() {
+                    reporter.error("Usage: breakpoint <arg>");
                     continue;
                 }
                 let arg = arg.expect("never fails");
-                if let Some(arg) = BreakpointArg::parse(arg) {
-                    breakpoints_args.push(arg);
-                    println!("Breakpoint {} added", breakpoints_args.len());
-                } else {
-                    println!("Invalid breakpoint '{arg}'");
+                debugger.add_breakpoint(arg);
+            }
+            "clear" => debugger.clear_breakpoints(words.next()),
+            "probe" => {
+                let arg = words.next();
+                let expression = words.collect::<Vec<_>>().join(" ");
+                match (arg, expression.is_empty()) {
+                    (Some(arg), false) => debugger.add_probe(arg, &expression),
+                    _ => reporter.error("Usage: probe <location> <expression>"),
                 }
             }
-            "run" => {
-                if child.is_some() {
-                    println!("Program already running");
+            "commands" => {
+                let Some(index) = words.next().and_then(|arg| arg.parse().ok()) else {
+                    reporter.error("Usage: commands <breakpoint>");
+                    continue;
+                };
+                let Some(commands) = debugger.breakpoint_commands(index) else {
+                    reporter.error(format!("No breakpoint {index}"));
                     continue;
+                };
+                // In a command file, the lines of the block follow it
+                if pending.is_empty() {
+                    reporter.info(format!(
+                        "Type commands for breakpoint {index}, one per line, and end with 'end'"
+                    ));
                 }
-                match launch_program(&program) {
-                    Ok(pid) => {
-                        breakpoints = breakpoints_args
-                            .iter()
-                            .map(|el| {
-                                let breakpoint = Breakpoint::create(el.to_address(), pid).unwrap();
-                                breakpoint
-                            })
-                            .collect();
-                        child = Some(pid);
-                        ptrace::cont(pid, None).unwrap();
-                        let waitstatus = waitpid(pid, None).unwrap();
-                        wait_and_check(
-                            &waitstatus,
-                            &mut child,
-                            &mut breakpoints,
-                            &mut hit_breakpoint_index,
-                        );
-                    }
-                    Err(errno) => println!("Error launching '{program}' : {}", errno.desc()),
+                commands.clear();
+                loop {
+                    let line = match pending.pop_front() {
+                        Some(line) => line,
+                        None if batch => break,
+                        None => {
+                            reporter.prompt();
+                            let mut line = String::new();
+                            if stdin().read_line(&mut line).unwrap() == 0 {
+                                break;
+                            }
+                            line
+                        }
+                    };
+                    if line.trim() == "end" {
+                        break;
+                    }
+                    if !line.trim().is_empty() {
+                        commands.push(line.trim().to_owned());
+                    }
                 }
             }
-
-            "continue" => match child {
-                Some(pid) => {
-                    if let Some(index) = hit_breakpoint_index {
-                        breakpoints.iter_mut().enumerate().for_each(|(i, bp)| {
-                            if i != index {
-                                bp.write().unwrap()
-                            }
-                        });
-                        breakpoints.get_mut(index).unwrap().run().unwrap();
-                        hit_breakpoint_index = None
-                    } else {
-                        breakpoints.iter_mut().for_each(|bp| bp.write().unwrap());
-                    }
-                    ptrace::cont(pid, None).unwrap();
-                    let waitstatus = waitpid(pid, None).unwrap();
-                    wait_and_check(
-                        &waitstatus,
-                        &mut child,
-                        &mut breakpoints,
-                        &mut hit_breakpoint_index,
-                    );
+            "handle" => {
+                let Some(signal) = words.next().and_then(signals::parse) else {
+                    reporter.error("Usage: handle <signal> [stop|nostop] [print|noprint] [pass|nopass]");
+                    continue;
+                };
+                debugger.handle_signal(signal, &words.collect::<Vec<_>>());
+            }
+            "watch-syscall-return" => debugger.catch_syscall(words.next(), true),
+            "catch" => match words.next() {
+                Some("syscall") => debugger.catch_syscall(words.next(), false),
+                _ => reporter.error("Usage: catch syscall [<name>]"),
+            },
+            "set" => match words.next() {
+                Some("args") => match rest.strip_prefix("args").and_then(split_arguments) {
+                    Some(args) => debugger.set_args(args),
+                    None => reporter.error("Usage: set args <args>..., with the quotes closed"),
+                },
+                Some("env") => {
+                    let variable = words.collect::<Vec<_>>().join(" ");
+                    match variable.split_once('=') {
+                        Some((name, value)) if !name.is_empty() => debugger.set_env(name, value),
+                        _ => reporter.error("Usage: set env <name>=<value>"),
+                    }
                 }
+                Some(arg) if arg.starts_with('$') => {
+                    let assignment = [arg].into_iter().chain(words).collect::<String>();
+                    let register = assignment.split_once('=').and_then(|(name, value)| {
+                        Some((name.strip_prefix('$')?, parse_address(value)?))
+                    });
+                    match (register, debugger.child()) {
+                        (Some((name, value)), Some(_)) => debugger.set_register(name, value as _),
+                        (Some(_), None) => reporter.error("No program running"),
+                        (None, _) => reporter.error("Usage: set $<register> = <value>"),
+                    }
+                }
+                Some(arg) if arg.starts_with('*') => {
+                    let assignment = [arg].into_iter().chain(words).collect::<String>();
+                    let write = assignment.split_once('=').and_then(|(addr, value)| {
+                        Some((parse_address(addr.strip_prefix('*')?)?, parse_address(value)?))
+                    });
+                    match (write, debugger.child()) {
+                        (Some((addr, value)), Some(_)) => debugger.set_memory(addr, value as _),
+                        (Some(_), None) => reporter.error("No program running"),
+                        (None, _) => reporter.error("Usage: set *<addr> = <value>"),
+                    }
+                }
+                Some("disassembly-flavor") => match words.next().and_then(Flavor::parse) {
+                    Some(flavor) => disassembler::set_flavor(flavor),
+                    None => reporter.error("Usage: set disassembly-flavor att|intel"),
+                },
+                Some("breakpoint") => match (words.next(), words.next()) {
+                    (Some("pending"), Some("on")) => breakpoint::set_pending(true),
+                    (Some("pending"), Some("off")) => breakpoint::set_pending(false),
+                    _ => reporter.error("Usage: set breakpoint pending on|off"),
+                },
+                Some("charset") => match words.next() {
+                    Some("ascii") => utils::set_charset(Charset::Ascii),
+                    Some("utf-8") => utils::set_charset(Charset::Utf8),
+                    Some("latin1") => utils::set_charset(Charset::Latin1),
+                    _ => reporter.error("Usage: set charset ascii|utf-8|latin1"),
+                },
+                Some("demangle") => match words.next() {
+                    Some("on") => symbols::set_demangle(true),
+                    Some("off") => symbols::set_demangle(false),
+                    _ => reporter.error("Usage: set demangle on|off"),
+                },
+                Some("aslr") => match words.next() {
+                    Some("on") => debugger::set_aslr(true),
+                    Some("off") => debugger::set_aslr(false),
+                    _ => reporter.error("Usage: set aslr on|off"),
+                },
+                Some("follow-fork-mode") => match words.next().and_then(ForkMode::parse) {
+                    Some(mode) => debugger::set_follow_fork_mode(mode),
+                    None => reporter.error("Usage: set follow-fork-mode parent|child"),
+                },
+                Some("variable" | "var") => {
+                    let assignment = words.collect::<Vec<_>>().join(" ");
+                    match (assignment.split_once('='), debugger.child()) {
+                        (Some((name, value)), Some(_)) if !name.trim().is_empty() => {
+                            debugger.set_variable(name.trim(), value)
+                        }
+                        (Some(_), None) => reporter.error("No program running"),
+                        _ => reporter.error("Usage: set variable <name> = <value>"),
+                    }
+                }
+                _ => reporter.error(
+                    "Usage: set env <name>=<value>, set $<register> = <value>, set *<addr> = <value>, set variable <name> = <value>, set args <args>..., set disassembly-flavor att|intel, set demangle on|off, set charset ascii|utf-8|latin1, set aslr on|off, set follow-fork-mode parent|child or set breakpoint pending on|off",
+                ),
+            },
+            "unset" => match (words.next(), words.next()) {
+                (Some("env"), Some(name)) => debugger.unset_env(name),
+                _ => reporter.error("Usage: unset env <name>"),
+            },
+            "run" => match split_arguments(rest) {
+                Some(args) if args.is_empty() => debugger.run(None),
+                Some(args) => debugger.run(Some(&args)),
+                None => reporter.error("Usage: run [<args>...], with the quotes closed"),
+            },
+            "start" => debugger.start(),
+            "starti" => debugger.starti(),
+            "continue" => match debugger.child() {
+                Some(_) if words.next() == Some("&") => debugger.cont_background(),
+                Some(_) => debugger.cont(),
                 None => {
-                    println!("No program running");
+                    reporter.error("No program running");
+                }
+            },
+            "interrupt" => match debugger.child() {
+                Some(_) => debugger.interrupt(),
+                None => {
+                    reporter.error("No program running");
+                }
+            },
+            "kill" => match debugger.child() {
+                Some(_) => debugger.kill(),
+                None => {
+                    reporter.error("No program running");
+                }
+            },
+            "detach" => match debugger.child() {
+                Some(_) => debugger.detach(),
+                None => {
+                    reporter.error("No program running");
                 }
             },
             "info" => {
-                let arg = words.next();
-                if let None = arg {
-                    println!("Usage: breakpoint <arg>");
+                let Some(arg) = words.next() else {
+                    reporter.error(info_usage());
+                    continue;
+                };
+                let Some(&(_, _, needs_program)) =
+                    INFO_COMMANDS.iter().find(|(name, _, _)| *name == arg)
+                else {
+                    reporter.error(format!("No info for '{arg}'"));
+                    continue;
+                };
+                if needs_program && debugger.child().is_none() {
+                    reporter.error("No program running");
                     continue;
                 }
-                let arg = arg.expect("never fails");
                 match arg {
-                    "registers" => match child {
-                        Some(pid) => {
-                            let regs = ptrace::getregs(pid).unwrap();
-                            println!("{:#x?}", regs);
+                    "registers" => match words.next() {
+                            Some("raw") => debugger.info_registers(true),
+                            Some("fpu") => debugger.info_fp_registers(),
+                            Some("changed") => debugger.info_changed_registers(),
+                            Some("vector") => {
+                                let size = match words.next() {
+                                    Some("byte") => 1,
+                                    Some("word") => 2,
+                                    None | Some("dword") => 4,
+                                    Some("qword") => 8,
+                                    Some(_) => {
+                                        reporter.error(
+                                            "Usage: info registers vector [byte|word|dword|qword]",
+                                        );
+                                        continue;
+                                    }
+                                };
+                                debugger.info_vector_registers(size);
+                            }
+                            Some(name) => {
+                                let names: Vec<&str> = [name].into_iter().chain(words).collect();
+                                debugger.info_named_registers(&names);
+                            }
+                            None => debugger.info_registers(false),
+                    },
+                    "threads" => debugger.info_threads(),
+                    "inferiors" => reporter.report(Event::Inferiors {
+                        inferiors: inferiors
+                            .iter()
+                            .enumerate()
+                            .map(|(index, inferior)| {
+                                (index + 1, inferior.program(), inferior.child())
+                            })
+                            .collect(),
+                        current: current + 1,
+                    }),
+                    "line" => match (words.next(), debugger.child()) {
+                        (Some(addr), _) => match parse_address(addr) {
+                            Some(addr) => debugger.info_line(Some(addr)),
+                            None => reporter.error("Usage: info line [<addr>]"),
+                        },
+                        (None, Some(_)) => debugger.info_line(None),
+                        (None, None) => {
+                            reporter.error("No program running");
                         }
-                        None => {
-                            println!("No program running");
+                    },
+                    "frame" => debugger.info_frame(),
+                    "watchpoints" => debugger.info_watchpoints(),
+                    "display" => debugger.info_display(),
+                    "auxv" => debugger.info_auxv(),
+                    "all-breakpoints-hit" => debugger.info_breakpoint_hits(),
+                    "sharedlibrary" => debugger.info_shared_libraries(),
+                    "args" | "locals" => debugger.info_variables(arg == "args"),
+                    "functions" | "variables" => {
+                        let mut args: Vec<&str> = words.collect();
+                        let by_name = args.first() == Some(&"--by-name");
+                        if by_name {
+                            args.remove(0);
+                        }
+                        let regex = match args.as_slice() {
+                            [] => None,
+                            [pattern] => match Regex::parse(pattern) {
+                                Some(regex) => Some(regex),
+                                None => {
+                                    reporter.error(format!("Invalid regular expression '{pattern}'"));
+                                    continue;
+                                }
+                            },
+                            _ => {
+                                reporter.error(format!("Usage: info {arg} [--by-name] [<regex>]"));
+                                continue;
+                            }
+                        };
+                        match arg {
+                            "functions" => debugger.info_functions(regex.as_ref(), by_name),
+                            _ => debugger.info_global_variables(regex.as_ref(), by_name),
                         }
+                    }
+                    "symbol" => match words.next().and_then(parse_address) {
+                        Some(addr) => debugger.info_symbol(addr),
+                        None => reporter.error("Usage: info symbol <addr>"),
                     },
                     other => {
-                        println!("No info for '{other}'");
+                        reporter.error(format!("No info for '{other}'"));
                     }
                 }
             }
-            "stepi" => match child {
-                Some(pid) => {
-                    let waitstatus;
-                    if let Some(index) = hit_breakpoint_index {
-                        breakpoints.iter_mut().enumerate().for_each(|(i, bp)| {
-                            if i != index {
-                                bp.write().unwrap()
-                            }
-                        });
-                        waitstatus = breakpoints.get_mut(index).unwrap().run().unwrap();
-                        hit_breakpoint_index = None
-                    } else {
-                        breakpoints.iter_mut().for_each(|bp| bp.write().unwrap());
-                        ptrace::step(pid, None).unwrap();
-                        waitstatus = waitpid(pid, None).unwrap();
-                    }
-                    wait_and_check(
-                        &waitstatus,
-                        &mut child,
-                        &mut breakpoints,
-                        &mut hit_breakpoint_index,
-                    );
+            "list" => debugger.list(words.next()),
+            "tui" => match words.next() {
+                Some("enable") if tui.is_none() => {
+                    tui = reporter.is_text().then(Tui::enable).flatten();
+                    if tui.is_none() {
+                        reporter.error("The TUI needs a terminal of at least 20 rows and text output");
+                    }
+                }
+                Some("disable") => {
+                    if let Some(tui) = tui.take() {
+                        tui.disable();
+                    }
+                }
+                Some("enable") => {}
+                _ => reporter.error("Usage: tui enable|disable"),
+            },
+            "disassemble" => {
+                let mut args: Vec<&str> = words.collect();
+                let with_source = args.first() == Some(&"/s");
+                if with_source {
+                    args.remove(0);
+                }
+                let len = match args.get(1).map(|len| parse_address(len)) {
+                    Some(None) => {
+                        reporter.error("Usage: disassemble [/s] [<location> [<len>]]");
+                        continue;
+                    }
+                    len => len.flatten(),
+                };
+                match debugger.child() {
+                    Some(_) => debugger.disassemble(args.first().copied(), len, with_source),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "thread" => {
+                let Some(id) = words.next().and_then(|arg| arg.parse().ok()) else {
+                    reporter.error("Usage: thread <id>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.select_thread(id),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "regs" => match (words.next(), words.next(), debugger.child()) {
+                (Some("list"), None, _) => debugger.list_registers(),
+                (Some("save"), Some(name), Some(_)) => debugger.save_registers(name),
+                (Some("restore"), Some(name), Some(_)) => debugger.restore_registers(name),
+                (Some("save" | "restore"), Some(_), None) => reporter.error("No program running"),
+                _ => reporter.error("Usage: regs <save|restore> <name> or regs list"),
+            },
+            "print" => {
+                let expression = words.collect::<Vec<_>>().join(" ");
+                if expression.is_empty() {
+                    reporter.error("Usage: print <expression>");
+                    continue;
+                }
+                match debugger.child() {
+                    Some(_) => debugger.print(&expression),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "display" => {
+                let expression = words.collect::<Vec<_>>().join(" ");
+                match (expression.is_empty(), debugger.child()) {
+                    (false, _) => debugger.display(&expression),
+                    (true, Some(_)) => debugger.show_displays(),
+                    (true, None) => reporter.error("No program running"),
+                }
+            }
+            "undisplay" => match words.next().and_then(|arg| arg.parse().ok()) {
+                Some(index) => debugger.undisplay(index),
+                None => reporter.error("Usage: undisplay <n>"),
+            },
+            "jump" => {
+                let Some(addr) = words.next().and_then(parse_address) else {
+                    reporter.error("Usage: jump <addr> [--continue]");
+                    continue;
+                };
+                let resume = words.next() == Some("--continue");
+                match debugger.child() {
+                    Some(_) => debugger.jump(addr, resume),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "assemble" => {
+                let location = words.next();
+                let text = words.collect::<Vec<_>>().join(" ");
+                let (Some(location), false) = (location, text.is_empty()) else {
+                    reporter.error("Usage: assemble <location> \"<instructions>\"");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.assemble(location, text.trim_matches('"')),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "unpatch" => {
+                let Some(location) = words.next() else {
+                    reporter.error("Usage: unpatch <location>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.unpatch(location),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "dump" => {
+                let addr = words.next().and_then(parse_address);
+                let len = words.next().and_then(parse_address);
+                let (Some(addr), Some(len), Some(path)) = (addr, len, words.next()) else {
+                    reporter.error("Usage: dump <addr> <len> <path> [--force]");
+                    continue;
+                };
+                let force = words.next() == Some("--force");
+                match debugger.child() {
+                    Some(_) => debugger.dump(addr, len, path, force),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "restore" => {
+                let path = words.next();
+                let addr = words.next().and_then(parse_address);
+                let (Some(path), Some(addr)) = (path, addr) else {
+                    reporter.error("Usage: restore <path> <addr>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.restore(path, addr),
+                    None => {
+                        reporter.error("No program running");
+                    }
                 }
+            }
+            "generate-core-file" | "dump-core" => {
+                let Some(path) = words.next() else {
+                    reporter.error("Usage: generate-core-file <path>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.generate_core_file(path),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            command if command == "x" || command.starts_with("x/") => {
+                let mut args = rest;
+                let mut spec = command.strip_prefix("x/");
+                if spec.is_none()
+                    && let Some(arg) = rest.strip_prefix('/')
+                {
+                    let (arg, location) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+                    spec = Some(arg);
+                    args = location.trim();
+                }
+                let format = examine::Format::parse(spec.unwrap_or(""));
+                let (Some(format), false) = (format, args.is_empty()) else {
+                    reporter.error("Usage: x[/<count><x|d|u|c|s|i><b|h|w|g>] <location>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.examine(&format, args),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "search" => {
+                let start = words.next().and_then(parse_address);
+                let len = words.next().and_then(parse_address);
+                let pattern = parse_pattern(&words.collect::<Vec<_>>().join(" "));
+                let (Some(start), Some(len), Some(pattern)) = (start, len, pattern) else {
+                    reporter.error("Usage: search <start> <len> <pattern>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.search(start, len, &pattern),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "watch" | "rwatch" | "awatch" => {
+                let Some(location) = words.next() else {
+                    reporter.error(format!("Usage: {command} <location> [<len>]"));
+                    continue;
+                };
+                if let Some(name) = location.strip_prefix('$')
+                    && command == "watch"
+                {
+                    match debugger.child() {
+                        Some(_) => debugger.watch_register(name),
+                        None => {
+                            reporter.error("No program running");
+                        }
+                    }
+                    continue;
+                }
+                let len = match words.next().map(parse_address) {
+                    Some(Some(len)) => Some(len),
+                    Some(None) => {
+                        reporter.error(format!("Usage: {command} <location> [<len>]"));
+                        continue;
+                    }
+                    None => None,
+                };
+                let kind = match command {
+                    "watch" => WatchKind::Write,
+                    "rwatch" => WatchKind::Read,
+                    _ => WatchKind::Access,
+                };
+                debugger.add_watchpoint(kind, location, len);
+            }
+            "stepi" => match debugger.child() {
+                Some(_) => debugger.stepi(),
+                None => {
+                    reporter.error("No program running");
+                }
+            },
+            "nexti" => match debugger.child() {
+                Some(_) => debugger.nexti(),
                 None => {
-                    println!("No program running");
+                    reporter.error("No program running");
+                }
+            },
+            "count" => {
+                let (Some(from), Some(to)) = (words.next(), words.next()) else {
+                    reporter.error("Usage: count <from> <to>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.count_instructions(from, to),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "until" => {
+                let Some(arg) = words.next() else {
+                    reporter.error("Usage: until <location>");
+                    continue;
+                };
+                match debugger.child() {
+                    Some(_) => debugger.until(arg),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "stack" => {
+                let count = match words.next().map(str::parse) {
+                    None => 16,
+                    Some(Ok(count)) if count > 0 => count,
+                    Some(_) => {
+                        reporter.error("Usage: stack [<n>]");
+                        continue;
+                    }
+                };
+                match debugger.child() {
+                    Some(_) => debugger.stack(count),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "backtrace" => {
+                let limit = match words.next().map(|limit| limit.parse::<usize>()) {
+                    Some(Ok(0) | Err(_)) => {
+                        reporter.error("Usage: backtrace [<frames>]");
+                        continue;
+                    }
+                    limit => limit.map(Result::unwrap),
+                };
+                match debugger.child() {
+                    Some(_) => debugger.backtrace(limit),
+                    None => {
+                        reporter.error("No program running");
+                    }
+                }
+            }
+            "record" => {
+                let depth = match words.next() {
+                    Some("stop") => {
+                        debugger.record_stop();
+                        continue;
+                    }
+                    Some(depth) => depth.parse().ok().filter(|&depth| depth > 0),
+                    None => Some(record::DEFAULT_DEPTH),
+                };
+                match (depth, debugger.child()) {
+                    (Some(depth), Some(_)) => debugger.record(depth),
+                    (Some(_), None) => reporter.error("No program running"),
+                    (None, _) => reporter.error("Usage: record [<depth>|stop]"),
+                }
+            }
+            "reverse-stepi" => match debugger.child() {
+                Some(_) => debugger.reverse_stepi(),
+                None => {
+                    reporter.error("No program running");
+                }
+            },
+            "reverse-continue" => match debugger.child() {
+                Some(_) => debugger.reverse_continue(),
+                None => {
+                    reporter.error("No program running");
+                }
+            },
+            "return" => match debugger.child() {
+                Some(_) => debugger.force_return(Some(rest).filter(|value| !value.is_empty())),
+                None => reporter.error("No program running"),
+            },
+            "finish" => match debugger.child() {
+                Some(_) => debugger.finish(),
+                None => {
+                    reporter.error("No program running");
+                }
+            },
+            "step" | "next" => match debugger.child() {
+                Some(_) => debugger.step_line(command == "step"),
+                None => {
+                    reporter.error("No program running");
                 }
             },
             other => {
-                println!("Unknown command '{other}'");
+                reporter.error(format!("Unknown command '{other}'"));
             }
         }
     }
 }
 
 fn main() {
-    let mut args = env::args();
-    if args.len() < 2 {
-        eprintln!(
-            "Usage: {} <program to trace> [<args>...]",
-            args.next().unwrap()
-        );
+    let mut args = env::args().peekable();
+    let name = args.next().unwrap();
+    let usage = format!(
+        "Usage: {name} [--json] [--color auto|always|never] [--listen <socket>] [--log <file>] [--source <file> [--batch]] <program to trace> [<args>...]"
+    );
+    let config = Config::load();
+    if let Some(flavor) = config.disassembly_flavor {
+        disassembler::set_flavor(flavor);
+    }
+    if let Some(aslr) = config.aslr {
+        debugger::set_aslr(aslr);
+    }
+    if let Some(mode) = config.follow_fork_mode {
+        debugger::set_follow_fork_mode(mode);
+    }
+    let mut format = Format::Text;
+    let mut color = config.color;
+    let mut listen = None;
+    let mut commands = config.commands;
+    let mut source = false;
+    let mut batch = false;
+    while let Some(option) = args.next_if(|arg| arg.starts_with("--")) {
+        match (
+            option.as_str(),
+            args.next_if(|_| {
+                ["--color", "--listen", "--log", "--source"].contains(&option.as_str())
+            })
+            .as_deref(),
+        ) {
+            ("--json", _) => format = Format::Json,
+            ("--color", Some("auto")) => color = None,
+            ("--color", Some("always")) => color = Some(true),
+            ("--color", Some("never")) => color = Some(false),
+            ("--listen", Some(path)) => listen = Some(path.to_string()),
+            ("--log", Some(path)) => {
+                if let Err(err) = reporter::open_log(path) {
+                    eprintln!("Cannot open the log file {path}: {err}");
+                    exit(1);
+                }
+            }
+            ("--source", Some(path)) => match fs::read_to_string(path) {
+                Ok(text) => {
+                    // One command per line, the empty lines and the comments starting with `#` are skipped
+                    commands.extend(
+                        text.lines()
+                            .map(str::trim)
+                            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                            .map(str::to_owned),
+                    );
+                    source = true;
+                }
+                Err(err) => {
+                    eprintln!("Cannot read the command file {path}: {err}");
+                    exit(1);
+                }
+            },
+            ("--batch", _) => batch = true,
+            _ => {
+                eprintln!("{usage}");
+                return;
+            }
+        }
+    }
+    if args.peek().is_none() || (batch && !source) {
+        eprintln!("{usage}");
         return;
     }
 
-    args.next().unwrap();
-    main_loop(args);
+    // The output goes to the clients of the socket, which are not terminals
+    let color = color.unwrap_or(listen.is_none() && stdout().is_terminal());
+    let control = listen.map(|path| match ControlSocket::bind(&path) {
+        Ok(control) => {
+            eprintln!("Listening on {path}");
+            control
+        }
+        Err(err) => {
+            eprintln!("Cannot listen on {path}: {err}");
+            exit(1);
+        }
+    });
+
+    main_loop(args, Reporter::new(format, color), control, commands, batch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases() -> Vec<(String, String)> {
+        DEFAULT_ALIASES
+            .into_iter()
+            .chain([("bm", "breakpoint main")])
+            .map(|(alias, command)| (alias.to_owned(), command.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn expand_command_completes_a_unique_prefix() {
+        assert_eq!(
+            expand_command("brea main", &aliases()).as_deref(),
+            Ok("breakpoint main")
+        );
+        assert_eq!(
+            expand_command("  disas  ", &aliases()).as_deref(),
+            Ok("disassemble ")
+        );
+    }
+
+    #[test]
+    fn expand_command_rejects_an_ambiguous_prefix() {
+        let err = expand_command("ste", &aliases()).unwrap_err();
+        assert!(err.starts_with("Ambiguous command 'ste'"), "{err}");
+        assert!(err.contains("stepi") && err.contains("step"), "{err}");
+    }
+
+    #[test]
+    fn expand_command_prefers_an_exact_match_to_a_longer_command() {
+        // `step` is also a prefix of `stepi`
+        assert_eq!(expand_command("step", &aliases()).as_deref(), Ok("step"));
+        assert_eq!(
+            expand_command("x/4xg $rsp", &aliases()).as_deref(),
+            Ok("x/4xg $rsp")
+        );
+    }
+
+    #[test]
+    fn expand_command_expands_aliases() {
+        assert_eq!(
+            expand_command("b main", &aliases()).as_deref(),
+            Ok("breakpoint main")
+        );
+        assert_eq!(expand_command("s", &aliases()).as_deref(), Ok("stepi "));
+        assert_eq!(
+            expand_command("bm", &aliases()).as_deref(),
+            Ok("breakpoint main ")
+        );
+    }
+
+    #[test]
+    fn expand_command_leaves_unknown_commands() {
+        assert_eq!(
+            expand_command("frobnicate 1", &aliases()).as_deref(),
+            Ok("frobnicate 1")
+        );
+        assert_eq!(expand_command("", &aliases()).as_deref(), Ok(""));
+    }
 }
@@ -0,0 +1,58 @@
+use std::fs;
+
+use nix::unistd::Pid;
+
+/// A memory mapping of a thread, as listed in `/proc/<pid>/maps`
+pub struct MemoryMap {
+    pub start: usize,
+    pub end: usize,
+    /// The permissions of the mapping, like `r-xp`
+    pub perms: String,
+    pub offset: usize,
+    pub path: Option<String>,
+}
+
+impl MemoryMap {
+    /// Parses a line of `/proc/<pid>/maps`
+    ///
+    /// The format of a line is `start-end perms offset dev inode [path]`
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(6, ' ');
+        let (start, end) = fields.next()?.split_once('-')?;
+        let perms = fields.next()?;
+        let offset = fields.next()?;
+        let _dev = fields.next()?;
+        let _inode = fields.next()?;
+        let path = fields
+            .next()
+            .map(|path| path.trim_start().to_owned())
+            .filter(|path| !path.is_empty());
+
+        Some(Self {
+            start: usize::from_str_radix(start, 16).ok()?,
+            end: usize::from_str_radix(end, 16).ok()?,
+            perms: perms.to_owned(),
+            offset: usize::from_str_radix(offset, 16).ok()?,
+            path,
+        })
+    }
+
+    /// Checks if `addr` is inside the mapping
+    pub fn contains(&self, addr: usize) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.perms.contains('x')
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.perms.contains('w')
+    }
+}
+
+/// Reads the memory mappings of the thread `pid`
+pub fn read_maps(pid: Pid) -> Option<Vec<MemoryMap>> {
+    let maps = fs::read_to_string(format!("/proc/{pid}/maps")).ok()?;
+    maps.lines().map(MemoryMap::parse).collect()
+}
@@ -0,0 +1,111 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use nix::{errno::Errno, libc::user_regs_struct, sys::ptrace, unistd::Pid};
+
+use crate::{
+    disassembler,
+    utils::{read_data, read_data_fixed, register, write_data},
+};
+
+/// The number of steps kept by default by `record`
+pub const DEFAULT_DEPTH: usize = 10000;
+
+/// The state of a thread before it executed an instruction
+///
+/// Only the memory the instruction can write is saved: its memory operand
+/// and the word below the stack pointer, written by `push` and `call`.
+struct Step {
+    regs: user_regs_struct,
+    /// The saved bytes and their address
+    memory: Vec<(usize, Vec<u8>)>,
+}
+
+/// The reason the state of the recorded thread can't be saved or restored
+pub enum Error {
+    /// The stack pointer is too low for the word below it to be saved
+    StackPointer(u64),
+    /// The saved memory at this address can't be written back
+    UnwritableMemory(usize),
+    /// The saved registers can't be written back
+    Registers(Errno),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::StackPointer(rsp) => {
+                write!(f, "Cannot save the memory below the stack pointer {rsp:#x}")
+            }
+            Error::UnwritableMemory(addr) => write!(f, "Cannot write memory at {addr:#x}"),
+            Error::Registers(errno) => write!(f, "Cannot set the registers : {}", errno.desc()),
+        }
+    }
+}
+
+/// The instructions executed by a thread while recording, the most recent last
+///
+/// At most `depth` steps are kept, the oldest ones are forgotten first.
+pub struct History {
+    pub thread: Pid,
+    depth: usize,
+    steps: VecDeque<Step>,
+}
+
+impl History {
+    pub fn new(thread: Pid, depth: usize) -> Self {
+        Self {
+            thread,
+            depth,
+            steps: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Forgets the recorded instructions
+    pub fn clear(&mut self) {
+        self.steps.clear();
+    }
+
+    /// Saves the state of the thread before it executes its next instruction
+    ///
+    /// Nothing is saved if the word below the stack pointer is outside of the address space.
+    pub fn record(&mut self) -> Result<(), Error> {
+        let regs = ptrace::getregs(self.thread).unwrap();
+        let mut memory = Vec::new();
+        let stack = (regs.rsp as usize)
+            .checked_sub(8)
+            .ok_or(Error::StackPointer(regs.rsp))?;
+        if let Some(word) = read_data_fixed::<8>(self.thread, stack) {
+            memory.push((stack, word.to_vec()));
+        }
+        let access = read_data_fixed::<15>(self.thread, regs.rip as _)
+            .and_then(|code| disassembler::decode(&code, regs.rip as _))
+            .and_then(|instruction| instruction.memory_access(|name| register(&regs, name)));
+        if let Some((addr, size)) = access {
+            memory.push((addr, read_data(self.thread, addr, size)));
+        }
+        if self.steps.len() == self.depth {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(Step { regs, memory });
+        Ok(())
+    }
+
+    /// Restores the state of the thread before its last recorded instruction
+    ///
+    /// Returns `false` if there is no recorded instruction left.
+    pub fn undo(&mut self) -> Result<bool, Error> {
+        let Some(step) = self.steps.pop_back() else {
+            return Ok(false);
+        };
+        // The memory is restored in reverse order, in case both saved ranges overlap
+        for (addr, bytes) in step.memory.iter().rev() {
+            write_data(self.thread, *addr, bytes).map_err(|_| Error::UnwritableMemory(*addr))?;
+        }
+        ptrace::setregs(self.thread, step.regs).map_err(Error::Registers)?;
+        Ok(true)
+    }
+}
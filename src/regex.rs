@@ -0,0 +1,148 @@
+/// What a regular expression item matches, a single character
+enum Atom {
+    Char(char),
+    /// `.`, any character
+    Any,
+    /// `[...]`, one of the characters of the ranges, or none of them with `[^...]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Char(expected) => c == *expected,
+            Atom::Any => true,
+            Atom::Class { negated, ranges } => {
+                ranges.iter().any(|&(start, end)| start <= c && c <= end) != *negated
+            }
+        }
+    }
+}
+
+/// How many times an item is repeated
+#[derive(Clone, Copy, PartialEq)]
+enum Repeat {
+    Once,
+    /// `?`
+    Optional,
+    /// `*`
+    Any,
+    /// `+`
+    AtLeastOnce,
+}
+
+/// A regular expression, matching anywhere in a text unless anchored with `^` or `$`
+///
+/// The characters, `.`, the classes like `[a-z_]` or `[^0-9]`, the repetitions `*`, `+` and `?`
+/// and the escapes like `\.` are supported.
+pub struct Regex {
+    items: Vec<(Atom, Repeat)>,
+    start: bool,
+    end: bool,
+}
+
+impl Regex {
+    /// Parses `pattern`, `None` if it is not a valid regular expression
+    pub fn parse(pattern: &str) -> Option<Regex> {
+        let (start, pattern) = match pattern.strip_prefix('^') {
+            Some(pattern) => (true, pattern),
+            None => (false, pattern),
+        };
+        let (end, pattern) = match pattern.strip_suffix('$') {
+            Some(pattern) if !pattern.ends_with('\\') => (true, pattern),
+            _ => (false, pattern),
+        };
+        let mut items: Vec<(Atom, Repeat)> = Vec::new();
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            let atom = match c {
+                '.' => Atom::Any,
+                '\\' => Atom::Char(chars.next()?),
+                '[' => {
+                    let negated = chars.next_if_eq(&'^').is_some();
+                    let mut ranges = Vec::new();
+                    // A `]` first in the class is one of its characters
+                    let mut first = true;
+                    loop {
+                        let start = match chars.next()? {
+                            ']' if !first => break,
+                            '\\' => chars.next()?,
+                            start => start,
+                        };
+                        first = false;
+                        let end = match chars.peek() {
+                            Some('-') => {
+                                chars.next();
+                                match chars.next()? {
+                                    // A `-` last in the class is one of its characters
+                                    ']' => {
+                                        ranges.push((start, start));
+                                        ranges.push(('-', '-'));
+                                        break;
+                                    }
+                                    '\\' => chars.next()?,
+                                    end => end,
+                                }
+                            }
+                            _ => start,
+                        };
+                        if end < start {
+                            return None;
+                        }
+                        ranges.push((start, end));
+                    }
+                    Atom::Class { negated, ranges }
+                }
+                '*' | '+' | '?' => {
+                    let (_, repeat) = items.last_mut()?;
+                    if *repeat != Repeat::Once {
+                        return None;
+                    }
+                    *repeat = match c {
+                        '*' => Repeat::Any,
+                        '+' => Repeat::AtLeastOnce,
+                        _ => Repeat::Optional,
+                    };
+                    continue;
+                }
+                c => Atom::Char(c),
+            };
+            items.push((atom, Repeat::Once));
+        }
+        Some(Regex { items, start, end })
+    }
+
+    /// Checks if the regular expression matches `text`, or a part of it
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        if self.start {
+            return self.matches_at(&self.items, &text);
+        }
+        (0..=text.len()).any(|start| self.matches_at(&self.items, &text[start..]))
+    }
+
+    /// Checks if `items` match the beginning of `text`, or the whole of it if the regular expression ends with `$`
+    fn matches_at(&self, items: &[(Atom, Repeat)], text: &[char]) -> bool {
+        let Some(((atom, repeat), items)) = items.split_first() else {
+            return !self.end || text.is_empty();
+        };
+        let (min, max) = match repeat {
+            Repeat::Once => (1, 1),
+            Repeat::Optional => (0, 1),
+            Repeat::Any => (0, usize::MAX),
+            Repeat::AtLeastOnce => (1, usize::MAX),
+        };
+        let matched = text
+            .iter()
+            .take(max)
+            .take_while(|&&c| atom.matches(c))
+            .count();
+        // The repetitions are greedy, fewer characters are tried when the rest doesn't match
+        (min..=matched)
+            .rev()
+            .any(|count| self.matches_at(items, &text[count..]))
+    }
+}
@@ -0,0 +1,90 @@
+use std::{ffi::OsStr, fs, os::unix::ffi::OsStrExt, path::PathBuf};
+
+use nix::unistd::Pid;
+
+use crate::utils::{read_cstring, read_data_fixed};
+
+/// The tag of the entry of the dynamic section the dynamic linker fills with the address of `r_debug`
+const DT_DEBUG: u64 = 21;
+/// The state of `r_debug` once the libraries are mapped, and before a library is added or removed
+const RT_CONSISTENT: u32 = 0;
+/// The most entries of the dynamic section or of the list of libraries read, in case they are corrupted
+const MAX_ENTRIES: usize = 1024;
+/// The longest path of a library read
+const PATH_MAX: usize = 4096;
+
+/// Reads the word at `addr` in the memory of `pid`
+fn read_u64(pid: Pid, addr: usize) -> Option<u64> {
+    read_data_fixed(pid, addr).map(u64::from_le_bytes)
+}
+
+/// The `r_debug` structure, through which the dynamic linker tells debuggers which libraries are loaded
+///
+/// The linker lists the loaded objects in its `link_map` list and calls the function at `r_brk`
+/// each time it is about to add or remove objects, and once it is done.
+pub struct Rendezvous {
+    addr: usize,
+}
+
+impl Rendezvous {
+    /// Finds `r_debug` from the `DT_DEBUG` entry of the dynamic section at `dynamic` in the memory of `pid`
+    ///
+    /// `None` is returned for a statically linked program, or before the linker has filled the entry.
+    pub fn find(pid: Pid, dynamic: usize) -> Option<Rendezvous> {
+        // The entries are pairs of a tag and a value, up to the `DT_NULL` tag
+        (0..MAX_ENTRIES)
+            .map_while(|index| {
+                let entry = dynamic.checked_add(16 * index)?;
+                Some((read_u64(pid, entry)?, read_u64(pid, entry + 8)?))
+            })
+            .take_while(|&(tag, _)| tag != 0)
+            .find(|&(tag, _)| tag == DT_DEBUG)
+            .map(|(_, addr)| addr as usize)
+            .filter(|&addr| addr != 0)
+            .map(|addr| Rendezvous { addr })
+    }
+
+    /// Returns the address of the function the linker calls when the list of libraries changes, `r_brk`
+    pub fn breakpoint(&self, pid: Pid) -> Option<usize> {
+        read_u64(pid, self.addr + 16).map(|addr| addr as usize)
+    }
+
+    /// Checks if the list of libraries can be read, the linker isn't adding or removing any
+    pub fn is_consistent(&self, pid: Pid) -> bool {
+        read_data_fixed::<4>(pid, self.addr + 24)
+            .is_some_and(|state| u32::from_le_bytes(state) == RT_CONSISTENT)
+    }
+
+    /// Lists the paths of the libraries in the `link_map` list of the linker with their load bias
+    ///
+    /// The program itself, without name, and the objects without file like the vDSO are skipped.
+    /// Relative paths, as given to `dlopen`, are relative to the working directory of the program.
+    pub fn libraries(&self, pid: Pid) -> Option<Vec<(String, usize)>> {
+        let mut libraries = Vec::new();
+        let mut map = read_u64(pid, self.addr + 8)? as usize;
+        for _ in 0..MAX_ENTRIES {
+            if map == 0 {
+                break;
+            }
+            let bias = read_u64(pid, map)? as usize;
+            let name = read_cstring(pid, read_u64(pid, map + 8)? as usize, PATH_MAX)?;
+            map = read_u64(pid, map + 24)? as usize;
+            if name.is_empty() {
+                continue;
+            }
+            let mut path = PathBuf::from(OsStr::from_bytes(&name));
+            if path.is_relative() {
+                let cwd = PathBuf::from(format!("/proc/{pid}/cwd"));
+                let Ok(absolute) = fs::canonicalize(cwd.join(path)) else {
+                    continue;
+                };
+                path = absolute;
+            }
+            if !path.is_file() {
+                continue;
+            }
+            libraries.push((path.to_string_lossy().into_owned(), bias));
+        }
+        Some(libraries)
+    }
+}
@@ -0,0 +1,2119 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, Write, stdout},
+    mem::MaybeUninit,
+    sync::{
+        OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nix::{
+    libc::{self, user_fpregs_struct, user_regs_struct},
+    sys::{ptrace, signal::Signal},
+    unistd::Pid,
+};
+
+use crate::{
+    disassembler::Instruction,
+    dwarf::SourceLocation,
+    frame::StackFrame,
+    signals::SignalHandling,
+    syscalls,
+    utils::{REGISTERS, eflags_names, extended_to_f64, register, st, xmm},
+    watchpoint::{WatchKind, Watchpoint},
+    xstate::VectorRegisters,
+};
+
+/// The registers in the grid of `info registers`, three per row, followed by `eflags` and the segment registers
+const GRID_REGISTERS: [&str; 17] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip",
+];
+
+/// The name of the symbol containing an address, and the offset of the address in it
+pub type SymbolOffset<'a> = (&'a str, usize);
+
+/// The value of a variable of the current frame, as printed by `info locals`
+pub struct VariableValue<'a> {
+    pub name: &'a str,
+    pub type_name: &'a str,
+    /// The formatted value, or why it can't be read
+    pub value: Result<String, String>,
+}
+
+/// A member of a structure read from memory, as printed by `print (struct <name> *)<address>`
+pub struct MemberValue<'a> {
+    pub name: &'a str,
+    /// The offset of the member from the beginning of the structure
+    pub offset: usize,
+    pub type_name: &'a str,
+    pub value: String,
+}
+
+/// An entry of the auxiliary vector, as printed by `info auxv`
+pub struct AuxvLine {
+    pub key: u64,
+    /// The name of the type of the entry, `None` for a type unknown to DBFS
+    pub name: Option<&'static str>,
+    pub value: u64,
+    /// The value is an address or flags, printed in hexadecimal
+    pub hex: bool,
+    /// The string the value points to, for the entries like `AT_PLATFORM`
+    pub string: Option<String>,
+}
+
+/// A word of the stack, as printed by `stack`
+pub struct StackWord<'a> {
+    pub addr: usize,
+    pub value: u64,
+    /// The symbol the value points into
+    pub symbol: Option<SymbolOffset<'a>>,
+    /// The value points just after a call instruction
+    pub return_address: bool,
+}
+
+/// How many times a breakpoint was hit since the program was run, as printed by `info all-breakpoints-hit`
+pub struct BreakpointHits<'a> {
+    pub index: usize,
+    /// The address of the breakpoint, `None` if its location is not resolved
+    pub addr: Option<usize>,
+    pub symbol: Option<SymbolOffset<'a>>,
+    pub hits: usize,
+}
+
+/// Something that happened in the debugger or in the program, to be told to the user
+pub enum Event<'a> {
+    /// A general information
+    Info(String),
+    /// A command failed or cannot be executed
+    Error(String),
+    BreakpointAdded {
+        index: usize,
+    },
+    /// The breakpoints `first` to `last` were added by a single `breakpoint` command
+    BreakpointsAdded {
+        first: usize,
+        last: usize,
+    },
+    /// `count` breakpoints were deleted by `clear`
+    BreakpointsCleared {
+        count: usize,
+    },
+    /// The breakpoint `index` is in a library that isn't loaded yet, it is set once the library is loaded
+    BreakpointPending {
+        index: usize,
+    },
+    /// The pending breakpoint `index` was set at `addr` as its library was loaded
+    BreakpointResolved {
+        index: usize,
+        addr: usize,
+    },
+    /// The hardware watchpoint `index` of `len` bytes at `addr` was added
+    WatchpointAdded {
+        index: usize,
+        kind: WatchKind,
+        /// `None` until the program is run when the address of the symbol isn't known yet
+        addr: Option<usize>,
+        symbol: Option<SymbolOffset<'a>>,
+        len: usize,
+        /// The canonical frame address of the frame holding the watched memory, if it is in the stack
+        frame: Option<usize>,
+        /// The watchpoint is checked by single stepping
+        software: bool,
+    },
+    /// The watchpoint `index` was deleted as the frame holding the watched memory returned
+    WatchpointScopeLeft {
+        index: usize,
+    },
+    /// The memory watched by the watchpoint `index` was accessed, changing from `old` to `new` if `written`
+    WatchpointHit {
+        index: usize,
+        kind: WatchKind,
+        software: bool,
+        addr: usize,
+        written: bool,
+        old: u64,
+        new: u64,
+        /// The instruction that accessed the memory, `None` if it can't be found
+        instruction: Option<&'a Instruction>,
+    },
+    Watchpoints(&'a [Watchpoint]),
+    /// The indices of all the breakpoints at `addr`
+    BreakpointHit {
+        indices: Vec<usize>,
+        addr: usize,
+    },
+    BreakpointHits(Vec<BreakpointHits<'a>>),
+    Exited {
+        code: i32,
+    },
+    /// The debugger detached from the program `pid`, which keeps running
+    Detached {
+        pid: Pid,
+    },
+    Signaled {
+        signal: Signal,
+    },
+    /// The frames of the call stack from the innermost, `corrupted` if the frame chain looked invalid
+    /// and `truncated` if more frames follow
+    Backtrace {
+        frames: Vec<StackFrame>,
+        corrupted: bool,
+        truncated: bool,
+    },
+    /// The frame of the current function, `cfa` being the stack pointer before the call,
+    /// `saved_rbp` where the frame pointer of the caller is saved, once the prologue saved it,
+    /// and `locals` the range of the stack holding the local variables, if any
+    FrameInfo {
+        frame: StackFrame,
+        cfa: usize,
+        return_addr: Option<usize>,
+        rbp: usize,
+        rsp: usize,
+        saved_rbp: Option<usize>,
+        locals: Option<(usize, usize)>,
+    },
+    /// The program received `signal` without stopping
+    SignalReceived {
+        signal: Signal,
+    },
+    SignalHandling {
+        signal: Signal,
+        handling: SignalHandling,
+    },
+    /// The program was stopped by `signal`
+    Stopped {
+        signal: Signal,
+    },
+    /// The program stopped at the ptrace event `code`, `PTRACE_EVENT_EXEC` for example
+    PtraceStop {
+        code: i32,
+    },
+    /// The program was resumed by a `SIGCONT`
+    Continued,
+    /// The program stopped at `addr`, with the function and source location if they are known
+    Location {
+        addr: usize,
+        function: Option<&'a str>,
+        location: Option<SourceLocation>,
+    },
+    EntryPoint {
+        addr: usize,
+    },
+    /// The program was run until `main` by `start`
+    MainReached {
+        addr: usize,
+    },
+    /// Lines of the source file `file` with their number
+    Source {
+        file: &'a str,
+        lines: Vec<(u64, String)>,
+    },
+    /// The source location of the instruction at `addr`, if it is known
+    Line {
+        addr: usize,
+        location: Option<SourceLocation>,
+    },
+    /// The symbol containing `addr` and the offset of `addr` in it, if there is one
+    Symbol {
+        addr: usize,
+        symbol: Option<SymbolOffset<'a>>,
+    },
+    NewThread {
+        id: usize,
+        pid: Pid,
+    },
+    ThreadExited {
+        id: usize,
+        pid: Pid,
+    },
+    SwitchedThread {
+        id: usize,
+        pid: Pid,
+    },
+    Threads {
+        threads: Vec<(usize, Pid)>,
+        current: Pid,
+    },
+    InferiorAdded {
+        id: usize,
+        program: &'a str,
+    },
+    SwitchedInferior {
+        id: usize,
+        program: &'a str,
+    },
+    /// The programs being debugged with the pid of those running
+    Inferiors {
+        inferiors: Vec<(usize, &'a str, Option<Pid>)>,
+        current: usize,
+    },
+    /// The names and the addresses of the functions printed by `info functions`
+    Functions(Vec<(&'a str, usize)>),
+    /// The names, the addresses and the sizes of the variables printed by `info variables`
+    GlobalVariables(Vec<(&'a str, usize, Option<usize>)>),
+    /// The paths of the mapped shared libraries, their load address and if their symbols were read
+    SharedLibraries(Vec<(String, usize, bool)>),
+    /// The general purpose registers, in a grid or as raw as the structure read from the kernel
+    Registers {
+        regs: &'a user_regs_struct,
+        raw: bool,
+    },
+    /// The value of the general purpose register `name`
+    Register {
+        name: &'a str,
+        value: u64,
+    },
+    /// The names of the saved registers with their instruction pointer
+    Snapshots(Vec<(&'a str, u64)>),
+    /// The x87 and SSE registers
+    FpRegisters(&'a user_fpregs_struct),
+    /// The vector registers, printed as elements of `size` bytes
+    VectorRegisters {
+        registers: &'a VectorRegisters,
+        size: usize,
+    },
+    /// The program entered a syscall, `call` being the syscall with its decoded arguments
+    SyscallEntry {
+        regs: &'a user_regs_struct,
+        call: String,
+    },
+    /// The syscall `number` returned `value`, `entry` being the registers at its entry if they are known
+    /// and `call` the syscall with its decoded arguments
+    SyscallExit {
+        number: u64,
+        value: i64,
+        entry: Option<&'a user_regs_struct>,
+        call: Option<String>,
+    },
+    /// `finish` returned to `addr`, in a caller without debugging information
+    Returned {
+        addr: usize,
+        symbol: Option<SymbolOffset<'a>>,
+    },
+    /// The value returned by a function, formatted according to its type if it is known
+    ReturnValue {
+        type_name: Option<&'a str>,
+        value: Option<String>,
+        rax: u64,
+    },
+    /// The value of an expression
+    Value(u64),
+    /// The value of a variable, formatted according to its type
+    Variable {
+        name: &'a str,
+        type_name: &'a str,
+        value: String,
+    },
+    /// The memory at `addr` formatted as the type `type_name`, member by member for a structure
+    TypedMemory {
+        addr: usize,
+        type_name: &'a str,
+        value: String,
+        members: Vec<MemberValue<'a>>,
+    },
+    /// The parameters of the current function if `arguments` is set, otherwise its local variables
+    Variables {
+        arguments: bool,
+        variables: Vec<VariableValue<'a>>,
+    },
+    /// The value of the displayed expression `index`, or why it can't be evaluated
+    Display {
+        index: usize,
+        expression: &'a str,
+        value: Result<u64, String>,
+    },
+    /// The entries of the auxiliary vector of the program
+    Auxv(Vec<AuxvLine>),
+    /// The displayed expressions with their number, by `info display`
+    Displays(&'a [(usize, String)]),
+    /// The probe `index` printing `expression` was hit for the `hits`-th time
+    ProbeHit {
+        index: usize,
+        hits: usize,
+        expression: &'a str,
+        value: u64,
+    },
+    RegisterChanged {
+        name: &'a str,
+        old: u64,
+        new: u64,
+    },
+    /// The registers that changed since the previous stop, with their old and new values
+    ChangedRegisters(Vec<(&'a str, u64, u64)>),
+    /// `len` bytes of the `requested` bytes at `addr` were written to `path`
+    Dumped {
+        path: &'a str,
+        addr: usize,
+        len: usize,
+        requested: usize,
+    },
+    /// `len` of the `requested` bytes of the file at `path` were written at `addr` by `restore`
+    Restored {
+        path: &'a str,
+        addr: usize,
+        len: usize,
+        requested: usize,
+    },
+    SearchMatches(Vec<usize>),
+    /// `count` instructions were single stepped, `reached` if the end address was reached,
+    /// with the most executed addresses, their symbol and how many times they were executed
+    InstructionCount {
+        count: usize,
+        reached: bool,
+        hottest: Vec<(usize, Option<SymbolOffset<'a>>, usize)>,
+    },
+    CoreFile(&'a str),
+    /// `len` bytes of code at `addr` were overwritten by `assemble`
+    Patched {
+        addr: usize,
+        len: usize,
+    },
+    /// The `len` bytes overwritten by the patch at `addr` were restored
+    Unpatched {
+        addr: usize,
+        len: usize,
+    },
+    /// The units of `size` bytes at `addr` formatted by `x`, printed `per_line` on each line
+    Memory {
+        addr: usize,
+        size: usize,
+        per_line: usize,
+        values: Vec<String>,
+    },
+    /// The words at the top of the stack, `sp` being the stack pointer
+    Stack {
+        sp: usize,
+        words: Vec<StackWord<'a>>,
+    },
+    /// The addresses and the quoted strings printed by `x/s`
+    Strings(Vec<(usize, String)>),
+    /// Disassembled instructions, `current` is the address of the next instruction to execute
+    Instructions {
+        instructions: &'a [Instruction],
+        current: usize,
+    },
+}
+
+/// How the events are written
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable text
+    Text,
+    /// A JSON object per line
+    Json,
+}
+
+/// Writes the events to the standard output
+#[derive(Clone, Copy)]
+pub struct Reporter {
+    format: Format,
+    style: Style,
+}
+
+impl Reporter {
+    /// Creates a reporter, the text is colored with ANSI escape codes if `color` is `true`
+    pub fn new(format: Format, color: bool) -> Self {
+        Self {
+            format,
+            style: Style { color },
+        }
+    }
+
+    /// Checks if the events are written as text rather than JSON
+    pub fn is_text(&self) -> bool {
+        self.format == Format::Text
+    }
+
+    /// Writes `event` in the format of the reporter, and in the log file as text
+    pub fn report(&self, event: Event) {
+        if let Event::Error(_) = event {
+            ERRORED.store(true, Ordering::Relaxed);
+        }
+        if LOG.get().is_some() {
+            log(&text(&event, Style { color: false }));
+        }
+        match self.format {
+            Format::Text => println!("{}", text(&event, self.style)),
+            Format::Json => println!("{}", json(&event)),
+        }
+    }
+
+    /// Writes the command entered by the user in the log file
+    pub fn log_command(&self, command: &str) {
+        if LOG.get().is_some() {
+            log(&format!("> {command}"));
+        }
+    }
+
+    /// Shortcut to report an `Event::Info`
+    pub fn info(&self, text: impl Into<String>) {
+        self.report(Event::Info(text.into()));
+    }
+
+    /// Shortcut to report an `Event::Error`
+    pub fn error(&self, message: impl Into<String>) {
+        self.report(Event::Error(message.into()));
+    }
+
+    /// Prints the prompt of the command line, there is no prompt in JSON
+    pub fn prompt(&self) {
+        if self.format == Format::Text {
+            print!("> ");
+            stdout().flush().unwrap();
+        }
+    }
+}
+
+/// The file where the commands and the events are written with the time they happened, see `open_log`
+static LOG: OnceLock<File> = OnceLock::new();
+
+/// Whether an error has been reported, for the exit code of `--batch`
+static ERRORED: AtomicBool = AtomicBool::new(false);
+
+/// Checks if an error has been reported since the debugger started
+pub fn has_errored() -> bool {
+    ERRORED.load(Ordering::Relaxed)
+}
+
+/// Writes the commands and the events to the file `path` from now on, the file is truncated
+pub fn open_log(path: &str) -> io::Result<()> {
+    let _ = LOG.set(File::create(path)?);
+    Ok(())
+}
+
+/// Writes each line of `text` to the log file, after the current time
+///
+/// The lines are written without buffering, so that the log is complete if the debugger crashes.
+fn log(text: &str) {
+    let Some(mut file) = LOG.get() else {
+        return;
+    };
+    let time = timestamp();
+    let lines: String = text
+        .lines()
+        .map(|line| format!("[{time}] {line}\n"))
+        .collect();
+    let _ = file.write_all(lines.as_bytes());
+}
+
+/// Formats the current local time, like `2024-05-01 14:03:27.120`
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs() as libc::time_t;
+    let mut tm = MaybeUninit::<libc::tm>::uninit();
+    // localtime_r only writes to `tm`, which is initialized if it succeeds
+    let tm = unsafe {
+        if libc::localtime_r(&seconds, tm.as_mut_ptr()).is_null() {
+            return format!("{}.{:03}", now.as_secs(), now.subsec_millis());
+        }
+        tm.assume_init()
+    };
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec,
+        now.subsec_millis()
+    )
+}
+
+/// The colors of the text output
+#[derive(Clone, Copy)]
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    const ADDRESS: &str = "36";
+    const BREAKPOINT: &str = "1;33";
+    const REGISTER: &str = "32";
+    const CHANGED: &str = "1;31";
+
+    /// Surrounds `text` with the ANSI escape codes of the SGR parameters `code`
+    fn paint(self, text: impl Display, code: &str) -> String {
+        if self.color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn addr(self, addr: usize) -> String {
+        self.paint(format_args!("{addr:#x}"), Self::ADDRESS)
+    }
+
+    fn register(self, name: impl Display) -> String {
+        self.paint(name, Self::REGISTER)
+    }
+}
+
+/// How a watchpoint of kind `kind` is named when it is added or hit, `software` if checked by single stepping
+fn watchpoint_title(kind: WatchKind, software: bool) -> &'static str {
+    match kind {
+        WatchKind::Write if software => "Software watchpoint",
+        WatchKind::Write => "Hardware watchpoint",
+        WatchKind::Read => "Hardware read watchpoint",
+        WatchKind::Access => "Hardware access (read/write) watchpoint",
+    }
+}
+
+/// Formats `event` as text for a human
+fn text(event: &Event, style: Style) -> String {
+    match event {
+        Event::Info(text) => text.clone(),
+        Event::Error(message) => message.clone(),
+        Event::BreakpointAdded { index } => format!("Breakpoint {index} added"),
+        Event::BreakpointsAdded { first, last } => format!("Breakpoints {first}-{last} added"),
+        Event::BreakpointsCleared { count } => {
+            let plural = if *count == 1 { "" } else { "s" };
+            format!("Deleted {count} breakpoint{plural}")
+        }
+        Event::BreakpointPending { index } => {
+            format!("Breakpoint {index} pending (will be set when the library is loaded)")
+        }
+        Event::BreakpointResolved { index, addr } => {
+            format!("Breakpoint {index} set at {}", style.addr(*addr))
+        }
+        Event::BreakpointHit { indices, addr } => {
+            let plural = if indices.len() > 1 { "s" } else { "" };
+            let indices: Vec<String> = indices.iter().map(usize::to_string).collect();
+            let message = format!("Reached breakpoint{plural} {}", indices.join(", "));
+            format!(
+                "{} at {}",
+                style.paint(message, Style::BREAKPOINT),
+                style.addr(*addr)
+            )
+        }
+        Event::WatchpointAdded {
+            index,
+            kind,
+            addr,
+            symbol,
+            len,
+            frame,
+            software,
+        } => {
+            let mut location = Vec::new();
+            if let Some(addr) = addr {
+                location.push(style.addr(*addr));
+            }
+            match symbol {
+                Some((name, 0)) => location.push(name.to_string()),
+                Some((name, offset)) => location.push(format!("{name}+{offset:#x}")),
+                None => {}
+            }
+            let scope = match frame {
+                Some(_) => ", deleted when its frame returns",
+                None => "",
+            };
+            format!(
+                "{} {index}: {} ({len} bytes){scope}",
+                watchpoint_title(*kind, *software),
+                location.join(" ")
+            )
+        }
+        Event::WatchpointScopeLeft { index } => format!(
+            "Watchpoint {index} deleted because the program has left the frame of the watched memory"
+        ),
+        Event::WatchpointHit {
+            index,
+            kind,
+            software,
+            addr,
+            written,
+            old,
+            new,
+            instruction,
+        } => {
+            let message = format!("{} {index}", watchpoint_title(*kind, *software));
+            let message = style.paint(message, Style::BREAKPOINT);
+            let (mut text, access) = match written {
+                true => (
+                    format!(
+                        "{message}: {} written\nOld value = {old:#x} ({old})\nNew value = {new:#x} ({new})",
+                        style.addr(*addr)
+                    ),
+                    "Written",
+                ),
+                false => (
+                    format!(
+                        "{message}: {} read\nValue = {new:#x} ({new})",
+                        style.addr(*addr)
+                    ),
+                    "Read",
+                ),
+            };
+            if let Some(instruction) = instruction {
+                text += &format!(
+                    "\n{access} by {}: {instruction}",
+                    style.addr(instruction.addr)
+                );
+            }
+            text
+        }
+        Event::Watchpoints(watchpoints) => {
+            if watchpoints.is_empty() {
+                return String::from("No watchpoints.");
+            }
+            let mut text = String::from("Num  Type             Address             Len  Hits");
+            for (index, watchpoint) in watchpoints.iter().enumerate() {
+                text.push_str(&format!(
+                    "\n{:<4} {:<16} {:<19} {:<4} {}",
+                    index + 1,
+                    watchpoint.kind.name(watchpoint.software),
+                    format!("{:#x}", watchpoint.addr),
+                    watchpoint.len,
+                    watchpoint.hits
+                ));
+            }
+            text
+        }
+        Event::Exited { code } => format!("Program exited with exit code {code}"),
+        Event::Detached { pid } => format!("Detached from process {pid}"),
+        Event::Signaled { signal } => format!("Program terminated by signal {signal}"),
+        Event::Stopped { signal } => format!("Program stopped by signal {signal}"),
+        Event::SignalReceived { signal } => format!("Program received signal {signal}"),
+        Event::SignalHandling { signal, handling } => {
+            let flag = |set: bool| if set { "Yes" } else { "No" };
+            format!(
+                "Signal    Stop Print Pass\n{:<9} {:<4} {:<5} {}",
+                signal.as_str(),
+                flag(handling.stop),
+                flag(handling.print),
+                flag(handling.pass)
+            )
+        }
+        Event::PtraceStop { code } => match ptrace_event_name(*code) {
+            Some(name) => format!("Program stopped at {name}"),
+            None => format!("Program stopped at unknown ptrace event {code}"),
+        },
+        Event::Continued => "Program continued".to_string(),
+        Event::Location {
+            addr,
+            function,
+            location,
+        } => match (location, function) {
+            (Some(location), Some(function)) => format!("{function} at {location}"),
+            (Some(location), None) => format!("At {location}"),
+            (None, _) => format!("Program interrupted at {}", style.addr(*addr)),
+        },
+        Event::EntryPoint { addr } => {
+            format!("Program stopped at its entry point {}", style.addr(*addr))
+        }
+        Event::MainReached { addr } => {
+            format!("Program stopped at main {}", style.addr(*addr))
+        }
+        Event::Source { lines, .. } => {
+            let lines: Vec<String> = lines
+                .iter()
+                .map(|(number, line)| format!("{number}\t{line}"))
+                .collect();
+            lines.join("\n")
+        }
+        Event::Line { addr, location } => match location {
+            Some(location) => format!("{} is at {location}", style.addr(*addr)),
+            None => format!("No line info for {}", style.addr(*addr)),
+        },
+        Event::BreakpointHits(breakpoints) => {
+            let mut text = String::from("Breakpoint hits:");
+            for breakpoint in breakpoints {
+                let index = style.paint(breakpoint.index, Style::BREAKPOINT);
+                let location = match (breakpoint.addr, breakpoint.symbol) {
+                    (Some(addr), Some((name, 0))) => format!("{} in {name}", style.addr(addr)),
+                    (Some(addr), Some((name, offset))) => {
+                        format!("{} in {name}+{offset:#x}", style.addr(addr))
+                    }
+                    (Some(addr), None) => style.addr(addr),
+                    (None, _) => String::from("<unresolved>"),
+                };
+                let times = if breakpoint.hits == 1 {
+                    "time"
+                } else {
+                    "times"
+                };
+                text.push_str(&format!(
+                    "\n  {index} at {location}: {} {times}",
+                    breakpoint.hits
+                ));
+            }
+            text
+        }
+        Event::Symbol { addr, symbol } => match symbol {
+            Some((name, 0)) => format!("{} is {name}", style.addr(*addr)),
+            Some((name, offset)) => format!("{} is {name}+{offset:#x}", style.addr(*addr)),
+            None => format!("No symbol matches {}", style.addr(*addr)),
+        },
+        Event::NewThread { id, pid } => format!("[New thread {id} ({pid})]"),
+        Event::ThreadExited { id, pid } => format!("[Thread {id} ({pid}) exited]"),
+        Event::SwitchedThread { id, pid } => format!("[Switching to thread {id} ({pid})]"),
+        Event::Threads { threads, current } => {
+            let mut text = String::from("  Id   Pid");
+            for (id, pid) in threads {
+                let marker = if pid == current { '*' } else { ' ' };
+                text.push_str(&format!("\n{marker} {id:<4} {pid}"));
+            }
+            text
+        }
+        Event::InferiorAdded { id, program } => format!("Added inferior {id} ({program})"),
+        Event::SwitchedInferior { id, program } => {
+            format!("[Switching to inferior {id} ({program})]")
+        }
+        Event::Inferiors { inferiors, current } => {
+            let mut text = String::from("  Id   Pid      Program");
+            for (id, program, pid) in inferiors {
+                let marker = if id == current { '*' } else { ' ' };
+                let pid = pid.map_or(String::from("-"), |pid| pid.to_string());
+                text.push_str(&format!("\n{marker} {id:<4} {pid:<8} {program}"));
+            }
+            text
+        }
+        Event::Functions(functions) if functions.is_empty() => String::from("No functions found"),
+        Event::Functions(functions) => {
+            let lines: Vec<String> = functions
+                .iter()
+                .map(|(name, addr)| format!("{}  {name}", style.addr(*addr)))
+                .collect();
+            lines.join("\n")
+        }
+        Event::GlobalVariables(variables) if variables.is_empty() => {
+            String::from("No variables found")
+        }
+        Event::GlobalVariables(variables) => {
+            let lines: Vec<String> = variables
+                .iter()
+                .map(|(name, addr, size)| {
+                    let size = size.map_or(String::from("?"), |size| size.to_string());
+                    format!("{}  {size:>6}  {name}", style.addr(*addr))
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::SharedLibraries(libraries) if libraries.is_empty() => {
+            String::from("No shared libraries loaded")
+        }
+        Event::SharedLibraries(libraries) => {
+            let mut text = String::from("Base                Symbols  Path");
+            for (path, base, loaded) in libraries {
+                let symbols = if *loaded { "Yes" } else { "No" };
+                let base = style.addr(*base);
+                text.push_str(&format!("\n{base:<19} {symbols:<8} {path}"));
+            }
+            text
+        }
+        Event::Registers { regs, raw: false } => {
+            let cell = |name: &str| {
+                let value = register(regs, name).unwrap();
+                format!("{} {value:#018x}", style.register(format!("{name:<7}")))
+            };
+            let mut lines: Vec<String> = GRID_REGISTERS
+                .chunks(3)
+                .map(|names| {
+                    let cells: Vec<String> = names.iter().map(|name| cell(name)).collect();
+                    cells.join("   ")
+                })
+                .collect();
+            lines.push(format!(
+                "{} [ {} ]",
+                cell("eflags"),
+                eflags_names(regs.eflags).join(" ")
+            ));
+            let segments: Vec<String> = ["cs", "ss", "ds", "es", "fs", "gs"]
+                .iter()
+                .map(|name| {
+                    let value = register(regs, name).unwrap();
+                    format!("{} {value:#x}", style.register(*name))
+                })
+                .collect();
+            lines.push(segments.join("  "));
+            lines.push([cell("fs_base"), cell("gs_base"), cell("orig_rax")].join("   "));
+            lines.join("\n")
+        }
+        Event::Registers { regs, raw: true } => {
+            let lines: Vec<String> = format!("{regs:#x?}")
+                .lines()
+                .map(|line| match line.split_once(':') {
+                    Some((name, value)) if REGISTERS.contains(&name.trim()) => {
+                        let indent = name.len() - name.trim_start().len();
+                        let (indent, name) = name.split_at(indent);
+                        format!("{indent}{}:{value}", style.register(name))
+                    }
+                    _ => line.to_owned(),
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::Register { name, value } => format!(
+            "{} {value:#018x} ({value})",
+            style.register(format!("{name:<8}"))
+        ),
+        Event::Snapshots(snapshots) if snapshots.is_empty() => String::from("No saved registers"),
+        Event::Snapshots(snapshots) => {
+            let lines: Vec<String> = snapshots
+                .iter()
+                .map(|(name, rip)| {
+                    format!(
+                        "{name:<16} {} = {}",
+                        style.register("rip"),
+                        style.addr(*rip as _)
+                    )
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::FpRegisters(fpregs) => {
+            let control = [
+                ("fctrl", fpregs.cwd as u32),
+                ("fstat", fpregs.swd as _),
+                ("ftag", fpregs.ftw as _),
+                ("mxcsr", fpregs.mxcsr),
+            ];
+            let lines: Vec<String> = control
+                .iter()
+                .map(|(name, value)| {
+                    format!("{}  {value:#x}", style.register(format!("{name:<5}")))
+                })
+                .collect();
+            let mut text = lines.join("\n");
+            for index in 0..8 {
+                let value = st(fpregs, index);
+                let float = extended_to_f64(value);
+                let name = style.register(format!("st{index:<4}"));
+                text.push_str(&format!("\n{name} {value:#022x} {float}"));
+            }
+            for index in 0..16 {
+                let value = xmm(fpregs, index);
+                let (f64s, f32s) = xmm_floats(value);
+                let name = style.register(format!("xmm{index:<3}"));
+                text.push_str(&format!("\n{name} {value:#034x} f64 {f64s:?} f32 {f32s:?}"));
+            }
+            text
+        }
+        Event::VectorRegisters { registers, size } => {
+            let mut lines = Vec::new();
+            for (index, register) in registers.registers.iter().enumerate() {
+                let name = format!("{}{index}", registers.name);
+                // A row per 128 bits lane, from the lowest element
+                for (lane, bytes) in register.chunks(16).enumerate() {
+                    let name = if lane == 0 { name.as_str() } else { "" };
+                    let elements: Vec<String> = vector_elements(bytes, *size)
+                        .iter()
+                        .map(|element| format!("{element:#0width$x}", width = 2 + 2 * size))
+                        .collect();
+                    lines.push(format!(
+                        "{} [{:>2}] {}",
+                        style.register(format!("{name:<6}")),
+                        lane * 16 / size,
+                        elements.join(" ")
+                    ));
+                }
+            }
+            for (index, mask) in registers.masks.iter().enumerate() {
+                let name = style.register(format!("{:<6}", format!("k{index}")));
+                lines.push(format!("{name} {mask:#x}"));
+            }
+            lines.join("\n")
+        }
+        Event::SyscallEntry { call, .. } => format!("Syscall entry {call}"),
+        Event::SyscallExit {
+            number,
+            value,
+            call,
+            ..
+        } => match call {
+            Some(call) => format!("Syscall exit {call} = {value}"),
+            None => format!("Syscall exit {} = {value}", syscalls::format_name(*number)),
+        },
+        Event::Returned { addr, symbol } => match symbol {
+            Some((name, 0)) => format!("Returned to {} in {name}", style.addr(*addr)),
+            Some((name, offset)) => {
+                format!("Returned to {} in {name}+{offset:#x}", style.addr(*addr))
+            }
+            None => format!("Returned to {}", style.addr(*addr)),
+        },
+        Event::ReturnValue {
+            type_name,
+            value,
+            rax,
+        } => match (type_name, value) {
+            (Some(type_name), Some(value)) => format!("Value returned: ({type_name}) {value}"),
+            (Some(type_name), None) => format!("Value returned: ({type_name}) rax = {rax:#x}"),
+            (None, _) => format!("Value returned: rax = {rax:#x}"),
+        },
+        Event::Value(value) => format!("{value:#x} ({value})"),
+        Event::Variable { name, value, .. } => format!("{name} = {value}"),
+        Event::TypedMemory {
+            addr,
+            type_name,
+            value,
+            members,
+        } => {
+            let cast = format!("({type_name} *) {}", style.addr(*addr));
+            if members.is_empty() {
+                return format!("{cast} = {value}");
+            }
+            let lines: Vec<String> = members
+                .iter()
+                .map(|member| {
+                    format!(
+                        "  +{:<5} {} {} = {}",
+                        format!("{:#x}", member.offset),
+                        member.type_name,
+                        member.name,
+                        member.value
+                    )
+                })
+                .collect();
+            format!("{cast} = {{\n{}\n}}", lines.join("\n"))
+        }
+        Event::Variables {
+            arguments,
+            variables,
+        } => {
+            if variables.is_empty() {
+                return String::from(if *arguments {
+                    "No arguments."
+                } else {
+                    "No locals."
+                });
+            }
+            let lines: Vec<String> = variables
+                .iter()
+                .map(|variable| match &variable.value {
+                    Ok(value) | Err(value) => format!("{} = {value}", variable.name),
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::Display {
+            index,
+            expression,
+            value,
+        } => match value {
+            Ok(value) => format!("{index}: {expression} = {value:#x} ({value})"),
+            Err(err) => format!("{index}: {expression} = <error: {err}>"),
+        },
+        Event::Auxv(entries) => {
+            let lines: Vec<String> = entries
+                .iter()
+                .map(|entry| {
+                    let name = entry
+                        .name
+                        .map_or_else(|| format!("AT_??? ({})", entry.key), String::from);
+                    let value = match entry.hex {
+                        true => style.addr(entry.value as _),
+                        false => entry.value.to_string(),
+                    };
+                    match &entry.string {
+                        Some(string) => format!("{name:<22} {value} {string}"),
+                        None => format!("{name:<22} {value}"),
+                    }
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::Displays(displays) => {
+            if displays.is_empty() {
+                return String::from("There are no auto-display expressions now.");
+            }
+            let mut text = String::from("Auto-display expressions now in effect:\nNum  Expression");
+            for (index, expression) in displays.iter() {
+                text.push_str(&format!("\n{index:<4} {expression}"));
+            }
+            text
+        }
+        Event::ProbeHit {
+            index,
+            hits,
+            expression,
+            value,
+        } => format!(
+            "Probe {} (hit {hits}): {expression} = {value:#x} ({value})",
+            style.paint(index, Style::BREAKPOINT)
+        ),
+        Event::RegisterChanged { name, old, new } => {
+            let name = style.register(format_args!("${name}"));
+            format!("{name} changed from {old:#x} to {new:#x}")
+        }
+        Event::ChangedRegisters(changed) if changed.is_empty() => {
+            String::from("No register changed since the previous stop")
+        }
+        Event::ChangedRegisters(changed) => {
+            let lines: Vec<String> = changed
+                .iter()
+                .map(|(name, old, new)| {
+                    let name = style.register(format!("{name:<8}"));
+                    let new = style.paint(format_args!("{new:#x}"), Style::CHANGED);
+                    format!("{name} {old:#x} -> {new}")
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::Dumped {
+            path,
+            addr,
+            len,
+            requested,
+        } => {
+            if len < requested {
+                format!(
+                    "Dumped {len} of {requested} bytes to '{path}', the memory at {} is not readable",
+                    style.addr(addr + len)
+                )
+            } else {
+                format!("Dumped {len} bytes to '{path}'")
+            }
+        }
+        Event::Restored {
+            path,
+            addr,
+            len,
+            requested,
+        } => {
+            if len < requested {
+                format!(
+                    "Restored {len} of {requested} bytes from '{path}', the memory at {} cannot be written",
+                    style.addr(addr + len)
+                )
+            } else {
+                format!(
+                    "Restored {len} bytes from '{path}' at {}",
+                    style.addr(*addr)
+                )
+            }
+        }
+        Event::CoreFile(path) => format!("Saved corefile '{path}'"),
+        Event::Patched { addr, len } => format!("Patched {len} bytes at {}", style.addr(*addr)),
+        Event::Unpatched { addr, len } => format!("Restored {len} bytes at {}", style.addr(*addr)),
+        Event::SearchMatches(matches) => {
+            let mut text = String::new();
+            for addr in matches {
+                text.push_str(&format!("{}\n", style.addr(*addr)));
+            }
+            text.push_str(&format!("{} matches found", matches.len()));
+            text
+        }
+        Event::InstructionCount {
+            count,
+            reached,
+            hottest,
+        } => {
+            let mut text = match reached {
+                true => format!("Executed {count} instructions"),
+                false => format!("Executed {count} instructions before stopping"),
+            };
+            if !hottest.is_empty() {
+                text.push_str("\nMost executed addresses:");
+            }
+            for (addr, symbol, hits) in hottest {
+                let symbol = match symbol {
+                    Some((name, 0)) => format!(" <{name}>"),
+                    Some((name, offset)) => format!(" <{name}+{offset:#x}>"),
+                    None => String::new(),
+                };
+                text.push_str(&format!("\n  {}{symbol}: {hits}", style.addr(*addr)));
+            }
+            text
+        }
+        Event::Memory {
+            addr,
+            size,
+            per_line,
+            values,
+        } => {
+            let lines: Vec<String> = values
+                .chunks(*per_line)
+                .enumerate()
+                .map(|(row, values)| {
+                    let addr = addr + row * per_line * size;
+                    format!("{}:\t{}", style.addr(addr), values.join("\t"))
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::Stack { sp, words } => {
+            let lines: Vec<String> = words
+                .iter()
+                .map(|word| {
+                    let mut line = format!(
+                        "{}  rsp+{:<#6x} {:#018x}",
+                        style.addr(word.addr),
+                        word.addr - sp,
+                        word.value
+                    );
+                    match word.symbol {
+                        Some((name, 0)) => line.push_str(&format!(" <{name}>")),
+                        Some((name, offset)) => line.push_str(&format!(" <{name}+{offset:#x}>")),
+                        None => {}
+                    }
+                    if word.return_address {
+                        line.push_str(" return address");
+                    }
+                    line
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::Strings(strings) => {
+            let lines: Vec<String> = strings
+                .iter()
+                .map(|(addr, string)| format!("{}:\t{string}", style.addr(*addr)))
+                .collect();
+            lines.join("\n")
+        }
+        Event::Instructions {
+            instructions,
+            current,
+        } => {
+            let lines: Vec<String> = instructions
+                .iter()
+                .map(|instruction| {
+                    let marker = if instruction.addr == *current {
+                        "=>"
+                    } else {
+                        "  "
+                    };
+                    format!("{marker} {}:\t{instruction}", style.addr(instruction.addr))
+                })
+                .collect();
+            lines.join("\n")
+        }
+        Event::Backtrace {
+            frames,
+            corrupted,
+            truncated,
+        } => {
+            let mut lines: Vec<String> = frames
+                .iter()
+                .enumerate()
+                .map(|(index, frame)| {
+                    let mut line = format!(
+                        "#{index:<2} {} in {}",
+                        style.addr(frame.pc),
+                        frame.function.as_deref().unwrap_or("??")
+                    );
+                    if let Some(arguments) = &frame.arguments {
+                        let arguments: Vec<String> = arguments
+                            .iter()
+                            .map(|(name, value)| format!("{name}={value}"))
+                            .collect();
+                        line += &format!(" ({})", arguments.join(", "));
+                    }
+                    if let Some(location) = &frame.location {
+                        line += &format!(" at {location}");
+                    }
+                    line
+                })
+                .collect();
+            if *corrupted {
+                lines.push(String::from("<corrupted stack?>"));
+            }
+            if *truncated {
+                lines.push(String::from("(More stack frames follow...)"));
+            }
+            lines.join("\n")
+        }
+        Event::FrameInfo {
+            frame,
+            cfa,
+            return_addr,
+            rbp,
+            rsp,
+            saved_rbp,
+            locals,
+        } => {
+            let mut lines = vec![format!("Stack frame at {}:", style.addr(*cfa))];
+            let mut line = format!(
+                " rip = {} in {}",
+                style.addr(frame.pc),
+                frame.function.as_deref().unwrap_or("??")
+            );
+            if let Some(location) = &frame.location {
+                line += &format!(" at {location}");
+            }
+            if let Some(return_addr) = return_addr {
+                line += &format!("; saved rip = {}", style.addr(*return_addr));
+            }
+            lines.push(line);
+            lines.push(format!(
+                " {} = {}, {} = {}",
+                style.register("rbp"),
+                style.addr(*rbp),
+                style.register("rsp"),
+                style.addr(*rsp)
+            ));
+            match locals {
+                Some((start, end)) => lines.push(format!(
+                    " Locals between {} and {}",
+                    style.addr(*start),
+                    style.addr(*end)
+                )),
+                None => lines.push(String::from(" No locals on the stack")),
+            }
+            match saved_rbp {
+                Some(saved_rbp) => {
+                    lines.push(format!(
+                        " Saved registers: rbp at {}, rip at {}",
+                        style.addr(*saved_rbp),
+                        style.addr(cfa - 8)
+                    ));
+                }
+                None => lines.push(format!(" Saved registers: rip at {}", style.addr(cfa - 8))),
+            }
+            lines.join("\n")
+        }
+    }
+}
+
+/// Formats `event` as a JSON object, written on a single line
+fn json(event: &Event) -> String {
+    match event {
+        Event::Info(text) => JsonObject::new("info").field("text", text).finish(),
+        Event::Error(message) => JsonObject::new("error").field("message", message).finish(),
+        Event::BreakpointAdded { index } => JsonObject::new("breakpoint_added")
+            .field("index", index)
+            .finish(),
+        Event::BreakpointsAdded { first, last } => JsonObject::new("breakpoints_added")
+            .field("first", first)
+            .field("last", last)
+            .finish(),
+        Event::BreakpointsCleared { count } => JsonObject::new("breakpoints_cleared")
+            .field("count", count)
+            .finish(),
+        Event::BreakpointPending { index } => JsonObject::new("breakpoint_pending")
+            .field("index", index)
+            .finish(),
+        Event::BreakpointResolved { index, addr } => JsonObject::new("breakpoint_resolved")
+            .field("index", index)
+            .field("addr", Hex(*addr as _))
+            .finish(),
+        Event::BreakpointHit { indices, addr } => JsonObject::new("breakpoint")
+            .field("index", indices[0])
+            .field("indices", indices)
+            .field("addr", Hex(*addr as _))
+            .finish(),
+        Event::WatchpointAdded {
+            index,
+            kind,
+            addr,
+            symbol,
+            len,
+            frame,
+            software,
+        } => JsonObject::new("watchpoint_added")
+            .field("index", index)
+            .field("type", kind.name(*software))
+            .field("addr", addr.map(|addr| Hex(addr as _)))
+            .field("symbol", symbol.map(|(name, _)| name))
+            .field("offset", symbol.map(|(_, offset)| offset))
+            .field("len", len)
+            .field("frame", frame.map(|frame| Hex(frame as _)))
+            .finish(),
+        Event::WatchpointScopeLeft { index } => JsonObject::new("watchpoint_scope_left")
+            .field("index", index)
+            .finish(),
+        Event::WatchpointHit {
+            index,
+            kind,
+            software,
+            addr,
+            written,
+            old,
+            new,
+            instruction,
+        } => JsonObject::new("watchpoint")
+            .field("index", index)
+            .field("type", kind.name(*software))
+            .field("addr", Hex(*addr as _))
+            .field("access", if *written { "write" } else { "read" })
+            .field("old", Hex(*old as _))
+            .field("new", Hex(*new as _))
+            .field(
+                "instruction_addr",
+                instruction.map(|instruction| Hex(instruction.addr as _)),
+            )
+            .field(
+                "instruction",
+                instruction.map(|instruction| instruction.to_string()),
+            )
+            .finish(),
+        Event::Watchpoints(watchpoints) => {
+            let watchpoints: Vec<JsonObject> = watchpoints
+                .iter()
+                .enumerate()
+                .map(|(index, watchpoint)| {
+                    JsonObject::object()
+                        .field("index", index + 1)
+                        .field("type", watchpoint.kind.name(watchpoint.software))
+                        .field("addr", Hex(watchpoint.addr as _))
+                        .field("len", watchpoint.len)
+                        .field("hits", watchpoint.hits)
+                })
+                .collect();
+            JsonObject::new("watchpoints")
+                .field("watchpoints", watchpoints)
+                .finish()
+        }
+        Event::Exited { code } => JsonObject::new("exited").field("code", code).finish(),
+        Event::Detached { pid } => JsonObject::new("detached")
+            .field("pid", pid.as_raw())
+            .finish(),
+        Event::Signaled { signal } => JsonObject::new("signaled")
+            .field("signal", signal.as_str())
+            .finish(),
+        Event::Stopped { signal } => JsonObject::new("stopped")
+            .field("signal", signal.as_str())
+            .finish(),
+        Event::SignalReceived { signal } => JsonObject::new("signal_received")
+            .field("signal", signal.as_str())
+            .finish(),
+        Event::SignalHandling { signal, handling } => JsonObject::new("signal_handling")
+            .field("signal", signal.as_str())
+            .field("stop", handling.stop)
+            .field("print", handling.print)
+            .field("pass", handling.pass)
+            .finish(),
+        Event::PtraceStop { code } => JsonObject::new("ptrace_event")
+            .field("code", code)
+            .field("name", ptrace_event_name(*code))
+            .finish(),
+        Event::Continued => JsonObject::new("continued").finish(),
+        Event::Location {
+            addr,
+            function,
+            location,
+        } => JsonObject::new("location")
+            .field("addr", Hex(*addr as _))
+            .field("function", *function)
+            .field("file", location.as_ref().map(|location| &location.file))
+            .field("line", location.as_ref().map(|location| location.line))
+            .finish(),
+        Event::EntryPoint { addr } => JsonObject::new("entry_point")
+            .field("addr", Hex(*addr as _))
+            .finish(),
+        Event::MainReached { addr } => JsonObject::new("main_reached")
+            .field("addr", Hex(*addr as _))
+            .finish(),
+        Event::Source { file, lines } => {
+            let lines: Vec<JsonObject> = lines
+                .iter()
+                .map(|(number, line)| {
+                    JsonObject::object()
+                        .field("line", number)
+                        .field("text", line)
+                })
+                .collect();
+            JsonObject::new("source")
+                .field("file", *file)
+                .field("lines", lines)
+                .finish()
+        }
+        Event::Line { addr, location } => JsonObject::new("line")
+            .field("addr", Hex(*addr as _))
+            .field("file", location.as_ref().map(|location| &location.file))
+            .field("line", location.as_ref().map(|location| location.line))
+            .finish(),
+        Event::BreakpointHits(breakpoints) => {
+            let breakpoints: Vec<JsonObject> = breakpoints
+                .iter()
+                .map(|breakpoint| {
+                    JsonObject::object()
+                        .field("index", breakpoint.index)
+                        .field("addr", breakpoint.addr.map(|addr| Hex(addr as _)))
+                        .field("symbol", breakpoint.symbol.map(|(name, _)| name))
+                        .field("offset", breakpoint.symbol.map(|(_, offset)| offset))
+                        .field("hits", breakpoint.hits)
+                })
+                .collect();
+            JsonObject::new("breakpoint_hits")
+                .field("breakpoints", breakpoints)
+                .finish()
+        }
+        Event::Symbol { addr, symbol } => JsonObject::new("symbol")
+            .field("addr", Hex(*addr as _))
+            .field("name", symbol.map(|(name, _)| name))
+            .field("offset", symbol.map(|(_, offset)| offset))
+            .finish(),
+        Event::NewThread { id, pid } => JsonObject::new("new_thread")
+            .field("id", id)
+            .field("pid", pid.as_raw())
+            .finish(),
+        Event::ThreadExited { id, pid } => JsonObject::new("thread_exited")
+            .field("id", id)
+            .field("pid", pid.as_raw())
+            .finish(),
+        Event::SwitchedThread { id, pid } => JsonObject::new("switched_thread")
+            .field("id", id)
+            .field("pid", pid.as_raw())
+            .finish(),
+        Event::Threads { threads, current } => {
+            let threads: Vec<JsonObject> = threads
+                .iter()
+                .map(|(id, pid)| {
+                    JsonObject::object()
+                        .field("id", id)
+                        .field("pid", pid.as_raw())
+                        .field("current", pid == current)
+                })
+                .collect();
+            JsonObject::new("threads")
+                .field("threads", threads)
+                .finish()
+        }
+        Event::InferiorAdded { id, program } => JsonObject::new("inferior_added")
+            .field("id", id)
+            .field("program", *program)
+            .finish(),
+        Event::SwitchedInferior { id, program } => JsonObject::new("switched_inferior")
+            .field("id", id)
+            .field("program", *program)
+            .finish(),
+        Event::Functions(functions) => {
+            let functions: Vec<JsonObject> = functions
+                .iter()
+                .map(|(name, addr)| {
+                    JsonObject::object()
+                        .field("name", *name)
+                        .field("addr", Hex(*addr as _))
+                })
+                .collect();
+            JsonObject::new("functions")
+                .field("functions", functions)
+                .finish()
+        }
+        Event::GlobalVariables(variables) => {
+            let variables: Vec<JsonObject> = variables
+                .iter()
+                .map(|(name, addr, size)| {
+                    JsonObject::object()
+                        .field("name", *name)
+                        .field("addr", Hex(*addr as _))
+                        .field("size", size)
+                })
+                .collect();
+            JsonObject::new("global_variables")
+                .field("variables", variables)
+                .finish()
+        }
+        Event::SharedLibraries(libraries) => {
+            let libraries: Vec<JsonObject> = libraries
+                .iter()
+                .map(|(path, base, loaded)| {
+                    JsonObject::object()
+                        .field("path", path)
+                        .field("base", Hex(*base as _))
+                        .field("symbols", loaded)
+                })
+                .collect();
+            JsonObject::new("shared_libraries")
+                .field("libraries", libraries)
+                .finish()
+        }
+        Event::Inferiors { inferiors, current } => {
+            let inferiors: Vec<JsonObject> = inferiors
+                .iter()
+                .map(|(id, program, pid)| {
+                    JsonObject::object()
+                        .field("id", id)
+                        .field("program", *program)
+                        .field("pid", pid.map(Pid::as_raw))
+                        .field("current", id == current)
+                })
+                .collect();
+            JsonObject::new("inferiors")
+                .field("inferiors", inferiors)
+                .finish()
+        }
+        Event::Registers { regs, .. } => REGISTERS
+            .iter()
+            .fold(JsonObject::new("registers"), |object, name| {
+                object.field(name, Hex(register(regs, name).unwrap() as _))
+            })
+            .field("flags", eflags_names(regs.eflags))
+            .finish(),
+        Event::Register { name, value } => JsonObject::new("register")
+            .field("name", *name)
+            .field("hex", Hex(*value as _))
+            .field("value", value)
+            .finish(),
+        Event::Snapshots(snapshots) => {
+            let snapshots: Vec<JsonObject> = snapshots
+                .iter()
+                .map(|(name, rip)| {
+                    JsonObject::object()
+                        .field("name", *name)
+                        .field("rip", Hex(*rip as _))
+                })
+                .collect();
+            JsonObject::new("snapshots")
+                .field("snapshots", snapshots)
+                .finish()
+        }
+        Event::FpRegisters(fpregs) => {
+            let st: Vec<JsonObject> = (0..8)
+                .map(|index| {
+                    let value = st(fpregs, index);
+                    JsonObject::object()
+                        .field("raw", Hex(value))
+                        .field("f64", extended_to_f64(value))
+                })
+                .collect();
+            let xmm: Vec<JsonObject> = (0..16)
+                .map(|index| {
+                    let value = xmm(fpregs, index);
+                    let (f64s, f32s) = xmm_floats(value);
+                    JsonObject::object()
+                        .field("raw", Hex(value))
+                        .field("f64", Vec::from(f64s))
+                        .field("f32", Vec::from(f32s))
+                })
+                .collect();
+            JsonObject::new("fp_registers")
+                .field("fctrl", Hex(fpregs.cwd as _))
+                .field("fstat", Hex(fpregs.swd as _))
+                .field("ftag", Hex(fpregs.ftw as _))
+                .field("mxcsr", Hex(fpregs.mxcsr as _))
+                .field("st", st)
+                .field("xmm", xmm)
+                .finish()
+        }
+        Event::VectorRegisters { registers, size } => {
+            let vectors: Vec<JsonObject> = registers
+                .registers
+                .iter()
+                .enumerate()
+                .map(|(index, register)| {
+                    let elements: Vec<Hex> = vector_elements(register, *size)
+                        .into_iter()
+                        .map(|element| Hex(element as _))
+                        .collect();
+                    JsonObject::object()
+                        .field("name", format!("{}{index}", registers.name))
+                        .field("elements", elements)
+                })
+                .collect();
+            let masks: Vec<Hex> = registers.masks.iter().map(|mask| Hex(*mask as _)).collect();
+            JsonObject::new("vector_registers")
+                .field("size", size)
+                .field("registers", vectors)
+                .field("masks", masks)
+                .finish()
+        }
+        Event::SyscallEntry { regs, call } => {
+            let args =
+                [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9].map(|arg| Hex(arg as _));
+            JsonObject::new("syscall_entry")
+                .field("name", syscalls::format_name(regs.orig_rax))
+                .field("number", regs.orig_rax)
+                .field("args", Vec::from(args))
+                .field("call", call.as_str())
+                .finish()
+        }
+        Event::SyscallExit {
+            number,
+            value,
+            entry,
+            call,
+        } => {
+            let args = entry.map(|regs| {
+                Vec::from(
+                    [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9]
+                        .map(|arg| Hex(arg as _)),
+                )
+            });
+            JsonObject::new("syscall_exit")
+                .field("name", syscalls::format_name(*number))
+                .field("number", number)
+                .field("args", args)
+                .field("call", call.as_deref())
+                .field("value", value)
+                .finish()
+        }
+        Event::Returned { addr, symbol } => JsonObject::new("returned")
+            .field("addr", Hex(*addr as _))
+            .field("symbol", symbol.map(|(name, _)| name))
+            .field("offset", symbol.map(|(_, offset)| offset))
+            .finish(),
+        Event::ReturnValue {
+            type_name,
+            value,
+            rax,
+        } => JsonObject::new("return_value")
+            .field("type", *type_name)
+            .field("value", value.as_ref())
+            .field("rax", Hex(*rax as _))
+            .finish(),
+        Event::Value(value) => JsonObject::new("value")
+            .field("hex", Hex(*value as _))
+            .field("value", value)
+            .finish(),
+        Event::Variable {
+            name,
+            type_name,
+            value,
+        } => JsonObject::new("variable")
+            .field("name", *name)
+            .field("type", *type_name)
+            .field("value", value.as_str())
+            .finish(),
+        Event::TypedMemory {
+            addr,
+            type_name,
+            value,
+            members,
+        } => {
+            let members: Vec<JsonObject> = members
+                .iter()
+                .map(|member| {
+                    JsonObject::object()
+                        .field("name", member.name)
+                        .field("offset", member.offset)
+                        .field("type", member.type_name)
+                        .field("value", &member.value)
+                })
+                .collect();
+            JsonObject::new("typed_memory")
+                .field("addr", Hex(*addr as _))
+                .field("type", *type_name)
+                .field("value", value)
+                .field("members", members)
+                .finish()
+        }
+        Event::Variables {
+            arguments,
+            variables,
+        } => {
+            let variables: Vec<JsonObject> = variables
+                .iter()
+                .map(|variable| {
+                    JsonObject::object()
+                        .field("name", variable.name)
+                        .field("type", variable.type_name)
+                        .field("value", variable.value.as_ref().ok())
+                        .field("error", variable.value.as_ref().err())
+                })
+                .collect();
+            let kind = if *arguments { "arguments" } else { "locals" };
+            JsonObject::new("variables")
+                .field("kind", kind)
+                .field("variables", variables)
+                .finish()
+        }
+        Event::Display {
+            index,
+            expression,
+            value,
+        } => JsonObject::new("display")
+            .field("index", index)
+            .field("expression", *expression)
+            .field("hex", value.as_ref().ok().map(|value| Hex(*value as _)))
+            .field("value", value.as_ref().ok())
+            .field("error", value.as_ref().err())
+            .finish(),
+        Event::Auxv(entries) => {
+            let entries: Vec<JsonObject> = entries
+                .iter()
+                .map(|entry| {
+                    JsonObject::object()
+                        .field("key", entry.key)
+                        .field("name", entry.name)
+                        .field("value", entry.value)
+                        .field("string", entry.string.as_ref())
+                })
+                .collect();
+            JsonObject::new("auxv").field("entries", entries).finish()
+        }
+        Event::Displays(displays) => {
+            let displays: Vec<JsonObject> = displays
+                .iter()
+                .map(|(index, expression)| {
+                    JsonObject::object()
+                        .field("index", index)
+                        .field("expression", expression)
+                })
+                .collect();
+            JsonObject::new("displays")
+                .field("displays", displays)
+                .finish()
+        }
+        Event::ProbeHit {
+            index,
+            hits,
+            expression,
+            value,
+        } => JsonObject::new("probe")
+            .field("index", index)
+            .field("hits", hits)
+            .field("expression", *expression)
+            .field("hex", Hex(*value as _))
+            .field("value", value)
+            .finish(),
+        Event::RegisterChanged { name, old, new } => JsonObject::new("register_changed")
+            .field("register", *name)
+            .field("old", Hex(*old as _))
+            .field("new", Hex(*new as _))
+            .finish(),
+        Event::ChangedRegisters(changed) => {
+            let registers: Vec<JsonObject> = changed
+                .iter()
+                .map(|(name, old, new)| {
+                    JsonObject::object()
+                        .field("name", *name)
+                        .field("old", Hex(*old as _))
+                        .field("new", Hex(*new as _))
+                })
+                .collect();
+            JsonObject::new("changed_registers")
+                .field("registers", registers)
+                .finish()
+        }
+        Event::Dumped {
+            path,
+            addr,
+            len,
+            requested,
+        } => JsonObject::new("dump")
+            .field("path", *path)
+            .field("addr", Hex(*addr as _))
+            .field("length", len)
+            .field("requested", requested)
+            .finish(),
+        Event::Restored {
+            path,
+            addr,
+            len,
+            requested,
+        } => JsonObject::new("restore")
+            .field("path", *path)
+            .field("addr", Hex(*addr as _))
+            .field("length", len)
+            .field("requested", requested)
+            .finish(),
+        Event::CoreFile(path) => JsonObject::new("core_file").field("path", *path).finish(),
+        Event::Patched { addr, len } => JsonObject::new("patched")
+            .field("addr", Hex(*addr as _))
+            .field("length", len)
+            .finish(),
+        Event::Unpatched { addr, len } => JsonObject::new("unpatched")
+            .field("addr", Hex(*addr as _))
+            .field("length", len)
+            .finish(),
+        Event::SearchMatches(matches) => {
+            let matches: Vec<Hex> = matches.iter().map(|addr| Hex(*addr as _)).collect();
+            JsonObject::new("search").field("matches", matches).finish()
+        }
+        Event::InstructionCount {
+            count,
+            reached,
+            hottest,
+        } => {
+            let hottest: Vec<JsonObject> = hottest
+                .iter()
+                .map(|(addr, symbol, hits)| {
+                    JsonObject::object()
+                        .field("addr", Hex(*addr as _))
+                        .field("name", symbol.map(|(name, _)| name))
+                        .field("offset", symbol.map(|(_, offset)| offset))
+                        .field("count", hits)
+                })
+                .collect();
+            JsonObject::new("instruction_count")
+                .field("count", count)
+                .field("reached", reached)
+                .field("hottest", hottest)
+                .finish()
+        }
+        Event::Memory {
+            addr, size, values, ..
+        } => JsonObject::new("memory")
+            .field("addr", Hex(*addr as _))
+            .field("size", size)
+            .field("values", values)
+            .finish(),
+        Event::Stack { sp, words } => {
+            let words: Vec<JsonObject> = words
+                .iter()
+                .map(|word| {
+                    JsonObject::object()
+                        .field("addr", Hex(word.addr as _))
+                        .field("value", Hex(word.value as _))
+                        .field("symbol", word.symbol.map(|(name, _)| name))
+                        .field("offset", word.symbol.map(|(_, offset)| offset))
+                        .field("return_address", word.return_address)
+                })
+                .collect();
+            JsonObject::new("stack")
+                .field("sp", Hex(*sp as _))
+                .field("words", words)
+                .finish()
+        }
+        Event::Strings(strings) => {
+            let strings: Vec<JsonObject> = strings
+                .iter()
+                .map(|(addr, string)| {
+                    JsonObject::object()
+                        .field("addr", Hex(*addr as _))
+                        .field("value", string.as_str())
+                })
+                .collect();
+            JsonObject::new("strings")
+                .field("strings", strings)
+                .finish()
+        }
+        Event::Instructions {
+            instructions,
+            current,
+        } => {
+            let instructions: Vec<JsonObject> = instructions
+                .iter()
+                .map(|instruction| {
+                    JsonObject::object()
+                        .field("addr", Hex(instruction.addr as _))
+                        .field("len", instruction.len)
+                        .field("text", instruction.to_string())
+                        .field("current", instruction.addr == *current)
+                })
+                .collect();
+            JsonObject::new("instructions")
+                .field("instructions", instructions)
+                .finish()
+        }
+        Event::Backtrace {
+            frames,
+            corrupted,
+            truncated,
+        } => {
+            let frames: Vec<JsonObject> = frames
+                .iter()
+                .map(|frame| {
+                    let arguments = frame.arguments.as_ref().map(|arguments| {
+                        arguments
+                            .iter()
+                            .map(|(name, value)| {
+                                JsonObject::object()
+                                    .field("name", name)
+                                    .field("value", value)
+                            })
+                            .collect::<Vec<_>>()
+                    });
+                    JsonObject::object()
+                        .field("pc", Hex(frame.pc as _))
+                        .field("function", frame.function.as_deref())
+                        .field("arguments", arguments)
+                        .field(
+                            "file",
+                            frame.location.as_ref().map(|location| &location.file),
+                        )
+                        .field(
+                            "line",
+                            frame.location.as_ref().map(|location| location.line),
+                        )
+                })
+                .collect();
+            JsonObject::new("backtrace")
+                .field("frames", frames)
+                .field("corrupted", corrupted)
+                .field("truncated", truncated)
+                .finish()
+        }
+        Event::FrameInfo {
+            frame,
+            cfa,
+            return_addr,
+            rbp,
+            rsp,
+            saved_rbp,
+            locals,
+        } => JsonObject::new("frame")
+            .field("cfa", Hex(*cfa as _))
+            .field("pc", Hex(frame.pc as _))
+            .field("function", frame.function.as_deref())
+            .field(
+                "file",
+                frame.location.as_ref().map(|location| &location.file),
+            )
+            .field(
+                "line",
+                frame.location.as_ref().map(|location| location.line),
+            )
+            .field("return_address", return_addr.map(|addr| Hex(addr as _)))
+            .field("rbp", Hex(*rbp as _))
+            .field("rsp", Hex(*rsp as _))
+            .field("saved_rbp", saved_rbp.map(|addr| Hex(addr as _)))
+            .field("locals_start", locals.map(|(start, _)| Hex(start as _)))
+            .field("locals_end", locals.map(|(_, end)| Hex(end as _)))
+            .finish(),
+    }
+}
+
+/// The name of the ptrace event `code`
+fn ptrace_event_name(code: i32) -> Option<&'static str> {
+    use ptrace::Event::*;
+    [
+        (PTRACE_EVENT_FORK, "PTRACE_EVENT_FORK"),
+        (PTRACE_EVENT_VFORK, "PTRACE_EVENT_VFORK"),
+        (PTRACE_EVENT_CLONE, "PTRACE_EVENT_CLONE"),
+        (PTRACE_EVENT_EXEC, "PTRACE_EVENT_EXEC"),
+        (PTRACE_EVENT_VFORK_DONE, "PTRACE_EVENT_VFORK_DONE"),
+        (PTRACE_EVENT_EXIT, "PTRACE_EVENT_EXIT"),
+        (PTRACE_EVENT_SECCOMP, "PTRACE_EVENT_SECCOMP"),
+        (PTRACE_EVENT_STOP, "PTRACE_EVENT_STOP"),
+    ]
+    .into_iter()
+    .find(|&(event, _)| event as i32 == code)
+    .map(|(_, name)| name)
+}
+
+/// Interprets the bits of a SSE register as packed doubles and as packed floats
+fn xmm_floats(value: u128) -> ([f64; 2], [f32; 4]) {
+    let f64s = [0, 1].map(|lane| f64::from_bits((value >> (64 * lane)) as u64));
+    let f32s = [0, 1, 2, 3].map(|lane| f32::from_bits((value >> (32 * lane)) as u32));
+    (f64s, f32s)
+}
+
+/// Splits the little endian `bytes` of a vector register in elements of `size` bytes, from the lowest
+fn vector_elements(bytes: &[u8], size: usize) -> Vec<u64> {
+    bytes
+        .chunks(size)
+        .map(|element| {
+            let mut value = [0; 8];
+            value[..element.len()].copy_from_slice(element);
+            u64::from_le_bytes(value)
+        })
+        .collect()
+}
+
+/// A value written as a JSON value
+trait ToJson {
+    fn to_json(&self) -> String;
+}
+
+/// A number written as an hexadecimal string, like addresses
+struct Hex(u128);
+
+impl ToJson for Hex {
+    fn to_json(&self) -> String {
+        format!("\"{:#x}\"", self.0)
+    }
+}
+
+impl ToJson for str {
+    fn to_json(&self) -> String {
+        let mut json = String::from("\"");
+        for c in self.chars() {
+            match c {
+                '"' => json.push_str("\\\""),
+                '\\' => json.push_str("\\\\"),
+                '\n' => json.push_str("\\n"),
+                c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+                c => json.push(c),
+            }
+        }
+        json.push('"');
+        json
+    }
+}
+
+impl ToJson for String {
+    fn to_json(&self) -> String {
+        self.as_str().to_json()
+    }
+}
+
+macro_rules! float_to_json {
+    ($($float:ty),*) => {
+        $(impl ToJson for $float {
+            /// JSON has no representation for infinite and NaN values, they are written as `null`
+            fn to_json(&self) -> String {
+                if self.is_finite() {
+                    format!("{self:?}")
+                } else {
+                    String::from("null")
+                }
+            }
+        })*
+    };
+}
+
+float_to_json!(f32, f64);
+
+impl ToJson for bool {
+    fn to_json(&self) -> String {
+        self.to_string()
+    }
+}
+
+macro_rules! number_to_json {
+    ($($number:ty),*) => {
+        $(impl ToJson for $number {
+            fn to_json(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+number_to_json!(i32, i64, u64, usize);
+
+impl<T: ToJson + ?Sized> ToJson for &T {
+    fn to_json(&self) -> String {
+        (**self).to_json()
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn to_json(&self) -> String {
+        match self {
+            Some(value) => value.to_json(),
+            None => String::from("null"),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn to_json(&self) -> String {
+        let values: Vec<String> = self.iter().map(|value| value.to_json()).collect();
+        format!("[{}]", values.join(","))
+    }
+}
+
+/// A JSON object built field by field
+struct JsonObject {
+    fields: Vec<String>,
+}
+
+impl JsonObject {
+    /// Creates an object without fields
+    fn object() -> Self {
+        Self { fields: Vec::new() }
+    }
+
+    /// Creates the object describing an event, its `event` field is the kind of the event
+    fn new(event: &str) -> Self {
+        Self::object().field("event", event)
+    }
+
+    fn field(mut self, name: &str, value: impl ToJson) -> Self {
+        self.fields
+            .push(format!("{}:{}", name.to_json(), value.to_json()));
+        self
+    }
+
+    fn finish(self) -> String {
+        self.to_json()
+    }
+}
+
+impl ToJson for JsonObject {
+    fn to_json(&self) -> String {
+        format!("{{{}}}", self.fields.join(","))
+    }
+}
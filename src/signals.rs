@@ -0,0 +1,116 @@
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::{
+    libc::c_int,
+    sys::signal::{SaFlags, SigAction, SigHandler, SigSet, Signal, sigaction},
+};
+
+/// Set when a Ctrl-C is caught by `CtrlC`
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// What the debugger does when the program receives a signal
+#[derive(Clone, Copy)]
+pub struct SignalHandling {
+    /// The program stops and the debugger waits for commands
+    pub stop: bool,
+    /// The debugger tells the user about the signal
+    pub print: bool,
+    /// The signal is delivered to the program when it is resumed
+    pub pass: bool,
+}
+
+impl SignalHandling {
+    /// The handling of `signal` until it is changed by `handle`
+    ///
+    /// The signals that programs commonly receive during their normal execution are passed silently.
+    pub fn default(signal: Signal) -> Self {
+        match signal {
+            Signal::SIGALRM
+            | Signal::SIGURG
+            | Signal::SIGCHLD
+            | Signal::SIGWINCH
+            | Signal::SIGIO
+            | Signal::SIGVTALRM
+            | Signal::SIGPROF => Self {
+                stop: false,
+                print: false,
+                pass: true,
+            },
+            // The debugger uses SIGSTOP to stop the threads, and delivering it would stop the program again
+            Signal::SIGINT | Signal::SIGSTOP => Self {
+                stop: true,
+                print: true,
+                pass: false,
+            },
+            _ => Self {
+                stop: true,
+                print: true,
+                pass: true,
+            },
+        }
+    }
+
+    /// Applies the action `action` of the `handle` command, returns `None` if it is unknown
+    ///
+    /// Like in GDB, stopping implies printing and not printing implies not stopping.
+    pub fn apply(&mut self, action: &str) -> Option<()> {
+        match action {
+            "stop" => (self.stop, self.print) = (true, true),
+            "nostop" => self.stop = false,
+            "print" => self.print = true,
+            "noprint" => (self.stop, self.print) = (false, false),
+            "pass" => self.pass = true,
+            "nopass" => self.pass = false,
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+/// Parses a signal name, with or without the `SIG` prefix (`SIGUSR1` or `USR1`)
+pub fn parse(name: &str) -> Option<Signal> {
+    let name = name.to_ascii_uppercase();
+    match name.starts_with("SIG") {
+        true => Signal::from_str(&name).ok(),
+        false => Signal::from_str(&format!("SIG{name}")).ok(),
+    }
+}
+
+/// Catches the Ctrl-C of the user instead of terminating the debugger, as long as it lives
+///
+/// The handler doesn't restart the interrupted syscalls, so that a blocking `waitpid` fails with `EINTR`.
+/// The previous handler is restored when it is dropped.
+pub struct CtrlC {
+    previous: SigAction,
+}
+
+impl CtrlC {
+    pub fn catch() -> Self {
+        INTERRUPTED.store(false, Ordering::Relaxed);
+        let action = SigAction::new(
+            SigHandler::Handler(interrupt),
+            SaFlags::empty(),
+            SigSet::empty(),
+        );
+        let previous = unsafe { sigaction(Signal::SIGINT, &action) }.unwrap();
+        Self { previous }
+    }
+
+    /// Checks if a Ctrl-C was caught since the last call
+    pub fn interrupted(&self) -> bool {
+        INTERRUPTED.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl Drop for CtrlC {
+    fn drop(&mut self) {
+        unsafe { sigaction(Signal::SIGINT, &self.previous) }.unwrap();
+    }
+}
+
+extern "C" fn interrupt(_: c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
@@ -0,0 +1,15 @@
+use std::fs;
+
+/// Reads the lines `first` to `last` of the source file at `path`, with their number
+///
+/// Lines are numbered from 1 and the lines past the end of the file are ignored.
+pub fn read_lines(path: &str, first: u64, last: u64) -> Option<Vec<(u64, String)>> {
+    let source = fs::read_to_string(path).ok()?;
+    let lines = (1..)
+        .zip(source.lines())
+        .skip_while(|(number, _)| *number < first)
+        .take_while(|(number, _)| *number <= last)
+        .map(|(number, line)| (number, line.to_owned()))
+        .collect();
+    Some(lines)
+}
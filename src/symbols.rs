@@ -0,0 +1,234 @@
+use std::{
+    borrow::Cow,
+    fs,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use nix::unistd::Pid;
+use object::{Object, ObjectKind, ObjectSection, ObjectSymbol, SymbolKind};
+
+use crate::{auxv::entry_and_program_headers, maps::read_maps, unwind::CallFrameInfo};
+
+/// Whether the names of the symbols are printed demangled, changed by `set demangle`
+static DEMANGLE: AtomicBool = AtomicBool::new(true);
+
+/// Prints the names of the symbols demangled if `demangle` is set, or as found in the symbol tables
+pub fn set_demangle(demangle: bool) {
+    DEMANGLE.store(demangle, Ordering::Relaxed);
+}
+
+/// A named location read from an ELF symbol table
+pub struct Symbol {
+    /// The name as found in the symbol table, mangled for Rust and C++ symbols
+    pub name: String,
+    pub addr: usize,
+    pub size: usize,
+    /// Whether the symbol designates code or data
+    pub kind: SymbolKind,
+}
+
+impl Symbol {
+    /// The name of the symbol as printed to the user
+    ///
+    /// Rust and C++ names are demangled, like `foo::bar` for `_ZN3foo3barE`, unless demangling is disabled.
+    /// The other names are kept as is.
+    pub fn display_name(&self) -> Cow<'_, str> {
+        if !DEMANGLE.load(Ordering::Relaxed) {
+            return Cow::Borrowed(&self.name);
+        }
+        // Legacy Rust names are valid C++ names, with the hash of the crate as last component
+        if let Ok(demangled) = rustc_demangle::try_demangle(&self.name) {
+            return Cow::Owned(format!("{demangled:#}"));
+        }
+        // A name like `f` is also the mangling of a type, `float`, only the mangled names start with `_Z`
+        if !self.name.starts_with("_Z") {
+            return Cow::Borrowed(&self.name);
+        }
+        cpp_demangle::Symbol::new(self.name.as_bytes())
+            .ok()
+            .and_then(|symbol| symbol.demangle().ok())
+            .map_or(Cow::Borrowed(&self.name), Cow::Owned)
+    }
+}
+
+/// A shared library loaded by the traced program, with its symbols relocated to its load address
+pub struct SharedLibrary {
+    pub path: String,
+    pub symbols: SymbolTable,
+    pub call_frame_info: Option<CallFrameInfo>,
+}
+
+impl SharedLibrary {
+    /// Lists the paths of the shared libraries mapped in the memory of `pid` with their load bias
+    pub fn mapped(pid: Pid) -> Vec<(String, usize)> {
+        let mut libraries: Vec<(String, usize)> = Vec::new();
+        for map in read_maps(pid).unwrap_or_default() {
+            let Some(path) = map.path else {
+                continue;
+            };
+            let name = Path::new(&path).file_name().unwrap_or_default();
+            // Like `libc.so.6`, but not `ld.so.cache`
+            let library = name
+                .to_string_lossy()
+                .split_once(".so")
+                .is_some_and(|(_, version)| {
+                    version.is_empty()
+                        || version.strip_prefix('.').is_some_and(|version| {
+                            version
+                                .split('.')
+                                .all(|number| number.parse::<u32>().is_ok())
+                        })
+                });
+            if !library || libraries.iter().any(|(library, _)| *library == path) {
+                continue;
+            }
+            libraries.push((path, map.start - map.offset));
+        }
+        libraries
+    }
+
+    /// Reads the symbols of the library at `path` loaded with the load bias `bias`
+    pub fn load(path: String, bias: usize) -> Option<SharedLibrary> {
+        let mut symbols = SymbolTable::load(Path::new(&path))?;
+        symbols.bias = bias;
+        let mut call_frame_info = CallFrameInfo::load(Path::new(&path));
+        if let Some(call_frame_info) = call_frame_info.as_mut() {
+            call_frame_info.relocate(symbols.bias);
+        }
+        Some(SharedLibrary {
+            path,
+            symbols,
+            call_frame_info,
+        })
+    }
+
+    /// Checks if `name` designates the library, either by its file name (`libc.so.6`) or without the extension (`libc`)
+    pub fn matches(&self, name: &str) -> bool {
+        let file_name = Path::new(&self.path)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        file_name == name || file_name.starts_with(&format!("{name}.so"))
+    }
+}
+
+/// The symbols of an ELF executable
+///
+/// Addresses are stored as found in the file, the load bias is added when looking them up
+/// so that position independent executables resolve to their runtime address.
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+    entry: usize,
+    /// The address of the dynamic section, `None` for a statically linked executable
+    dynamic: Option<usize>,
+    position_independent: bool,
+    bias: usize,
+}
+
+impl SymbolTable {
+    /// Reads the `.symtab` and `.dynsym` tables of the ELF file at `path`
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        let symbols = file
+            .symbols()
+            .chain(file.dynamic_symbols())
+            .filter(|symbol| symbol.is_definition())
+            .filter_map(|symbol| {
+                Some(Symbol {
+                    name: symbol.name().ok()?.to_owned(),
+                    addr: symbol.address() as _,
+                    size: symbol.size() as _,
+                    kind: symbol.kind(),
+                })
+            })
+            .collect();
+
+        Some(Self {
+            symbols,
+            entry: file.entry() as _,
+            dynamic: file
+                .section_by_name(".dynamic")
+                .map(|section| section.address() as _),
+            position_independent: file.kind() == ObjectKind::Dynamic,
+            bias: 0,
+        })
+    }
+
+    /// Computes the load bias of the executable traced by `pid`
+    ///
+    /// The bias is the address the executable's first mapping has been loaded at.
+    /// When the mapping can't be found, it is computed from the entry point given by the kernel in the auxiliary vector.
+    /// It is always 0 for executables that are not position independent.
+    /// Returns the computed bias, the bias is reset to 0 if it cannot be computed
+    /// so that the bias of a previous run is never used.
+    pub fn relocate(&mut self, pid: Pid) -> Option<usize> {
+        self.bias = 0;
+        if !self.position_independent {
+            return Some(0);
+        }
+        let mapped = || {
+            let exe = fs::read_link(format!("/proc/{pid}/exe")).ok()?;
+            let exe = exe.to_str()?;
+            let map = read_maps(pid)?
+                .into_iter()
+                .find(|map| map.path.as_deref() == Some(exe))?;
+            Some(map.start - map.offset)
+        };
+        self.bias = mapped().or_else(|| {
+            let (entry, _) = entry_and_program_headers(pid)?;
+            entry.checked_sub(self.entry)
+        })?;
+        Some(self.bias)
+    }
+
+    /// Checks if the executable is loaded at an address known only once it is run
+    pub fn is_position_independent(&self) -> bool {
+        self.position_independent
+    }
+
+    /// Returns the runtime address of the entry point of the executable
+    pub fn entry(&self) -> usize {
+        self.entry + self.bias
+    }
+
+    /// Returns the runtime address of the dynamic section, where the dynamic linker finds `r_debug`
+    pub fn dynamic(&self) -> Option<usize> {
+        self.dynamic.map(|dynamic| dynamic + self.bias)
+    }
+
+    /// Returns the runtime address of the symbol `name`
+    pub fn lookup(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.addr + self.bias)
+    }
+
+    /// Returns the function symbols with their runtime address
+    pub fn functions(&self) -> impl Iterator<Item = (&Symbol, usize)> {
+        self.symbols_of_kind(SymbolKind::Text)
+    }
+
+    /// Returns the variable symbols with their runtime address
+    pub fn variables(&self) -> impl Iterator<Item = (&Symbol, usize)> {
+        self.symbols_of_kind(SymbolKind::Data)
+    }
+
+    fn symbols_of_kind(&self, kind: SymbolKind) -> impl Iterator<Item = (&Symbol, usize)> {
+        self.symbols
+            .iter()
+            .filter(move |symbol| symbol.kind == kind)
+            .map(|symbol| (symbol, symbol.addr + self.bias))
+    }
+
+    /// Returns the symbol whose bytes contain the runtime address `addr`, with its runtime address
+    pub fn symbol_at(&self, addr: usize) -> Option<(&Symbol, usize)> {
+        let addr = addr.checked_sub(self.bias)?;
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.addr <= addr && addr < symbol.addr + symbol.size)
+            .map(|symbol| (symbol, symbol.addr + self.bias))
+    }
+}
@@ -0,0 +1,630 @@
+use nix::{
+    errno::Errno,
+    libc::{self, user_regs_struct},
+    unistd::Pid,
+};
+
+use crate::utils::{format_string, read_cstring, read_data};
+
+/// The names of the x86_64 system calls, by number
+const SYSCALLS: [(u64, &str); 362] = [
+    (0, "read"),
+    (1, "write"),
+    (2, "open"),
+    (3, "close"),
+    (4, "stat"),
+    (5, "fstat"),
+    (6, "lstat"),
+    (7, "poll"),
+    (8, "lseek"),
+    (9, "mmap"),
+    (10, "mprotect"),
+    (11, "munmap"),
+    (12, "brk"),
+    (13, "rt_sigaction"),
+    (14, "rt_sigprocmask"),
+    (15, "rt_sigreturn"),
+    (16, "ioctl"),
+    (17, "pread64"),
+    (18, "pwrite64"),
+    (19, "readv"),
+    (20, "writev"),
+    (21, "access"),
+    (22, "pipe"),
+    (23, "select"),
+    (24, "sched_yield"),
+    (25, "mremap"),
+    (26, "msync"),
+    (27, "mincore"),
+    (28, "madvise"),
+    (29, "shmget"),
+    (30, "shmat"),
+    (31, "shmctl"),
+    (32, "dup"),
+    (33, "dup2"),
+    (34, "pause"),
+    (35, "nanosleep"),
+    (36, "getitimer"),
+    (37, "alarm"),
+    (38, "setitimer"),
+    (39, "getpid"),
+    (40, "sendfile"),
+    (41, "socket"),
+    (42, "connect"),
+    (43, "accept"),
+    (44, "sendto"),
+    (45, "recvfrom"),
+    (46, "sendmsg"),
+    (47, "recvmsg"),
+    (48, "shutdown"),
+    (49, "bind"),
+    (50, "listen"),
+    (51, "getsockname"),
+    (52, "getpeername"),
+    (53, "socketpair"),
+    (54, "setsockopt"),
+    (55, "getsockopt"),
+    (56, "clone"),
+    (57, "fork"),
+    (58, "vfork"),
+    (59, "execve"),
+    (60, "exit"),
+    (61, "wait4"),
+    (62, "kill"),
+    (63, "uname"),
+    (64, "semget"),
+    (65, "semop"),
+    (66, "semctl"),
+    (67, "shmdt"),
+    (68, "msgget"),
+    (69, "msgsnd"),
+    (70, "msgrcv"),
+    (71, "msgctl"),
+    (72, "fcntl"),
+    (73, "flock"),
+    (74, "fsync"),
+    (75, "fdatasync"),
+    (76, "truncate"),
+    (77, "ftruncate"),
+    (78, "getdents"),
+    (79, "getcwd"),
+    (80, "chdir"),
+    (81, "fchdir"),
+    (82, "rename"),
+    (83, "mkdir"),
+    (84, "rmdir"),
+    (85, "creat"),
+    (86, "link"),
+    (87, "unlink"),
+    (88, "symlink"),
+    (89, "readlink"),
+    (90, "chmod"),
+    (91, "fchmod"),
+    (92, "chown"),
+    (93, "fchown"),
+    (94, "lchown"),
+    (95, "umask"),
+    (96, "gettimeofday"),
+    (97, "getrlimit"),
+    (98, "getrusage"),
+    (99, "sysinfo"),
+    (100, "times"),
+    (101, "ptrace"),
+    (102, "getuid"),
+    (103, "syslog"),
+    (104, "getgid"),
+    (105, "setuid"),
+    (106, "setgid"),
+    (107, "geteuid"),
+    (108, "getegid"),
+    (109, "setpgid"),
+    (110, "getppid"),
+    (111, "getpgrp"),
+    (112, "setsid"),
+    (113, "setreuid"),
+    (114, "setregid"),
+    (115, "getgroups"),
+    (116, "setgroups"),
+    (117, "setresuid"),
+    (118, "getresuid"),
+    (119, "setresgid"),
+    (120, "getresgid"),
+    (121, "getpgid"),
+    (122, "setfsuid"),
+    (123, "setfsgid"),
+    (124, "getsid"),
+    (125, "capget"),
+    (126, "capset"),
+    (127, "rt_sigpending"),
+    (128, "rt_sigtimedwait"),
+    (129, "rt_sigqueueinfo"),
+    (130, "rt_sigsuspend"),
+    (131, "sigaltstack"),
+    (132, "utime"),
+    (133, "mknod"),
+    (134, "uselib"),
+    (135, "personality"),
+    (136, "ustat"),
+    (137, "statfs"),
+    (138, "fstatfs"),
+    (139, "sysfs"),
+    (140, "getpriority"),
+    (141, "setpriority"),
+    (142, "sched_setparam"),
+    (143, "sched_getparam"),
+    (144, "sched_setscheduler"),
+    (145, "sched_getscheduler"),
+    (146, "sched_get_priority_max"),
+    (147, "sched_get_priority_min"),
+    (148, "sched_rr_get_interval"),
+    (149, "mlock"),
+    (150, "munlock"),
+    (151, "mlockall"),
+    (152, "munlockall"),
+    (153, "vhangup"),
+    (154, "modify_ldt"),
+    (155, "pivot_root"),
+    (156, "_sysctl"),
+    (157, "prctl"),
+    (158, "arch_prctl"),
+    (159, "adjtimex"),
+    (160, "setrlimit"),
+    (161, "chroot"),
+    (162, "sync"),
+    (163, "acct"),
+    (164, "settimeofday"),
+    (165, "mount"),
+    (166, "umount2"),
+    (167, "swapon"),
+    (168, "swapoff"),
+    (169, "reboot"),
+    (170, "sethostname"),
+    (171, "setdomainname"),
+    (172, "iopl"),
+    (173, "ioperm"),
+    (174, "create_module"),
+    (175, "init_module"),
+    (176, "delete_module"),
+    (177, "get_kernel_syms"),
+    (178, "query_module"),
+    (179, "quotactl"),
+    (180, "nfsservctl"),
+    (181, "getpmsg"),
+    (182, "putpmsg"),
+    (183, "afs_syscall"),
+    (184, "tuxcall"),
+    (185, "security"),
+    (186, "gettid"),
+    (187, "readahead"),
+    (188, "setxattr"),
+    (189, "lsetxattr"),
+    (190, "fsetxattr"),
+    (191, "getxattr"),
+    (192, "lgetxattr"),
+    (193, "fgetxattr"),
+    (194, "listxattr"),
+    (195, "llistxattr"),
+    (196, "flistxattr"),
+    (197, "removexattr"),
+    (198, "lremovexattr"),
+    (199, "fremovexattr"),
+    (200, "tkill"),
+    (201, "time"),
+    (202, "futex"),
+    (203, "sched_setaffinity"),
+    (204, "sched_getaffinity"),
+    (205, "set_thread_area"),
+    (206, "io_setup"),
+    (207, "io_destroy"),
+    (208, "io_getevents"),
+    (209, "io_submit"),
+    (210, "io_cancel"),
+    (211, "get_thread_area"),
+    (212, "lookup_dcookie"),
+    (213, "epoll_create"),
+    (214, "epoll_ctl_old"),
+    (215, "epoll_wait_old"),
+    (216, "remap_file_pages"),
+    (217, "getdents64"),
+    (218, "set_tid_address"),
+    (219, "restart_syscall"),
+    (220, "semtimedop"),
+    (221, "fadvise64"),
+    (222, "timer_create"),
+    (223, "timer_settime"),
+    (224, "timer_gettime"),
+    (225, "timer_getoverrun"),
+    (226, "timer_delete"),
+    (227, "clock_settime"),
+    (228, "clock_gettime"),
+    (229, "clock_getres"),
+    (230, "clock_nanosleep"),
+    (231, "exit_group"),
+    (232, "epoll_wait"),
+    (233, "epoll_ctl"),
+    (234, "tgkill"),
+    (235, "utimes"),
+    (236, "vserver"),
+    (237, "mbind"),
+    (238, "set_mempolicy"),
+    (239, "get_mempolicy"),
+    (240, "mq_open"),
+    (241, "mq_unlink"),
+    (242, "mq_timedsend"),
+    (243, "mq_timedreceive"),
+    (244, "mq_notify"),
+    (245, "mq_getsetattr"),
+    (246, "kexec_load"),
+    (247, "waitid"),
+    (248, "add_key"),
+    (249, "request_key"),
+    (250, "keyctl"),
+    (251, "ioprio_set"),
+    (252, "ioprio_get"),
+    (253, "inotify_init"),
+    (254, "inotify_add_watch"),
+    (255, "inotify_rm_watch"),
+    (256, "migrate_pages"),
+    (257, "openat"),
+    (258, "mkdirat"),
+    (259, "mknodat"),
+    (260, "fchownat"),
+    (261, "futimesat"),
+    (262, "newfstatat"),
+    (263, "unlinkat"),
+    (264, "renameat"),
+    (265, "linkat"),
+    (266, "symlinkat"),
+    (267, "readlinkat"),
+    (268, "fchmodat"),
+    (269, "faccessat"),
+    (270, "pselect6"),
+    (271, "ppoll"),
+    (272, "unshare"),
+    (273, "set_robust_list"),
+    (274, "get_robust_list"),
+    (275, "splice"),
+    (276, "tee"),
+    (277, "sync_file_range"),
+    (278, "vmsplice"),
+    (279, "move_pages"),
+    (280, "utimensat"),
+    (281, "epoll_pwait"),
+    (282, "signalfd"),
+    (283, "timerfd_create"),
+    (284, "eventfd"),
+    (285, "fallocate"),
+    (286, "timerfd_settime"),
+    (287, "timerfd_gettime"),
+    (288, "accept4"),
+    (289, "signalfd4"),
+    (290, "eventfd2"),
+    (291, "epoll_create1"),
+    (292, "dup3"),
+    (293, "pipe2"),
+    (294, "inotify_init1"),
+    (295, "preadv"),
+    (296, "pwritev"),
+    (297, "rt_tgsigqueueinfo"),
+    (298, "perf_event_open"),
+    (299, "recvmmsg"),
+    (300, "fanotify_init"),
+    (301, "fanotify_mark"),
+    (302, "prlimit64"),
+    (303, "name_to_handle_at"),
+    (304, "open_by_handle_at"),
+    (305, "clock_adjtime"),
+    (306, "syncfs"),
+    (307, "sendmmsg"),
+    (308, "setns"),
+    (309, "getcpu"),
+    (310, "process_vm_readv"),
+    (311, "process_vm_writev"),
+    (312, "kcmp"),
+    (313, "finit_module"),
+    (314, "sched_setattr"),
+    (315, "sched_getattr"),
+    (316, "renameat2"),
+    (317, "seccomp"),
+    (318, "getrandom"),
+    (319, "memfd_create"),
+    (320, "kexec_file_load"),
+    (321, "bpf"),
+    (322, "execveat"),
+    (323, "userfaultfd"),
+    (324, "membarrier"),
+    (325, "mlock2"),
+    (326, "copy_file_range"),
+    (327, "preadv2"),
+    (328, "pwritev2"),
+    (329, "pkey_mprotect"),
+    (330, "pkey_alloc"),
+    (331, "pkey_free"),
+    (332, "statx"),
+    (333, "io_pgetevents"),
+    (334, "rseq"),
+    (424, "pidfd_send_signal"),
+    (425, "io_uring_setup"),
+    (426, "io_uring_enter"),
+    (427, "io_uring_register"),
+    (428, "open_tree"),
+    (429, "move_mount"),
+    (430, "fsopen"),
+    (431, "fsconfig"),
+    (432, "fsmount"),
+    (433, "fspick"),
+    (434, "pidfd_open"),
+    (435, "clone3"),
+    (436, "close_range"),
+    (437, "openat2"),
+    (438, "pidfd_getfd"),
+    (439, "faccessat2"),
+    (440, "process_madvise"),
+    (441, "epoll_pwait2"),
+    (442, "mount_setattr"),
+    (443, "quotactl_fd"),
+    (444, "landlock_create_ruleset"),
+    (445, "landlock_add_rule"),
+    (446, "landlock_restrict_self"),
+    (447, "memfd_secret"),
+    (448, "process_mrelease"),
+    (449, "futex_waitv"),
+    (450, "set_mempolicy_home_node"),
+];
+
+/// How an argument of a syscall is printed by `format_call`
+#[derive(Clone, Copy)]
+enum Arg {
+    /// A file descriptor
+    Fd,
+    /// A length or an offset
+    Int,
+    /// An address or a number printed in hexadecimal
+    Hex,
+    /// A directory file descriptor, which may be `AT_FDCWD`
+    DirFd,
+    /// A null-terminated string, like a path
+    Path,
+    /// A buffer whose length is the argument `len`, printed if the program is writing it
+    Input { len: usize },
+    /// A buffer filled by the syscall, whose length is the return value, printed once it returned
+    Output,
+    /// The flags of `open`
+    OpenFlags,
+    /// The permissions of a created file, printed if the argument `flags` creates it
+    Mode { flags: usize },
+    /// The memory protection of `mmap`
+    Protection,
+    /// The flags of `mmap`
+    MapFlags,
+}
+
+/// The arguments of the syscalls decoded by `format_call`, by number
+const ARGS: [(u64, &[Arg]); 5] = [
+    (0, &[Arg::Fd, Arg::Output, Arg::Int]),
+    (1, &[Arg::Fd, Arg::Input { len: 2 }, Arg::Int]),
+    (2, &[Arg::Path, Arg::OpenFlags, Arg::Mode { flags: 1 }]),
+    (
+        9,
+        &[
+            Arg::Hex,
+            Arg::Int,
+            Arg::Protection,
+            Arg::MapFlags,
+            Arg::Fd,
+            Arg::Hex,
+        ],
+    ),
+    (
+        257,
+        &[
+            Arg::DirFd,
+            Arg::Path,
+            Arg::OpenFlags,
+            Arg::Mode { flags: 2 },
+        ],
+    ),
+];
+
+/// The access modes of `open`, by value
+const ACCESS_MODES: [&str; 3] = ["O_RDONLY", "O_WRONLY", "O_RDWR"];
+
+const OPEN_FLAGS: [(i32, &str); 12] = [
+    (libc::O_CREAT, "O_CREAT"),
+    (libc::O_EXCL, "O_EXCL"),
+    (libc::O_NOCTTY, "O_NOCTTY"),
+    (libc::O_TRUNC, "O_TRUNC"),
+    (libc::O_APPEND, "O_APPEND"),
+    (libc::O_NONBLOCK, "O_NONBLOCK"),
+    (libc::O_DSYNC, "O_DSYNC"),
+    (libc::O_DIRECT, "O_DIRECT"),
+    (libc::O_LARGEFILE, "O_LARGEFILE"),
+    (libc::O_DIRECTORY, "O_DIRECTORY"),
+    (libc::O_NOFOLLOW, "O_NOFOLLOW"),
+    (libc::O_CLOEXEC, "O_CLOEXEC"),
+];
+
+const PROTECTIONS: [(i32, &str); 3] = [
+    (libc::PROT_READ, "PROT_READ"),
+    (libc::PROT_WRITE, "PROT_WRITE"),
+    (libc::PROT_EXEC, "PROT_EXEC"),
+];
+
+const MAP_FLAGS: [(i32, &str); 10] = [
+    (libc::MAP_SHARED, "MAP_SHARED"),
+    (libc::MAP_PRIVATE, "MAP_PRIVATE"),
+    (libc::MAP_FIXED, "MAP_FIXED"),
+    (libc::MAP_ANONYMOUS, "MAP_ANONYMOUS"),
+    (libc::MAP_GROWSDOWN, "MAP_GROWSDOWN"),
+    (libc::MAP_DENYWRITE, "MAP_DENYWRITE"),
+    (libc::MAP_NORESERVE, "MAP_NORESERVE"),
+    (libc::MAP_POPULATE, "MAP_POPULATE"),
+    (libc::MAP_STACK, "MAP_STACK"),
+    (libc::MAP_FIXED_NOREPLACE, "MAP_FIXED_NOREPLACE"),
+];
+
+/// The number of bytes of a buffer printed by `format_call`
+const PREVIEW_LEN: usize = 32;
+
+/// The maximal length of a path read by `format_call`
+const PATH_MAX: usize = 4096;
+
+/// Formats the bits of `value` named in `flags` joined by `|`, followed by the unnamed bits in hexadecimal
+fn format_flags(value: i32, flags: &[(i32, &str)], mut names: Vec<String>) -> String {
+    let mut rest = value;
+    for &(flag, name) in flags {
+        if value & flag == flag {
+            names.push(name.to_owned());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 || names.is_empty() {
+        names.push(format!("{rest:#x}"));
+    }
+    names.join("|")
+}
+
+/// Formats the buffer of `len` bytes at `addr` in the memory of `pid`, or its address if it can't be read
+fn format_buffer(pid: Pid, addr: u64, len: u64) -> String {
+    let preview = (len as usize).min(PREVIEW_LEN);
+    let bytes = read_data(pid, addr as _, preview);
+    if bytes.len() < preview {
+        return format!("{addr:#x}");
+    }
+    format_string(&bytes, len as usize > preview)
+}
+
+/// Formats the argument `index` of the syscall whose arguments are `args`
+///
+/// `value` is the value returned by the syscall, if it returned.
+fn format_arg(args: &[u64; 6], index: usize, arg: Arg, pid: Pid, value: Option<i64>) -> String {
+    let raw = args[index];
+    match arg {
+        Arg::Fd => (raw as i32).to_string(),
+        Arg::Int => (raw as i64).to_string(),
+        Arg::Hex => format!("{raw:#x}"),
+        Arg::DirFd if raw as i32 == libc::AT_FDCWD => "AT_FDCWD".to_owned(),
+        Arg::DirFd => (raw as i32).to_string(),
+        Arg::Path => match read_cstring(pid, raw as _, PATH_MAX) {
+            Some(path) => format_string(&path, path.len() == PATH_MAX),
+            None => format!("{raw:#x}"),
+        },
+        Arg::Input { len } => format_buffer(pid, raw, args[len]),
+        Arg::Output => match value {
+            Some(len) if len >= 0 => format_buffer(pid, raw, len as _),
+            _ => format!("{raw:#x}"),
+        },
+        Arg::OpenFlags => {
+            let mode = raw as i32 & libc::O_ACCMODE;
+            let access = ACCESS_MODES.get(mode as usize).copied();
+            let names = access.map(str::to_owned).into_iter().collect();
+            format_flags(raw as i32 & !libc::O_ACCMODE, &OPEN_FLAGS, names)
+        }
+        Arg::Mode { .. } => format!("0{:o}", raw as u32),
+        Arg::Protection if raw == 0 => "PROT_NONE".to_owned(),
+        Arg::Protection => format_flags(raw as _, &PROTECTIONS, Vec::new()),
+        Arg::MapFlags => format_flags(raw as _, &MAP_FLAGS, Vec::new()),
+    }
+}
+
+/// The system calls the program stops at
+pub enum SyscallCatch {
+    All,
+    Only(u64),
+}
+
+impl SyscallCatch {
+    /// Parses the argument of `catch syscall`, a syscall name or number
+    pub fn parse(arg: Option<&str>) -> Option<Self> {
+        let Some(arg) = arg else {
+            return Some(SyscallCatch::All);
+        };
+        match arg.parse() {
+            Ok(number) => Some(SyscallCatch::Only(number)),
+            Err(_) => Some(SyscallCatch::Only(number(arg)?)),
+        }
+    }
+
+    /// Checks if the program stops at the syscall numbered `number`
+    pub fn matches(&self, number: u64) -> bool {
+        match self {
+            SyscallCatch::All => true,
+            SyscallCatch::Only(only) => *only == number,
+        }
+    }
+}
+
+/// A stop of a thread at a syscall, the entry and the exit being told apart by the previous stop of the thread
+#[derive(Clone, Copy)]
+pub enum SyscallStop {
+    /// The registers hold the number and the arguments of the syscall
+    Entry(user_regs_struct),
+    /// The syscall returned, with the registers at its entry if the thread stopped there
+    Exit(Option<user_regs_struct>),
+}
+
+impl SyscallStop {
+    /// Returns the stop of a thread whose registers are `regs`, after its previous syscall stop `previous`
+    ///
+    /// The exit follows the entry of the same syscall at the same address, unless the thread was not
+    /// stopped at the exit, like when it single steps over the syscall. Otherwise a stop is an entry
+    /// if rax is -ENOSYS, which the kernel sets before executing a syscall.
+    pub fn next(previous: Option<&SyscallStop>, regs: &user_regs_struct) -> Self {
+        match previous {
+            Some(SyscallStop::Entry(entry))
+                if entry.orig_rax == regs.orig_rax && entry.rip == regs.rip =>
+            {
+                SyscallStop::Exit(Some(*entry))
+            }
+            _ if regs.rax as i64 == -(Errno::ENOSYS as i64) => SyscallStop::Entry(*regs),
+            _ => SyscallStop::Exit(None),
+        }
+    }
+}
+
+/// Returns the name of the syscall numbered `number`
+pub fn name(number: u64) -> Option<&'static str> {
+    SYSCALLS
+        .iter()
+        .find(|(nr, _)| *nr == number)
+        .map(|(_, name)| *name)
+}
+
+/// Returns the number of the syscall `name`
+pub fn number(name: &str) -> Option<u64> {
+    SYSCALLS
+        .iter()
+        .find(|(_, nr_name)| *nr_name == name)
+        .map(|(nr, _)| *nr)
+}
+
+/// Formats the syscall of the thread `pid` with its arguments, `regs` being the registers at its entry
+///
+/// The arguments of the common syscalls are decoded, like `openat(AT_FDCWD, "/etc/hosts", O_RDONLY)`,
+/// the buffers they write being printed once they returned `value`.
+/// The number of arguments of the other syscalls is not known, so the six argument registers are printed,
+/// like `dup2(0x3, 0x1, 0x0, 0x0, 0x0, 0x0)`.
+pub fn format_call(regs: &user_regs_struct, pid: Pid, value: Option<i64>) -> String {
+    let args = [regs.rdi, regs.rsi, regs.rdx, regs.r10, regs.r8, regs.r9];
+    let formatted: Vec<String> = match ARGS.iter().find(|(number, _)| *number == regs.orig_rax) {
+        Some((_, decoders)) => decoders
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| match arg {
+                Arg::Mode { flags } => args[*flags] as i32 & (libc::O_CREAT | libc::O_TMPFILE) != 0,
+                _ => true,
+            })
+            .map(|(index, &arg)| format_arg(&args, index, arg, pid, value))
+            .collect(),
+        None => args.iter().map(|arg| format!("{arg:#x}")).collect(),
+    };
+    format!("{}({})", format_name(regs.orig_rax), formatted.join(", "))
+}
+
+/// Returns the name of the syscall numbered `number`, or the number if it is unknown
+pub fn format_name(number: u64) -> String {
+    match name(number) {
+        Some(name) => name.to_owned(),
+        None => format!("syscall_{number}"),
+    }
+}
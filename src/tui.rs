@@ -0,0 +1,179 @@
+use std::{
+    io::{IsTerminal, Write, stdout},
+    mem::MaybeUninit,
+    sync::Once,
+};
+
+use nix::libc::{self, user_regs_struct};
+
+use crate::utils::register;
+
+/// The registers shown in the registers pane, as many per row as the terminal is wide enough for
+const PANE_REGISTERS: [&str; 18] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip", "eflags",
+];
+/// The width of a register in the registers pane, with the space separating it from the next one
+const REGISTER_WIDTH: usize = 27;
+/// The smallest terminal the panes fit in, with a few rows for the commands
+const MIN_ROWS: usize = 20;
+
+/// What the code pane shows: the source around the current line, or the instructions around `rip`
+pub struct CodeView {
+    /// The source file or `disassembly`
+    pub title: String,
+    /// The lines of the pane, with whether they are the current line
+    pub lines: Vec<(bool, String)>,
+    /// The registers of the current thread, `None` if no program is running
+    pub registers: Option<user_regs_struct>,
+}
+
+/// The split screen of `tui enable`
+///
+/// The code and the registers of the stopped program are drawn at the top of the terminal,
+/// the commands and their output scroll in the rows below, the scrolling region of the terminal.
+pub struct Tui {
+    rows: usize,
+    cols: usize,
+}
+
+/// Registers `reset_terminal` to be run at exit
+static AT_EXIT: Once = Once::new();
+
+/// Lets the whole terminal scroll again, when the debugger exits with the TUI enabled
+extern "C" fn reset_terminal() {
+    let reset = b"\x1b[r";
+    unsafe { libc::write(libc::STDOUT_FILENO, reset.as_ptr().cast(), reset.len()) };
+}
+
+/// Returns the number of rows and columns of the terminal
+fn terminal_size() -> Option<(usize, usize)> {
+    let mut size = MaybeUninit::<libc::winsize>::zeroed();
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, size.as_mut_ptr()) };
+    if res != 0 {
+        return None;
+    }
+    let size = unsafe { size.assume_init() };
+    Some((size.ws_row as _, size.ws_col as _))
+}
+
+/// Expands the tabs of `text` and cuts it to `cols` characters
+fn fit(text: &str, cols: usize) -> String {
+    let mut line = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        let count = if c == '\t' { 8 - width % 8 } else { 1 };
+        if width + count > cols {
+            break;
+        }
+        match c {
+            '\t' => line.extend(std::iter::repeat_n(' ', count)),
+            c => line.push(c),
+        }
+        width += count;
+    }
+    line
+}
+
+impl Tui {
+    /// Splits the terminal, `None` if the output is not a terminal large enough
+    pub fn enable() -> Option<Tui> {
+        if !stdout().is_terminal() {
+            return None;
+        }
+        let (rows, cols) = terminal_size().filter(|&(rows, _)| rows >= MIN_ROWS)?;
+        AT_EXIT.call_once(|| unsafe {
+            libc::atexit(reset_terminal);
+        });
+        let tui = Tui { rows, cols };
+        tui.layout();
+        Some(tui)
+    }
+
+    /// Gives the whole terminal back to the commands
+    pub fn disable(&self) {
+        print!("\x1b[r\x1b[2J\x1b[H");
+        let _ = stdout().flush();
+    }
+
+    /// The number of registers on a row of the registers pane
+    fn registers_per_row(&self) -> usize {
+        ((self.cols + 2) / REGISTER_WIDTH).clamp(1, 4)
+    }
+
+    /// The rows of the registers pane, with its title
+    fn register_rows(&self) -> usize {
+        1 + PANE_REGISTERS.len().div_ceil(self.registers_per_row())
+    }
+
+    /// The rows of the code pane, without its title
+    pub fn code_rows(&self) -> usize {
+        // The code takes about half of the terminal, the commands the rest
+        (self.rows / 2).saturating_sub(self.register_rows()).max(3)
+    }
+
+    /// The first row of the commands, below the panes and their separator
+    fn command_row(&self) -> usize {
+        1 + self.code_rows() + self.register_rows() + 2
+    }
+
+    /// Clears the terminal and restricts the scrolling to the rows of the commands
+    fn layout(&self) {
+        let top = self.command_row();
+        print!("\x1b[2J\x1b[{top};{}r\x1b[{top};1H", self.rows);
+        let _ = stdout().flush();
+    }
+
+    /// Draws the panes, restoring the cursor in the rows of the commands
+    ///
+    /// The terminal is split again if it has been resized.
+    pub fn draw(&mut self, view: &CodeView) {
+        if let Some((rows, cols)) = terminal_size().filter(|&(rows, _)| rows >= MIN_ROWS)
+            && (rows, cols) != (self.rows, self.cols)
+        {
+            (self.rows, self.cols) = (rows, cols);
+            self.layout();
+        }
+        let mut screen = vec![format!("\x1b[7m{}\x1b[0m", fit(&view.title, self.cols))];
+        for row in 0..self.code_rows() {
+            let line = match view.lines.get(row) {
+                Some((true, text)) => {
+                    format!("\x1b[7m{}\x1b[0m", fit(&format!("> {text}"), self.cols))
+                }
+                Some((false, text)) => fit(&format!("  {text}"), self.cols),
+                None => String::new(),
+            };
+            screen.push(line);
+        }
+        screen.push(format!("\x1b[7m{}\x1b[0m", fit("Registers", self.cols)));
+        let values = view.registers.as_ref().map(|regs| {
+            PANE_REGISTERS.map(|name| {
+                register(regs, name)
+                    .map_or(String::new(), |value| format!("{name:<6} {value:#018x}"))
+            })
+        });
+        let per_row = self.registers_per_row();
+        for row in 0..self.register_rows() - 1 {
+            let line = match &values {
+                Some(values) => values
+                    .iter()
+                    .skip(per_row * row)
+                    .take(per_row)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join("  "),
+                None if row == 0 => String::from("No program running"),
+                None => String::new(),
+            };
+            screen.push(fit(&line, self.cols));
+        }
+        screen.push("\u{2500}".repeat(self.cols));
+        let mut out = stdout().lock();
+        let _ = write!(out, "\x1b7");
+        for (row, line) in screen.iter().enumerate() {
+            let _ = write!(out, "\x1b[{};1H\x1b[2K{line}", row + 1);
+        }
+        let _ = write!(out, "\x1b8");
+        let _ = out.flush();
+    }
+}
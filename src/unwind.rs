@@ -0,0 +1,198 @@
+use std::{fs, path::Path, rc::Rc};
+
+use gimli::{
+    BaseAddresses, CfaRule, DebugFrame, EhFrame, EndianRcSlice, Register, RegisterRule,
+    RunTimeEndian, UnwindContext, UnwindSection, UnwindTableRow, X86_64,
+};
+use nix::{libc::user_regs_struct, unistd::Pid};
+use object::{Object, ObjectSection};
+
+use crate::utils::read_data_fixed;
+
+type Reader = EndianRcSlice<RunTimeEndian>;
+
+/// The registers preserved across calls by the System V ABI
+const CALLEE_SAVED: [Register; 6] = [
+    X86_64::RBX,
+    X86_64::RBP,
+    X86_64::R12,
+    X86_64::R13,
+    X86_64::R14,
+    X86_64::R15,
+];
+
+/// The general purpose registers of a frame, indexed by their DWARF number, `None` if unknown
+///
+/// The number 16 is the return address column, which holds the instruction pointer of the frame.
+#[derive(Clone, Copy)]
+pub struct Registers([Option<u64>; 17]);
+
+impl Registers {
+    pub fn new(regs: &user_regs_struct) -> Self {
+        Self(
+            [
+                regs.rax, regs.rdx, regs.rcx, regs.rbx, regs.rsi, regs.rdi, regs.rbp, regs.rsp,
+                regs.r8, regs.r9, regs.r10, regs.r11, regs.r12, regs.r13, regs.r14, regs.r15,
+                regs.rip,
+            ]
+            .map(Some),
+        )
+    }
+
+    pub fn get(&self, register: Register) -> Option<u64> {
+        *self.0.get(register.0 as usize)?
+    }
+
+    pub fn set(&mut self, register: Register, value: Option<u64>) {
+        self.0[register.0 as usize] = value;
+    }
+
+    /// The instruction pointer of the frame
+    pub fn pc(&self) -> Option<u64> {
+        self.get(X86_64::RA)
+    }
+
+    /// The registers a called function preserves, the others are unknown
+    pub fn callee_saved(&self) -> Self {
+        let mut registers = Self([None; 17]);
+        for register in CALLEE_SAVED {
+            registers.set(register, self.get(register));
+        }
+        registers
+    }
+}
+
+/// A frame of the call stack, found by unwinding it
+pub struct Unwound {
+    /// The canonical frame address, the stack pointer before the function of the frame was called
+    pub cfa: usize,
+    /// Where the frame pointer of the caller is saved, if it has been saved
+    pub saved_rbp: Option<usize>,
+    /// The registers of the caller once the function returns, its instruction pointer being the return address
+    pub caller: Registers,
+}
+
+/// The call frame information of an ELF file, from its `.eh_frame` or `.debug_frame` section
+///
+/// Addresses are looked up as found in the file until `relocate` is called with the load bias of the file.
+pub struct CallFrameInfo {
+    eh_frame: Option<(EhFrame<Reader>, BaseAddresses)>,
+    debug_frame: Option<DebugFrame<Reader>>,
+    bias: usize,
+}
+
+impl CallFrameInfo {
+    /// Reads the call frame information of the ELF file at `path`
+    ///
+    /// Returns `None` if the file has neither an `.eh_frame` nor a `.debug_frame` section
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        let file = object::File::parse(&*data).ok()?;
+        let endian = if file.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let reader = |data: &[u8]| EndianRcSlice::new(Rc::from(data), endian);
+
+        let eh_frame = file.section_by_name(".eh_frame").and_then(|section| {
+            let mut bases = BaseAddresses::default().set_eh_frame(section.address());
+            if let Some(text) = file.section_by_name(".text") {
+                bases = bases.set_text(text.address());
+            }
+            if let Some(got) = file.section_by_name(".got") {
+                bases = bases.set_got(got.address());
+            }
+            let mut eh_frame = EhFrame::from(reader(section.data().ok()?));
+            eh_frame.set_address_size(8);
+            Some((eh_frame, bases))
+        });
+        let debug_frame = file.section_by_name(".debug_frame").and_then(|section| {
+            let mut debug_frame = DebugFrame::from(reader(section.data().ok()?));
+            debug_frame.set_address_size(8);
+            Some(debug_frame)
+        });
+        if eh_frame.is_none() && debug_frame.is_none() {
+            return None;
+        }
+        Some(Self {
+            eh_frame,
+            debug_frame,
+            bias: 0,
+        })
+    }
+
+    /// Moves all addresses to the load bias `bias`, see `SymbolTable::relocate`
+    pub fn relocate(&mut self, bias: usize) {
+        self.bias = bias;
+    }
+
+    /// Unwinds the frame of the thread `pid` executing `pc` with the registers `registers`
+    ///
+    /// For the frames of callers, `pc` should be the return address minus one,
+    /// as a call can be the last instruction of a function.
+    /// Returns `None` if there is no call frame information for `pc`
+    /// or if it depends on unknown registers or on DWARF expressions.
+    pub fn unwind(&self, pid: Pid, pc: usize, registers: &Registers) -> Option<Unwound> {
+        let addr = pc.wrapping_sub(self.bias) as u64;
+        let mut context = Box::new(UnwindContext::new());
+        if let Some((eh_frame, bases)) = &self.eh_frame
+            && let Ok(row) = eh_frame.unwind_info_for_address(
+                bases,
+                &mut context,
+                addr,
+                EhFrame::cie_from_offset,
+            )
+        {
+            return evaluate(row, pid, registers);
+        }
+        let row = self
+            .debug_frame
+            .as_ref()?
+            .unwind_info_for_address(
+                &BaseAddresses::default(),
+                &mut context,
+                addr,
+                DebugFrame::cie_from_offset,
+            )
+            .ok()?;
+        evaluate(row, pid, registers)
+    }
+}
+
+/// Applies the rules of `row` to find the canonical frame address and the registers of the caller
+fn evaluate(row: &UnwindTableRow<usize>, pid: Pid, registers: &Registers) -> Option<Unwound> {
+    let cfa = match row.cfa() {
+        CfaRule::RegisterAndOffset { register, offset } => {
+            registers.get(*register)?.checked_add_signed(*offset)? as usize
+        }
+        CfaRule::Expression(_) => return None,
+    };
+    let saved = |offset: i64| cfa.checked_add_signed(offset as _);
+    let mut caller = registers.callee_saved();
+    for number in 0..=X86_64::RA.0 {
+        let register = Register(number);
+        let value = match row.register(register) {
+            None => continue,
+            Some(RegisterRule::SameValue) => registers.get(register),
+            Some(RegisterRule::Offset(offset)) => saved(offset)
+                .and_then(|addr| read_data_fixed(pid, addr))
+                .map(u64::from_ne_bytes),
+            Some(RegisterRule::ValOffset(offset)) => saved(offset).map(|value| value as _),
+            Some(RegisterRule::Register(other)) => registers.get(other),
+            Some(RegisterRule::Constant(value)) => Some(value),
+            Some(_) => None,
+        };
+        caller.set(register, value);
+    }
+    caller.set(X86_64::RSP, Some(cfa as _));
+    let saved_rbp = match row.register(X86_64::RBP) {
+        Some(RegisterRule::Offset(offset)) => saved(offset),
+        _ => None,
+    };
+    Some(Unwound {
+        cfa,
+        saved_rbp,
+        caller,
+    })
+}
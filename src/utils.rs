@@ -1,60 +1,581 @@
-use nix::{sys::ptrace, unistd::Pid};
+use std::{
+    mem::MaybeUninit,
+    ptr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use nix::{
+    errno::Errno,
+    libc::{self, user_fpregs_struct, user_regs_struct},
+    sys::ptrace,
+    unistd::Pid,
+};
 
 const WORD_SIZE: usize = size_of::<usize>();
+const PAGE_SIZE: usize = 4096;
+
+/// How the bytes of the strings of the program are printed, changed by `set charset`
+#[derive(Clone, Copy, PartialEq)]
+pub enum Charset {
+    /// The bytes past ASCII are escaped
+    Ascii,
+    /// The bytes are decoded as UTF-8, the invalid sequences being replaced by `U+FFFD`
+    Utf8,
+    /// Each byte is the character of the same code
+    Latin1,
+}
+
+/// The charset of the strings, as a `Charset`
+static CHARSET: AtomicU8 = AtomicU8::new(Charset::Ascii as u8);
+
+/// Prints the strings of the program with the charset `charset`
+pub fn set_charset(charset: Charset) {
+    CHARSET.store(charset as u8, Ordering::Relaxed);
+}
+
+/// The charset the strings are printed with
+fn charset() -> Charset {
+    match CHARSET.load(Ordering::Relaxed) {
+        charset if charset == Charset::Utf8 as u8 => Charset::Utf8,
+        charset if charset == Charset::Latin1 as u8 => Charset::Latin1,
+        _ => Charset::Ascii,
+    }
+}
+
+/// Parses an hexadecimal address prefixed by `0x` or a decimal address
+pub fn parse_address(arg: &str) -> Option<usize> {
+    match arg.strip_prefix("0x") {
+        Some(addr) => usize::from_str_radix(addr, 16).ok(),
+        None if arg.bytes().all(|c| c.is_ascii_digit()) => arg.parse().ok(),
+        None => None,
+    }
+}
+
+/// Splits the arguments of a program like a shell, `None` if a quote is not closed
+///
+/// The arguments are separated by whitespace, which is kept between single or double quotes.
+/// A backslash escapes the next character, except between single quotes.
+pub fn split_arguments(text: &str) -> Option<Vec<String>> {
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => arguments.extend(current.take()),
+            '\'' => {
+                let argument = current.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => argument.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let argument = current.get_or_insert_default();
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => argument.push(chars.next()?),
+                        c => argument.push(c),
+                    }
+                }
+            }
+            '\\' => current.get_or_insert_default().push(chars.next()?),
+            c => current.get_or_insert_default().push(c),
+        }
+    }
+    arguments.extend(current);
+    Some(arguments)
+}
+
+/// Parses a byte pattern, either a quoted ASCII string (`"abc"`) or hexadecimal bytes (`de ad be ef` or `deadbeef`)
+pub fn parse_pattern(arg: &str) -> Option<Vec<u8>> {
+    if let Some(text) = arg.strip_prefix('"') {
+        let text = text.strip_suffix('"')?;
+        return (!text.is_empty() && text.is_ascii()).then(|| text.as_bytes().to_vec());
+    }
+    let digits: String = arg.split_whitespace().collect();
+    let digits = digits.strip_prefix("0x").unwrap_or(&digits);
+    if digits.is_empty() || !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
 
 /// Writes the buffer `buf` to `addr` in the thread's memory
 /// Returns `Ok(())` if all the bytes were written.
 /// In an error happend during writing, Err(n) contains `n`, the number of bytes written.
+///
+/// The memory is written by aligned words, the words only partly covered by `buf`, at the beginning
+/// and at the end, are read first so that the bytes around `buf` are preserved.
+/// Nothing is written if the range goes past the end of the address space.
 pub fn write_data(pid: Pid, addr: usize, buf: &[u8]) -> Result<(), usize> {
-    for bytes_written in (0..buf.len()).step_by(WORD_SIZE) {
-        let rest = buf.len() - bytes_written;
-        if rest > WORD_SIZE {
-            // we have more that WORD_SIZE bytes to write, wa can simply write the entire next word
-            let mut data: [u8; WORD_SIZE] = [0; WORD_SIZE];
-            data.copy_from_slice(&buf[bytes_written..bytes_written + 4]);
-            let data = usize::from_ne_bytes(data);
-            ptrace::write(pid, (addr + bytes_written) as _, data as _)
-                .map_err(|_| bytes_written)?;
-        } else {
-            // we have less than WORD_SIZE bytes to write, we must copy the existing data in order to not overwriting it
-            let present_data =
-                ptrace::read(pid, (addr + bytes_written) as _).map_err(|_| bytes_written)?;
-            let mut present_data = present_data.to_ne_bytes();
-            present_data[0..rest].copy_from_slice(&buf[bytes_written..]);
-            let data = usize::from_ne_bytes(present_data);
-            ptrace::write(pid, (addr + bytes_written) as _, data as _)
-                .map_err(|_| bytes_written)?;
+    let end = addr.checked_add(buf.len()).ok_or(0usize)?;
+    let mut word_addr = addr - addr % WORD_SIZE;
+    let mut bytes_written = 0;
+    while word_addr < end {
+        let start = addr.max(word_addr);
+        let len = end.min(word_addr.saturating_add(WORD_SIZE)) - start;
+        let mut data = [0; WORD_SIZE];
+        if len < WORD_SIZE {
+            // the word is not entirely overwritten, we must copy the existing data in order to not overwrite it
+            let present_data = ptrace::read(pid, word_addr as _).map_err(|_| bytes_written)?;
+            data = present_data.to_ne_bytes();
         }
+        let offset = start - word_addr;
+        data[offset..offset + len].copy_from_slice(&buf[bytes_written..bytes_written + len]);
+        let data = usize::from_ne_bytes(data);
+        ptrace::write(pid, word_addr as _, data as _).map_err(|_| bytes_written)?;
+        bytes_written += len;
+        // The last word of the address space has been written
+        let Some(next) = word_addr.checked_add(WORD_SIZE) else {
+            break;
+        };
+        word_addr = next;
     }
     Ok(())
 }
 
-// Reads `N` bytes if thread's memory into buffer
+/// Reads up to `rest` bytes of the thread's memory at `addr`, at most a word
+///
+/// Returns the bytes read, at the beginning of the word, and their number.
+/// When the word at `addr` crosses into an unmapped page, only the bytes before the page boundary are read,
+/// by reading the word ending at the boundary.
+fn read_word(pid: Pid, addr: usize, rest: usize) -> Option<([u8; WORD_SIZE], usize)> {
+    if let Ok(data) = ptrace::read(pid, addr as _) {
+        return Some((data.to_ne_bytes(), rest.min(WORD_SIZE)));
+    }
+    let count = rest.min(PAGE_SIZE - addr % PAGE_SIZE);
+    if count >= WORD_SIZE {
+        return None;
+    }
+    let shift = WORD_SIZE - count;
+    let data = ptrace::read(pid, addr.checked_sub(shift)? as _)
+        .ok()?
+        .to_ne_bytes();
+    let mut res = [0; WORD_SIZE];
+    res[..count].copy_from_slice(&data[shift..]);
+    Some((res, count))
+}
+
+/// Reads `N` bytes of the thread's memory at `addr`
+///
+/// Returns `None` if any of the bytes cannot be read, or if they go past the end of the address space.
 pub fn read_data_fixed<const N: usize>(pid: Pid, addr: usize) -> Option<[u8; N]> {
     let mut res: [u8; N] = [0; N];
     for bytes_read in (0..N).step_by(WORD_SIZE) {
-        let data = ptrace::read(pid, (addr + bytes_read) as _).ok()?;
         let rest = N - bytes_read;
-        if rest > WORD_SIZE {
-            res[bytes_read..bytes_read + WORD_SIZE].copy_from_slice(&data.to_ne_bytes());
-        } else {
-            res[bytes_read..].copy_from_slice(&data.to_ne_bytes()[..rest]);
+        let (data, count) = read_word(pid, addr.checked_add(bytes_read)?, rest)?;
+        if count < rest.min(WORD_SIZE) {
+            return None;
         }
+        res[bytes_read..bytes_read + count].copy_from_slice(&data[..count]);
     }
     Some(res)
 }
 
-// Reads `n` bytes if thread's memory into buffer
-pub fn read_data(pid: Pid, addr: usize, n: usize) -> Option<Vec<u8>> {
-    let mut res = Vec::with_capacity(n);
+/// Reads up to `n` bytes of the thread's memory at `addr`
+///
+/// Reading stops at the first byte that cannot be read, or at the end of the address space,
+/// so the returned buffer only contains the readable prefix of the range. It is never longer than `n`,
+/// as `read_word` never returns more than the `rest` bytes asked.
+/// `n` can be larger than the memory of the program, the buffer grows as the words are read.
+pub fn read_data(pid: Pid, addr: usize, n: usize) -> Vec<u8> {
+    let mut res = Vec::with_capacity(n.min(PAGE_SIZE));
     for bytes_read in (0..n).step_by(WORD_SIZE) {
-        let data = ptrace::read(pid, (addr + bytes_read) as _).ok()?;
-        let rest = n - bytes_read;
-        if rest > WORD_SIZE {
-            res.extend_from_slice(&data.to_ne_bytes());
-        } else {
-            res.extend_from_slice(&data.to_ne_bytes()[..rest])
+        let Some((data, count)) = addr
+            .checked_add(bytes_read)
+            .and_then(|addr| read_word(pid, addr, n - bytes_read))
+        else {
+            break;
+        };
+        res.extend_from_slice(&data[..count]);
+        if count < WORD_SIZE {
+            break;
+        }
+    }
+    res
+}
+
+/// Reads the null-terminated string at `addr` in the thread's memory, without its null byte
+///
+/// At most `max` bytes are read, `None` is returned if the memory at `addr` cannot be read.
+pub fn read_cstring(pid: Pid, addr: usize, max: usize) -> Option<Vec<u8>> {
+    let mut res = Vec::new();
+    while res.len() < max {
+        let Some((data, count)) = addr
+            .checked_add(res.len())
+            .and_then(|addr| read_word(pid, addr, max - res.len()))
+        else {
+            break;
+        };
+        match data[..count].iter().position(|&byte| byte == 0) {
+            Some(end) => {
+                res.extend_from_slice(&data[..end]);
+                return Some(res);
+            }
+            None => res.extend_from_slice(&data[..count]),
+        }
+        if count < WORD_SIZE {
+            break;
+        }
+    }
+    (!res.is_empty() || max == 0).then_some(res)
+}
+
+/// Formats `bytes` as a quoted string with the non printable characters escaped, followed by `...` if `truncated`
+///
+/// The bytes are decoded with the charset of `set charset`, those it can't print being escaped as `\xNN`.
+pub fn format_string(bytes: &[u8], truncated: bool) -> String {
+    // The characters of the string, or the bytes that are not characters of the charset
+    let chars: Vec<Result<char, u8>> = match charset() {
+        Charset::Ascii => bytes
+            .iter()
+            .map(|&byte| match byte.is_ascii() {
+                true => Ok(byte as char),
+                false => Err(byte),
+            })
+            .collect(),
+        Charset::Utf8 => String::from_utf8_lossy(bytes).chars().map(Ok).collect(),
+        Charset::Latin1 => bytes.iter().map(|&byte| Ok(byte as char)).collect(),
+    };
+    let mut text = String::from("\"");
+    for c in chars {
+        match c {
+            Ok('\n') => text.push_str("\\n"),
+            Ok('\t') => text.push_str("\\t"),
+            Ok(c @ ('"' | '\\')) => {
+                text.push('\\');
+                text.push(c);
+            }
+            Ok(c) if !c.is_control() => text.push(c),
+            Ok(c) => text.push_str(&format!("\\x{:02x}", c as u32)),
+            Err(byte) => text.push_str(&format!("\\x{byte:02x}")),
+        }
+    }
+    text.push('"');
+    if truncated {
+        text.push_str("...");
+    }
+    text
+}
+
+/// The names of the general purpose registers, in the order of `user_regs_struct`
+pub const REGISTERS: [&str; 27] = [
+    "r15", "r14", "r13", "r12", "rbp", "rbx", "r11", "r10", "r9", "r8", "rax", "rcx", "rdx", "rsi",
+    "rdi", "orig_rax", "rip", "cs", "eflags", "rsp", "ss", "fs_base", "gs_base", "ds", "es", "fs",
+    "gs",
+];
+
+/// Returns the value of the general purpose register `name`, like `rax`
+pub fn register(regs: &user_regs_struct, name: &str) -> Option<u64> {
+    let mut regs = *regs;
+    register_mut(&mut regs, name).map(|value| *value)
+}
+
+/// The status and control flags of `eflags`, with their bit
+const EFLAGS: [(&str, u32); 9] = [
+    ("CF", 0),
+    ("PF", 2),
+    ("AF", 4),
+    ("ZF", 6),
+    ("SF", 7),
+    ("TF", 8),
+    ("IF", 9),
+    ("DF", 10),
+    ("OF", 11),
+];
+
+/// Returns the names of the flags set in `eflags`, like `ZF` for the zero flag
+pub fn eflags_names(eflags: u64) -> Vec<&'static str> {
+    EFLAGS
+        .iter()
+        .filter(|(_, bit)| eflags & (1 << bit) != 0)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// The names of registers accepted as used by gdb, with the register they designate
+pub const REGISTER_ALIASES: [(&str, &str); 3] = [("pc", "rip"), ("sp", "rsp"), ("fp", "rbp")];
+
+/// Returns a mutable reference to the general purpose register `name`, like `rax` or an alias like `pc`
+pub fn register_mut<'a>(regs: &'a mut user_regs_struct, name: &str) -> Option<&'a mut u64> {
+    let name = REGISTER_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, register)| register);
+    let value = match name {
+        "rax" => &mut regs.rax,
+        "rbx" => &mut regs.rbx,
+        "rcx" => &mut regs.rcx,
+        "rdx" => &mut regs.rdx,
+        "rsi" => &mut regs.rsi,
+        "rdi" => &mut regs.rdi,
+        "rbp" => &mut regs.rbp,
+        "rsp" => &mut regs.rsp,
+        "r8" => &mut regs.r8,
+        "r9" => &mut regs.r9,
+        "r10" => &mut regs.r10,
+        "r11" => &mut regs.r11,
+        "r12" => &mut regs.r12,
+        "r13" => &mut regs.r13,
+        "r14" => &mut regs.r14,
+        "r15" => &mut regs.r15,
+        "rip" => &mut regs.rip,
+        "eflags" => &mut regs.eflags,
+        "orig_rax" => &mut regs.orig_rax,
+        "cs" => &mut regs.cs,
+        "ss" => &mut regs.ss,
+        "ds" => &mut regs.ds,
+        "es" => &mut regs.es,
+        "fs" => &mut regs.fs,
+        "gs" => &mut regs.gs,
+        "fs_base" => &mut regs.fs_base,
+        "gs_base" => &mut regs.gs_base,
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Reads the floating point and SSE registers of the thread
+pub fn get_fpregs(pid: Pid) -> Option<user_fpregs_struct> {
+    let mut fpregs = MaybeUninit::<user_fpregs_struct>::uninit();
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETFPREGS,
+            pid.as_raw(),
+            ptr::null_mut::<libc::c_void>(),
+            fpregs.as_mut_ptr(),
+        )
+    };
+    Errno::result(res).ok()?;
+    Some(unsafe { fpregs.assume_init() })
+}
+
+/// Returns the 128 bits of the SSE register xmm`index`
+pub fn xmm(fpregs: &user_fpregs_struct, index: usize) -> u128 {
+    (0..4).fold(0, |value, word| {
+        value | (fpregs.xmm_space[index * 4 + word] as u128) << (32 * word)
+    })
+}
+
+/// Returns the 80 bits of the x87 register st`index`
+pub fn st(fpregs: &user_fpregs_struct, index: usize) -> u128 {
+    let value = (0..4).fold(0, |value, word| {
+        value | (fpregs.st_space[index * 4 + word] as u128) << (32 * word)
+    });
+    value & ((1 << 80) - 1)
+}
+
+/// Converts a x87 extended precision value to the nearest double
+pub fn extended_to_f64(value: u128) -> f64 {
+    let mantissa = value as u64;
+    let exponent = (value >> 64) as u16 & 0x7fff;
+    let sign = if (value >> 79) & 1 == 1 { -1.0 } else { 1.0 };
+    let magnitude = match exponent {
+        0 if mantissa == 0 => 0.0,
+        0x7fff if mantissa << 1 == 0 => f64::INFINITY,
+        0x7fff => f64::NAN,
+        // The integer bit is explicit, the mantissa is a fixed point number with 63 fractional bits
+        _ => mantissa as f64 * 2f64.powi(exponent as i32 - 16383 - 63),
+    };
+    sign * magnitude
+}
+
+#[cfg(test)]
+mod tests {
+    use nix::{
+        sys::{
+            signal::{Signal, kill, raise},
+            wait::waitpid,
+        },
+        unistd::{ForkResult, fork},
+    };
+
+    use super::*;
+
+    /// A forked copy of the test stopped under `ptrace`, whose memory is the memory of the test when it was forked
+    struct Tracee(Pid);
+
+    impl Tracee {
+        fn fork() -> Tracee {
+            match unsafe { fork() }.unwrap() {
+                ForkResult::Child => {
+                    let _ = ptrace::traceme();
+                    let _ = raise(Signal::SIGSTOP);
+                    unsafe { libc::_exit(0) }
+                }
+                ForkResult::Parent { child } => {
+                    waitpid(child, None).unwrap();
+                    Tracee(child)
+                }
+            }
+        }
+    }
+
+    impl Drop for Tracee {
+        fn drop(&mut self) {
+            let _ = kill(self.0, Signal::SIGKILL);
+            let _ = waitpid(self.0, None);
+        }
+    }
+
+    /// Maps a page filled with `0, 1, 2, ...` followed by an unmapped page, returning the address of the first page
+    fn page_before_hole() -> usize {
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                2 * PAGE_SIZE,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(addr, libc::MAP_FAILED);
+        unsafe {
+            libc::munmap(addr.cast::<u8>().add(PAGE_SIZE).cast(), PAGE_SIZE);
+            for i in 0..PAGE_SIZE {
+                *addr.cast::<u8>().add(i) = i as u8;
+            }
+        }
+        addr as usize
+    }
+
+    #[test]
+    fn read_data_past_the_mapping_returns_the_readable_prefix() {
+        let page = page_before_hole();
+        let tracee = Tracee::fork();
+        let data = read_data(tracee.0, page, 0xffff_ffff_ffff);
+        assert_eq!(data.len(), PAGE_SIZE);
+        assert!(data.iter().enumerate().all(|(i, &byte)| byte == i as u8));
+    }
+
+    #[test]
+    fn read_data_returns_the_bytes_asked() {
+        let page = page_before_hole();
+        let tracee = Tracee::fork();
+        for n in [0, 1, 7, 8, 9, PAGE_SIZE] {
+            let data = read_data(tracee.0, page, n);
+            assert_eq!(data.len(), n);
+            assert!(data.iter().enumerate().all(|(i, &byte)| byte == i as u8));
+        }
+        // Unaligned, so that the first and the last words are partly read
+        let data = read_data(tracee.0, page + 3, 9);
+        assert_eq!(data, (3..12).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn read_data_across_the_end_of_a_mapping_stops_at_the_page_boundary() {
+        let page = page_before_hole();
+        let tracee = Tracee::fork();
+        let end = page + PAGE_SIZE;
+        for before in [1, 3, 8, 13] {
+            let data = read_data(tracee.0, end - before, 32);
+            let expected: Vec<u8> = (PAGE_SIZE - before..PAGE_SIZE).map(|i| i as u8).collect();
+            assert_eq!(data, expected);
+        }
+        assert!(read_data(tracee.0, end, 8).is_empty());
+    }
+
+    #[test]
+    fn parse_address_reads_hexadecimal_and_decimal() {
+        assert_eq!(parse_address("0x401136"), Some(0x401136));
+        assert_eq!(parse_address("0xFF"), Some(255));
+        assert_eq!(parse_address("4198710"), Some(0x401136));
+        assert_eq!(parse_address("0xffffffffffffffff"), Some(usize::MAX));
+        assert_eq!(parse_address("18446744073709551615"), Some(usize::MAX));
+    }
+
+    #[test]
+    fn parse_address_rejects_invalid_and_overflowing_numbers() {
+        for arg in [
+            "",
+            "0x",
+            "0x10000000000000000",
+            "18446744073709551616",
+            "-1",
+            "+1",
+            "12ab",
+            "0xg",
+            "main",
+            " 1",
+        ] {
+            assert_eq!(parse_address(arg), None, "{arg:?}");
+        }
+    }
+
+    #[test]
+    fn memory_accesses_past_the_end_of_the_address_space_fail() {
+        let tracee = Tracee::fork();
+        assert!(read_data(tracee.0, usize::MAX - 3, 16).is_empty());
+        assert!(read_data(tracee.0, usize::MAX, usize::MAX).is_empty());
+        assert_eq!(read_data_fixed::<8>(tracee.0, usize::MAX - 3), None);
+        assert_eq!(read_data_fixed::<16>(tracee.0, usize::MAX - 7), None);
+        assert_eq!(read_cstring(tracee.0, usize::MAX, 16), None);
+        // The range would wrap around, nothing is written
+        assert_eq!(write_data(tracee.0, usize::MAX - 3, &[0; 8]), Err(0));
+        assert_eq!(write_data(tracee.0, usize::MAX, &[0; 2]), Err(0));
+        // In the range, but not mapped
+        assert_eq!(write_data(tracee.0, usize::MAX - 15, &[0; 8]), Err(0));
+    }
+
+    #[test]
+    fn write_data_of_a_few_unaligned_bytes_keeps_the_bytes_around() {
+        let page = page_before_hole();
+        let tracee = Tracee::fork();
+        // Inside a word, then across two words
+        for start in [3, 6] {
+            write_data(tracee.0, page + start, &[0xaa, 0xbb, 0xcc]).unwrap();
+            let mut expected: Vec<u8> = (0..16).collect();
+            expected[start..start + 3].copy_from_slice(&[0xaa, 0xbb, 0xcc]);
+            assert_eq!(read_data(tracee.0, page, 16), expected);
+            write_data(tracee.0, page, &(0..16).collect::<Vec<u8>>()).unwrap();
+        }
+    }
+
+    #[test]
+    fn split_arguments_splits_like_a_shell() {
+        let split = |text| split_arguments(text).unwrap();
+        assert_eq!(split("a  b\tc"), ["a", "b", "c"]);
+        assert_eq!(split("  "), Vec::<String>::new());
+        assert_eq!(split("'a b' \"c d\""), ["a b", "c d"]);
+        assert_eq!(split("a'b c'd"), ["ab cd"]);
+        assert_eq!(split("''"), [""]);
+        assert_eq!(split(r"a\ b \'"), ["a b", "'"]);
+        assert_eq!(split(r#"'\n' "\"\\""#), [r"\n", r#""\"#]);
+    }
+
+    #[test]
+    fn split_arguments_rejects_unclosed_quotes() {
+        for text in ["'a", "\"a", r#""a\""#, "a\\"] {
+            assert_eq!(split_arguments(text), None, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn parse_pattern_reads_strings_and_hexadecimal_bytes() {
+        assert_eq!(parse_pattern("\"abc\""), Some(b"abc".to_vec()));
+        assert_eq!(parse_pattern("\"a b\""), Some(b"a b".to_vec()));
+        assert_eq!(
+            parse_pattern("de ad be ef"),
+            Some(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!(
+            parse_pattern("deadBEEF"),
+            Some(vec![0xde, 0xad, 0xbe, 0xef])
+        );
+        assert_eq!(parse_pattern("0x00ff"), Some(vec![0, 0xff]));
+    }
+
+    #[test]
+    fn parse_pattern_rejects_invalid_patterns() {
+        for arg in ["", "\"\"", "\"abc", "\"é\"", "abc", "0x", "zz", "d e a"] {
+            assert_eq!(parse_pattern(arg), None, "{arg:?}");
         }
     }
-    Some(res)
 }
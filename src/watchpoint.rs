@@ -0,0 +1,130 @@
+use std::mem::offset_of;
+
+use nix::{
+    libc::{self, TRAP_HWBKPT},
+    sys::ptrace,
+    unistd::Pid,
+};
+
+use crate::{breakpoint::BreakpointArg, utils::read_data};
+
+/// The number of debug registers holding the addresses of the watchpoints
+pub const SLOTS: usize = 4;
+
+/// The accesses to the watched memory that stop the program
+#[derive(Clone, Copy, PartialEq)]
+pub enum WatchKind {
+    /// `watch`, the memory is written
+    Write,
+    /// `rwatch`, the memory is read
+    Read,
+    /// `awatch`, the memory is read or written
+    Access,
+}
+
+impl WatchKind {
+    /// The name of the kind, as listed by `info watchpoints`, `software` if checked by single stepping
+    pub fn name(self, software: bool) -> &'static str {
+        match self {
+            WatchKind::Write if software => "sw watchpoint",
+            WatchKind::Write => "hw watchpoint",
+            WatchKind::Read => "read watchpoint",
+            WatchKind::Access => "acc watchpoint",
+        }
+    }
+}
+
+/// A hardware watchpoint, set in the debug registers of every thread of the program
+///
+/// When the debug registers can't be set, a watchpoint on writes is checked by single stepping instead.
+///
+/// x86 debug registers can't watch only reads, so a read watchpoint also stops on writes,
+/// which are told apart by comparing the watched value with its value at the previous hit.
+pub struct Watchpoint {
+    pub kind: WatchKind,
+    /// The location given by the user, resolved again to `addr` when the program is run
+    pub location: BreakpointArg,
+    pub addr: usize,
+    /// 1, 2, 4 or 8 bytes, the address being aligned on it
+    pub len: usize,
+    pub hits: usize,
+    /// The watched value when the watchpoint was set or last hit
+    pub value: u64,
+    /// For memory in the stack, the frame holding it, the watchpoint is deleted when the frame returns
+    pub scope: Option<Scope>,
+    /// The value is checked after each single step, as the debug registers can't be set
+    pub software: bool,
+}
+
+/// A frame of the stack of a thread
+#[derive(Clone, Copy)]
+pub struct Scope {
+    pub thread: Pid,
+    /// The canonical frame address of the frame, that the stack pointer reaches when the frame returns
+    pub cfa: usize,
+    /// The start of the function of the frame, telling it apart from a later call using the same stack
+    pub function: Option<usize>,
+}
+
+impl Watchpoint {
+    /// Reads the watched value in the memory of `pid`, `None` if it can't be read
+    pub fn read_value(&self, pid: Pid) -> Option<u64> {
+        let bytes = read_data(pid, self.addr, self.len);
+        let mut value = [0; 8];
+        value
+            .get_mut(..self.len)?
+            .copy_from_slice(bytes.get(..self.len)?);
+        Some(u64::from_le_bytes(value))
+    }
+}
+
+/// The offset of the debug register `index` in the user area of a thread
+fn debug_register(index: usize) -> ptrace::AddressType {
+    (offset_of!(libc::user, u_debugreg) + index * 8) as _
+}
+
+/// Sets the debug registers of the thread `tid` so that it stops on `watchpoints`
+///
+/// The watchpoint `i` uses the debug register `i`, the registers of the other slots are disabled.
+pub fn install(tid: Pid, watchpoints: &[Watchpoint]) -> nix::Result<()> {
+    let mut control = 0;
+    for (slot, watchpoint) in watchpoints.iter().enumerate().take(SLOTS) {
+        ptrace::write_user(tid, debug_register(slot), watchpoint.addr as _)?;
+        let access = match watchpoint.kind {
+            WatchKind::Write => 0b01,
+            WatchKind::Read | WatchKind::Access => 0b11,
+        };
+        let len = match watchpoint.len {
+            1 => 0b00,
+            2 => 0b01,
+            8 => 0b10,
+            _ => 0b11,
+        };
+        // The local enable bit, then the access and the length of the slot
+        control |= 1 << (2 * slot);
+        control |= ((len << 2) | access) << (16 + 4 * slot);
+    }
+    ptrace::write_user(tid, debug_register(7), control)
+}
+
+/// Checks if the thread `tid` was stopped by a watchpoint, after the instruction accessing the memory
+pub fn is_hit(tid: Pid) -> bool {
+    ptrace::getsiginfo(tid).is_ok_and(|siginfo| siginfo.si_code == TRAP_HWBKPT)
+}
+
+/// Returns the slots of the watchpoints that stopped the thread `tid`, empty if no watchpoint stopped it
+///
+/// Several watchpoints are hit when they watch the same memory.
+/// The status register is cleared, as the processor doesn't clear it.
+pub fn triggered(tid: Pid) -> Vec<usize> {
+    if !is_hit(tid) {
+        return Vec::new();
+    }
+    let Ok(status) = ptrace::read_user(tid, debug_register(6)) else {
+        return Vec::new();
+    };
+    let _ = ptrace::write_user(tid, debug_register(6), 0);
+    (0..SLOTS)
+        .filter(|slot| status & (1 << slot) != 0)
+        .collect()
+}
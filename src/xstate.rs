@@ -0,0 +1,127 @@
+use std::arch::x86_64::__cpuid_count;
+
+use nix::{errno::Errno, libc, unistd::Pid};
+
+use crate::utils::{get_fpregs, xmm};
+
+/// The type of the register set holding the XSAVE area of a thread
+const NT_X86_XSTATE: libc::c_int = 0x202;
+
+/// Where the kernel stores the enabled state components, the XCR0 register, in the XSAVE area
+const XCR0_OFFSET: usize = 464;
+/// The state components saved in the XSAVE area, the others being in their initial state
+const XSTATE_BV_OFFSET: usize = 512;
+/// The SSE registers in the legacy region of the XSAVE area
+const XMM_OFFSET: usize = 160;
+
+/// The state components of the vector registers
+const AVX: u32 = 2;
+const OPMASK: u32 = 5;
+const ZMM_HI256: u32 = 6;
+const HI16_ZMM: u32 = 7;
+
+/// The vector registers of a thread, as wide as the processor and the kernel support
+pub struct VectorRegisters {
+    /// `xmm`, `ymm` or `zmm`
+    pub name: &'static str,
+    /// The bytes of each register in little endian, 16, 32 or 64 bytes long
+    pub registers: Vec<Vec<u8>>,
+    /// The AVX-512 mask registers `k0` to `k7`, empty without AVX-512
+    pub masks: Vec<u64>,
+}
+
+/// Reads the XSAVE area of the thread, `None` if the kernel can't give it
+fn read_xstate(pid: Pid) -> Option<Vec<u8>> {
+    // The size of the XSAVE area for all the components the processor supports
+    let size = __cpuid_count(0xd, 0).ecx as usize;
+    let mut xstate = vec![0u8; size];
+    let mut iov = libc::iovec {
+        iov_base: xstate.as_mut_ptr().cast(),
+        iov_len: xstate.len(),
+    };
+    let res = unsafe {
+        libc::ptrace(
+            libc::PTRACE_GETREGSET,
+            pid.as_raw(),
+            NT_X86_XSTATE,
+            &mut iov as *mut libc::iovec,
+        )
+    };
+    Errno::result(res).ok()?;
+    xstate.truncate(iov.iov_len);
+    Some(xstate)
+}
+
+/// Returns the `len` bytes of the state component `index` of `xstate`, `None` if the component isn't enabled
+///
+/// A component in its initial state isn't saved, its registers are zero.
+fn component(xstate: &[u8], index: u32, len: usize) -> Option<Vec<u8>> {
+    let word = |offset: usize| {
+        Some(u64::from_le_bytes(
+            xstate.get(offset..offset + 8)?.try_into().ok()?,
+        ))
+    };
+    if word(XCR0_OFFSET)? & (1 << index) == 0 {
+        return None;
+    }
+    if word(XSTATE_BV_OFFSET)? & (1 << index) == 0 {
+        return Some(vec![0; len]);
+    }
+    // The components are at the offsets given by the processor, as the kernel doesn't compact them
+    let offset = __cpuid_count(0xd, index).ebx as usize;
+    Some(xstate.get(offset..offset + len)?.to_vec())
+}
+
+/// Reads the vector registers of the thread
+///
+/// The AVX and AVX-512 registers are read from the XSAVE area, when it can't be read only the SSE registers are.
+pub fn get_vector_registers(pid: Pid) -> Option<VectorRegisters> {
+    let Some(xstate) = read_xstate(pid).filter(|xstate| xstate.len() > XSTATE_BV_OFFSET) else {
+        let fpregs = get_fpregs(pid)?;
+        return Some(VectorRegisters {
+            name: "xmm",
+            registers: (0..16)
+                .map(|index| xmm(&fpregs, index).to_le_bytes().to_vec())
+                .collect(),
+            masks: Vec::new(),
+        });
+    };
+    let mut registers: Vec<Vec<u8>> = xstate[XMM_OFFSET..XMM_OFFSET + 16 * 16]
+        .chunks(16)
+        .map(<[u8]>::to_vec)
+        .collect();
+    let Some(ymm_hi) = component(&xstate, AVX, 16 * 16) else {
+        return Some(VectorRegisters {
+            name: "xmm",
+            registers,
+            masks: Vec::new(),
+        });
+    };
+    for (register, hi) in registers.iter_mut().zip(ymm_hi.chunks(16)) {
+        register.extend_from_slice(hi);
+    }
+    let avx512 = (
+        component(&xstate, OPMASK, 8 * 8),
+        component(&xstate, ZMM_HI256, 16 * 32),
+        component(&xstate, HI16_ZMM, 16 * 64),
+    );
+    let (Some(masks), Some(zmm_hi), Some(hi16)) = avx512 else {
+        return Some(VectorRegisters {
+            name: "ymm",
+            registers,
+            masks: Vec::new(),
+        });
+    };
+    for (register, hi) in registers.iter_mut().zip(zmm_hi.chunks(32)) {
+        register.extend_from_slice(hi);
+    }
+    registers.extend(hi16.chunks(64).map(<[u8]>::to_vec));
+    Some(VectorRegisters {
+        name: "zmm",
+        registers,
+        masks: masks
+            .chunks(8)
+            .map(|mask| u64::from_le_bytes(mask.try_into().unwrap()))
+            .collect(),
+    })
+}
@@ -0,0 +1,170 @@
+//! Builds the C programs of `tests/fixtures` and runs the debugger on them with commands on its input
+
+use std::{
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The longest a debugging session can take before the test fails
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Numbers the temporary files of the builds, as the tests build in parallel
+static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+/// The directory of the built programs, which is also the working directory of the debugger
+fn build_dir() -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixtures");
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Compiles `tests/fixtures/<fixture>.c` with debugging information and `flags` to the program `name`
+pub fn build(fixture: &str, name: &str, flags: &[&str]) -> PathBuf {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{fixture}.c"));
+    let program = build_dir().join(name);
+    // Built under a temporary name then renamed, so that a test never runs a program being written
+    let build = BUILDS.fetch_add(1, Ordering::Relaxed);
+    let temporary = build_dir().join(format!("{name}.{}.{build}", std::process::id()));
+    let status = Command::new("cc")
+        .arg("-g")
+        .args(flags)
+        .arg("-o")
+        .arg(&temporary)
+        .arg(&source)
+        .status()
+        .expect("the tests need a C compiler, cc");
+    assert!(status.success(), "cannot compile {}", source.display());
+    fs::rename(&temporary, &program).unwrap();
+    program
+}
+
+/// The JSON events printed by a debugging session, one per line
+pub struct Session {
+    pub events: Vec<String>,
+    /// The other lines, printed by the program
+    pub output: Vec<String>,
+    /// The exit code of the debugger
+    pub status: Option<i32>,
+}
+
+/// Runs the debugger on `program` with `commands`, returning once it exits at the end of the commands
+///
+/// The running program, if any, is killed when the debugger reaches the end of its input.
+pub fn debug(program: &Path, commands: &[&str]) -> Session {
+    run(program, &[], commands)
+}
+
+/// Runs the debugger on `program` with the command file `script`, in batch mode with `batch`
+///
+/// Without `batch`, the debugger reads an empty input after the file.
+pub fn debug_source(program: &Path, script: &[&str], batch: bool) -> Session {
+    let build = BUILDS.fetch_add(1, Ordering::Relaxed);
+    let path = build_dir().join(format!("script.{}.{build}", std::process::id()));
+    fs::write(&path, script.join("\n")).unwrap();
+    let path = path.to_str().unwrap();
+    let options: &[&str] = if batch {
+        &["--source", path, "--batch"]
+    } else {
+        &["--source", path]
+    };
+    let session = run(program, options, &[]);
+    fs::remove_file(path).unwrap();
+    session
+}
+
+/// Runs the debugger with `options` on `program`, writing `commands` to its input
+fn run(program: &Path, options: &[&str], commands: &[&str]) -> Session {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dbfs"))
+        .arg("--json")
+        .args(options)
+        .arg(program)
+        .current_dir(build_dir())
+        // Without the defaults of the user
+        .env("HOME", "/nonexistent")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut input = child.stdin.take().unwrap();
+    for command in commands {
+        writeln!(input, "{command}").unwrap();
+    }
+    drop(input);
+    let mut stdout = child.stdout.take().unwrap();
+    let output = thread::spawn(move || {
+        let mut output = String::new();
+        stdout.read_to_string(&mut output).unwrap();
+        output
+    });
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            child.kill().unwrap();
+            panic!("the debugger didn't exit after {options:?} {commands:?}");
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    // The output of the program is mixed with the events, until the program exits if the debugger detached from it
+    let (events, output) = output
+        .join()
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .partition(|line| line.starts_with("{\"event\":"));
+    Session {
+        events,
+        output,
+        status: status.code(),
+    }
+}
+
+impl Session {
+    /// The events named `name`, in the order they were printed
+    pub fn events(&self, name: &str) -> Vec<&str> {
+        let prefix = format!("{{\"event\":\"{name}\"");
+        self.events
+            .iter()
+            .filter(|event| {
+                event
+                    .strip_prefix(&prefix)
+                    .is_some_and(|rest| rest.starts_with([',', '}']))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Returns the values of the fields `key` of the JSON object `event` and of the objects it contains, without their quotes
+///
+/// Only the values that are numbers, strings without commas or quotes, booleans and `null` can be read.
+pub fn fields<'a>(event: &'a str, key: &str) -> Vec<&'a str> {
+    let pattern = format!("\"{key}\":");
+    event
+        .match_indices(&pattern)
+        .map(|(start, _)| {
+            let rest = &event[start + pattern.len()..];
+            let end = rest.find([',', '}', ']']).unwrap_or(rest.len());
+            rest[..end].trim_matches('"')
+        })
+        .collect()
+}
+
+/// Returns the value of the first field `key` of `event`, as `fields`
+pub fn field<'a>(event: &'a str, key: &str) -> Option<&'a str> {
+    fields(event, key).into_iter().next()
+}
+
+/// Parses an address printed as `0x...`
+pub fn address(value: &str) -> usize {
+    usize::from_str_radix(value.strip_prefix("0x").unwrap(), 16).unwrap()
+}
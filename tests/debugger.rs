@@ -0,0 +1,575 @@
+//! Runs the debugger on the small C programs of `tests/fixtures`, as a user would
+
+mod common;
+
+use common::{address, build, debug, debug_source, field, fields};
+
+#[test]
+fn continue_stops_at_each_hit_of_a_breakpoint_in_a_loop() {
+    let program = build("loop", "loop", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint tick",
+            "run",
+            "continue",
+            "continue",
+            "continue",
+            "continue",
+            "continue",
+        ],
+    );
+    assert_eq!(session.events("breakpoint").len(), 5);
+    let exited = session.events("exited");
+    assert_eq!(exited.len(), 1);
+    // 0 + 1 + 2 + 3 + 4
+    assert_eq!(field(exited[0], "code"), Some("10"));
+}
+
+#[test]
+fn stepi_executes_a_single_instruction() {
+    let program = build("loop", "loop-stepi", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint tick",
+            "run",
+            "x/2i $rip",
+            "stepi",
+            "info registers rip",
+        ],
+    );
+    let instructions = session.events("instructions");
+    let addrs = fields(instructions[0], "addr");
+    let rip = session.events("register");
+    // The first instruction of a function doesn't jump
+    assert_eq!(address(field(rip[0], "hex").unwrap()), address(addrs[1]));
+}
+
+#[test]
+fn dump_writes_the_readable_prefix_of_a_length_past_the_mappings() {
+    let program = build("loop", "loop-dump", &["-O0"]);
+    let path = program.with_file_name("loop-dump.bin");
+    let _ = std::fs::remove_file(&path);
+    // The program is run without address randomization, main is at the same address in both sessions
+    let main = debug(&program, &["start"]).events("main_reached")[0].to_owned();
+    let main = field(&main, "addr").unwrap();
+    let session = debug(
+        &program,
+        &[
+            "start",
+            &format!("dump {main} 0xffffffffffff {}", path.display()),
+        ],
+    );
+    let dump = session.events("dump");
+    assert_eq!(dump.len(), 1);
+    let len: u64 = field(dump[0], "length").unwrap().parse().unwrap();
+    assert!(len > 0 && len < 0xffff_ffff_ffff);
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+}
+
+#[test]
+fn examine_and_stack_stop_at_the_end_of_the_stack_for_large_counts() {
+    let program = build("loop", "loop-examine", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "start",
+            "x/999999999xg $rsp",
+            "x/18446744073709551615xb $rsp",
+            "stack 1000000000",
+            "stack 18446744073709551615",
+            "info registers rip",
+        ],
+    );
+    assert_eq!(session.events("memory").len(), 2);
+    assert_eq!(session.events("stack").len(), 2);
+    // The session goes on after the units past the stack are reported unreadable
+    assert!(!session.events("error").is_empty());
+    assert_eq!(session.events("register").len(), 1);
+}
+
+#[test]
+fn breakpoints_added_while_the_program_is_stopped_are_hit() {
+    let program = build("loop", "loop-added", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint main",
+            "run",
+            "breakpoint tick",
+            "commands 2",
+            "info registers rip",
+            "end",
+            "continue",
+            "continue",
+        ],
+    );
+    let hits: Vec<_> = session
+        .events("breakpoint")
+        .iter()
+        .map(|hit| field(hit, "index").unwrap())
+        .collect();
+    assert_eq!(hits, ["1", "2", "2"]);
+    // The commands of the breakpoint run at each hit
+    assert_eq!(session.events("register").len(), 2);
+}
+
+#[test]
+fn probes_added_while_the_program_is_stopped_are_printed() {
+    let program = build("loop", "loop-probe", &["-O0"]);
+    let session = debug(
+        &program,
+        &["breakpoint main", "run", "probe tick $rdi", "continue"],
+    );
+    let probes = session.events("probe");
+    let values: Vec<_> = probes
+        .iter()
+        .map(|probe| field(probe, "value").unwrap())
+        .collect();
+    assert_eq!(values, ["0", "1", "2", "3", "4"]);
+    assert_eq!(session.events("exited").len(), 1);
+}
+
+#[test]
+fn breakpoints_at_a_source_line_stop_at_its_first_instruction() {
+    let program = build("loop", "loop-line", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint loop.c:5",
+            "run",
+            "info registers rip",
+            "continue",
+        ],
+    );
+    let hits = session.events("breakpoint");
+    assert_eq!(hits.len(), 2);
+    let rip = address(field(session.events("register")[0], "hex").unwrap());
+    assert_eq!(address(field(hits[0], "addr").unwrap()), rip);
+    assert!(session.events("error").is_empty());
+}
+
+#[test]
+fn continue_after_clearing_the_breakpoint_of_the_stop_runs_the_original_instruction() {
+    let program = build("loop", "loop-clear", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint tick",
+            "run",
+            "x/2i $rip",
+            "clear tick",
+            "stepi",
+            "info registers rip",
+            "continue",
+        ],
+    );
+    assert_eq!(session.events("breakpoint").len(), 1);
+    let cleared = session.events("breakpoints_cleared");
+    assert_eq!(field(cleared[0], "count"), Some("1"));
+    let instructions = session.events("instructions");
+    let second = address(fields(instructions[0], "addr")[1]);
+    let rip = address(field(session.events("register")[0], "hex").unwrap());
+    assert_eq!(rip, second);
+    let exited = session.events("exited");
+    assert_eq!(field(exited[0], "code"), Some("10"));
+}
+
+#[test]
+fn continue_from_a_breakpoint_stops_at_a_breakpoint_on_the_next_instruction() {
+    let program = build("loop", "loop-adjacent", &["-O0"]);
+    let session = debug(&program, &["breakpoint tick", "run", "x/2i $rip"]);
+    let instructions = session.events("instructions");
+    let second = fields(instructions[0], "addr")[1];
+    let next = format!("breakpoint {second}");
+    let session = debug(
+        &program,
+        &["breakpoint tick", &next, "run", "continue", "continue"],
+    );
+    let hits: Vec<_> = session
+        .events("breakpoint")
+        .iter()
+        .map(|hit| field(hit, "index").unwrap())
+        .collect();
+    assert_eq!(hits, ["1", "2", "1"]);
+    assert_eq!(field(session.events("breakpoint")[1], "addr"), Some(second));
+}
+
+#[test]
+fn writing_over_a_breakpoint_keeps_the_written_code_and_the_breakpoint() {
+    let program = build("patch", "patch", &["-O0"]);
+    let session = debug(&program, &["breakpoint patched", "run"]);
+    let patched = field(session.events("breakpoint")[0], "addr").unwrap();
+    // mov $42, %eax; ret; nop; nop
+    let write = format!("set *{patched} = 0x9090c30000002ab8");
+    let session = debug(
+        &program,
+        &[
+            "breakpoint patched",
+            "run",
+            &write,
+            "continue",
+            &format!("x/1xg {patched}"),
+            "continue",
+        ],
+    );
+    assert!(session.events("error").is_empty(), "{:?}", session.events);
+    assert_eq!(session.events("breakpoint").len(), 2);
+    let memory = session.events("memory");
+    assert!(memory[0].contains("0x9090c30000002ab8"), "{}", memory[0]);
+    let exited = session.events("exited");
+    assert_eq!(field(exited[0], "code"), Some("84"));
+}
+
+#[test]
+fn stepi_leaves_the_other_threads_stopped() {
+    let program = build("threads", "threads", &["-O0", "-pthread"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint ready",
+            "run",
+            "thread 1",
+            "info registers rip",
+            "thread 2",
+            "info registers rip",
+            "stepi",
+            "info registers rip",
+            "thread 1",
+            "info registers rip",
+        ],
+    );
+    let rips: Vec<_> = session
+        .events("register")
+        .iter()
+        .map(|register| address(field(register, "hex").unwrap()))
+        .collect();
+    let [main_before, worker_before, worker_after, main_after] = rips[..] else {
+        panic!("{:?}", session.events);
+    };
+    assert_ne!(worker_after, worker_before);
+    // The main thread was spinning when the second thread stopped, it didn't run during the step
+    assert_eq!(main_after, main_before);
+}
+
+#[test]
+fn breakpoints_are_hit_again_when_the_program_is_run_again() {
+    let program = build("loop", "loop-rerun", &["-O0"]);
+    let mut commands = vec!["breakpoint tick", "run"];
+    commands.extend(["continue"; 5]);
+    // Once after the program exited, then after killing it
+    commands.extend(["run", "kill", "run"]);
+    let session = debug(&program, &commands);
+    let hits = session.events("breakpoint");
+    assert_eq!(hits.len(), 7);
+    assert!(
+        hits.iter()
+            .all(|hit| field(hit, "addr") == field(hits[0], "addr"))
+    );
+    assert_eq!(session.events("exited").len(), 1);
+    assert!(session.events("error").is_empty(), "{:?}", session.events);
+}
+
+#[test]
+fn detach_removes_the_breakpoints_from_the_program() {
+    let program = build("detach", "detach", &["-O0"]);
+    let session = debug(&program, &["breakpoint tick", "run", "detach"]);
+    assert_eq!(session.events("breakpoint").len(), 1);
+    assert_eq!(session.events("detached").len(), 1);
+    // A trap left in tick would kill the program with SIGTRAP before it prints
+    assert_eq!(session.output, ["ticks 10"]);
+}
+
+#[test]
+fn continue_from_an_int3_of_the_program_runs_past_it() {
+    let program = build("trap", "trap", &["-O0"]);
+    let session = debug(
+        &program,
+        &["breakpoint tick", "run", "continue", "continue", "continue"],
+    );
+    // tick(1), the int3, then tick(2)
+    assert_eq!(session.events("breakpoint").len(), 2);
+    assert_eq!(session.events("location").len(), 1);
+    let exited = session.events("exited");
+    assert_eq!(field(exited[0], "code"), Some("3"));
+}
+
+#[test]
+fn the_child_of_a_fork_runs_without_the_breakpoints_of_the_parent() {
+    let program = build("fork", "fork", &["-O0"]);
+    let session = debug(
+        &program,
+        &["breakpoint tick", "run", "continue", "continue"],
+    );
+    // tick(1) and tick(4) in the parent, the child isn't stopped by tick(2)
+    assert_eq!(session.events("breakpoint").len(), 2);
+    let exited = session.events("exited");
+    // The child exited with 1 + 2 instead of being killed by the trap
+    assert_eq!(field(exited[0], "code"), Some("8"));
+}
+
+#[test]
+fn follow_fork_mode_child_debugs_the_child_of_a_fork() {
+    let program = build("fork", "fork-child", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "set follow-fork-mode child",
+            "breakpoint tick",
+            "run",
+            "continue",
+            "continue",
+        ],
+    );
+    // tick(1) in the parent, then tick(2) in the child
+    assert_eq!(session.events("breakpoint").len(), 2);
+    let exited = session.events("exited");
+    assert_eq!(field(exited[0], "code"), Some("3"));
+}
+
+#[test]
+fn programs_run_at_other_addresses_with_aslr() {
+    let program = build("loop", "loop-aslr", &["-O0"]);
+    let rip = |aslr| {
+        let session = debug(&program, &[aslr, "start", "info registers rip"]);
+        address(field(session.events("register")[0], "hex").unwrap())
+    };
+    assert_eq!(rip("set aslr off"), rip("set aslr off"));
+    // The load address of a PIE has 28 random bits, two runs at the same address are unlikely
+    assert!((0..3).any(|_| rip("set aslr on") != rip("set aslr on")));
+}
+
+#[test]
+fn nexti_over_a_call_with_the_stack_pointer_at_the_bottom_of_the_address_space() {
+    let program = build("loop", "loop-nexti", &["-O0"]);
+    let session = debug(&program, &["breakpoint tick", "run", "backtrace"]);
+    let backtrace = session.events("backtrace");
+    // The call of tick, 5 bytes before its return address in main
+    let call = address(fields(backtrace[0], "pc")[1]) - 5;
+    let session = debug(
+        &program,
+        &[
+            &format!("breakpoint {call:#x}"),
+            "run",
+            "set $rsp = 4",
+            "nexti",
+        ],
+    );
+    assert_eq!(session.events("breakpoint").len(), 1);
+    // The call can't push its return address
+    let signaled = session.events("signaled");
+    assert_eq!(field(signaled[0], "signal"), Some("SIGSEGV"));
+}
+
+#[test]
+fn backtrace_and_finish_unwind_functions_without_frame_pointer() {
+    let program = build("unwind", "unwind", &["-O2", "-fomit-frame-pointer"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint leaf",
+            "run",
+            "backtrace",
+            "finish",
+            "finish",
+            "continue",
+        ],
+    );
+    let backtrace = session.events("backtrace");
+    assert_eq!(fields(backtrace[0], "function"), ["leaf", "middle", "main"]);
+    let functions: Vec<_> = session
+        .events("location")
+        .iter()
+        .map(|location| field(location, "function").unwrap())
+        .collect();
+    assert_eq!(functions, ["middle", "main"]);
+    let values: Vec<_> = session
+        .events("return_value")
+        .iter()
+        .map(|value| field(value, "value").unwrap())
+        .collect();
+    // leaf(5) then middle(4)
+    assert_eq!(values, ["15", "16"]);
+    let exited = session.events("exited");
+    assert_eq!(field(exited[0], "code"), Some("0"));
+}
+
+#[test]
+fn finish_from_main_returns_to_the_c_runtime() {
+    let program = build("loop", "loop-finish", &["-O0"]);
+    let session = debug(&program, &["breakpoint main", "run", "finish"]);
+    // The C runtime has no debugging information, the return is still an ordinary stop
+    assert!(session.events("location").is_empty());
+    assert_eq!(session.events("returned").len(), 1);
+    let value = session.events("return_value");
+    assert_eq!(field(value[0], "value"), Some("10"));
+}
+
+#[test]
+fn commands_blocks_of_a_command_file_run_at_each_hit_before_the_rest_of_the_file() {
+    let program = build("loop", "loop-source", &["-O0"]);
+    let session = debug_source(
+        &program,
+        &[
+            "breakpoint tick",
+            "commands 1",
+            "info registers rdi",
+            "end",
+            "run",
+            "continue",
+            "continue",
+            "kill",
+        ],
+        false,
+    );
+    assert_eq!(session.events("breakpoint").len(), 3);
+    let values: Vec<&str> = session
+        .events("register")
+        .into_iter()
+        .filter_map(|event| field(event, "value"))
+        .collect();
+    assert_eq!(values, ["0", "1", "2"]);
+    // The lines of the block are not run as commands of the file
+    assert!(session.events("error").is_empty());
+    assert_eq!(session.events("signaled").len(), 1);
+}
+
+#[test]
+fn batch_mode_exits_with_the_exit_code_of_the_program_after_breakpoint_commands() {
+    let program = build("loop", "loop-batch", &["-O0"]);
+    let session = debug_source(
+        &program,
+        &["breakpoint tick", "commands 1", "continue", "end", "run"],
+        true,
+    );
+    assert_eq!(session.events("breakpoint").len(), 5);
+    let exited = session.events("exited");
+    assert_eq!(exited.len(), 1);
+    // tick adds 0 to 4 to the exit code of the program
+    assert_eq!(field(exited[0], "code"), Some("10"));
+    assert_eq!(session.status, Some(10));
+}
+
+#[test]
+fn assemble_at_the_end_of_the_address_space_reports_an_error() {
+    let program = build("loop", "loop-assemble", &["-O0"]);
+    let session = debug(
+        &program,
+        &["start", "assemble 0xffffffffffffffff \"nop\"", "kill"],
+    );
+    let errors = session.events("error");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("Cannot access memory at 0xffffffffffffffff"));
+    assert_eq!(session.events("signaled").len(), 1);
+}
+
+#[test]
+fn print_evaluates_expressions_with_variables() {
+    let program = build("point", "point-print", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint point.c:12",
+            "run",
+            "print *q",
+            "print local + 1",
+            "print *q * 2",
+            "print &local - q",
+            "print *p",
+            "kill",
+        ],
+    );
+    let values: Vec<u64> = session
+        .events("value")
+        .into_iter()
+        .filter_map(|event| field(event, "value"))
+        .map(|value| value.parse().unwrap())
+        .collect();
+    assert_eq!(values, [-7i64 as u64, -6i64 as u64, -14i64 as u64, 0]);
+    let errors = session.events("error");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("'*p' of type 'struct point' is not a number"));
+    assert_eq!(session.events("signaled").len(), 1);
+}
+
+#[test]
+fn print_casts_the_value_of_a_pointer_variable_and_the_address_of_a_variable() {
+    let program = build("point", "point-cast", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint point.c:12",
+            "run",
+            "print (struct point *)p",
+            "print (struct point *)&gp",
+            "print (int *)q",
+            "kill",
+        ],
+    );
+    assert!(session.events("error").is_empty());
+    let typed = session.events("typed_memory");
+    assert_eq!(typed.len(), 3);
+    for event in &typed[..2] {
+        assert_eq!(field(event, "type"), Some("struct point"));
+        assert_eq!(fields(event, "name"), ["x", "y"]);
+        assert_eq!(&fields(event, "value")[1..], ["3", "4"]);
+    }
+    assert_eq!(field(typed[0], "addr"), field(typed[1], "addr"));
+    assert_eq!(field(typed[2], "value"), Some("-7"));
+    assert_eq!(session.events("signaled").len(), 1);
+}
+
+#[test]
+fn info_frame_prints_the_range_of_the_locals_of_a_leaf_function() {
+    let program = build("frame", "frame-info", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "breakpoint frame.c:6",
+            "run",
+            "info frame",
+            "print &local",
+            "print &uc",
+            "print &f",
+            "kill",
+        ],
+    );
+    let frame = session.events("frame");
+    assert_eq!(frame.len(), 1);
+    let start = address(field(frame[0], "locals_start").unwrap());
+    let end = address(field(frame[0], "locals_end").unwrap());
+    // The frame of the leaf function is not allocated, its locals are under the stack pointer
+    assert!(end <= address(field(frame[0], "rsp").unwrap()));
+    let addresses: Vec<usize> = session
+        .events("value")
+        .into_iter()
+        .map(|event| address(field(event, "hex").unwrap()))
+        .collect();
+    assert_eq!(addresses.len(), 3);
+    assert!(addresses.iter().all(|&addr| start <= addr && addr < end));
+    assert_eq!(addresses.iter().min(), Some(&start));
+    assert_eq!(session.events("signaled").len(), 1);
+}
+
+#[test]
+fn recording_stops_with_an_error_when_the_stack_pointer_is_at_the_bottom_of_the_address_space() {
+    let program = build("loop", "loop-record", &["-O0"]);
+    let session = debug(
+        &program,
+        &[
+            "start",
+            "set $rsp = 0x4",
+            "record",
+            "stepi",
+            "reverse-stepi",
+            "kill",
+        ],
+    );
+    let errors = session.events("error");
+    assert_eq!(errors.len(), 2, "{errors:?}");
+    assert!(
+        errors[0].contains("Cannot save the memory below the stack pointer 0x4, recording stopped")
+    );
+    assert!(errors[1].contains("The program is not being recorded"));
+}
@@ -0,0 +1,15 @@
+/* Prints the sum of the calls to tick, which the debugger can detach from */
+#include <stdio.h>
+
+int ticks;
+
+void tick(int i) {
+    ticks += i;
+}
+
+int main(void) {
+    for (int i = 0; i < 5; i++)
+        tick(i);
+    printf("ticks %d\n", ticks);
+    return 0;
+}
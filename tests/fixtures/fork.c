@@ -0,0 +1,22 @@
+/* Forks a child that calls tick, while the parent waits for it */
+#include <sys/wait.h>
+#include <unistd.h>
+
+int ticks;
+
+void tick(int i) {
+    ticks += i;
+}
+
+int main(void) {
+    tick(1);
+    pid_t child = fork();
+    if (child == 0) {
+        tick(2);
+        return ticks;
+    }
+    int status;
+    waitpid(child, &status, 0);
+    tick(4);
+    return ticks + WEXITSTATUS(status);
+}
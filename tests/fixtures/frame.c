@@ -0,0 +1,11 @@
+/* A leaf function keeping its locals in the red zone, under the stack pointer */
+int work(int n) {
+    int local = n * 2;
+    unsigned char uc = 'u';
+    float f = 1.5f;
+    return local + uc + (int)f;
+}
+
+int main(void) {
+    return work(3) - 125;
+}
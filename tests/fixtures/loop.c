@@ -0,0 +1,12 @@
+/* Calls tick 5 times */
+int ticks;
+
+void tick(int i) {
+    ticks += i;
+}
+
+int main(void) {
+    for (int i = 0; i < 5; i++)
+        tick(i);
+    return ticks;
+}
@@ -0,0 +1,15 @@
+/* Makes the code of patched writable, so that the debugger can write over it */
+#include <stdint.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+int patched(void) {
+    return 1;
+}
+
+int main(void) {
+    long page = sysconf(_SC_PAGESIZE);
+    uintptr_t start = (uintptr_t)patched & ~(page - 1);
+    mprotect((void *)start, 2 * page, PROT_READ | PROT_WRITE | PROT_EXEC);
+    return patched() + patched();
+}
@@ -0,0 +1,18 @@
+/* Passes pointers to a structure and to a local variable */
+struct point {
+    int x;
+    int y;
+};
+
+struct point gp = {3, 4};
+
+int norm(struct point *p) {
+    int local = -7;
+    int *q = &local;
+    return p->x * p->x + p->y * p->y + *q;
+}
+
+int main(void) {
+    struct point *p = &gp;
+    return norm(p) - 18;
+}
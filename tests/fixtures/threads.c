@@ -0,0 +1,25 @@
+/* The main thread spins while a second thread calls ready, once the main thread is spinning */
+#include <pthread.h>
+
+volatile int stop;
+volatile long spins;
+
+void ready(void) {
+}
+
+void *worker(void *arg) {
+    while (!spins)
+        ;
+    ready();
+    stop = 1;
+    return arg;
+}
+
+int main(void) {
+    pthread_t thread;
+    pthread_create(&thread, NULL, worker, NULL);
+    while (!stop)
+        spins++;
+    pthread_join(thread, NULL);
+    return 0;
+}
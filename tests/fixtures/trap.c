@@ -0,0 +1,13 @@
+/* Executes an int3 of its own between two calls to tick */
+int ticks;
+
+void tick(int i) {
+    ticks += i;
+}
+
+int main(void) {
+    tick(1);
+    __asm__ volatile("int3");
+    tick(2);
+    return ticks;
+}
@@ -0,0 +1,18 @@
+/* Nested calls that the compiler can't inline, to unwind without frame pointer */
+__attribute__((noinline)) int leaf(int n) {
+    __asm__ volatile("" ::: "memory");
+    return n * 3;
+}
+
+__attribute__((noinline)) int middle(int n) {
+    /* Moves the stack pointer, so that the return address isn't at the top of the stack */
+    volatile int buffer[8];
+    buffer[0] = n;
+    int value = leaf(buffer[0] + 1);
+    __asm__ volatile("" ::: "memory");
+    return value + 1;
+}
+
+int main(void) {
+    return middle(4) - 16;
+}